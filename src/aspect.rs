@@ -1,27 +1,175 @@
 
-use {ComponentManager, EntityData};
+use std::ops::Not;
 
-pub struct Aspect<T: ComponentManager>(Box<Fn(&EntityData<T>, &T) -> bool + 'static>);
+use {ComponentManager, ComponentRegistry, EntityData};
+use {MaybeSend, Result};
+
+/// A check on an entity's component values, for the `where` section of `aspect!`.
+pub type Predicate<T> = boxed!(Fn(&EntityData<T>, &T) -> bool + 'static);
+
+pub struct Aspect<T: ComponentManager>
+{
+    check: boxed!(Fn(&EntityData<T>, &T) -> bool + 'static),
+    info: AspectInfo,
+}
+
+/// What an aspect looks at when checking an entity, eg: for deciding which systems can run
+/// together or which entities a client is interested in.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AspectInfo
+{
+    /// Component fields the aspect checks for, sorted. Nested fields are written as `physics.body`.
+    pub fields: Vec<&'static str>,
+    /// Whether the aspect checks the entity's layers.
+    pub layers: bool,
+    /// Whether the aspect runs closures that may read anything, such as a `where` section.
+    pub opaque: bool,
+}
+
+impl AspectInfo
+{
+    /// Info for an aspect that checks the given fields.
+    pub fn new(mut fields: Vec<&'static str>, layers: bool, opaque: bool) -> AspectInfo
+    {
+        fields.sort();
+        fields.dedup();
+        AspectInfo
+        {
+            fields: fields,
+            layers: layers,
+            opaque: opaque,
+        }
+    }
+
+    /// Info for an aspect built from a closure, which may read anything.
+    pub fn opaque() -> AspectInfo
+    {
+        AspectInfo::new(Vec::new(), false, true)
+    }
+
+    /// Returns whether the aspect may read a component field.
+    pub fn reads(&self, field: &str) -> bool
+    {
+        self.opaque || self.fields.contains(&field)
+    }
+
+    /// Combines the info of two aspects.
+    pub fn merge(&self, other: &AspectInfo) -> AspectInfo
+    {
+        AspectInfo::new(self.fields.iter().chain(other.fields.iter()).cloned().collect(),
+            self.layers || other.layers, self.opaque || other.opaque)
+    }
+}
 
 impl<T: ComponentManager> Aspect<T>
 {
     pub fn all() -> Aspect<T>
     {
-        Aspect(Box::new(|_, _| true))
+        Aspect::with_info(Box::new(|_, _| true), AspectInfo::default())
     }
 
     pub fn none() -> Aspect<T>
     {
-        Aspect(Box::new(|_, _| false))
+        Aspect::with_info(Box::new(|_, _| false), AspectInfo::default())
+    }
+
+    /// Matches entities waiting to be removed by `DataHelper::remove_entity_after()`, eg:
+    /// `moving.and(!Aspect::despawning())` to leave dying entities alone.
+    pub fn despawning() -> Aspect<T>
+    {
+        Aspect::with_info(Box::new(|en, co: &T| co.despawning_flags().has(en)), AspectInfo::opaque())
+    }
+
+    pub fn new(inner: boxed!(Fn(&EntityData<T>, &T) -> bool + 'static)) -> Aspect<T>
+    {
+        Aspect::with_info(inner, AspectInfo::opaque())
     }
 
-    pub unsafe fn new(inner: Box<Fn(&EntityData<T>, &T) -> bool + 'static>) -> Aspect<T>
+    /// Creates an aspect that describes what it reads, as `aspect!` does.
+    pub fn with_info(inner: boxed!(Fn(&EntityData<T>, &T) -> bool + 'static), info: AspectInfo) -> Aspect<T>
     {
-        Aspect(inner)
+        Aspect
+        {
+            check: inner,
+            info: info,
+        }
+    }
+
+    /// Creates an aspect from a closure, eg: `Aspect::from_fn(|en, co| co.position.has(en))`.
+    pub fn from_fn<F>(check: F) -> Aspect<T>
+        where F: Fn(&EntityData<T>, &T) -> bool + MaybeSend + 'static
+    {
+        Aspect::new(Box::new(check))
+    }
+
+    /// Parses an aspect from component names, eg: `"position, velocity, !frozen"` for a console or
+    /// script. Names prefixed with `!` must be missing.
+    ///
+    /// Unknown names are reported with suggestions, see `ComponentRegistry`.
+    pub fn parse(text: &str) -> Result<Aspect<T>>
+    {
+        let registry = ComponentRegistry::<T>::new();
+        let mut all = Vec::new();
+        let mut none = Vec::new();
+        let mut fields = Vec::new();
+        for name in text.split(',').map(|name| name.trim()).filter(|name| !name.is_empty())
+        {
+            let (list, name) = match name.strip_prefix('!')
+            {
+                Some(name) => (&mut none, name.trim()),
+                None => (&mut all, name),
+            };
+            let field = registry.lookup(name)?;
+            list.push(field);
+            fields.push(registry.names()[field]);
+        }
+        Ok(Aspect::with_info(Box::new(move |en, co| {
+            let mask = en.mask(co);
+            all.iter().all(|&field| mask.contains(field)) && !none.iter().any(|&field| mask.contains(field))
+        }), AspectInfo::new(fields, false, false)))
+    }
+
+    /// Matches entities that match both aspects.
+    pub fn and(self, other: Aspect<T>) -> Aspect<T>
+    {
+        let info = self.info.merge(&other.info);
+        Aspect::with_info(Box::new(move |en, co| self.check(en, co) && other.check(en, co)), info)
+    }
+
+    /// Matches entities that match either aspect.
+    pub fn or(self, other: Aspect<T>) -> Aspect<T>
+    {
+        let info = self.info.merge(&other.info);
+        Aspect::with_info(Box::new(move |en, co| self.check(en, co) || other.check(en, co)), info)
+    }
+
+    /// What the aspect looks at when checking an entity.
+    pub fn info(&self) -> &AspectInfo
+    {
+        &self.info
     }
 
     pub fn check<'a>(&self, entity: &EntityData<'a, T>, components: &T) -> bool
     {
-        (self.0)(entity, components)
+        (self.check)(entity, components)
+    }
+}
+
+impl<T: ComponentManager> Not for Aspect<T>
+{
+    type Output = Aspect<T>;
+    /// Matches entities that don't match the aspect.
+    fn not(self) -> Aspect<T>
+    {
+        let info = self.info.clone();
+        Aspect::with_info(Box::new(move |en, co| !self.check(en, co)), info)
     }
 }
+
+/// Boxes a closure for the `where` section of `aspect!`, letting the compiler infer its arguments.
+#[doc(hidden)]
+pub fn predicate<T, F>(predicate: F) -> Predicate<T>
+    where T: ComponentManager, F: Fn(&EntityData<T>, &T) -> bool + MaybeSend + 'static
+{
+    Box::new(predicate)
+}
@@ -0,0 +1,539 @@
+//! Storage for values whose type is only known at runtime, eg: components defined by scripts or
+//! plugins rather than in `components!`.
+//!
+//! A `DynamicColumn` is a packed array of one type, like a `Vec`, but the type isn't part of the
+//! column's own type. It remembers the `TypeId` of its values and checks it on every typed access,
+//! and it drops the values it holds, so it works for `String`s and `Vec`s as well as plain data.
+//!
+//! A `SparseColumn` is the same, but keyed by entity index with gaps, like a `#[hot]` list.
+
+use std::alloc::{self, Layout};
+use std::any::{self, Any, TypeId};
+use std::cmp;
+use std::mem;
+use std::ptr::{self, NonNull};
+use std::slice;
+
+use {Error, Result};
+
+/// An allocation for values of a type chosen at runtime, along with what's needed to check and
+/// drop them. Which of the values are initialized is up to the owner.
+struct RawValues
+{
+    type_id: TypeId,
+    type_name: &'static str,
+    /// Layout of a single value.
+    layout: Layout,
+    /// Drops a value in place, or `None` if the type doesn't need dropping.
+    drop: Option<unsafe fn(*mut u8)>,
+    data: NonNull<u8>,
+    capacity: usize,
+}
+
+// Only `Send + Sync` types can be stored.
+unsafe impl Send for RawValues {}
+unsafe impl Sync for RawValues {}
+
+unsafe fn drop_value<T>(value: *mut u8)
+{
+    ptr::drop_in_place(value as *mut T);
+}
+
+impl RawValues
+{
+    fn new<T: Any + Send + Sync>() -> RawValues
+    {
+        let layout = Layout::new::<T>();
+        RawValues
+        {
+            type_id: TypeId::of::<T>(),
+            type_name: any::type_name::<T>(),
+            layout: layout,
+            drop: if mem::needs_drop::<T>() { Some(drop_value::<T>) } else { None },
+            data: dangling(layout),
+            capacity: if layout.size() == 0 { usize::MAX } else { 0 },
+        }
+    }
+
+    #[inline]
+    fn is<T: Any>(&self) -> bool
+    {
+        self.type_id == TypeId::of::<T>()
+    }
+
+    fn check<T: Any>(&self) -> Result<()>
+    {
+        if self.is::<T>()
+        {
+            Ok(())
+        }
+        else
+        {
+            Err(Error::WrongType { expected: self.type_name, found: any::type_name::<T>() })
+        }
+    }
+
+    #[inline]
+    fn value_ptr(&self, pos: usize) -> *mut u8
+    {
+        unsafe { self.data.as_ptr().add(pos * self.layout.size()) }
+    }
+
+    /// Drops the value at `pos`, which must be initialized.
+    unsafe fn drop_at(&self, pos: usize)
+    {
+        if let Some(drop) = self.drop
+        {
+            drop(self.value_ptr(pos));
+        }
+    }
+
+    fn memory_bytes(&self) -> usize
+    {
+        if self.layout.size() == 0 { 0 } else { self.capacity * self.layout.size() }
+    }
+
+    /// Makes room for at least `required` values, growing geometrically.
+    fn grow(&mut self, required: usize)
+    {
+        if required > self.capacity
+        {
+            let capacity = cmp::max(cmp::max(required, self.capacity * 2), 4);
+            self.reallocate(capacity);
+        }
+    }
+
+    /// Grows or shrinks the allocation to exactly `capacity` values. Values past the new capacity
+    /// must already have been dropped.
+    fn reallocate(&mut self, capacity: usize)
+    {
+        if self.layout.size() == 0 || capacity == self.capacity
+        {
+            return;
+        }
+        if capacity == 0
+        {
+            unsafe { alloc::dealloc(self.data.as_ptr(), array_layout(self.layout, self.capacity)); }
+            self.data = dangling(self.layout);
+            self.capacity = 0;
+            return;
+        }
+        let layout = array_layout(self.layout, capacity);
+        let data = unsafe
+        {
+            if self.capacity == 0
+            {
+                alloc::alloc(layout)
+            }
+            else
+            {
+                alloc::realloc(self.data.as_ptr(), array_layout(self.layout, self.capacity), layout.size())
+            }
+        };
+        self.data = NonNull::new(data).unwrap_or_else(|| alloc::handle_alloc_error(layout));
+        self.capacity = capacity;
+    }
+}
+
+impl Drop for RawValues
+{
+    fn drop(&mut self)
+    {
+        self.reallocate(0);
+    }
+}
+
+/// A packed array of values of a type chosen at runtime.
+pub struct DynamicColumn
+{
+    values: RawValues,
+    len: usize,
+}
+
+impl DynamicColumn
+{
+    /// Creates an empty column for values of type `T`.
+    pub fn new<T: Any + Send + Sync>() -> DynamicColumn
+    {
+        DynamicColumn
+        {
+            values: RawValues::new::<T>(),
+            len: 0,
+        }
+    }
+
+    /// Creates an empty column with room for `capacity` values of type `T`.
+    pub fn with_capacity<T: Any + Send + Sync>(capacity: usize) -> DynamicColumn
+    {
+        let mut column = DynamicColumn::new::<T>();
+        column.reserve(capacity);
+        column
+    }
+
+    pub fn type_id(&self) -> TypeId
+    {
+        self.values.type_id
+    }
+
+    /// Name of the value type, for error messages.
+    pub fn type_name(&self) -> &'static str
+    {
+        self.values.type_name
+    }
+
+    /// Distance in bytes between consecutive values.
+    pub fn stride(&self) -> usize
+    {
+        self.values.layout.size()
+    }
+
+    /// Whether the column holds values of type `T`.
+    pub fn is<T: Any>(&self) -> bool
+    {
+        self.values.is::<T>()
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.len == 0
+    }
+
+    /// Number of values that can be stored without reallocating.
+    pub fn capacity(&self) -> usize
+    {
+        self.values.capacity
+    }
+
+    /// Approximate number of bytes allocated on the heap for the values.
+    pub fn memory_bytes(&self) -> usize
+    {
+        self.values.memory_bytes()
+    }
+
+    /// Adds a value to the end, or fails if it isn't of the column's type.
+    pub fn push<T: Any>(&mut self, value: T) -> Result<()>
+    {
+        self.values.check::<T>()?;
+        self.reserve(1);
+        unsafe { ptr::write(self.values.value_ptr(self.len) as *mut T, value); }
+        self.len += 1;
+        Ok(())
+    }
+
+    /// The value at `pos`, or `None` if there isn't one or it isn't a `T`.
+    pub fn get<T: Any>(&self, pos: usize) -> Option<&T>
+    {
+        self.as_slice::<T>().and_then(|values| values.get(pos))
+    }
+
+    pub fn get_mut<T: Any>(&mut self, pos: usize) -> Option<&mut T>
+    {
+        self.as_mut_slice::<T>().and_then(|values| values.get_mut(pos))
+    }
+
+    /// Every value, or `None` if they aren't `T`s.
+    pub fn as_slice<T: Any>(&self) -> Option<&[T]>
+    {
+        if self.is::<T>()
+        {
+            Some(unsafe { slice::from_raw_parts(self.values.data.as_ptr() as *const T, self.len) })
+        }
+        else
+        {
+            None
+        }
+    }
+
+    pub fn as_mut_slice<T: Any>(&mut self) -> Option<&mut [T]>
+    {
+        if self.is::<T>()
+        {
+            Some(unsafe { slice::from_raw_parts_mut(self.values.data.as_ptr() as *mut T, self.len) })
+        }
+        else
+        {
+            None
+        }
+    }
+
+    /// Removes the value at `pos`, moving the last value into its place. Returns `None` if there
+    /// is no such value or it isn't a `T`.
+    pub fn swap_remove<T: Any>(&mut self, pos: usize) -> Option<T>
+    {
+        if !self.is::<T>() || pos >= self.len
+        {
+            return None;
+        }
+        let value = unsafe { ptr::read(self.values.value_ptr(pos) as *const T) };
+        self.len -= 1;
+        if pos != self.len
+        {
+            unsafe { ptr::copy_nonoverlapping(self.values.value_ptr(self.len), self.values.value_ptr(pos), self.stride()); }
+        }
+        Some(value)
+    }
+
+    /// Same as `swap_remove()`, but drops the value instead of returning it, so the type doesn't
+    /// need to be known. Returns whether there was a value to remove.
+    pub fn swap_remove_drop(&mut self, pos: usize) -> bool
+    {
+        if pos >= self.len
+        {
+            return false;
+        }
+        self.len -= 1;
+        if pos != self.len
+        {
+            // Swap the removed value past the end, where it's no longer part of the column.
+            unsafe { ptr::swap_nonoverlapping(self.values.value_ptr(pos), self.values.value_ptr(self.len), self.stride()); }
+        }
+        unsafe { self.values.drop_at(self.len); }
+        true
+    }
+
+    /// Drops every value, keeping the allocation.
+    pub fn clear(&mut self)
+    {
+        let len = self.len;
+        self.len = 0;
+        for pos in 0..len
+        {
+            unsafe { self.values.drop_at(pos); }
+        }
+    }
+
+    /// Makes room for at least `additional` more values.
+    pub fn reserve(&mut self, additional: usize)
+    {
+        self.values.grow(self.len.checked_add(additional).expect("capacity overflow"));
+    }
+
+    /// Frees as much unused storage as possible.
+    pub fn shrink_to_fit(&mut self)
+    {
+        let len = self.len;
+        self.values.reallocate(len);
+    }
+}
+
+impl Drop for DynamicColumn
+{
+    fn drop(&mut self)
+    {
+        self.clear();
+    }
+}
+
+/// Values of a type chosen at runtime, each in the slot of its entity's index, eg: to hold a
+/// component defined by a script.
+///
+/// Like a `#[hot]` list, there's a slot for every index up to the highest one in use. Which slots
+/// hold a value is tracked with one bit per slot, so only those values are dropped.
+pub struct SparseColumn
+{
+    values: RawValues,
+    /// One bit for each slot, set if it holds a value.
+    occupied: Vec<u64>,
+    len: usize,
+}
+
+impl SparseColumn
+{
+    /// Creates an empty column for values of type `T`.
+    pub fn new<T: Any + Send + Sync>() -> SparseColumn
+    {
+        SparseColumn
+        {
+            values: RawValues::new::<T>(),
+            occupied: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn type_id(&self) -> TypeId
+    {
+        self.values.type_id
+    }
+
+    /// Name of the value type, for error messages.
+    pub fn type_name(&self) -> &'static str
+    {
+        self.values.type_name
+    }
+
+    /// Distance in bytes between consecutive slots.
+    pub fn stride(&self) -> usize
+    {
+        self.values.layout.size()
+    }
+
+    /// Whether the column holds values of type `T`.
+    pub fn is<T: Any>(&self) -> bool
+    {
+        self.values.is::<T>()
+    }
+
+    /// Number of slots holding a value.
+    pub fn len(&self) -> usize
+    {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.len == 0
+    }
+
+    /// Number of slots that can be used without reallocating.
+    pub fn capacity(&self) -> usize
+    {
+        self.values.capacity
+    }
+
+    /// Approximate number of bytes allocated on the heap for the values.
+    pub fn memory_bytes(&self) -> usize
+    {
+        self.values.memory_bytes() + self.occupied.capacity() * mem::size_of::<u64>()
+    }
+
+    /// Whether the slot holds a value.
+    pub fn contains(&self, index: usize) -> bool
+    {
+        self.occupied.get(index / 64).is_some_and(|word| word & (1 << (index % 64)) != 0)
+    }
+
+    /// Stores a value, returning the one it replaced, or fails if it isn't of the column's type.
+    pub fn insert<T: Any>(&mut self, index: usize, value: T) -> Result<Option<T>>
+    {
+        self.values.check::<T>()?;
+        if self.contains(index)
+        {
+            let old = unsafe { &mut *(self.values.value_ptr(index) as *mut T) };
+            return Ok(Some(mem::replace(old, value)));
+        }
+        self.values.grow(index.checked_add(1).expect("capacity overflow"));
+        if index / 64 >= self.occupied.len()
+        {
+            self.occupied.resize(index / 64 + 1, 0);
+        }
+        unsafe { ptr::write(self.values.value_ptr(index) as *mut T, value); }
+        self.occupied[index / 64] |= 1 << (index % 64);
+        self.len += 1;
+        Ok(None)
+    }
+
+    /// Takes the value out of a slot. Returns `None` if the slot is empty or the value isn't a `T`.
+    pub fn remove<T: Any>(&mut self, index: usize) -> Option<T>
+    {
+        if !self.is::<T>() || !self.vacate(index)
+        {
+            return None;
+        }
+        Some(unsafe { ptr::read(self.values.value_ptr(index) as *const T) })
+    }
+
+    /// Same as `remove()`, but drops the value instead of returning it, so the type doesn't need
+    /// to be known. Returns whether there was a value to remove.
+    pub fn remove_drop(&mut self, index: usize) -> bool
+    {
+        if !self.vacate(index)
+        {
+            return false;
+        }
+        unsafe { self.values.drop_at(index); }
+        true
+    }
+
+    /// The value in a slot, or `None` if it's empty or the value isn't a `T`.
+    pub fn get<T: Any>(&self, index: usize) -> Option<&T>
+    {
+        if self.is::<T>() && self.contains(index)
+        {
+            Some(unsafe { &*(self.values.value_ptr(index) as *const T) })
+        }
+        else
+        {
+            None
+        }
+    }
+
+    pub fn get_mut<T: Any>(&mut self, index: usize) -> Option<&mut T>
+    {
+        if self.is::<T>() && self.contains(index)
+        {
+            Some(unsafe { &mut *(self.values.value_ptr(index) as *mut T) })
+        }
+        else
+        {
+            None
+        }
+    }
+
+    /// Indices of the slots holding a value, in order.
+    pub fn indices(&self) -> Vec<usize>
+    {
+        (0..self.occupied.len() * 64).filter(|&index| self.contains(index)).collect()
+    }
+
+    /// Drops every value, keeping the allocation.
+    pub fn clear(&mut self)
+    {
+        let indices = self.indices();
+        for word in &mut self.occupied
+        {
+            *word = 0;
+        }
+        self.len = 0;
+        for index in indices
+        {
+            unsafe { self.values.drop_at(index); }
+        }
+    }
+
+    /// Frees the slots past the highest one in use.
+    pub fn shrink_to_fit(&mut self)
+    {
+        let slots = self.indices().last().map_or(0, |&index| index + 1);
+        self.values.reallocate(slots);
+        self.occupied.truncate(slots.div_ceil(64));
+        self.occupied.shrink_to_fit();
+    }
+
+    /// Marks a slot as empty, returning whether it held a value.
+    fn vacate(&mut self, index: usize) -> bool
+    {
+        if !self.contains(index)
+        {
+            return false;
+        }
+        self.occupied[index / 64] &= !(1 << (index % 64));
+        self.len -= 1;
+        true
+    }
+}
+
+impl Drop for SparseColumn
+{
+    fn drop(&mut self)
+    {
+        self.clear();
+    }
+}
+
+/// Layout of `count` values laid out one after another.
+fn array_layout(layout: Layout, count: usize) -> Layout
+{
+    let size = layout.size().checked_mul(count).expect("capacity overflow");
+    Layout::from_size_align(size, layout.align()).expect("capacity overflow")
+}
+
+/// A well-aligned pointer for values that haven't been allocated.
+fn dangling(layout: Layout) -> NonNull<u8>
+{
+    NonNull::new(ptr::without_provenance_mut(layout.align())).unwrap()
+}
@@ -1,162 +1,2973 @@
 
-use std::collections::{HashMap, VecMap};
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
-use std::ops::{Index, IndexMut};
+use std::mem;
+use std::ops::{Deref, DerefMut, Index, IndexMut, Range};
+use std::slice;
+use std::sync::Arc;
 
-use self::InnerComponentList::{Hot, Cold};
+use self::InnerComponentList::{Hot, Cold, Adaptive, Dense, Single, Custom};
 
 use {BuildData, EditData, ModifyData};
-use {IndexedEntity};
+use {Entity, EntityRefs, IndexedEntity};
+use {Error, Result};
 use ComponentManager;
+use MaybeSend;
+use inspect::{InspectComponent, InspectValue};
+use reflect::Reflect;
 
 pub trait Component: 'static {}
 
 impl<T:'static> Component for T {}
 
-pub struct ComponentList<C: ComponentManager, T: Component>(InnerComponentList<T>, PhantomData<fn(C)>);
+/// Components that can be read as plain bytes, see `ComponentList::as_bytes()`.
+///
+/// Implementing it promises that the type has no padding and holds no pointers or references, eg:
+/// a `#[repr(C)]` struct of `f32`s.
+pub unsafe trait Pod: Copy + 'static {}
+
+unsafe impl Pod for u8 {}
+unsafe impl Pod for u16 {}
+unsafe impl Pod for u32 {}
+unsafe impl Pod for u64 {}
+unsafe impl Pod for i8 {}
+unsafe impl Pod for i16 {}
+unsafe impl Pod for i32 {}
+unsafe impl Pod for i64 {}
+unsafe impl Pod for f32 {}
+unsafe impl Pod for f64 {}
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+pub struct ComponentList<C: ComponentManager, T: Component>
+{
+    inner: InnerComponentList<T>,
+    name: &'static str,
+    on_add: Option<boxed!(FnMut(Entity, &mut T) + 'static)>,
+    on_remove: Option<boxed!(FnMut(Entity, &mut T) + 'static)>,
+    changed: Vec<Entity>,
+    _manager: PhantomData<fn(C)>,
+}
+
+// The hooks are only ever called through `&mut self`, so sharing the list between threads is fine
+// even though they may not be `Sync`.
+unsafe impl<C: ComponentManager, T: Component + Sync> Sync for ComponentList<C, T> {}
 
 enum InnerComponentList<T: Component>
 {
-    Hot(VecMap<T>),
+    Hot(SparseVec<T>),
     Cold(HashMap<usize, T>),
+    Adaptive(AdaptiveMap<T>),
+    Dense(DenseVec<T>),
+    Single(Option<(usize, Entity, T)>, SingletonPolicy),
+    Custom(boxed!(ComponentStorage<T>)),
 }
 
-impl<C: ComponentManager, T: Component> ComponentList<C, T>
+/// Storage for the components of a `ComponentList`, keyed by entity index.
+///
+/// Implement it to keep components somewhere the built-in kinds don't, eg: in paged arrays or a
+/// memory-mapped file, and use it with `#[storage(MyStorage)]` in `components!`, which creates
+/// the storage with `Default`. Storage is shared between threads by `ParEntitySystem`, so it
+/// must be `Sync`.
+pub trait ComponentStorage<T>: MaybeSend + Sync
 {
-    pub fn hot() -> ComponentList<C, T>
+    /// Stores a component, returning the one it replaced.
+    fn insert(&mut self, index: usize, component: T) -> Option<T>;
+    fn remove(&mut self, index: usize) -> Option<T>;
+    fn get(&self, index: usize) -> Option<&T>;
+    fn get_mut(&mut self, index: usize) -> Option<&mut T>;
+    /// Mutably borrows the components at two different indices at once, or `None` if either is
+    /// missing. `a` and `b` are never equal.
+    fn get_pair_mut(&mut self, a: usize, b: usize) -> Option<(&mut T, &mut T)>;
+    /// Number of stored components.
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool
     {
-        ComponentList(Hot(VecMap::new()), PhantomData)
+        self.len() == 0
     }
+    /// Every stored component with its index, in any order.
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = (usize, &'a T)> + 'a>;
+    /// Calls `f` on every stored component, in any order.
+    fn for_each_mut(&mut self, f: &mut dyn FnMut(&mut T));
 
-    pub fn cold() -> ComponentList<C, T>
+    /// Number of components that can be stored without reallocating.
+    fn capacity(&self) -> usize
     {
-        ComponentList(Cold(HashMap::new()), PhantomData)
+        self.len()
     }
 
-    pub fn add(&mut self, entity: &BuildData<C>, component: T) -> Option<T>
+    fn reserve(&mut self, _additional: usize)
+    {
+
+    }
+
+    fn shrink_to_fit(&mut self)
+    {
+
+    }
+
+    /// Approximate number of bytes allocated on the heap.
+    fn memory_bytes(&self) -> usize
+    {
+        0
+    }
+}
+
+/// A map from small integer keys to values, stored as a `Vec` indexed by key.
+///
+/// This is the storage behind `#[hot]` components. Which slots are filled is also kept in a
+/// bitset, so iteration skips empty stretches 64 keys at a time.
+pub struct SparseVec<T>
+{
+    slots: Vec<Option<T>>,
+    occupied: BitSet,
+    len: usize,
+}
+
+impl<T> SparseVec<T>
+{
+    pub fn new() -> SparseVec<T>
     {
-        match self.0
+        SparseVec
         {
-            Hot(ref mut c) => c.insert(entity.0.index(), component),
-            Cold(ref mut c) => c.insert(entity.0.index(), component),
+            slots: Vec::new(),
+            occupied: BitSet::new(),
+            len: 0,
         }
     }
 
-    pub fn insert(&mut self, entity: &ModifyData<C>, component: T) -> Option<T>
+    #[inline]
+    pub fn len(&self) -> usize
+    {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool
+    {
+        self.len == 0
+    }
+
+    /// Stores a value at a key, returning the value that was there before.
+    pub fn insert(&mut self, key: usize, value: T) -> Option<T>
     {
-        match self.0
+        if key >= self.slots.len()
         {
-            Hot(ref mut c) => c.insert(entity.entity().index(), component),
-            Cold(ref mut c) => c.insert(entity.entity().index(), component),
+            let len = key + 1;
+            self.slots.reserve(len - self.slots.len());
+            while self.slots.len() < len
+            {
+                self.slots.push(None);
+            }
         }
+        if !self.occupied.insert(key)
+        {
+            self.len += 1;
+        }
+        self.slots[key].replace(value)
     }
 
-    pub fn remove(&mut self, entity: &ModifyData<C>) -> Option<T>
+    pub fn remove(&mut self, key: usize) -> Option<T>
     {
-        match self.0
+        if self.occupied.remove(key)
+        {
+            self.len -= 1;
+            self.slots[key].take()
+        }
+        else
         {
-            Hot(ref mut c) => c.remove(&entity.entity().index()),
-            Cold(ref mut c) => c.remove(&entity.entity().index()),
+            None
         }
     }
 
-    pub fn set<U: EditData<C>>(&mut self, entity: &U, component: T) -> Option<T>
+    #[inline]
+    pub fn get(&self, key: usize) -> Option<&T>
+    {
+        self.slots.get(key).and_then(|slot| slot.as_ref())
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, key: usize) -> Option<&mut T>
+    {
+        self.slots.get_mut(key).and_then(|slot| slot.as_mut())
+    }
+
+    /// Mutably borrows the values of two different keys at once.
+    pub fn get_pair_mut(&mut self, a: usize, b: usize) -> Option<(&mut T, &mut T)>
     {
-        match self.0
+        match self.slots.get_disjoint_mut([a, b])
         {
-            Hot(ref mut c) => c.insert(entity.entity().index(), component),
-            Cold(ref mut c) => c.insert(entity.entity().index(), component),
+            Ok([Some(a), Some(b)]) => Some((a, b)),
+            _ => None,
         }
     }
 
-    pub fn get<U: EditData<C>>(&self, entity: &U) -> Option<T> where T: Clone
+    #[inline]
+    pub fn contains_key(&self, key: usize) -> bool
+    {
+        self.occupied.contains(key)
+    }
+
+    pub fn clear(&mut self)
     {
-        match self.0
+        self.slots.clear();
+        self.occupied = BitSet::new();
+        self.len = 0;
+    }
+
+    /// Number of slots allocated, i.e. one past the highest key that can be stored without
+    /// reallocating.
+    #[inline]
+    pub fn capacity(&self) -> usize
+    {
+        self.slots.capacity()
+    }
+
+    /// Allocates at least `additional` more slots past the highest key inserted so far.
+    pub fn reserve(&mut self, additional: usize)
+    {
+        self.slots.reserve(additional);
+        self.occupied.reserve(self.slots.len() + additional);
+    }
+
+    /// Frees the slots past the highest filled key.
+    pub fn shrink_to_fit(&mut self)
+    {
+        let len = self.keys().last().map_or(0, |key| key + 1);
+        self.slots.truncate(len);
+        self.slots.shrink_to_fit();
+        self.occupied.shrink_to_fit();
+    }
+
+    /// Bytes allocated on the heap.
+    pub fn memory_bytes(&self) -> usize
+    {
+        self.slots.capacity() * mem::size_of::<Option<T>>() + self.occupied.memory_bytes()
+    }
+
+    /// Iterates over the filled keys, in increasing order.
+    pub fn keys(&self) -> Keys<'_>
+    {
+        Keys
         {
-            Hot(ref c) => c.get(&entity.entity().index()).cloned(),
-            Cold(ref c) => c.get(&entity.entity().index()).cloned(),
+            words: &self.occupied.words,
+            word: 0,
+            bits: self.occupied.words.first().cloned().unwrap_or(0),
         }
     }
 
-    pub fn has<U: EditData<C>>(&self, entity: &U) -> bool
+    /// Iterates over the keys and values, in increasing order of key.
+    pub fn iter(&self) -> Iter<'_, T>
     {
-        match self.0
+        Iter
         {
-            Hot(ref c) => c.contains_key(&entity.entity().index()),
-            Cold(ref c) => c.contains_key(&entity.entity().index()),
+            keys: self.keys(),
+            slots: &self.slots,
         }
     }
 
-    pub fn borrow<U: EditData<C>>(&mut self, entity: &U) -> Option<&mut T>
+    /// Iterates over the values mutably, in increasing order of key.
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T>
+    {
+        self.slots.iter_mut().filter_map(Option::as_mut)
+    }
+}
+
+impl<T> Default for SparseVec<T>
+{
+    fn default() -> SparseVec<T>
+    {
+        SparseVec::new()
+    }
+}
+
+/// Iterator over the keys of a `SparseVec`.
+pub struct Keys<'a>
+{
+    words: &'a [u64],
+    word: usize,
+    bits: u64,
+}
+
+impl<'a> Iterator for Keys<'a>
+{
+    type Item = usize;
+    fn next(&mut self) -> Option<usize>
     {
-        match self.0
+        while self.bits == 0
         {
-            Hot(ref mut c) => c.get_mut(&entity.entity().index()),
-            Cold(ref mut c) => c.get_mut(&entity.entity().index()),
+            self.word += 1;
+            match self.words.get(self.word)
+            {
+                Some(&bits) => self.bits = bits,
+                None => return None,
+            }
         }
+        let bit = self.bits.trailing_zeros() as usize;
+        self.bits &= self.bits - 1;
+        Some(self.word * 64 + bit)
     }
+}
 
-    pub unsafe fn clear(&mut self, entity: &IndexedEntity<C>)
+/// Iterator over the keys and values of a `SparseVec`.
+pub struct Iter<'a, T: 'a>
+{
+    keys: Keys<'a>,
+    slots: &'a [Option<T>],
+}
+
+impl<'a, T> Iterator for Iter<'a, T>
+{
+    type Item = (usize, &'a T);
+    fn next(&mut self) -> Option<(usize, &'a T)>
+    {
+        match self.keys.next()
+        {
+            Some(key) => self.slots[key].as_ref().map(|value| (key, value)),
+            None => None,
+        }
+    }
+}
+
+/// Map that moves between a `HashMap` and a `SparseVec` depending on how densely it's filled.
+///
+/// The thresholds for packing and unpacking are far apart, so a list hovering around either one
+/// doesn't keep moving back and forth.
+struct AdaptiveMap<T>
+{
+    inner: AdaptiveInner<T>,
+    /// One past the highest key inserted while unpacked.
+    span: usize,
+}
+
+enum AdaptiveInner<T>
+{
+    Unpacked(HashMap<usize, T>),
+    Packed(SparseVec<T>),
+}
+
+/// Below this many values, a map is never packed.
+const ADAPTIVE_MIN_LEN: usize = 64;
+
+impl<T> AdaptiveMap<T>
+{
+    fn new() -> AdaptiveMap<T>
+    {
+        AdaptiveMap
+        {
+            inner: AdaptiveInner::Unpacked(HashMap::new()),
+            span: 0,
+        }
+    }
+
+    fn insert(&mut self, index: usize, value: T) -> Option<T>
+    {
+        let old = match self.inner
+        {
+            AdaptiveInner::Unpacked(ref mut c) => {
+                self.span = ::std::cmp::max(self.span, index + 1);
+                c.insert(index, value)
+            },
+            AdaptiveInner::Packed(ref mut c) => c.insert(index, value),
+        };
+        let pack = match self.inner
+        {
+            // Pack at half full
+            AdaptiveInner::Unpacked(ref c) => c.len() >= ADAPTIVE_MIN_LEN && c.len() * 2 >= self.span,
+            AdaptiveInner::Packed(_) => false,
+        };
+        if pack
+        {
+            self.pack();
+        }
+        old
+    }
+
+    fn remove(&mut self, index: usize) -> Option<T>
     {
-        match self.0
+        let (old, unpack) = match self.inner
         {
-            Hot(ref mut c) => c.remove(&entity.index()),
-            Cold(ref mut c) => c.remove(&entity.index()),
+            AdaptiveInner::Unpacked(ref mut c) => (c.remove(&index), false),
+            AdaptiveInner::Packed(ref mut c) => {
+                let old = c.remove(index);
+                // Unpack at an eighth full
+                (old, c.len() * 8 < c.slots.len())
+            },
         };
+        if unpack
+        {
+            self.unpack();
+        }
+        old
+    }
+
+    fn get(&self, index: usize) -> Option<&T>
+    {
+        match self.inner
+        {
+            AdaptiveInner::Unpacked(ref c) => c.get(&index),
+            AdaptiveInner::Packed(ref c) => c.get(index),
+        }
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T>
+    {
+        match self.inner
+        {
+            AdaptiveInner::Unpacked(ref mut c) => c.get_mut(&index),
+            AdaptiveInner::Packed(ref mut c) => c.get_mut(index),
+        }
+    }
+
+    fn get_pair_mut(&mut self, a: usize, b: usize) -> Option<(&mut T, &mut T)>
+    {
+        match self.inner
+        {
+            AdaptiveInner::Unpacked(ref mut c) => map_pair_mut(c, a, b),
+            AdaptiveInner::Packed(ref mut c) => c.get_pair_mut(a, b),
+        }
+    }
+
+    fn len(&self) -> usize
+    {
+        match self.inner
+        {
+            AdaptiveInner::Unpacked(ref c) => c.len(),
+            AdaptiveInner::Packed(ref c) => c.len(),
+        }
+    }
+
+    fn capacity(&self) -> usize
+    {
+        match self.inner
+        {
+            AdaptiveInner::Unpacked(ref c) => c.capacity(),
+            AdaptiveInner::Packed(ref c) => c.capacity(),
+        }
+    }
+
+    fn reserve(&mut self, additional: usize)
+    {
+        match self.inner
+        {
+            AdaptiveInner::Unpacked(ref mut c) => c.reserve(additional),
+            AdaptiveInner::Packed(ref mut c) => c.reserve(additional),
+        }
+    }
+
+    fn shrink_to_fit(&mut self)
+    {
+        match self.inner
+        {
+            AdaptiveInner::Unpacked(ref mut c) => c.shrink_to_fit(),
+            AdaptiveInner::Packed(ref mut c) => c.shrink_to_fit(),
+        }
+    }
+
+    fn memory_bytes(&self) -> usize
+    {
+        match self.inner
+        {
+            AdaptiveInner::Unpacked(ref c) => map_bytes(c),
+            AdaptiveInner::Packed(ref c) => c.memory_bytes(),
+        }
+    }
+
+    fn for_each_mut<F: FnMut(&mut T)>(&mut self, f: F)
+    {
+        match self.inner
+        {
+            AdaptiveInner::Unpacked(ref mut c) => c.values_mut().for_each(f),
+            AdaptiveInner::Packed(ref mut c) => c.values_mut().for_each(f),
+        }
+    }
+
+    fn pack(&mut self)
+    {
+        let mut packed = SparseVec::new();
+        if let AdaptiveInner::Unpacked(ref mut c) = self.inner
+        {
+            for (index, value) in c.drain()
+            {
+                packed.insert(index, value);
+            }
+        }
+        self.inner = AdaptiveInner::Packed(packed);
+    }
+
+    fn unpack(&mut self)
+    {
+        let mut unpacked = HashMap::new();
+        self.span = 0;
+        if let AdaptiveInner::Packed(ref mut c) = self.inner
+        {
+            let keys: Vec<usize> = c.keys().collect();
+            for index in keys
+            {
+                if let Some(value) = c.remove(index)
+                {
+                    self.span = index + 1;
+                    unpacked.insert(index, value);
+                }
+            }
+        }
+        self.inner = AdaptiveInner::Unpacked(unpacked);
     }
 }
 
-impl<C: ComponentManager, T: Component, U: EditData<C>> Index<U> for ComponentList<C, T>
+/// Components packed in a `Vec`, with a table from entity index to position.
+struct DenseVec<T>
 {
-    type Output = T;
-    fn index(&self, en: U) -> &T
+    values: Vec<T>,
+    /// Entity index of each value.
+    owners: Vec<usize>,
+    /// Position of each entity's value, or `EMPTY`.
+    slots: Vec<usize>,
+    /// Positions written since the last `take_changed_range()`, as `(start, end)`.
+    changed: Option<(usize, usize)>,
+}
+
+const EMPTY: usize = !0;
+
+impl<T> DenseVec<T>
+{
+    fn new() -> DenseVec<T>
+    {
+        DenseVec
+        {
+            values: Vec::new(),
+            owners: Vec::new(),
+            slots: Vec::new(),
+            changed: None,
+        }
+    }
+
+    fn touch(&mut self, start: usize, end: usize)
+    {
+        self.changed = Some(match self.changed
+        {
+            Some((old_start, old_end)) => (old_start.min(start), old_end.max(end)),
+            None => (start, end),
+        });
+    }
+
+    fn take_changed_range(&mut self) -> Option<Range<usize>>
+    {
+        let (start, end) = self.changed.take()?;
+        let end = end.min(self.values.len());
+        if start < end { Some(start..end) } else { None }
+    }
+
+    #[inline]
+    fn position(&self, index: usize) -> Option<usize>
+    {
+        match self.slots.get(index)
+        {
+            Some(&pos) if pos != EMPTY => Some(pos),
+            _ => None,
+        }
+    }
+
+    fn insert(&mut self, index: usize, value: T) -> Option<T>
+    {
+        if let Some(pos) = self.position(index)
+        {
+            self.touch(pos, pos + 1);
+            return Some(mem::replace(&mut self.values[pos], value));
+        }
+        if index >= self.slots.len()
+        {
+            self.slots.resize(index + 1, EMPTY);
+        }
+        let pos = self.values.len();
+        self.slots[index] = pos;
+        self.values.push(value);
+        self.owners.push(index);
+        self.touch(pos, pos + 1);
+        None
+    }
+
+    fn remove(&mut self, index: usize) -> Option<T>
+    {
+        let pos = self.position(index)?;
+        self.slots[index] = EMPTY;
+        let value = self.values.swap_remove(pos);
+        self.owners.swap_remove(pos);
+        if pos < self.owners.len()
+        {
+            self.slots[self.owners[pos]] = pos;
+            self.touch(pos, pos + 1);
+        }
+        Some(value)
+    }
+
+    fn get(&self, index: usize) -> Option<&T>
+    {
+        self.position(index).map(|pos| &self.values[pos])
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T>
     {
-        match self.0
+        match self.position(index)
         {
-            Hot(ref c) => &c[en.entity().index()],
-            Cold(ref c) => &c[&en.entity().index()],
+            Some(pos) => {
+                self.touch(pos, pos + 1);
+                Some(&mut self.values[pos])
+            },
+            None => None,
         }
     }
+
+    fn get_pair_mut(&mut self, a: usize, b: usize) -> Option<(&mut T, &mut T)>
+    {
+        let (a, b) = (self.position(a)?, self.position(b)?);
+        self.touch(a, a + 1);
+        self.touch(b, b + 1);
+        self.values.get_disjoint_mut([a, b]).ok().map(|[a, b]| (a, b))
+    }
+
+    fn capacity(&self) -> usize
+    {
+        self.values.capacity()
+    }
+
+    fn reserve(&mut self, additional: usize)
+    {
+        self.values.reserve(additional);
+        self.owners.reserve(additional);
+    }
+
+    fn shrink_to_fit(&mut self)
+    {
+        let len = self.owners.iter().max().map_or(0, |&index| index + 1);
+        self.slots.truncate(len);
+        self.slots.shrink_to_fit();
+        self.values.shrink_to_fit();
+        self.owners.shrink_to_fit();
+    }
+
+    fn memory_bytes(&self) -> usize
+    {
+        self.values.capacity() * mem::size_of::<T>()
+            + (self.owners.capacity() + self.slots.capacity()) * mem::size_of::<usize>()
+    }
+
+    fn for_each_mut<F: FnMut(&mut T)>(&mut self, f: F)
+    {
+        let len = self.values.len();
+        self.touch(0, len);
+        self.values.iter_mut().for_each(f);
+    }
 }
 
-impl<C: ComponentManager, T: Component, U: EditData<C>> IndexMut<U> for ComponentList<C, T>
+/// A place in a `ComponentList` that may or may not hold a component.
+pub struct Entry<'a, C: ComponentManager + 'a, T: Component>
 {
-    fn index_mut(&mut self, en: U) -> &mut T
+    list: &'a mut ComponentList<C, T>,
+    entity: &'a IndexedEntity<C>,
+}
+
+impl<'a, C: ComponentManager, T: Component> Entry<'a, C, T>
+{
+    /// Returns whether the entity already has the component.
+    pub fn is_occupied(&self) -> bool
+    {
+        self.list.inner.contains(self.entity.index())
+    }
+
+    /// Runs `f` on the component, if the entity has one.
+    pub fn and_modify<F: FnOnce(&mut T)>(self, f: F) -> Entry<'a, C, T>
+    {
+        if let Some(component) = self.list.inner.get_mut(self.entity.index())
+        {
+            f(component);
+        }
+        self
+    }
+
+    /// Adds `default` if the entity has no component, then returns the component.
+    pub fn or_insert(self, default: T) -> &'a mut T
+    {
+        self.or_insert_with(|| default)
+    }
+
+    /// Adds the result of `default` if the entity has no component, then returns the component.
+    ///
+    /// `default` isn't called if the entity already has one.
+    pub fn or_insert_with<F: FnOnce() -> T>(self, default: F) -> &'a mut T
     {
-        match self.0
+        let index = self.entity.index();
+        if !self.list.inner.contains(index)
         {
-            Hot(ref mut c) => c.get_mut(&en.entity().index()),
-            Cold(ref mut c) => c.get_mut(&en.entity().index()),
-        }.expect(&format!("Could not find entry for {:?}", **en.entity()))
+            self.list.insert_tracked(self.entity, default());
+        }
+        self.list.inner.get_mut(index).unwrap()
+    }
+
+    pub fn or_default(self) -> &'a mut T where T: Default
+    {
+        self.or_insert_with(T::default)
     }
 }
 
-pub trait EntityBuilder<T: ComponentManager>
+/// What a `#[singleton]` list does when an entity is given the component while another entity
+/// has it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SingletonPolicy
 {
-    fn build<'a>(&mut self, BuildData<'a, T>, &mut T);
+    /// The other entity loses the component, as if it had been removed.
+    Replace,
+    /// Panic with `Error::SingletonTaken`, leaving the component with the other entity.
+    /// `ComponentList::try_set()` returns the error instead.
+    Panic,
 }
 
-impl<T: ComponentManager, F> EntityBuilder<T> for F where F: FnMut(BuildData<T>, &mut T)
+impl<T: Component> InnerComponentList<T>
 {
-    fn build(&mut self, e: BuildData<T>, c: &mut T)
+    fn insert(&mut self, index: usize, entity: Entity, component: T) -> Option<T>
+    {
+        match *self
+        {
+            Hot(ref mut c) => c.insert(index, component),
+            Cold(ref mut c) => c.insert(index, component),
+            Adaptive(ref mut c) => c.insert(index, component),
+            Dense(ref mut c) => c.insert(index, component),
+            Custom(ref mut c) => c.insert(index, component),
+            Single(ref mut c, _) => {
+                // Any other owner has already been displaced by the `ComponentList`.
+                let old = c.take().map(|(_, _, old)| old);
+                *c = Some((index, entity, component));
+                old
+            },
+        }
+    }
+
+    fn remove(&mut self, index: usize) -> Option<T>
     {
-        (*self)(e, c);
+        match *self
+        {
+            Hot(ref mut c) => c.remove(index),
+            Cold(ref mut c) => c.remove(&index),
+            Adaptive(ref mut c) => c.remove(index),
+            Dense(ref mut c) => c.remove(index),
+            Custom(ref mut c) => c.remove(index),
+            Single(ref mut c, _) => match c.take()
+            {
+                Some((i, _, old)) if i == index => Some(old),
+                other => {
+                    *c = other;
+                    None
+                }
+            },
+        }
     }
+
+    fn get(&self, index: usize) -> Option<&T>
+    {
+        match *self
+        {
+            Hot(ref c) => c.get(index),
+            Cold(ref c) => c.get(&index),
+            Adaptive(ref c) => c.get(index),
+            Dense(ref c) => c.get(index),
+            Custom(ref c) => c.get(index),
+            Single(ref c, _) => c.as_ref().and_then(|&(i, _, ref v)| if i == index { Some(v) } else { None }),
+        }
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T>
+    {
+        match *self
+        {
+            Hot(ref mut c) => c.get_mut(index),
+            Cold(ref mut c) => c.get_mut(&index),
+            Adaptive(ref mut c) => c.get_mut(index),
+            Dense(ref mut c) => c.get_mut(index),
+            Custom(ref mut c) => c.get_mut(index),
+            Single(ref mut c, _) => c.as_mut().and_then(|&mut (i, _, ref mut v)| if i == index { Some(v) } else { None }),
+        }
+    }
+
+    /// Callers check that `a` and `b` differ.
+    fn get_pair_mut(&mut self, a: usize, b: usize) -> Option<(&mut T, &mut T)>
+    {
+        match *self
+        {
+            Hot(ref mut c) => c.get_pair_mut(a, b),
+            Cold(ref mut c) => map_pair_mut(c, a, b),
+            Adaptive(ref mut c) => c.get_pair_mut(a, b),
+            Dense(ref mut c) => c.get_pair_mut(a, b),
+            Custom(ref mut c) => c.get_pair_mut(a, b),
+            // Only one entity can have the component
+            Single(..) => None,
+        }
+    }
+
+    fn contains(&self, index: usize) -> bool
+    {
+        self.get(index).is_some()
+    }
+
+    fn len(&self) -> usize
+    {
+        match *self
+        {
+            Hot(ref c) => c.len(),
+            Cold(ref c) => c.len(),
+            Adaptive(ref c) => c.len(),
+            Dense(ref c) => c.values.len(),
+            Custom(ref c) => c.len(),
+            Single(ref c, _) => if c.is_some() { 1 } else { 0 },
+        }
+    }
+
+    fn capacity(&self) -> usize
+    {
+        match *self
+        {
+            Hot(ref c) => c.capacity(),
+            Cold(ref c) => c.capacity(),
+            Adaptive(ref c) => c.capacity(),
+            Dense(ref c) => c.capacity(),
+            Custom(ref c) => c.capacity(),
+            Single(..) => 1,
+        }
+    }
+
+    fn reserve(&mut self, additional: usize)
+    {
+        match *self
+        {
+            Hot(ref mut c) => c.reserve(additional),
+            Cold(ref mut c) => c.reserve(additional),
+            Adaptive(ref mut c) => c.reserve(additional),
+            Dense(ref mut c) => c.reserve(additional),
+            Custom(ref mut c) => c.reserve(additional),
+            Single(..) => {},
+        }
+    }
+
+    fn shrink_to_fit(&mut self)
+    {
+        match *self
+        {
+            Hot(ref mut c) => c.shrink_to_fit(),
+            Cold(ref mut c) => c.shrink_to_fit(),
+            Adaptive(ref mut c) => c.shrink_to_fit(),
+            Dense(ref mut c) => c.shrink_to_fit(),
+            Custom(ref mut c) => c.shrink_to_fit(),
+            Single(..) => {},
+        }
+    }
+
+    fn memory_bytes(&self) -> usize
+    {
+        match *self
+        {
+            Hot(ref c) => c.memory_bytes(),
+            Cold(ref c) => map_bytes(c),
+            Adaptive(ref c) => c.memory_bytes(),
+            Dense(ref c) => c.memory_bytes(),
+            Custom(ref c) => c.memory_bytes(),
+            Single(..) => 0,
+        }
+    }
+
+    fn for_each_mut<F: FnMut(&mut T)>(&mut self, mut f: F)
+    {
+        match *self
+        {
+            Hot(ref mut c) => c.values_mut().for_each(f),
+            Cold(ref mut c) => c.values_mut().for_each(f),
+            Adaptive(ref mut c) => c.for_each_mut(f),
+            Dense(ref mut c) => c.for_each_mut(f),
+            Custom(ref mut c) => c.for_each_mut(&mut f),
+            Single(ref mut c, _) => if let Some((_, _, ref mut value)) = *c { f(value) },
+        }
+    }
+}
+
+impl<T: MaybeSend + Sync> ComponentStorage<T> for SparseVec<T>
+{
+    fn insert(&mut self, index: usize, component: T) -> Option<T>
+    {
+        SparseVec::insert(self, index, component)
+    }
+
+    fn remove(&mut self, index: usize) -> Option<T>
+    {
+        SparseVec::remove(self, index)
+    }
+
+    fn get(&self, index: usize) -> Option<&T>
+    {
+        SparseVec::get(self, index)
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T>
+    {
+        SparseVec::get_mut(self, index)
+    }
+
+    fn get_pair_mut(&mut self, a: usize, b: usize) -> Option<(&mut T, &mut T)>
+    {
+        SparseVec::get_pair_mut(self, a, b)
+    }
+
+    fn len(&self) -> usize
+    {
+        SparseVec::len(self)
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = (usize, &'a T)> + 'a>
+    {
+        Box::new(SparseVec::iter(self))
+    }
+
+    fn for_each_mut(&mut self, f: &mut dyn FnMut(&mut T))
+    {
+        self.values_mut().for_each(f);
+    }
+
+    fn capacity(&self) -> usize
+    {
+        SparseVec::capacity(self)
+    }
+
+    fn reserve(&mut self, additional: usize)
+    {
+        SparseVec::reserve(self, additional);
+    }
+
+    fn shrink_to_fit(&mut self)
+    {
+        SparseVec::shrink_to_fit(self);
+    }
+
+    fn memory_bytes(&self) -> usize
+    {
+        SparseVec::memory_bytes(self)
+    }
+}
+
+impl<T: MaybeSend + Sync> ComponentStorage<T> for HashMap<usize, T>
+{
+    fn insert(&mut self, index: usize, component: T) -> Option<T>
+    {
+        HashMap::insert(self, index, component)
+    }
+
+    fn remove(&mut self, index: usize) -> Option<T>
+    {
+        HashMap::remove(self, &index)
+    }
+
+    fn get(&self, index: usize) -> Option<&T>
+    {
+        HashMap::get(self, &index)
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T>
+    {
+        HashMap::get_mut(self, &index)
+    }
+
+    fn get_pair_mut(&mut self, a: usize, b: usize) -> Option<(&mut T, &mut T)>
+    {
+        map_pair_mut(self, a, b)
+    }
+
+    fn len(&self) -> usize
+    {
+        HashMap::len(self)
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = (usize, &'a T)> + 'a>
+    {
+        Box::new(HashMap::iter(self).map(|(&index, value)| (index, value)))
+    }
+
+    fn for_each_mut(&mut self, f: &mut dyn FnMut(&mut T))
+    {
+        self.values_mut().for_each(f);
+    }
+
+    fn capacity(&self) -> usize
+    {
+        HashMap::capacity(self)
+    }
+
+    fn reserve(&mut self, additional: usize)
+    {
+        HashMap::reserve(self, additional);
+    }
+
+    fn shrink_to_fit(&mut self)
+    {
+        HashMap::shrink_to_fit(self);
+    }
+
+    fn memory_bytes(&self) -> usize
+    {
+        map_bytes(self)
+    }
+}
+
+/// Storage that allocates components a page at a time and reuses the slots of removed ones, for
+/// `#[storage(Arena<T>)]` fields whose entities come and go in waves.
+///
+/// Pages are never moved or freed while any component is stored, so spawning a wave of entities
+/// after an earlier one was removed doesn't allocate at all.
+pub struct Arena<T>
+{
+    pages: Vec<Vec<Option<T>>>,
+    page_size: usize,
+    /// Slots handed out so far, in use or free.
+    allocated: usize,
+    free: Vec<usize>,
+    /// Slot of each entity's component, or `EMPTY`.
+    slots: Vec<usize>,
+    len: usize,
+}
+
+impl<T> Arena<T>
+{
+    /// Creates an arena with pages of 256 components.
+    pub fn new() -> Arena<T>
+    {
+        Arena::with_page_size(256)
+    }
+
+    /// Panics if `page_size` is zero.
+    pub fn with_page_size(page_size: usize) -> Arena<T>
+    {
+        assert!(page_size > 0, "page size must be at least 1");
+        Arena
+        {
+            pages: Vec::new(),
+            page_size: page_size,
+            allocated: 0,
+            free: Vec::new(),
+            slots: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Number of pages allocated.
+    pub fn pages(&self) -> usize
+    {
+        self.pages.len()
+    }
+
+    fn slot(&self, index: usize) -> Option<usize>
+    {
+        match self.slots.get(index)
+        {
+            Some(&slot) if slot != EMPTY => Some(slot),
+            _ => None,
+        }
+    }
+
+    fn add_page(&mut self)
+    {
+        let page_size = self.page_size;
+        self.pages.push((0..page_size).map(|_| None).collect());
+    }
+}
+
+impl<T> Default for Arena<T>
+{
+    fn default() -> Arena<T>
+    {
+        Arena::new()
+    }
+}
+
+impl<T: MaybeSend + Sync> ComponentStorage<T> for Arena<T>
+{
+    fn insert(&mut self, index: usize, component: T) -> Option<T>
+    {
+        if let Some(slot) = self.slot(index)
+        {
+            return self.pages[slot / self.page_size][slot % self.page_size].replace(component);
+        }
+        let slot = match self.free.pop()
+        {
+            Some(slot) => slot,
+            None => {
+                if self.allocated == self.pages.len() * self.page_size
+                {
+                    self.add_page();
+                }
+                self.allocated += 1;
+                self.allocated - 1
+            },
+        };
+        if index >= self.slots.len()
+        {
+            self.slots.resize(index + 1, EMPTY);
+        }
+        self.slots[index] = slot;
+        self.len += 1;
+        self.pages[slot / self.page_size][slot % self.page_size] = Some(component);
+        None
+    }
+
+    fn remove(&mut self, index: usize) -> Option<T>
+    {
+        let slot = self.slot(index)?;
+        self.slots[index] = EMPTY;
+        self.free.push(slot);
+        self.len -= 1;
+        self.pages[slot / self.page_size][slot % self.page_size].take()
+    }
+
+    fn get(&self, index: usize) -> Option<&T>
+    {
+        let slot = self.slot(index)?;
+        self.pages[slot / self.page_size][slot % self.page_size].as_ref()
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T>
+    {
+        let slot = self.slot(index)?;
+        self.pages[slot / self.page_size][slot % self.page_size].as_mut()
+    }
+
+    fn get_pair_mut(&mut self, a: usize, b: usize) -> Option<(&mut T, &mut T)>
+    {
+        let (a, b) = (self.slot(a)?, self.slot(b)?);
+        let size = self.page_size;
+        let (a, b) = if a / size == b / size
+        {
+            match self.pages[a / size].get_disjoint_mut([a % size, b % size])
+            {
+                Ok([a, b]) => (a, b),
+                Err(_) => return None,
+            }
+        }
+        else
+        {
+            match self.pages.get_disjoint_mut([a / size, b / size])
+            {
+                Ok([page_a, page_b]) => (&mut page_a[a % size], &mut page_b[b % size]),
+                Err(_) => return None,
+            }
+        };
+        match (a, b)
+        {
+            (&mut Some(ref mut a), &mut Some(ref mut b)) => Some((a, b)),
+            _ => None,
+        }
+    }
+
+    fn len(&self) -> usize
+    {
+        self.len
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = (usize, &'a T)> + 'a>
+    {
+        Box::new((0..self.slots.len()).filter_map(move |index| self.get(index).map(|c| (index, c))))
+    }
+
+    fn for_each_mut(&mut self, f: &mut dyn FnMut(&mut T))
+    {
+        self.pages.iter_mut().flatten().flatten().for_each(f);
+    }
+
+    fn capacity(&self) -> usize
+    {
+        self.pages.len() * self.page_size
+    }
+
+    fn reserve(&mut self, additional: usize)
+    {
+        while self.capacity() - self.len < additional
+        {
+            self.add_page();
+        }
+    }
+
+    /// Frees every page once the arena is empty. Otherwise pages stay where they are.
+    fn shrink_to_fit(&mut self)
+    {
+        if self.len == 0
+        {
+            self.pages = Vec::new();
+            self.allocated = 0;
+            self.free = Vec::new();
+            self.slots = Vec::new();
+        }
+    }
+
+    fn memory_bytes(&self) -> usize
+    {
+        self.capacity() * mem::size_of::<Option<T>>()
+            + (self.free.capacity() + self.slots.capacity()) * mem::size_of::<usize>()
+    }
+}
+
+/// Estimate of the bytes a `HashMap` has allocated: one entry and a control byte per bucket.
+fn map_bytes<T>(map: &HashMap<usize, T>) -> usize
+{
+    map.capacity() * (mem::size_of::<(usize, T)>() + 1)
+}
+
+/// Mutably borrows the values of two different keys at once.
+fn map_pair_mut<T>(map: &mut HashMap<usize, T>, a: usize, b: usize) -> Option<(&mut T, &mut T)>
+{
+    match map.get_disjoint_mut([&a, &b])
+    {
+        [Some(a), Some(b)] => Some((a, b)),
+        _ => None,
+    }
+}
+
+impl<C: ComponentManager, T: Component> ComponentList<C, T>
+{
+    pub fn hot() -> ComponentList<C, T>
+    {
+        ComponentList::new(Hot(SparseVec::new()))
+    }
+
+    pub fn cold() -> ComponentList<C, T>
+    {
+        ComponentList::new(Cold(HashMap::new()))
+    }
+
+    /// Storage that starts out as `cold()` and switches to `hot()` once most entities have the
+    /// component, switching back if they lose it again.
+    pub fn adaptive() -> ComponentList<C, T>
+    {
+        ComponentList::new(Adaptive(AdaptiveMap::new()))
+    }
+
+    /// Storage that keeps all components packed together in a `Vec`, for fast iteration.
+    ///
+    /// Removing a component moves the last one into its place, so the order isn't stable.
+    pub fn dense() -> ComponentList<C, T>
+    {
+        ComponentList::new(Dense(DenseVec::new()))
+    }
+
+    /// Storage for a component that at most one entity has at a time.
+    ///
+    /// By default, giving the component to another entity takes it away from its current owner.
+    pub fn singleton() -> ComponentList<C, T>
+    {
+        ComponentList::new(Single(None, SingletonPolicy::Replace))
+    }
+
+    /// Storage provided by the application, see `ComponentStorage`.
+    pub fn storage<S: ComponentStorage<T> + 'static>(storage: S) -> ComponentList<C, T>
+    {
+        ComponentList::new(Custom(Box::new(storage)))
+    }
+
+    fn new(inner: InnerComponentList<T>) -> ComponentList<C, T>
+    {
+        ComponentList
+        {
+            inner: inner,
+            name: "unnamed",
+            on_add: None,
+            on_remove: None,
+            changed: Vec::new(),
+            _manager: PhantomData,
+        }
+    }
+
+    /// Sets the name used to refer to this list in errors.
+    pub fn named(mut self, name: &'static str) -> ComponentList<C, T>
+    {
+        self.name = name;
+        self
+    }
+
+    #[inline]
+    pub fn name(&self) -> &'static str
+    {
+        self.name
+    }
+
+    /// Returns every component of a `#[dense]` list, in no particular order.
+    ///
+    /// Always `None` for other kinds of list.
+    pub fn as_slice(&self) -> Option<&[T]>
+    {
+        match self.inner
+        {
+            Dense(ref c) => Some(&c.values),
+            _ => None,
+        }
+    }
+
+    /// Mutable version of `as_slice()`. The whole slice counts as changed for
+    /// `take_changed_range()`.
+    pub fn as_mut_slice(&mut self) -> Option<&mut [T]>
+    {
+        match self.inner
+        {
+            Dense(ref mut c) => {
+                let len = c.values.len();
+                c.touch(0, len);
+                Some(&mut c.values)
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns the bytes of `as_slice()`, eg: to copy the components straight into a GPU buffer.
+    ///
+    /// Always `None` for lists that aren't `#[dense]`.
+    pub fn as_bytes(&self) -> Option<&[u8]> where T: Pod
+    {
+        self.as_slice().map(|values| unsafe {
+            slice::from_raw_parts(values.as_ptr() as *const u8, mem::size_of_val(values))
+        })
+    }
+
+    /// Returns the positions in `as_slice()` that may have been written since the last call, eg:
+    /// to upload only that part of a GPU buffer, then starts tracking again.
+    ///
+    /// Any mutable access to a component counts as a write, and removing one moves the last
+    /// component into its place. The slice may also have become shorter. Always `None` for lists
+    /// that aren't `#[dense]`.
+    pub fn take_changed_range(&mut self) -> Option<Range<usize>>
+    {
+        match self.inner
+        {
+            Dense(ref mut c) => c.take_changed_range(),
+            _ => None,
+        }
+    }
+
+    /// Returns the entity that has the component, if this is a `#[singleton]` list.
+    ///
+    /// Always `None` for other kinds of list.
+    pub fn get_owner(&self) -> Option<Entity>
+    {
+        match self.inner
+        {
+            Single(ref c, _) => c.as_ref().map(|&(_, owner, _)| owner),
+            _ => None,
+        }
+    }
+
+    /// Changes what happens when a second entity is given a `#[singleton]` component.
+    ///
+    /// Does nothing for other kinds of list.
+    pub fn set_singleton_policy(&mut self, policy: SingletonPolicy)
+    {
+        if let Single(_, ref mut p) = self.inner
+        {
+            *p = policy;
+        }
+    }
+
+    /// Sets callbacks that run whenever a component is added to or removed from an entity.
+    ///
+    /// Replacing a component counts as removing the old one and adding the new one. Components
+    /// that are cleared because their entity was removed also run `on_remove`.
+    pub fn set_hooks<A, R>(&mut self, on_add: A, on_remove: R)
+        where A: FnMut(Entity, &mut T) + MaybeSend + 'static, R: FnMut(Entity, &mut T) + MaybeSend + 'static
+    {
+        self.on_add = Some(Box::new(on_add));
+        self.on_remove = Some(Box::new(on_remove));
+    }
+
+    /// Removes any callbacks set with `set_hooks()`.
+    pub fn clear_hooks(&mut self)
+    {
+        self.on_add = None;
+        self.on_remove = None;
+    }
+
+    /// Same as `set()`, for entities being built.
+    pub fn add(&mut self, entity: &BuildData<C>, component: T) -> Option<T>
+    {
+        self.set(entity, component)
+    }
+
+    /// Same as `set()`, for entities being modified.
+    pub fn insert(&mut self, entity: &ModifyData<C>, component: T) -> Option<T>
+    {
+        self.set(entity, component)
+    }
+
+    pub fn remove(&mut self, entity: &ModifyData<C>) -> Option<T>
+    {
+        let old = self.remove_at(entity.entity());
+        if old.is_some()
+        {
+            self.changed.push(**entity.entity());
+        }
+        old
+    }
+
+    /// Adds or replaces an entity's component, returning the old one.
+    pub fn set<U: EditData<C>>(&mut self, entity: &U, component: T) -> Option<T>
+    {
+        self.insert_tracked(entity.entity(), component)
+    }
+
+    /// Like `set()`, but returns `Error::SingletonTaken` instead of panicking when another entity
+    /// has a `#[singleton]` component with `SingletonPolicy::Panic`, which it keeps.
+    pub fn try_set<U: EditData<C>>(&mut self, entity: &U, component: T) -> Result<Option<T>>
+    {
+        self.check_owner(entity.entity())?;
+        Ok(self.set(entity, component))
+    }
+
+    pub fn get<U: EditData<C>>(&self, entity: &U) -> Option<T> where T: Clone
+    {
+        self.inner.get(entity.entity().index()).cloned()
+    }
+
+    /// Like `get()`, but reports which component was missing.
+    pub fn try_get<U: EditData<C>>(&self, entity: &U) -> Result<T> where T: Clone
+    {
+        self.try_index(entity).cloned()
+    }
+
+    /// Like indexing, but returns an error instead of panicking.
+    pub fn try_index<U: EditData<C>>(&self, entity: &U) -> Result<&T>
+    {
+        self.inner.get(entity.entity().index()).ok_or(Error::MissingComponent { entity: **entity.entity(), component_name: self.name })
+    }
+
+    /// Like mutable indexing, but returns an error instead of panicking.
+    pub fn try_index_mut<U: EditData<C>>(&mut self, entity: &U) -> Result<&mut T>
+    {
+        let name = self.name;
+        self.inner.get_mut(entity.entity().index()).ok_or(Error::MissingComponent { entity: **entity.entity(), component_name: name })
+    }
+
+    /// Number of entities that have the component.
+    pub fn len(&self) -> usize
+    {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.len() == 0
+    }
+
+    /// Number of components that can be stored without reallocating.
+    ///
+    /// For `#[hot]` lists this is in terms of entity indices rather than a count.
+    pub fn capacity(&self) -> usize
+    {
+        self.inner.capacity()
+    }
+
+    /// Makes room for at least `additional` more components.
+    pub fn reserve(&mut self, additional: usize)
+    {
+        self.inner.reserve(additional);
+    }
+
+    /// Frees as much unused storage as possible.
+    pub fn shrink_to_fit(&mut self)
+    {
+        self.inner.shrink_to_fit();
+    }
+
+    /// Approximate number of bytes allocated on the heap for the components.
+    pub fn memory_bytes(&self) -> usize
+    {
+        self.inner.memory_bytes()
+    }
+
+    /// Hashes whether the entity has the component, and the component itself if it does.
+    pub fn hash_entity<H: Hasher>(&self, entity: &IndexedEntity<C>, state: &mut H) where T: Hash
+    {
+        self.inner.get(entity.index()).hash(state);
+    }
+
+    /// Gives an entity a component read from serialized data.
+    #[cfg(feature = "serde")]
+    pub unsafe fn deserialize(&mut self, entity: &IndexedEntity<C>, value: ::scene::Value) -> Result<()>
+        where T: ::serde::de::DeserializeOwned
+    {
+        match ::serde_json::from_value(value)
+        {
+            Ok(component) => {
+                self.insert_tracked(entity, component);
+                Ok(())
+            },
+            Err(err) => Err(Error::Deserialize { component_name: self.name, message: err.to_string() }),
+        }
+    }
+
+    /// Serializes an entity's component, if it has one.
+    #[cfg(feature = "serde")]
+    pub fn serialize(&self, entity: &IndexedEntity<C>) -> Option<Result<::scene::Value>>
+        where T: ::serde::Serialize
+    {
+        self.inner.get(entity.index()).map(|component| {
+            ::serde_json::to_value(component).map_err(|err| {
+                Error::Serialize { component_name: self.name, message: err.to_string() }
+            })
+        })
+    }
+
+    /// Clears every `EntityRef` in the list that refers to an entity for which `removed` is true.
+    pub fn clear_refs(&mut self, removed: &dyn Fn(&Entity) -> bool) where T: EntityRefs
+    {
+        self.inner.for_each_mut(|component| { component.clear_refs(removed); });
+    }
+
+    /// Gets an entity's place in the list, for in-place access or lazy insertion.
+    pub fn entry<'a, U: EditData<C>>(&'a mut self, entity: &'a U) -> Entry<'a, C, T>
+    {
+        Entry
+        {
+            list: self,
+            entity: entity.entity(),
+        }
+    }
+
+    pub fn has<U: EditData<C>>(&self, entity: &U) -> bool
+    {
+        self.inner.contains(entity.entity().index())
+    }
+
+    /// Same as `has()`, for the component manager.
+    pub fn contains(&self, entity: &IndexedEntity<C>) -> bool
+    {
+        self.inner.contains(entity.index())
+    }
+
+    pub fn borrow<U: EditData<C>>(&mut self, entity: &U) -> Option<&mut T>
+    {
+        self.inner.get_mut(entity.entity().index())
+    }
+
+    /// Moves the entities that gained or lost this component since the last call into `changed`.
+    pub fn take_changed(&mut self, changed: &mut Vec<Entity>)
+    {
+        changed.append(&mut self.changed);
+    }
+
+    /// Mutably borrows the components of two different entities at once.
+    ///
+    /// Returns `None` if either entity lacks the component, or if both are the same entity.
+    pub fn get_pair_mut<U: EditData<C>, V: EditData<C>>(&mut self, a: &U, b: &V) -> Option<(&mut T, &mut T)>
+    {
+        let (a, b) = (a.entity().index(), b.entity().index());
+        if a == b
+        {
+            return None;
+        }
+        self.inner.get_pair_mut(a, b)
+    }
+
+    pub unsafe fn clear(&mut self, entity: &IndexedEntity<C>)
+    {
+        self.remove_at(entity);
+    }
+
+    /// The entity's component as shown in an inspector, if it has one.
+    pub fn inspect(&self, entity: &IndexedEntity<C>) -> Option<InspectValue> where T: InspectComponent
+    {
+        self.inner.get(entity.index()).map(|component| component.inspect())
+    }
+
+    /// The entity's component, for reading its fields by name.
+    pub fn reflect(&self, entity: &IndexedEntity<C>) -> Option<&dyn Reflect> where T: Reflect
+    {
+        self.inner.get(entity.index()).map(|component| component as &dyn Reflect)
+    }
+
+    /// The entity's component, for writing its fields by name.
+    pub fn reflect_mut(&mut self, entity: &IndexedEntity<C>) -> Option<&mut dyn Reflect> where T: Reflect
+    {
+        self.inner.get_mut(entity.index()).map(|component| component as &mut dyn Reflect)
+    }
+
+    /// Moves an entity's component to an entity of another world.
+    pub unsafe fn transfer(&mut self, from: &IndexedEntity<C>, to: &mut ComponentList<C, T>, into: &IndexedEntity<C>)
+    {
+        if let Some(component) = self.remove_at(from)
+        {
+            to.insert_tracked(into, component);
+        }
+    }
+
+    /// Moves an entity's component to a new index, without calling hooks or recording a change.
+    pub unsafe fn remap(&mut self, from: &IndexedEntity<C>, to: &IndexedEntity<C>)
+    {
+        if let Some(component) = self.inner.remove(from.index())
+        {
+            self.inner.insert(to.index(), **to, component);
+        }
+    }
+
+    fn insert_tracked(&mut self, entity: &IndexedEntity<C>, component: T) -> Option<T>
+    {
+        let old = self.insert_at(entity, component);
+        if old.is_none()
+        {
+            self.changed.push(**entity);
+        }
+        old
+    }
+
+    fn insert_at(&mut self, entity: &IndexedEntity<C>, component: T) -> Option<T>
+    {
+        self.displace_owner(entity);
+        let mut old = self.inner.insert(entity.index(), **entity, component);
+        if let (Some(old), Some(hook)) = (old.as_mut(), self.on_remove.as_mut())
+        {
+            hook(**entity, old);
+        }
+        if let Some(ref mut hook) = self.on_add
+        {
+            hook(**entity, self.inner.get_mut(entity.index()).unwrap());
+        }
+        old
+    }
+
+    /// Takes a `#[singleton]` component away from its owner before another entity is given it.
+    ///
+    /// With `SingletonPolicy::Panic`, it panics while the owner still has the component.
+    fn displace_owner(&mut self, entity: &IndexedEntity<C>)
+    {
+        if let Err(err) = self.check_owner(entity)
+        {
+            panic!("{}", err);
+        }
+        let displaced = match self.inner
+        {
+            Single(ref mut c, _) if c.as_ref().is_some_and(|&(index, _, _)| index != entity.index()) => {
+                c.take().map(|(_, owner, old)| (owner, old))
+            },
+            _ => None,
+        };
+        if let Some((owner, mut old)) = displaced
+        {
+            if let Some(ref mut hook) = self.on_remove
+            {
+                hook(owner, &mut old);
+            }
+            self.changed.push(owner);
+        }
+    }
+
+    /// Fails if the policy won't let a `#[singleton]` component be taken from another entity.
+    fn check_owner(&self, entity: &IndexedEntity<C>) -> Result<()>
+    {
+        match self.inner
+        {
+            Single(Some((index, owner, _)), SingletonPolicy::Panic) if index != entity.index() => {
+                Err(Error::SingletonTaken { component_name: self.name, owner: owner })
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn remove_at(&mut self, entity: &IndexedEntity<C>) -> Option<T>
+    {
+        let mut old = self.inner.remove(entity.index());
+        if let (Some(old), Some(hook)) = (old.as_mut(), self.on_remove.as_mut())
+        {
+            hook(**entity, old);
+        }
+        old
+    }
+}
+
+impl<C: ComponentManager, T: Component, U: EditData<C>> Index<U> for ComponentList<C, T>
+{
+    type Output = T;
+    fn index(&self, en: U) -> &T
+    {
+        match self.try_index(&en)
+        {
+            Ok(c) => c,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+impl<C: ComponentManager, T: Component, U: EditData<C>> IndexMut<U> for ComponentList<C, T>
+{
+    fn index_mut(&mut self, en: U) -> &mut T
+    {
+        match self.try_index_mut(&en)
+        {
+            Ok(c) => c,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+/// Implemented by every kind of component list, so `DataHelper::has_component()` can check any
+/// field.
+pub trait ComponentAccess<C: ComponentManager>
+{
+    fn contains(&self, entity: &IndexedEntity<C>) -> bool;
+}
+
+/// Implemented by the component lists that can lend out an entity's component, for
+/// `DataHelper::component()`.
+pub trait ComponentRef<C: ComponentManager>: ComponentAccess<C>
+{
+    type Component;
+    fn component(&self, entity: &IndexedEntity<C>) -> Option<&Self::Component>;
+}
+
+impl<C: ComponentManager, T: Component> ComponentAccess<C> for ComponentList<C, T>
+{
+    fn contains(&self, entity: &IndexedEntity<C>) -> bool
+    {
+        self.contains(entity)
+    }
+}
+
+impl<C: ComponentManager, T: Component> ComponentRef<C> for ComponentList<C, T>
+{
+    type Component = T;
+    fn component(&self, entity: &IndexedEntity<C>) -> Option<&T>
+    {
+        self.inner.get(entity.index())
+    }
+}
+
+impl<C: ComponentManager, T: Component> ComponentAccess<C> for FlagList<C, T>
+{
+    fn contains(&self, entity: &IndexedEntity<C>) -> bool
+    {
+        self.contains(entity)
+    }
+}
+
+impl<C: ComponentManager, T: Component> ComponentAccess<C> for SharedList<C, T>
+{
+    fn contains(&self, entity: &IndexedEntity<C>) -> bool
+    {
+        self.contains(entity)
+    }
+}
+
+impl<C: ComponentManager, T: Component> ComponentRef<C> for SharedList<C, T>
+{
+    type Component = T;
+    fn component(&self, entity: &IndexedEntity<C>) -> Option<&T>
+    {
+        self.inner.get(entity.index()).map(|component| &**component)
+    }
+}
+
+impl<C: ComponentManager, T: SoaComponent> ComponentAccess<C> for SoaList<C, T>
+{
+    fn contains(&self, entity: &IndexedEntity<C>) -> bool
+    {
+        self.contains(entity)
+    }
+}
+
+impl<C: ComponentManager, N: ComponentManager> ComponentAccess<C> for NestedList<C, N>
+{
+    fn contains(&self, entity: &IndexedEntity<C>) -> bool
+    {
+        self.contains(entity)
+    }
+}
+
+/// Storage for marker components, which only records whether each entity has the component.
+///
+/// Values passed in are discarded, so this is meant for zero-sized types.
+pub struct FlagList<C: ComponentManager, T: Component>
+{
+    bits: BitSet,
+    name: &'static str,
+    changed: Vec<Entity>,
+    _marker: PhantomData<fn(C, T)>,
+}
+
+impl<C: ComponentManager, T: Component> FlagList<C, T>
+{
+    pub fn flag() -> FlagList<C, T>
+    {
+        FlagList
+        {
+            bits: BitSet::new(),
+            name: "unnamed",
+            changed: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Sets the name used to refer to this list in errors.
+    pub fn named(mut self, name: &'static str) -> FlagList<C, T>
+    {
+        self.name = name;
+        self
+    }
+
+    #[inline]
+    pub fn name(&self) -> &'static str
+    {
+        self.name
+    }
+
+    /// Same as `set()`, for entities being built.
+    pub fn add(&mut self, entity: &BuildData<C>, flag: T) -> bool
+    {
+        self.set(entity, flag)
+    }
+
+    /// Same as `set()`, for entities being modified.
+    pub fn insert(&mut self, entity: &ModifyData<C>, flag: T) -> bool
+    {
+        self.set(entity, flag)
+    }
+
+    /// Flags an entity. Returns whether it was already flagged.
+    pub fn set<U: EditData<C>>(&mut self, entity: &U, _: T) -> bool
+    {
+        self.insert_at(entity.entity())
+    }
+
+    /// Unflags an entity. Returns whether it was flagged.
+    pub fn remove(&mut self, entity: &ModifyData<C>) -> bool
+    {
+        let had = self.bits.remove(entity.entity().index());
+        if had
+        {
+            self.changed.push(**entity.entity());
+        }
+        had
+    }
+
+    #[inline]
+    pub fn has<U: EditData<C>>(&self, entity: &U) -> bool
+    {
+        self.bits.contains(entity.entity().index())
+    }
+
+    /// Same as `has()`, for the component manager.
+    pub fn contains(&self, entity: &IndexedEntity<C>) -> bool
+    {
+        self.bits.contains(entity.index())
+    }
+
+    /// Moves the entities that gained or lost this flag since the last call into `changed`.
+    pub fn take_changed(&mut self, changed: &mut Vec<Entity>)
+    {
+        changed.append(&mut self.changed);
+    }
+
+    /// Flags an entity, after checking that the serialized data is a valid component.
+    #[cfg(feature = "serde")]
+    pub unsafe fn deserialize(&mut self, entity: &IndexedEntity<C>, value: ::scene::Value) -> Result<()>
+        where T: ::serde::de::DeserializeOwned
+    {
+        match ::serde_json::from_value::<T>(value)
+        {
+            Ok(_) => {
+                self.insert_at(entity);
+                Ok(())
+            },
+            Err(err) => Err(Error::Deserialize { component_name: self.name, message: err.to_string() }),
+        }
+    }
+
+    /// Serializes an entity's flag as `null`, if it has one.
+    #[cfg(feature = "serde")]
+    pub fn serialize(&self, entity: &IndexedEntity<C>) -> Option<Result<::scene::Value>>
+    {
+        if self.bits.contains(entity.index()) { Some(Ok(::scene::Value::Null)) } else { None }
+    }
+
+    /// Hashes whether the entity is flagged.
+    pub fn hash_entity<H: Hasher>(&self, entity: &IndexedEntity<C>, state: &mut H)
+    {
+        self.bits.contains(entity.index()).hash(state);
+    }
+
+    /// Number of flagged entities.
+    pub fn len(&self) -> usize
+    {
+        self.bits.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.len() == 0
+    }
+
+    /// Number of entity indices that can be flagged without reallocating.
+    pub fn capacity(&self) -> usize
+    {
+        self.bits.capacity()
+    }
+
+    /// Makes room to flag at least `additional` more entity indices.
+    pub fn reserve(&mut self, additional: usize)
+    {
+        let len = self.bits.words.len() * 64;
+        self.bits.reserve(len + additional);
+    }
+
+    /// Frees the storage past the highest flagged entity index.
+    pub fn shrink_to_fit(&mut self)
+    {
+        self.bits.shrink_to_fit();
+    }
+
+    /// Number of bytes allocated on the heap for the flags.
+    pub fn memory_bytes(&self) -> usize
+    {
+        self.bits.memory_bytes()
+    }
+
+    pub unsafe fn clear(&mut self, entity: &IndexedEntity<C>)
+    {
+        self.bits.remove(entity.index());
+    }
+
+    /// Shows the flag in an inspector as `true`, if the entity has it.
+    pub fn inspect(&self, entity: &IndexedEntity<C>) -> Option<InspectValue>
+    {
+        if self.bits.contains(entity.index()) { Some(InspectValue::Bool(true)) } else { None }
+    }
+
+    /// Moves an entity's flag to an entity of another world.
+    pub unsafe fn transfer(&mut self, from: &IndexedEntity<C>, to: &mut FlagList<C, T>, into: &IndexedEntity<C>)
+    {
+        if self.bits.remove(from.index())
+        {
+            to.insert_at(into);
+        }
+    }
+
+    /// Moves an entity's flag to a new index, without recording a change.
+    pub unsafe fn remap(&mut self, from: &IndexedEntity<C>, to: &IndexedEntity<C>)
+    {
+        if self.bits.remove(from.index())
+        {
+            self.bits.insert(to.index());
+        }
+    }
+
+    fn insert_at(&mut self, entity: &IndexedEntity<C>) -> bool
+    {
+        let had = self.bits.insert(entity.index());
+        if !had
+        {
+            self.changed.push(**entity);
+        }
+        had
+    }
+}
+
+/// Storage for `#[shared]` components, where many entities can refer to the same immutable value,
+/// eg: the material of every tile made of stone.
+///
+/// Each entity holds an `Arc` to its value. `share()` gives an entity a value another entity
+/// already has, and `make_mut()` gives an entity its own copy before changing it.
+pub struct SharedList<C: ComponentManager, T: Component>
+{
+    inner: SparseVec<Arc<T>>,
+    name: &'static str,
+    changed: Vec<Entity>,
+    _manager: PhantomData<fn(C)>,
+}
+
+impl<C: ComponentManager, T: Component> SharedList<C, T>
+{
+    pub fn shared() -> SharedList<C, T>
+    {
+        SharedList
+        {
+            inner: SparseVec::new(),
+            name: "unnamed",
+            changed: Vec::new(),
+            _manager: PhantomData,
+        }
+    }
+
+    /// Sets the name used to refer to this list in errors.
+    pub fn named(mut self, name: &'static str) -> SharedList<C, T>
+    {
+        self.name = name;
+        self
+    }
+
+    #[inline]
+    pub fn name(&self) -> &'static str
+    {
+        self.name
+    }
+
+    /// Same as `set()`, for entities being built.
+    pub fn add(&mut self, entity: &BuildData<C>, component: T) -> Option<Arc<T>>
+    {
+        self.set(entity, component)
+    }
+
+    /// Same as `set()`, for entities being modified.
+    pub fn insert(&mut self, entity: &ModifyData<C>, component: T) -> Option<Arc<T>>
+    {
+        self.set(entity, component)
+    }
+
+    /// Gives an entity a value of its own, returning the old one.
+    pub fn set<U: EditData<C>>(&mut self, entity: &U, component: T) -> Option<Arc<T>>
+    {
+        self.share(entity, Arc::new(component))
+    }
+
+    /// Gives an entity a value that can also belong to other entities, eg: one from `get()`.
+    pub fn share<U: EditData<C>>(&mut self, entity: &U, component: Arc<T>) -> Option<Arc<T>>
+    {
+        self.insert_tracked(entity.entity(), component)
+    }
+
+    pub fn remove(&mut self, entity: &ModifyData<C>) -> Option<Arc<T>>
+    {
+        let old = self.inner.remove(entity.entity().index());
+        if old.is_some()
+        {
+            self.changed.push(**entity.entity());
+        }
+        old
+    }
+
+    /// A handle to the entity's value, for sharing it with other entities.
+    pub fn get<U: EditData<C>>(&self, entity: &U) -> Option<Arc<T>>
+    {
+        self.inner.get(entity.entity().index()).cloned()
+    }
+
+    /// Like indexing, but returns an error instead of panicking.
+    pub fn try_index<U: EditData<C>>(&self, entity: &U) -> Result<&T>
+    {
+        self.inner.get(entity.entity().index()).map(|component| &**component)
+            .ok_or(Error::MissingComponent { entity: **entity.entity(), component_name: self.name })
+    }
+
+    /// The entity's value for changing, copying it first if other entities share it.
+    pub fn make_mut<U: EditData<C>>(&mut self, entity: &U) -> Option<&mut T> where T: Clone
+    {
+        self.inner.get_mut(entity.entity().index()).map(Arc::make_mut)
+    }
+
+    pub fn has<U: EditData<C>>(&self, entity: &U) -> bool
+    {
+        self.inner.contains_key(entity.entity().index())
+    }
+
+    /// Same as `has()`, for the component manager.
+    pub fn contains(&self, entity: &IndexedEntity<C>) -> bool
+    {
+        self.inner.contains_key(entity.index())
+    }
+
+    /// Moves the entities that gained or lost this component since the last call into `changed`.
+    pub fn take_changed(&mut self, changed: &mut Vec<Entity>)
+    {
+        changed.append(&mut self.changed);
+    }
+
+    /// Gives an entity a value read from serialized data, not shared with any other entity.
+    #[cfg(feature = "serde")]
+    pub unsafe fn deserialize(&mut self, entity: &IndexedEntity<C>, value: ::scene::Value) -> Result<()>
+        where T: ::serde::de::DeserializeOwned
+    {
+        match ::serde_json::from_value(value)
+        {
+            Ok(component) => {
+                self.insert_tracked(entity, Arc::new(component));
+                Ok(())
+            },
+            Err(err) => Err(Error::Deserialize { component_name: self.name, message: err.to_string() }),
+        }
+    }
+
+    /// Serializes an entity's value, if it has one.
+    #[cfg(feature = "serde")]
+    pub fn serialize(&self, entity: &IndexedEntity<C>) -> Option<Result<::scene::Value>>
+        where T: ::serde::Serialize
+    {
+        self.inner.get(entity.index()).map(|component| {
+            ::serde_json::to_value(&**component).map_err(|err| {
+                Error::Serialize { component_name: self.name, message: err.to_string() }
+            })
+        })
+    }
+
+    /// Hashes whether the entity has the component, and its value if it does.
+    pub fn hash_entity<H: Hasher>(&self, entity: &IndexedEntity<C>, state: &mut H) where T: Hash
+    {
+        self.inner.get(entity.index()).hash(state);
+    }
+
+    /// Number of entities that have the component.
+    pub fn len(&self) -> usize
+    {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.len() == 0
+    }
+
+    /// Number of entity indices that can have the component without reallocating.
+    pub fn capacity(&self) -> usize
+    {
+        self.inner.capacity()
+    }
+
+    /// Makes room for at least `additional` more entity indices.
+    pub fn reserve(&mut self, additional: usize)
+    {
+        self.inner.reserve(additional);
+    }
+
+    /// Frees the storage past the highest entity index with the component.
+    pub fn shrink_to_fit(&mut self)
+    {
+        self.inner.shrink_to_fit();
+    }
+
+    /// Approximate number of bytes allocated on the heap, counting each distinct value once.
+    pub fn memory_bytes(&self) -> usize
+    {
+        let values: HashSet<*const T> = self.inner.iter().map(|(_, component)| Arc::as_ptr(component)).collect();
+        self.inner.memory_bytes() + values.len() * mem::size_of::<T>()
+    }
+
+    pub unsafe fn clear(&mut self, entity: &IndexedEntity<C>)
+    {
+        self.inner.remove(entity.index());
+    }
+
+    /// The entity's value as shown in an inspector, if it has one.
+    pub fn inspect(&self, entity: &IndexedEntity<C>) -> Option<InspectValue> where T: InspectComponent
+    {
+        self.inner.get(entity.index()).map(|component| component.inspect())
+    }
+
+    /// Moves an entity's value to an entity of another world, still shared with the same entities.
+    pub unsafe fn transfer(&mut self, from: &IndexedEntity<C>, to: &mut SharedList<C, T>, into: &IndexedEntity<C>)
+    {
+        if let Some(component) = self.inner.remove(from.index())
+        {
+            to.insert_tracked(into, component);
+        }
+    }
+
+    /// Moves an entity's value to a new index, without recording a change.
+    pub unsafe fn remap(&mut self, from: &IndexedEntity<C>, to: &IndexedEntity<C>)
+    {
+        if let Some(component) = self.inner.remove(from.index())
+        {
+            self.inner.insert(to.index(), component);
+        }
+    }
+
+    fn insert_tracked(&mut self, entity: &IndexedEntity<C>, component: Arc<T>) -> Option<Arc<T>>
+    {
+        let old = self.inner.insert(entity.index(), component);
+        if old.is_none()
+        {
+            self.changed.push(**entity);
+        }
+        old
+    }
+}
+
+impl<C: ComponentManager, T: Component, U: EditData<C>> Index<U> for SharedList<C, T>
+{
+    type Output = T;
+    fn index(&self, en: U) -> &T
+    {
+        match self.try_index(&en)
+        {
+            Ok(c) => c,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+/// Implemented by components stored in `#[soa]` fields, usually with `soa_struct!`.
+pub trait SoaComponent: Sized + 'static
+{
+    /// One `Vec` for each of the struct's fields.
+    type Columns: SoaColumns<Self>;
+}
+
+/// Parallel arrays holding the fields of `T`, all of the same length.
+pub trait SoaColumns<T>: Default
+{
+    fn push(&mut self, value: T);
+    /// Removes the value at `pos`, moving the last one into its place.
+    fn swap_remove(&mut self, pos: usize) -> T;
+    /// Reassembles the value at `pos`.
+    fn get(&self, pos: usize) -> T;
+    fn replace(&mut self, pos: usize, value: T) -> T;
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool
+    {
+        self.len() == 0
+    }
+    fn capacity(&self) -> usize;
+    fn reserve(&mut self, additional: usize);
+    fn shrink_to_fit(&mut self);
+    fn memory_bytes(&self) -> usize;
+}
+
+/// Storage for `#[soa]` components, which keeps each field of the component in its own packed
+/// array, eg: `c.position.columns().x` holds the `x` of every position.
+///
+/// Kernels that go over one or two fields of every component (eg: integrating positions) can
+/// work on the columns directly, which vectorizes well. `position()` finds an entity's place in
+/// the columns, and `entities()` says whose component is at each place. Like `#[dense]` lists,
+/// removing a component moves the last one into its place.
+pub struct SoaList<C: ComponentManager, T: SoaComponent>
+{
+    columns: T::Columns,
+    entities: Vec<Entity>,
+    /// Entity index of each component.
+    owners: Vec<usize>,
+    /// Position of each entity's component, or `EMPTY`.
+    slots: Vec<usize>,
+    name: &'static str,
+    changed: Vec<Entity>,
+    _manager: PhantomData<fn(C)>,
+}
+
+impl<C: ComponentManager, T: SoaComponent> SoaList<C, T>
+{
+    pub fn soa() -> SoaList<C, T>
+    {
+        SoaList
+        {
+            columns: T::Columns::default(),
+            entities: Vec::new(),
+            owners: Vec::new(),
+            slots: Vec::new(),
+            name: "unnamed",
+            changed: Vec::new(),
+            _manager: PhantomData,
+        }
+    }
+
+    /// Sets the name used to refer to this list in errors.
+    pub fn named(mut self, name: &'static str) -> SoaList<C, T>
+    {
+        self.name = name;
+        self
+    }
+
+    #[inline]
+    pub fn name(&self) -> &'static str
+    {
+        self.name
+    }
+
+    /// Same as `set()`, for entities being built.
+    pub fn add(&mut self, entity: &BuildData<C>, component: T) -> Option<T>
+    {
+        self.set(entity, component)
+    }
+
+    /// Same as `set()`, for entities being modified.
+    pub fn insert(&mut self, entity: &ModifyData<C>, component: T) -> Option<T>
+    {
+        self.set(entity, component)
+    }
+
+    /// Adds or replaces an entity's component, returning the old one.
+    pub fn set<U: EditData<C>>(&mut self, entity: &U, component: T) -> Option<T>
+    {
+        self.insert_tracked(entity.entity(), component)
+    }
+
+    pub fn remove(&mut self, entity: &ModifyData<C>) -> Option<T>
+    {
+        let old = self.remove_at(entity.entity());
+        if old.is_some()
+        {
+            self.changed.push(**entity.entity());
+        }
+        old
+    }
+
+    /// Reassembles an entity's component from the columns.
+    pub fn get<U: EditData<C>>(&self, entity: &U) -> Option<T>
+    {
+        self.position(entity).map(|pos| self.columns.get(pos))
+    }
+
+    /// Where the entity's component is in the columns.
+    pub fn position<U: EditData<C>>(&self, entity: &U) -> Option<usize>
+    {
+        self.position_at(entity.entity())
+    }
+
+    pub fn has<U: EditData<C>>(&self, entity: &U) -> bool
+    {
+        self.position(entity).is_some()
+    }
+
+    /// Same as `has()`, for the component manager.
+    pub fn contains(&self, entity: &IndexedEntity<C>) -> bool
+    {
+        self.position_at(entity).is_some()
+    }
+
+    pub fn columns(&self) -> &T::Columns
+    {
+        &self.columns
+    }
+
+    /// The columns, for changing components in place. Their lengths must not be changed.
+    pub fn columns_mut(&mut self) -> &mut T::Columns
+    {
+        &mut self.columns
+    }
+
+    /// The entity whose component is at each position of the columns.
+    pub fn entities(&self) -> &[Entity]
+    {
+        &self.entities
+    }
+
+    /// Moves the entities that gained or lost this component since the last call into `changed`.
+    pub fn take_changed(&mut self, changed: &mut Vec<Entity>)
+    {
+        changed.append(&mut self.changed);
+    }
+
+    /// Gives an entity a component read from serialized data.
+    #[cfg(feature = "serde")]
+    pub unsafe fn deserialize(&mut self, entity: &IndexedEntity<C>, value: ::scene::Value) -> Result<()>
+        where T: ::serde::de::DeserializeOwned
+    {
+        match ::serde_json::from_value(value)
+        {
+            Ok(component) => {
+                self.insert_tracked(entity, component);
+                Ok(())
+            },
+            Err(err) => Err(Error::Deserialize { component_name: self.name, message: err.to_string() }),
+        }
+    }
+
+    /// Serializes an entity's component, if it has one.
+    #[cfg(feature = "serde")]
+    pub fn serialize(&self, entity: &IndexedEntity<C>) -> Option<Result<::scene::Value>>
+        where T: ::serde::Serialize
+    {
+        self.position_at(entity).map(|pos| {
+            ::serde_json::to_value(self.columns.get(pos)).map_err(|err| {
+                Error::Serialize { component_name: self.name, message: err.to_string() }
+            })
+        })
+    }
+
+    /// Hashes whether the entity has the component, and the component itself if it does.
+    pub fn hash_entity<H: Hasher>(&self, entity: &IndexedEntity<C>, state: &mut H) where T: Hash
+    {
+        self.position_at(entity).map(|pos| self.columns.get(pos)).hash(state);
+    }
+
+    /// The entity's component as shown in an inspector, if it has one.
+    pub fn inspect(&self, entity: &IndexedEntity<C>) -> Option<InspectValue> where T: InspectComponent
+    {
+        self.position_at(entity).map(|pos| self.columns.get(pos).inspect())
+    }
+
+    /// Number of entities that have the component.
+    pub fn len(&self) -> usize
+    {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.len() == 0
+    }
+
+    /// Number of components that can be stored without reallocating.
+    pub fn capacity(&self) -> usize
+    {
+        self.columns.capacity()
+    }
+
+    /// Makes room for at least `additional` more components.
+    pub fn reserve(&mut self, additional: usize)
+    {
+        self.columns.reserve(additional);
+        self.entities.reserve(additional);
+        self.owners.reserve(additional);
+    }
+
+    /// Frees as much unused storage as possible.
+    pub fn shrink_to_fit(&mut self)
+    {
+        let len = self.slots.iter().rposition(|&pos| pos != EMPTY).map_or(0, |index| index + 1);
+        self.slots.truncate(len);
+        self.slots.shrink_to_fit();
+        self.columns.shrink_to_fit();
+        self.entities.shrink_to_fit();
+        self.owners.shrink_to_fit();
+    }
+
+    /// Approximate number of bytes allocated on the heap for the components.
+    pub fn memory_bytes(&self) -> usize
+    {
+        self.columns.memory_bytes()
+            + self.entities.capacity() * mem::size_of::<Entity>()
+            + (self.owners.capacity() + self.slots.capacity()) * mem::size_of::<usize>()
+    }
+
+    pub unsafe fn clear(&mut self, entity: &IndexedEntity<C>)
+    {
+        self.remove_at(entity);
+    }
+
+    /// Moves an entity's component to an entity of another world.
+    pub unsafe fn transfer(&mut self, from: &IndexedEntity<C>, to: &mut SoaList<C, T>, into: &IndexedEntity<C>)
+    {
+        if let Some(component) = self.remove_at(from)
+        {
+            to.insert_tracked(into, component);
+        }
+    }
+
+    /// Moves an entity's component to a new index, without recording a change.
+    pub unsafe fn remap(&mut self, from: &IndexedEntity<C>, to: &IndexedEntity<C>)
+    {
+        if let Some(pos) = self.position_at(from)
+        {
+            self.slots[from.index()] = EMPTY;
+            if to.index() >= self.slots.len()
+            {
+                self.slots.resize(to.index() + 1, EMPTY);
+            }
+            self.slots[to.index()] = pos;
+            self.owners[pos] = to.index();
+        }
+    }
+
+    #[inline]
+    fn position_at(&self, entity: &IndexedEntity<C>) -> Option<usize>
+    {
+        match self.slots.get(entity.index())
+        {
+            Some(&pos) if pos != EMPTY => Some(pos),
+            _ => None,
+        }
+    }
+
+    fn insert_tracked(&mut self, entity: &IndexedEntity<C>, component: T) -> Option<T>
+    {
+        if let Some(pos) = self.position_at(entity)
+        {
+            return Some(self.columns.replace(pos, component));
+        }
+        if entity.index() >= self.slots.len()
+        {
+            self.slots.resize(entity.index() + 1, EMPTY);
+        }
+        self.slots[entity.index()] = self.entities.len();
+        self.columns.push(component);
+        self.entities.push(**entity);
+        self.owners.push(entity.index());
+        self.changed.push(**entity);
+        None
+    }
+
+    fn remove_at(&mut self, entity: &IndexedEntity<C>) -> Option<T>
+    {
+        let pos = self.position_at(entity)?;
+        self.slots[entity.index()] = EMPTY;
+        let value = self.columns.swap_remove(pos);
+        self.entities.swap_remove(pos);
+        self.owners.swap_remove(pos);
+        if pos < self.owners.len()
+        {
+            self.slots[self.owners[pos]] = pos;
+        }
+        Some(value)
+    }
+}
+
+/// 32-bit layer mask of every entity, eg: UI vs world, or team A vs team B.
+///
+/// Every `components!` struct has one, reached with `ComponentManager::layers()`, and `aspect!`
+/// can filter by it with a `layers: [..]` section. Entities start out with no layers.
+pub struct LayerList<C: ComponentManager>
+{
+    masks: Vec<u32>,
+    changed: Vec<Entity>,
+    _marker: PhantomData<fn(C)>,
+}
+
+impl<C: ComponentManager> LayerList<C>
+{
+    pub fn new() -> LayerList<C>
+    {
+        LayerList
+        {
+            masks: Vec::new(),
+            changed: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Sets the layer mask of an entity being built.
+    pub fn add(&mut self, entity: &BuildData<C>, mask: u32)
+    {
+        self.set(entity, mask);
+    }
+
+    /// Returns an entity's layer mask.
+    pub fn get<U: EditData<C>>(&self, entity: &U) -> u32
+    {
+        self.masks.get(entity.entity().index()).cloned().unwrap_or(0)
+    }
+
+    /// Replaces an entity's layer mask, returning the old one.
+    pub fn set<U: EditData<C>>(&mut self, entity: &U, mask: u32) -> u32
+    {
+        self.set_at(entity.entity(), mask)
+    }
+
+    /// Returns whether an entity is on any of the layers in `mask`.
+    pub fn has_any<U: EditData<C>>(&self, entity: &U, mask: u32) -> bool
+    {
+        self.get(entity) & mask != 0
+    }
+
+    pub fn take_changed(&mut self, changed: &mut Vec<Entity>)
+    {
+        changed.append(&mut self.changed);
+    }
+
+    pub unsafe fn clear(&mut self, entity: &IndexedEntity<C>)
+    {
+        if let Some(mask) = self.masks.get_mut(entity.index())
+        {
+            *mask = 0;
+        }
+    }
+
+    /// Moves an entity's layer mask to an entity of another world.
+    pub unsafe fn transfer(&mut self, from: &IndexedEntity<C>, to: &mut LayerList<C>, into: &IndexedEntity<C>)
+    {
+        let mask = self.masks.get(from.index()).cloned().unwrap_or(0);
+        self.clear(from);
+        if mask != 0
+        {
+            to.set_at(into, mask);
+        }
+    }
+
+    /// Moves an entity's layer mask to a new index, without recording a change.
+    pub unsafe fn remap(&mut self, from: &IndexedEntity<C>, to: &IndexedEntity<C>)
+    {
+        let mask = self.masks.get(from.index()).cloned().unwrap_or(0);
+        self.clear(from);
+        if mask != 0
+        {
+            if self.masks.len() <= to.index()
+            {
+                self.masks.resize(to.index() + 1, 0);
+            }
+            self.masks[to.index()] = mask;
+        }
+    }
+
+    /// Frees the storage past the highest entity index with a layer.
+    pub fn shrink_to_fit(&mut self)
+    {
+        let len = self.masks.iter().rposition(|&mask| mask != 0).map_or(0, |index| index + 1);
+        self.masks.truncate(len);
+        self.masks.shrink_to_fit();
+    }
+
+    fn set_at(&mut self, entity: &IndexedEntity<C>, mask: u32) -> u32
+    {
+        if self.masks.len() <= entity.index()
+        {
+            self.masks.resize(entity.index() + 1, 0);
+        }
+        let old = mem::replace(&mut self.masks[entity.index()], mask);
+        if old != mask
+        {
+            self.changed.push(**entity);
+        }
+        old
+    }
+}
+
+/// Implemented by component managers that have `N` as a `#[nested]` field, so that an entity's
+/// data can be used with `N`'s components, eg: `c.physics.body.add(&e.nested(), body)`.
+pub unsafe trait Nests<N: ComponentManager>: ComponentManager {}
+
+/// Another component manager used as a field in `components!`, eg: one from a library crate.
+///
+/// Dereferences to the nested manager, whose components share the entities of the outer one.
+pub struct NestedList<C: ComponentManager, N: ComponentManager>
+{
+    inner: N,
+    name: &'static str,
+    _marker: PhantomData<fn(C)>,
+}
+
+impl<C: ComponentManager, N: ComponentManager> NestedList<C, N>
+{
+    pub unsafe fn nested() -> NestedList<C, N>
+    {
+        NestedList
+        {
+            inner: N::new(),
+            name: "unnamed",
+            _marker: PhantomData,
+        }
+    }
+
+    /// Sets the name used to refer to this field in errors.
+    pub fn named(mut self, name: &'static str) -> NestedList<C, N>
+    {
+        self.name = name;
+        self
+    }
+
+    #[inline]
+    pub fn name(&self) -> &'static str
+    {
+        self.name
+    }
+
+    /// Number of components of every field of the nested manager.
+    pub fn len(&self) -> usize
+    {
+        self.inner.memory_report().components.iter().map(|c| c.len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize
+    {
+        self.inner.memory_report().components.iter().map(|c| c.capacity).sum()
+    }
+
+    pub fn reserve(&mut self, additional: usize)
+    {
+        self.inner.reserve(additional);
+    }
+
+    pub fn memory_bytes(&self) -> usize
+    {
+        self.inner.memory_report().total_bytes()
+    }
+
+    pub fn take_changed(&mut self, changed: &mut Vec<Entity>)
+    {
+        unsafe { self.inner.take_changed(changed); }
+    }
+
+    pub fn hash_entity<H: Hasher>(&self, entity: &IndexedEntity<C>, state: &mut H)
+    {
+        self.inner.hash_entity(unsafe { entity.cast() }, state);
+    }
+
+    /// Returns whether the entity has any of the nested manager's components.
+    pub fn contains(&self, entity: &IndexedEntity<C>) -> bool
+    {
+        !self.inner.component_mask(unsafe { entity.cast() }).is_empty()
+    }
+
+    pub fn clear_refs(&mut self, removed: &dyn Fn(&Entity) -> bool)
+    {
+        self.inner.clear_refs(removed);
+    }
+
+    /// Gives the entity the nested manager's components, read from an object by field name.
+    #[cfg(feature = "serde")]
+    pub unsafe fn deserialize(&mut self, entity: &IndexedEntity<C>, value: ::scene::Value) -> Result<()>
+    {
+        let fields = match value
+        {
+            ::scene::Value::Object(fields) => fields,
+            _ => return Err(Error::Deserialize { component_name: self.name, message: "expected an object".to_string() }),
+        };
+        for (field, value) in fields
+        {
+            match self.inner.deserialize_component(&field, entity.cast(), value)
+            {
+                Some(result) => result?,
+                None => return Err(Error::Deserialize
+                {
+                    component_name: self.name,
+                    message: format!("no persistent field `{}`", field),
+                }),
+            }
+        }
+        Ok(())
+    }
+
+    /// Serializes the entity's persistent nested components as an object, if it has any.
+    #[cfg(feature = "serde")]
+    pub fn serialize(&self, entity: &IndexedEntity<C>) -> Option<Result<::scene::Value>>
+    {
+        match self.inner.serialize_components(unsafe { entity.cast() })
+        {
+            Ok(ref components) if components.is_empty() => None,
+            Ok(components) => Some(Ok(::scene::Value::Object(components.into_iter().map(|(k, v)| (k.to_string(), v)).collect()))),
+            Err(err) => Some(Err(err)),
+        }
+    }
+
+    pub unsafe fn clear(&mut self, entity: &IndexedEntity<C>)
+    {
+        self.inner.remove_all(entity.cast());
+    }
+
+    /// Drops every component of the nested manager without telling systems, see
+    /// `DataHelper::reset_nested()`.
+    pub unsafe fn reset(&mut self)
+    {
+        self.inner = N::new();
+    }
+
+    /// The entity's `#[inspect]` nested components as a struct, if it has any.
+    pub fn inspect(&self, entity: &IndexedEntity<C>) -> Option<InspectValue>
+    {
+        let components = self.inner.inspect_components(unsafe { entity.cast() });
+        if components.is_empty() { None } else { Some(InspectValue::Struct(components)) }
+    }
+
+    /// Moves an entity's nested components to an entity of another world.
+    pub unsafe fn transfer(&mut self, from: &IndexedEntity<C>, to: &mut NestedList<C, N>, into: &IndexedEntity<C>)
+    {
+        self.inner.transfer(from.cast(), &mut to.inner, into.cast());
+    }
+
+    /// Moves an entity's nested components to a new index.
+    pub unsafe fn remap(&mut self, from: &IndexedEntity<C>, to: &IndexedEntity<C>)
+    {
+        self.inner.remap(from.cast(), to.cast());
+    }
+
+    pub fn shrink_to_fit(&mut self)
+    {
+        self.inner.shrink_to_fit();
+    }
+}
+
+impl<C: ComponentManager, N: ComponentManager> Deref for NestedList<C, N>
+{
+    type Target = N;
+    fn deref(&self) -> &N
+    {
+        &self.inner
+    }
+}
+
+impl<C: ComponentManager, N: ComponentManager> DerefMut for NestedList<C, N>
+{
+    fn deref_mut(&mut self) -> &mut N
+    {
+        &mut self.inner
+    }
+}
+
+struct BitSet
+{
+    words: Vec<u64>,
+}
+
+impl BitSet
+{
+    fn new() -> BitSet
+    {
+        BitSet
+        {
+            words: Vec::new(),
+        }
+    }
+
+    /// Sets a bit, returning whether it was already set.
+    fn insert(&mut self, index: usize) -> bool
+    {
+        let (word, bit) = (index / 64, 1 << (index % 64));
+        if word >= self.words.len()
+        {
+            self.words.resize(word + 1, 0);
+        }
+        let had = self.words[word] & bit != 0;
+        self.words[word] |= bit;
+        had
+    }
+
+    /// Clears a bit, returning whether it was set.
+    fn remove(&mut self, index: usize) -> bool
+    {
+        let had = self.contains(index);
+        if had
+        {
+            self.words[index / 64] &= !(1 << (index % 64));
+        }
+        had
+    }
+
+    fn len(&self) -> usize
+    {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    fn capacity(&self) -> usize
+    {
+        self.words.capacity() * 64
+    }
+
+    /// Makes room for bits up to `bits` without reallocating.
+    fn reserve(&mut self, bits: usize)
+    {
+        let words = bits.div_ceil(64);
+        if words > self.words.len()
+        {
+            let additional = words - self.words.len();
+            self.words.reserve(additional);
+        }
+    }
+
+    fn shrink_to_fit(&mut self)
+    {
+        while self.words.last() == Some(&0)
+        {
+            self.words.pop();
+        }
+        self.words.shrink_to_fit();
+    }
+
+    fn memory_bytes(&self) -> usize
+    {
+        self.words.capacity() * mem::size_of::<u64>()
+    }
+
+    #[inline]
+    fn contains(&self, index: usize) -> bool
+    {
+        self.words.get(index / 64).is_some_and(|word| word & (1 << (index % 64)) != 0)
+    }
+}
+
+/// Builds an entity's components. Builders are consumed, so closures can move data into the entity.
+pub trait EntityBuilder<T: ComponentManager>
+{
+    fn build<'a>(self, e: BuildData<'a, T>, c: &mut T);
+}
+
+impl<T: ComponentManager, F> EntityBuilder<T> for F where F: FnOnce(BuildData<T>, &mut T)
+{
+    fn build(self, e: BuildData<T>, c: &mut T)
+    {
+        self(e, c);
+    }
+}
+
+impl<T: ComponentManager> EntityBuilder<T> for () { fn build(self, _: BuildData<T>, _: &mut T) {} }
+
+/// A component paired with the field it goes in, eg: `MyComponents::position(Position { .. })`.
+///
+/// `components!` generates a function like this for each field (other than `#[nested]` ones).
+/// Tuples of them are `EntityBuilder`s, so simple entities can be built without a closure.
+pub struct Field<C: ComponentManager, T>
+{
+    value: T,
+    add: fn(&mut C, &BuildData<C>, T),
+}
+
+impl<C: ComponentManager, T> Field<C, T>
+{
+    #[doc(hidden)]
+    pub fn new(value: T, add: fn(&mut C, &BuildData<C>, T)) -> Field<C, T>
+    {
+        Field
+        {
+            value: value,
+            add: add,
+        }
+    }
+}
+
+/// Something that adds itself to an entity being built.
+pub trait ComponentField<C: ComponentManager>
+{
+    fn add_to<'a>(self, e: &BuildData<'a, C>, c: &mut C);
+}
+
+impl<C: ComponentManager, T> ComponentField<C> for Field<C, T>
+{
+    fn add_to(self, e: &BuildData<C>, c: &mut C)
+    {
+        (self.add)(c, e, self.value);
+    }
+}
+
+impl<C: ComponentManager, T> EntityBuilder<C> for Field<C, T>
+{
+    fn build(self, e: BuildData<C>, c: &mut C)
+    {
+        self.add_to(&e, c);
+    }
+}
+
+macro_rules! tuple_builder {
+    ($($T:ident),+) => {
+        impl<C: ComponentManager, $($T: ComponentField<C>),+> EntityBuilder<C> for ($($T,)+)
+        {
+            #[allow(non_snake_case)]
+            fn build(self, e: BuildData<C>, c: &mut C)
+            {
+                let ($($T,)+) = self;
+                $($T.add_to(&e, c);)+
+            }
+        }
+    };
 }
 
-impl<T: ComponentManager> EntityBuilder<T> for () { fn build(&mut self, _: BuildData<T>, _: &mut T) {} }
+tuple_builder!(A);
+tuple_builder!(A, B);
+tuple_builder!(A, B, D);
+tuple_builder!(A, B, D, E);
+tuple_builder!(A, B, D, E, F);
+tuple_builder!(A, B, D, E, F, G);
+tuple_builder!(A, B, D, E, F, G, H);
+tuple_builder!(A, B, D, E, F, G, H, I);
+tuple_builder!(A, B, D, E, F, G, H, I, J);
+tuple_builder!(A, B, D, E, F, G, H, I, J, K);
+tuple_builder!(A, B, D, E, F, G, H, I, J, K, L);
+tuple_builder!(A, B, D, E, F, G, H, I, J, K, L, N);
 
+/// Changes an existing entity's components. Like builders, modifiers are consumed.
 pub trait EntityModifier<T: ComponentManager>
 {
-    fn modify<'a>(&mut self, ModifyData<'a, T>, &mut T);
+    fn modify<'a>(self, e: ModifyData<'a, T>, c: &mut T);
 }
 
-impl<T: ComponentManager, F> EntityModifier<T> for F where F: FnMut(ModifyData<T>, &mut T)
+impl<T: ComponentManager, F> EntityModifier<T> for F where F: FnOnce(ModifyData<T>, &mut T)
 {
-    fn modify(&mut self, e: ModifyData<T>, c: &mut T)
+    fn modify(self, e: ModifyData<T>, c: &mut T)
     {
-        (*self)(e, c);
+        self(e, c);
     }
 }
 
-impl<T: ComponentManager> EntityModifier<T> for () { fn modify(&mut self, _: ModifyData<T>, _: &mut T) {} }
+impl<T: ComponentManager> EntityModifier<T> for () { fn modify(self, _: ModifyData<T>, _: &mut T) {} }
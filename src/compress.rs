@@ -0,0 +1,343 @@
+//! Compression of snapshots and other serialized output, eg: to keep save files of large worlds
+//! small.
+//!
+//! Data is split into blocks of up to 64 KiB, each compressed on its own in the LZ4 block format,
+//! so `Encoder` and `Decoder` can stream it without holding everything in memory. Each block is
+//! preceded by its length before and after compression, as two little-endian `u32`s. A block that
+//! doesn't get smaller is stored as it is, with the top bit of its compressed length set.
+
+use std::io::{self, Read, Write};
+
+use {Error, Result};
+
+/// Uncompressed size of a full block.
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Largest a full block can get when compressed, in the worst case for LZ4.
+const MAX_COMPRESSED_SIZE: usize = BLOCK_SIZE + BLOCK_SIZE / 255 + 16;
+
+/// Flags a block that is stored uncompressed.
+const STORED: u32 = 1 << 31;
+
+const MIN_MATCH: usize = 4;
+
+/// Matches can't start in the last 12 bytes of a block, and the last 5 bytes are always literals.
+const MATCH_LIMIT: usize = 12;
+const LAST_LITERALS: usize = 5;
+
+const HASH_BITS: u32 = 12;
+
+/// Compresses a whole buffer.
+pub fn compress(bytes: &[u8]) -> Vec<u8>
+{
+    let mut encoder = Encoder::new(Vec::new());
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Decompresses the output of `compress()` or an `Encoder`.
+pub fn decompress(bytes: &[u8]) -> Result<Vec<u8>>
+{
+    let mut out = Vec::new();
+    Decoder::new(bytes).read_to_end(&mut out).map_err(|err| Error::Parse(err.to_string()))?;
+    Ok(out)
+}
+
+/// Compresses everything written to it into another writer.
+///
+/// Data is written out a block at a time. The last block is written by `finish()`, or when the
+/// encoder is dropped, in which case errors are ignored.
+pub struct Encoder<W: Write>
+{
+    inner: Option<W>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> Encoder<W>
+{
+    pub fn new(inner: W) -> Encoder<W>
+    {
+        Encoder
+        {
+            inner: Some(inner),
+            buffer: Vec::with_capacity(BLOCK_SIZE),
+        }
+    }
+
+    /// Writes the last block, returning the inner writer.
+    pub fn finish(mut self) -> io::Result<W>
+    {
+        self.write_block()?;
+        Ok(self.inner.take().unwrap())
+    }
+
+    fn write_block(&mut self) -> io::Result<()>
+    {
+        if self.buffer.is_empty()
+        {
+            return Ok(());
+        }
+        let mut compressed = Vec::new();
+        compress_block(&self.buffer, &mut compressed);
+        let inner = self.inner.as_mut().unwrap();
+        inner.write_all(&(self.buffer.len() as u32).to_le_bytes())?;
+        if compressed.len() < self.buffer.len()
+        {
+            inner.write_all(&(compressed.len() as u32).to_le_bytes())?;
+            inner.write_all(&compressed)?;
+        }
+        else
+        {
+            inner.write_all(&(self.buffer.len() as u32 | STORED).to_le_bytes())?;
+            inner.write_all(&self.buffer)?;
+        }
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Encoder<W>
+{
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize>
+    {
+        let len = bytes.len().min(BLOCK_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&bytes[..len]);
+        if self.buffer.len() == BLOCK_SIZE
+        {
+            self.write_block()?;
+        }
+        Ok(len)
+    }
+
+    /// Writes the data so far as a block, even if it isn't full.
+    fn flush(&mut self) -> io::Result<()>
+    {
+        self.write_block()?;
+        self.inner.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: Write> Drop for Encoder<W>
+{
+    fn drop(&mut self)
+    {
+        if self.inner.is_some()
+        {
+            let _ = self.write_block();
+        }
+    }
+}
+
+/// Decompresses the output of an `Encoder` read from another reader.
+pub struct Decoder<R: Read>
+{
+    inner: R,
+    block: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> Decoder<R>
+{
+    pub fn new(inner: R) -> Decoder<R>
+    {
+        Decoder
+        {
+            inner: inner,
+            block: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Reads the next block. Returns false at the end of the data.
+    fn read_block(&mut self) -> io::Result<bool>
+    {
+        let mut header = [0; 8];
+        let mut read = 0;
+        while read < header.len()
+        {
+            match self.inner.read(&mut header[read..])?
+            {
+                0 if read == 0 => return Ok(false),
+                0 => return Err(corrupt()),
+                n => read += n,
+            }
+        }
+        let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let compressed = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        if len > BLOCK_SIZE
+        {
+            return Err(corrupt());
+        }
+        self.block.clear();
+        self.pos = 0;
+        if compressed & STORED != 0
+        {
+            if (compressed & !STORED) as usize != len
+            {
+                return Err(corrupt());
+            }
+            self.block.resize(len, 0);
+            self.inner.read_exact(&mut self.block)?;
+        }
+        else if compressed as usize > MAX_COMPRESSED_SIZE
+        {
+            return Err(corrupt());
+        }
+        else
+        {
+            let mut input = vec![0; compressed as usize];
+            self.inner.read_exact(&mut input)?;
+            decompress_block(&input, &mut self.block, len)?;
+        }
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for Decoder<R>
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>
+    {
+        while self.pos == self.block.len()
+        {
+            if !self.read_block()?
+            {
+                return Ok(0);
+            }
+        }
+        let len = buf.len().min(self.block.len() - self.pos);
+        buf[..len].copy_from_slice(&self.block[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+fn corrupt() -> io::Error
+{
+    io::Error::new(io::ErrorKind::InvalidData, "corrupt compressed data")
+}
+
+fn read_u32(bytes: &[u8], pos: usize) -> u32
+{
+    u32::from_le_bytes([bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]])
+}
+
+fn hash(sequence: u32) -> usize
+{
+    (sequence.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+}
+
+fn write_length(out: &mut Vec<u8>, mut len: usize)
+{
+    while len >= 255
+    {
+        out.push(255);
+        len -= 255;
+    }
+    out.push(len as u8);
+}
+
+/// Writes literals, followed by a match unless it's the last sequence of the block.
+fn write_sequence(out: &mut Vec<u8>, literals: &[u8], matched: Option<(usize, usize)>)
+{
+    let match_len = matched.map_or(0, |(_, len)| len - MIN_MATCH);
+    out.push((literals.len().min(15) << 4 | match_len.min(15)) as u8);
+    if literals.len() >= 15
+    {
+        write_length(out, literals.len() - 15);
+    }
+    out.extend_from_slice(literals);
+    if let Some((offset, _)) = matched
+    {
+        out.extend_from_slice(&(offset as u16).to_le_bytes());
+        if match_len >= 15
+        {
+            write_length(out, match_len - 15);
+        }
+    }
+}
+
+fn compress_block(input: &[u8], out: &mut Vec<u8>)
+{
+    let mut table = vec![usize::MAX; 1 << HASH_BITS];
+    let mut anchor = 0;
+    let mut pos = 0;
+    while pos + MATCH_LIMIT < input.len()
+    {
+        let sequence = read_u32(input, pos);
+        let candidate = ::std::mem::replace(&mut table[hash(sequence)], pos);
+        if candidate == usize::MAX || pos - candidate > 0xFFFF || read_u32(input, candidate) != sequence
+        {
+            pos += 1;
+            continue;
+        }
+        let max = input.len() - LAST_LITERALS - pos;
+        let mut len = MIN_MATCH;
+        while len < max && input[candidate + len] == input[pos + len]
+        {
+            len += 1;
+        }
+        write_sequence(out, &input[anchor..pos], Some((pos - candidate, len)));
+        pos += len;
+        anchor = pos;
+    }
+    write_sequence(out, &input[anchor..], None);
+}
+
+fn read_length(input: &[u8], pos: &mut usize, mut len: usize) -> io::Result<usize>
+{
+    if len == 15
+    {
+        loop
+        {
+            let byte = *input.get(*pos).ok_or_else(corrupt)?;
+            *pos += 1;
+            len += byte as usize;
+            if byte != 255
+            {
+                break;
+            }
+        }
+    }
+    Ok(len)
+}
+
+fn decompress_block(input: &[u8], out: &mut Vec<u8>, len: usize) -> io::Result<()>
+{
+    let mut pos = 0;
+    while pos < input.len()
+    {
+        let token = input[pos];
+        pos += 1;
+        let literals = read_length(input, &mut pos, (token >> 4) as usize)?;
+        let end = pos.checked_add(literals).filter(|&end| end <= input.len()).ok_or_else(corrupt)?;
+        out.extend_from_slice(&input[pos..end]);
+        pos = end;
+        if pos == input.len()
+        {
+            break;
+        }
+        if pos + 2 > input.len()
+        {
+            return Err(corrupt());
+        }
+        let offset = u16::from_le_bytes([input[pos], input[pos + 1]]) as usize;
+        pos += 2;
+        let match_len = read_length(input, &mut pos, (token & 15) as usize)? + MIN_MATCH;
+        if offset == 0 || offset > out.len() || out.len() + match_len > len
+        {
+            return Err(corrupt());
+        }
+        // The match can overlap the bytes it produces, so it's copied a byte at a time.
+        let start = out.len() - offset;
+        for i in 0..match_len
+        {
+            let byte = out[start + i];
+            out.push(byte);
+        }
+    }
+    if out.len() != len
+    {
+        return Err(corrupt());
+    }
+    Ok(())
+}
@@ -0,0 +1,133 @@
+//! Text commands for poking at a running world, eg: from an in-game console or an admin socket.
+//!
+//! Components are reached by name through `script::ScriptView`, so only `#[persist]` ones can be
+//! read or written. The built-in commands are:
+//!
+//! - `spawn` creates an empty entity and prints its id.
+//! - `destroy <id>` removes an entity.
+//! - `get <id> <component>` prints a component as JSON.
+//! - `set <id> <component> <value>` sets a component from JSON. Several plain values, as in
+//!   `set 3 position 1 2`, are read as a list of the struct's fields.
+//! - `list [component...]` prints the ids of the entities that have all of the components.
+//! - `help` prints the names of the commands.
+
+use std::collections::BTreeMap;
+
+use {ComponentManager, DataHelper, ServiceManager};
+use Entity;
+use {Error, Result};
+use scene::Value;
+use script::ScriptView;
+
+/// A command added with `Console::register()`. It's given the words after the command's name.
+pub type Command<C, M> = boxed!(Fn(&[&str], &mut DataHelper<C, M>) -> Result<String>);
+
+/// Runs lines of text as commands on a world.
+pub struct Console<C: ComponentManager, M: ServiceManager>
+{
+    commands: BTreeMap<String, Command<C, M>>,
+}
+
+impl<C: ComponentManager, M: ServiceManager> Console<C, M>
+{
+    /// Creates a console with only the built-in commands.
+    pub fn new() -> Console<C, M>
+    {
+        Console
+        {
+            commands: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a command, replacing any other with the same name, including built-in ones.
+    pub fn register(&mut self, name: &str, command: Command<C, M>)
+    {
+        self.commands.insert(name.to_string(), command);
+    }
+
+    /// Runs a line, returning what it printed.
+    pub fn run(&self, line: &str, data: &mut DataHelper<C, M>) -> Result<String>
+    {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (name, args) = match words.split_first()
+        {
+            Some((name, args)) => (*name, args),
+            None => return Ok(String::new()),
+        };
+        if let Some(command) = self.commands.get(name)
+        {
+            return command(args, data);
+        }
+        match (name, args)
+        {
+            ("spawn", []) => Ok(data.create_entity(()).id().to_string()),
+            ("destroy", [id]) => {
+                let entity = find(data, id)?;
+                data.remove_entity(entity);
+                Ok(String::new())
+            },
+            ("get", [id, component]) => {
+                let entity = find(data, id)?;
+                match ScriptView::new(data).get(&entity, component)?
+                {
+                    Some(value) => Ok(value.to_string()),
+                    None => Ok("none".to_string()),
+                }
+            },
+            ("set", [id, component, value @ ..]) if !value.is_empty() => {
+                let entity = find(data, id)?;
+                ScriptView::new(data).set(&entity, component, parse_value(value)?)?;
+                Ok(String::new())
+            },
+            ("list", components) => {
+                let mut entities: Vec<Entity> = data.entities().map(|e| **e).collect();
+                entities.sort_by_key(|e| e.id());
+                let mut view = ScriptView::new(data);
+                let mut ids = Vec::new();
+                for entity in entities
+                {
+                    let mut matches = true;
+                    for component in components
+                    {
+                        matches = matches && view.get(&entity, component)?.is_some();
+                    }
+                    if matches
+                    {
+                        ids.push(entity.id().to_string());
+                    }
+                }
+                Ok(ids.join(" "))
+            },
+            ("help", []) => {
+                let mut names: Vec<&str> = vec!["destroy", "get", "help", "list", "set", "spawn"];
+                names.extend(self.commands.keys().map(|name| &name[..]));
+                names.sort();
+                names.dedup();
+                Ok(names.join(" "))
+            },
+            ("spawn", _) | ("destroy", _) | ("get", _) | ("set", _) | ("help", _) =>
+                Err(Error::Parse(format!("wrong arguments for `{}`, see the console module docs", name))),
+            _ => Err(Error::Parse(format!("unknown command `{}`", name))),
+        }
+    }
+}
+
+/// Finds the entity with an id typed into the console.
+fn find<C: ComponentManager, M: ServiceManager>(data: &DataHelper<C, M>, id: &str) -> Result<Entity>
+{
+    let id: u64 = id.parse().map_err(|_| Error::Parse(format!("`{}` isn't an entity id", id)))?;
+    data.entities().map(|e| **e).find(|e| e.id() == id)
+        .ok_or_else(|| Error::Parse(format!("no entity with id {}", id)))
+}
+
+/// Reads a value as JSON, or several words as a list of JSON values.
+fn parse_value(words: &[&str]) -> Result<Value>
+{
+    if let Ok(value) = words.join(" ").parse()
+    {
+        return Ok(value);
+    }
+    words.iter().map(|word| word.parse().map_err(|_| Error::Parse(format!("`{}` isn't a JSON value", word))))
+        .collect::<Result<Vec<Value>>>()
+        .map(Value::Array)
+}
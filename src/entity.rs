@@ -5,6 +5,8 @@ use std::collections::hash_map::{HashMap, Values};
 use std::default::Default;
 use std::marker::PhantomData;
 use std::ops::Deref;
+use std::slice;
+use std::vec;
 
 use Aspect;
 use ComponentManager;
@@ -15,7 +17,9 @@ pub type Id = u64;
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Entity(Id);
 
+/// `repr(C)` so that it can be cast between component managers, see `IndexedEntity::cast()`.
 #[derive(Debug, Eq, Hash, PartialEq)]
+#[repr(C)]
 pub struct IndexedEntity<T: ComponentManager>(usize, Entity, PhantomData<fn(T)>);
 
 impl Entity
@@ -44,6 +48,13 @@ impl<T: ComponentManager> IndexedEntity<T>
     {
         IndexedEntity(self.0, self.1, self.2)
     }
+
+    /// Reinterprets the entity as belonging to a component manager nested inside `T`.
+    #[doc(hidden)]
+    pub unsafe fn cast<U: ComponentManager>(&self) -> &IndexedEntity<U>
+    {
+        &*(self as *const IndexedEntity<T> as *const IndexedEntity<U>)
+    }
 }
 
 impl<T: ComponentManager> Deref for IndexedEntity<T>
@@ -63,6 +74,91 @@ impl Default for Entity
     }
 }
 
+/// An entity stored inside a component, cleared by the world when that entity is removed.
+///
+/// The component's field must be marked `#[refs]` in `components!`, and its type must implement
+/// `EntityRefs`.
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct EntityRef(Option<Entity>);
+
+impl EntityRef
+{
+    pub fn new(entity: Entity) -> EntityRef
+    {
+        EntityRef(Some(entity))
+    }
+
+    /// A reference to no entity.
+    pub fn none() -> EntityRef
+    {
+        EntityRef(None)
+    }
+
+    /// Returns the entity, unless it has been removed.
+    pub fn get(&self) -> Option<Entity>
+    {
+        self.0
+    }
+
+    pub fn set(&mut self, entity: Entity)
+    {
+        self.0 = Some(entity);
+    }
+
+    pub fn clear(&mut self)
+    {
+        self.0 = None;
+    }
+
+    pub fn is_none(&self) -> bool
+    {
+        self.0.is_none()
+    }
+}
+
+/// Components holding `EntityRef`s.
+///
+/// Implement it for a component type by calling `clear_refs` on each of its references.
+pub trait EntityRefs
+{
+    /// Clears every reference to an entity for which `removed` is true. Returns whether any were.
+    fn clear_refs(&mut self, removed: &dyn Fn(&Entity) -> bool) -> bool;
+}
+
+impl EntityRefs for EntityRef
+{
+    fn clear_refs(&mut self, removed: &dyn Fn(&Entity) -> bool) -> bool
+    {
+        match self.0
+        {
+            Some(ref entity) if removed(entity) => {
+                self.0 = None;
+                true
+            },
+            _ => false,
+        }
+    }
+}
+
+impl<T: EntityRefs> EntityRefs for Option<T>
+{
+    fn clear_refs(&mut self, removed: &dyn Fn(&Entity) -> bool) -> bool
+    {
+        self.as_mut().is_some_and(|refs| refs.clear_refs(removed))
+    }
+}
+
+/// Cleared references are removed from the list.
+impl EntityRefs for Vec<EntityRef>
+{
+    fn clear_refs(&mut self, removed: &dyn Fn(&Entity) -> bool) -> bool
+    {
+        let len = self.len();
+        self.retain(|r| r.0.is_some_and(|entity| !removed(&entity)));
+        self.len() != len
+    }
+}
+
 pub struct FilteredEntityIter<'a, T: ComponentManager>
 {
     inner: EntityIter<'a, T>,
@@ -74,6 +170,8 @@ pub struct FilteredEntityIter<'a, T: ComponentManager>
 pub enum EntityIter<'a, T: ComponentManager>
 {
     Map(Values<'a, Entity, IndexedEntity<T>>),
+    Slice(slice::Iter<'a, IndexedEntity<T>>),
+    List(vec::IntoIter<EntityData<'a, T>>),
 }
 
 impl<'a, T: ComponentManager> EntityIter<'a, T>
@@ -96,11 +194,23 @@ impl<'a, T: ComponentManager> Iterator for EntityIter<'a, T>
     {
         match *self
         {
-            EntityIter::Map(ref mut values) => values.next().map(|x| EntityData(x))
+            EntityIter::Map(ref mut values) => values.next().map(|x| EntityData(x)),
+            EntityIter::Slice(ref mut values) => values.next().map(|x| EntityData(x)),
+            EntityIter::List(ref mut list) => list.next(),
         }
     }
 }
 
+impl<'a, T: ComponentManager> FilteredEntityIter<'a, T>
+{
+    /// Replaces the contents of `buf` with the remaining entities, so it can be reused.
+    pub fn collect_into(self, buf: &mut Vec<Entity>)
+    {
+        buf.clear();
+        buf.extend(self.map(|e| **e));
+    }
+}
+
 impl<'a, T: ComponentManager> Iterator for FilteredEntityIter<'a, T>
 {
     type Item = EntityData<'a, T>;
@@ -143,7 +253,18 @@ impl<T: ComponentManager> EntityManager<T>
         }
     }
 
-    pub fn iter(&self) -> EntityIter<T>
+    /// Returns a new `EntityManager` with room for `capacity` entities.
+    pub fn with_capacity(capacity: usize) -> EntityManager<T>
+    {
+        EntityManager
+        {
+            indices: IndexPool::with_capacity(capacity),
+            entities: HashMap::with_capacity(capacity),
+            next_id: 0,
+        }
+    }
+
+    pub fn iter(&self) -> EntityIter<'_, T>
     {
         EntityIter::Map(self.entities.values())
     }
@@ -158,6 +279,12 @@ impl<T: ComponentManager> EntityManager<T>
         &self.entities[entity]
     }
 
+    /// Returns the indexed entity, or `None` if the entity is not valid.
+    pub fn try_indexed(&self, entity: &Entity) -> Option<&IndexedEntity<T>>
+    {
+        self.entities.get(entity)
+    }
+
     /// Creates a new `Entity`, assigning it the first available index.
     pub fn create(&mut self) -> Entity
     {
@@ -177,7 +304,48 @@ impl<T: ComponentManager> EntityManager<T>
     /// Deletes an entity from the manager.
     pub fn remove(&mut self, entity: &Entity)
     {
-        self.entities.remove(entity).map(|e| self.indices.return_id(e.index()));
+        if let Some(e) = self.entities.remove(entity)
+        {
+            self.indices.return_id(e.index());
+        }
+    }
+
+    /// One more than the highest index given out since the manager was created or compacted.
+    pub fn high_water_mark(&self) -> usize
+    {
+        self.indices.next_index
+    }
+
+    /// Gives the entities the indices from zero up to their count, moving as few as possible.
+    ///
+    /// Returns the moved entities with their old and new indices, in order of old index.
+    pub fn compact(&mut self) -> Vec<(IndexedEntity<T>, IndexedEntity<T>)>
+    {
+        let count = self.entities.len();
+        let mut used = vec![false; count];
+        let mut outside = Vec::new();
+        for e in self.entities.values()
+        {
+            match used.get_mut(e.index())
+            {
+                Some(used) => *used = true,
+                None => outside.push((e.index(), e.1)),
+            }
+        }
+        outside.sort_by_key(|&(index, _)| index);
+        let free = used.iter().enumerate().filter(|&(_, &used)| !used).map(|(index, _)| index);
+        let mut moved = Vec::with_capacity(outside.len());
+        for ((old, entity), new) in outside.into_iter().zip(free)
+        {
+            self.entities.get_mut(&entity).unwrap().0 = new;
+            moved.push((IndexedEntity(old, entity, PhantomData), IndexedEntity(new, entity, PhantomData)));
+        }
+        self.indices = IndexPool
+        {
+            recycled: Vec::new(),
+            next_index: count,
+        };
+        moved
     }
 }
 
@@ -198,6 +366,15 @@ impl IndexPool
         }
     }
 
+    pub fn with_capacity(capacity: usize) -> IndexPool
+    {
+        IndexPool
+        {
+            recycled: Vec::with_capacity(capacity),
+            next_index: 0,
+        }
+    }
+
     pub fn count(&self) -> usize
     {
         self.next_index - self.recycled.len()
@@ -0,0 +1,120 @@
+//! Errors for operations that can be given bad input at runtime.
+
+use std::error;
+use std::fmt;
+use std::result;
+
+use Entity;
+use UnknownComponent;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error
+{
+    /// The entity doesn't exist, or has been removed.
+    NoSuchEntity(Entity),
+    /// The entity exists but doesn't have the component.
+    MissingComponent
+    {
+        entity: Entity,
+        component_name: &'static str,
+    },
+    /// A `#[singleton]` component was given to an entity while another entity had it.
+    SingletonTaken
+    {
+        component_name: &'static str,
+        owner: Entity,
+    },
+    /// A component was referred to by a name that isn't known.
+    UnknownComponent(UnknownComponent),
+    /// A component couldn't be serialized.
+    Serialize
+    {
+        component_name: &'static str,
+        message: String,
+    },
+    /// A component couldn't be read from serialized data.
+    Deserialize
+    {
+        component_name: &'static str,
+        message: String,
+    },
+    /// Serialized data wasn't in the expected format.
+    Parse(String),
+    /// Serialized data was saved with a newer schema version than is supported.
+    UnsupportedVersion
+    {
+        version: u32,
+        supported: u32,
+    },
+    /// A value was given to a `DynamicColumn` of another type.
+    WrongType
+    {
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// A replayed frame didn't match the recording.
+    Desync
+    {
+        frame: usize,
+    },
+    /// A message couldn't be passed to or from another world.
+    Transport(String),
+    /// Reading or writing failed.
+    Io(String),
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+impl From<UnknownComponent> for Error
+{
+    fn from(err: UnknownComponent) -> Error
+    {
+        Error::UnknownComponent(err)
+    }
+}
+
+impl fmt::Display for Error
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self
+        {
+            Error::NoSuchEntity(entity) => write!(f, "no such entity: {:?}", entity),
+            Error::MissingComponent { entity, component_name } => {
+                write!(f, "{:?} has no `{}` component", entity, component_name)
+            },
+            Error::SingletonTaken { component_name, owner } => {
+                write!(f, "the `{}` component already belongs to {:?}", component_name, owner)
+            },
+            Error::UnknownComponent(ref err) => fmt::Display::fmt(err, f),
+            Error::Serialize { component_name, ref message } => {
+                write!(f, "couldn't serialize the `{}` component: {}", component_name, message)
+            },
+            Error::Deserialize { component_name, ref message } => {
+                write!(f, "couldn't read the `{}` component: {}", component_name, message)
+            },
+            Error::Parse(ref message) => write!(f, "couldn't parse: {}", message),
+            Error::UnsupportedVersion { version, supported } => {
+                write!(f, "schema version {} is newer than the supported version {}", version, supported)
+            },
+            Error::WrongType { expected, found } => {
+                write!(f, "expected a value of type `{}`, found `{}`", expected, found)
+            },
+            Error::Desync { frame } => write!(f, "replay diverged from the recording at frame {}", frame),
+            Error::Transport(ref message) => write!(f, "couldn't pass a message: {}", message),
+            Error::Io(ref message) => write!(f, "i/o error: {}", message),
+        }
+    }
+}
+
+impl error::Error for Error
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)>
+    {
+        match *self
+        {
+            Error::UnknownComponent(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
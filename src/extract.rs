@@ -0,0 +1,18 @@
+//! Copying data out of a world for use elsewhere.
+//!
+//! The usual case is handing a render thread what it needs to draw a frame: after each update,
+//! the simulation thread extracts positions, sprites and so on into a plain structure it owns,
+//! then sends that structure across, while the `World` itself stays on the simulation thread.
+
+use ComponentManager;
+use EntityData;
+
+/// A structure that can be filled from the entities of a world.
+pub trait Extract<C: ComponentManager>
+{
+    /// Called before any entity is extracted, eg: to clear the previous snapshot.
+    fn begin(&mut self) {}
+
+    /// Called once for every entity in the world.
+    fn extract(&mut self, entity: EntityData<C>, components: &C);
+}
@@ -0,0 +1,251 @@
+//! Undo and redo for edits made through a `History`, eg: in a level editor.
+//!
+//! Only edits made through the `History` are recorded. Undoing the creation of an entity (or
+//! redoing its removal) removes it, and undoing its removal creates it again with the components
+//! of every field registered with `track()`. Recreated entities get new ids, so `History` keeps
+//! track of them: the entity returned by `create_entity()` can keep being passed to it, and
+//! `entity()` gives the id the entity currently has.
+
+use std::collections::HashMap;
+use std::mem;
+
+use {BuildData, EntityData, ModifyData};
+use {Component, ComponentList};
+use {ComponentManager, DataHelper, ServiceManager};
+use {Entity, IndexedEntity};
+use EntityBuilder;
+
+/// Gives back a component of an entity that is being recreated.
+type Restore<C> = Box<dyn FnOnce(BuildData<C>, &mut C)>;
+
+/// Copies an entity's component from one tracked field.
+type Snapshot<C> = Box<dyn Fn(&mut C, &IndexedEntity<C>) -> Option<Restore<C>>>;
+
+enum Edit<C: ComponentManager>
+{
+    /// An entity that was created or removed, with its components while it's removed.
+    Entity
+    {
+        entity: Entity,
+        removed: Option<Vec<Restore<C>>>,
+    },
+    /// Swaps the stored value of a component with the entity's current one.
+    Component
+    {
+        entity: Entity,
+        swap: Box<dyn FnMut(&mut C, &IndexedEntity<C>)>,
+    },
+}
+
+/// A record of edits that can be undone and redone.
+pub struct History<C: ComponentManager>
+{
+    undo: Vec<Edit<C>>,
+    redo: Vec<Edit<C>>,
+    fields: Vec<Snapshot<C>>,
+    /// Id each entity had when first seen, by current id.
+    original: HashMap<Entity, Entity>,
+    /// Current id of each recreated entity, by original id.
+    current: HashMap<Entity, Entity>,
+}
+
+impl<C: ComponentManager> History<C>
+{
+    pub fn new() -> History<C>
+    {
+        History
+        {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            fields: Vec::new(),
+            original: HashMap::new(),
+            current: HashMap::new(),
+        }
+    }
+
+    /// Registers a component field to be restored when an entity's removal is undone.
+    ///
+    /// `field` picks the list out of the components, eg: `|c| &mut c.position`.
+    pub fn track<T: Component + Clone>(&mut self, field: fn(&mut C) -> &mut ComponentList<C, T>)
+    {
+        self.fields.push(Box::new(move |c: &mut C, entity: &IndexedEntity<C>| {
+            field(c).get(&EntityData(entity)).map(|value| {
+                Box::new(move |e: BuildData<C>, c: &mut C| { field(c).add(&e, value); }) as Restore<C>
+            })
+        }));
+    }
+
+    /// Returns the id an entity currently has, following any recreation by undo or redo.
+    pub fn entity(&self, entity: Entity) -> Entity
+    {
+        let original = self.original(entity);
+        self.current.get(&original).cloned().unwrap_or(original)
+    }
+
+    pub fn can_undo(&self) -> bool
+    {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool
+    {
+        !self.redo.is_empty()
+    }
+
+    /// Forgets every recorded edit.
+    pub fn clear(&mut self)
+    {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    /// Creates an entity, recording it.
+    pub fn create_entity<M, B>(&mut self, data: &mut DataHelper<C, M>, builder: B) -> Entity
+        where M: ServiceManager, B: EntityBuilder<C>
+    {
+        let entity = data.create_entity(builder);
+        self.record(Edit::Entity
+        {
+            entity: entity,
+            removed: None,
+        });
+        entity
+    }
+
+    /// Removes an entity, recording its tracked components. Returns false if it doesn't exist.
+    pub fn remove_entity<M: ServiceManager>(&mut self, data: &mut DataHelper<C, M>, entity: Entity) -> bool
+    {
+        let entity = self.entity(entity);
+        let mut edit = Edit::Entity
+        {
+            entity: self.original(entity),
+            removed: None,
+        };
+        if !self.apply(data, &mut edit)
+        {
+            return false;
+        }
+        self.record(edit);
+        true
+    }
+
+    /// Sets an entity's component, recording the previous one. Returns false if the entity
+    /// doesn't exist.
+    pub fn set<M, T>(&mut self, data: &mut DataHelper<C, M>, entity: Entity, field: fn(&mut C) -> &mut ComponentList<C, T>, component: T) -> bool
+        where M: ServiceManager, T: Component
+    {
+        self.edit_component(data, entity, field, Some(component))
+    }
+
+    /// Removes an entity's component, recording it. Returns false if the entity doesn't exist.
+    pub fn remove_component<M, T>(&mut self, data: &mut DataHelper<C, M>, entity: Entity, field: fn(&mut C) -> &mut ComponentList<C, T>) -> bool
+        where M: ServiceManager, T: Component
+    {
+        self.edit_component(data, entity, field, None)
+    }
+
+    /// Undoes the last recorded edit. Returns false if there was nothing to undo.
+    pub fn undo<M: ServiceManager>(&mut self, data: &mut DataHelper<C, M>) -> bool
+    {
+        match self.undo.pop()
+        {
+            Some(mut edit) => {
+                self.apply(data, &mut edit);
+                self.redo.push(edit);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Redoes the last undone edit. Returns false if there was nothing to redo.
+    pub fn redo<M: ServiceManager>(&mut self, data: &mut DataHelper<C, M>) -> bool
+    {
+        match self.redo.pop()
+        {
+            Some(mut edit) => {
+                self.apply(data, &mut edit);
+                self.undo.push(edit);
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn edit_component<M, T>(&mut self, data: &mut DataHelper<C, M>, entity: Entity, field: fn(&mut C) -> &mut ComponentList<C, T>, component: Option<T>) -> bool
+        where M: ServiceManager, T: Component
+    {
+        let entity = self.entity(entity);
+        let mut value = component;
+        let mut edit = Edit::Component
+        {
+            entity: self.original(entity),
+            swap: Box::new(move |c: &mut C, entity: &IndexedEntity<C>| {
+                let list = field(c);
+                let e = ModifyData(entity);
+                let old = list.remove(&e);
+                if let Some(component) = value.take()
+                {
+                    list.insert(&e, component);
+                }
+                value = old;
+            }),
+        };
+        if !self.apply(data, &mut edit)
+        {
+            return false;
+        }
+        self.record(edit);
+        true
+    }
+
+    fn original(&self, entity: Entity) -> Entity
+    {
+        self.original.get(&entity).cloned().unwrap_or(entity)
+    }
+
+    fn record(&mut self, edit: Edit<C>)
+    {
+        self.undo.push(edit);
+        self.redo.clear();
+    }
+
+    /// Applies an edit, turning it into its own inverse. Returns false if the entity is gone.
+    fn apply<M: ServiceManager>(&mut self, data: &mut DataHelper<C, M>, edit: &mut Edit<C>) -> bool
+    {
+        match *edit
+        {
+            Edit::Entity { entity, ref mut removed } if removed.is_none() => {
+                let current = self.entity(entity);
+                let fields = &self.fields;
+                *removed = data.with_entity_data(&current, |e, c| {
+                    fields.iter().filter_map(|field| field(c, &e)).collect()
+                });
+                if removed.is_some()
+                {
+                    data.remove_entity(current);
+                }
+                removed.is_some()
+            },
+            Edit::Entity { entity, ref mut removed } => {
+                let mut pending = mem::take(removed).unwrap_or_default();
+                let recreated = data.create_entity(move |e: BuildData<C>, c: &mut C| {
+                    for restore in pending.drain(..)
+                    {
+                        restore(e, c);
+                    }
+                });
+                if let Some(old) = self.current.insert(entity, recreated)
+                {
+                    self.original.remove(&old);
+                }
+                self.original.insert(recreated, entity);
+                true
+            },
+            Edit::Component { entity, ref mut swap } => {
+                let current = self.entity(entity);
+                data.with_entity_data(&current, |e, c| swap(c, &e)).is_some()
+            },
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! A navigable model of a world's entities and components, for GUI inspectors and debug views.
+//!
+//! Fields marked `#[inspect]` in `components!` show up in the model. Their types must implement
+//! `InspectComponent`, which is done for the standard types here and can be done for structs with
+//! `inspect_struct!`:
+//!
+//! ```ignore
+//! inspect_struct!(Position { x, y });
+//! ```
+
+use {ComponentManager, DataHelper, ServiceManager};
+use Entity;
+
+/// A component, or part of one, as shown in an inspector.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InspectValue
+{
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+    List(Vec<InspectValue>),
+    /// Named fields, in declaration order.
+    Struct(Vec<(&'static str, InspectValue)>),
+    /// An optional value that is missing.
+    None,
+}
+
+/// Implemented by component types that can be shown in an inspector.
+pub trait InspectComponent
+{
+    fn inspect(&self) -> InspectValue;
+}
+
+/// An entity as shown in an inspector.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntityModel
+{
+    pub entity: Entity,
+    /// Name given with `DataHelper::set_name()`.
+    pub name: Option<String>,
+    /// The entity's `#[inspect]` components, in declaration order.
+    pub components: Vec<(&'static str, InspectValue)>,
+}
+
+/// Every entity, in order of creation.
+pub fn entities<C: ComponentManager, M: ServiceManager>(data: &DataHelper<C, M>) -> Vec<EntityModel>
+{
+    let mut entities: Vec<EntityModel> = data.entities().map(|e| model(data, &e)).collect();
+    entities.sort_by_key(|model| model.entity.id());
+    entities
+}
+
+/// One entity, if it exists.
+pub fn entity<C: ComponentManager, M: ServiceManager>(data: &DataHelper<C, M>, entity: &Entity) -> Option<EntityModel>
+{
+    data.entities().find(|e| ***e == *entity).map(|e| model(data, &e))
+}
+
+fn model<C: ComponentManager, M: ServiceManager>(data: &DataHelper<C, M>, entity: &::IndexedEntity<C>) -> EntityModel
+{
+    EntityModel
+    {
+        entity: **entity,
+        name: data.names().name(entity).map(|name| name.to_string()),
+        components: data.components.inspect_components(entity),
+    }
+}
+
+macro_rules! inspect_as {
+    ($variant:ident, $as_ty:ty, $($ty:ty),+) => {
+        $(
+            impl InspectComponent for $ty
+            {
+                fn inspect(&self) -> InspectValue
+                {
+                    InspectValue::$variant(*self as $as_ty)
+                }
+            }
+        )+
+    };
+}
+
+inspect_as!(Int, i64, i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+inspect_as!(Float, f64, f32, f64);
+
+impl InspectComponent for bool
+{
+    fn inspect(&self) -> InspectValue
+    {
+        InspectValue::Bool(*self)
+    }
+}
+
+impl InspectComponent for String
+{
+    fn inspect(&self) -> InspectValue
+    {
+        InspectValue::Text(self.clone())
+    }
+}
+
+impl InspectComponent for ()
+{
+    fn inspect(&self) -> InspectValue
+    {
+        InspectValue::Struct(Vec::new())
+    }
+}
+
+impl<T: InspectComponent> InspectComponent for Option<T>
+{
+    fn inspect(&self) -> InspectValue
+    {
+        match *self
+        {
+            Some(ref value) => value.inspect(),
+            None => InspectValue::None,
+        }
+    }
+}
+
+impl<T: InspectComponent> InspectComponent for Vec<T>
+{
+    fn inspect(&self) -> InspectValue
+    {
+        InspectValue::List(self.iter().map(|value| value.inspect()).collect())
+    }
+}
+
+impl InspectComponent for Entity
+{
+    fn inspect(&self) -> InspectValue
+    {
+        InspectValue::Int(self.id() as i64)
+    }
+}
@@ -16,34 +16,82 @@
 //!
 //! - An `Entity` is just an identifier. It contains no data or logic whatsoever.
 //! - A `Component` is a piece of data (eg: Position, Velocity, Colour). While containing logic can
-//! sometimes be useful, it's best practice to avoid it wherever possible.
+//!   sometimes be useful, it's best practice to avoid it wherever possible.
 //! - A `System` runs all the logic. Most of the time, it filters out entities based on their
-//! components, and only runs it's logic on the entities it's interested in. These filters are
-//! called `Aspect`s. Some systems ignore entities, and just apply logic to the world itself.
+//!   components, and only runs it's logic on the entities it's interested in. These filters are
+//!   called `Aspect`s. Some systems ignore entities, and just apply logic to the world itself.
 //! - An `Aspect` is a simple helper to filter entities based on their components.
 //! - A `Manager` is simply an object that observes when an entity is
-//! added/activated/deactivated/removed. They are used to help 'manage' the entities, rather than
-//! define data or logic.
+//!   added/activated/deactivated/removed. They are used to help 'manage' the entities, rather than
+//!   define data or logic.
 //! - The `World` organises all the above items together to make sure everything runs as it should.
 
 #![crate_name = "ecs"]
 #![crate_type = "lib"]
 
-#![feature(collections)]
-#![feature(collections_drain)]
+#![allow(clippy::new_without_default)]
+#![allow(clippy::missing_safety_doc)]
+#![allow(clippy::redundant_field_names)]
+#![allow(clippy::type_complexity)]
 
-pub use aspect::Aspect;
-pub use component::{Component, ComponentList};
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
+#[cfg(feature = "ron")]
+extern crate ron;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+
+pub use aspect::{Aspect, AspectInfo};
+pub use component::{Component, ComponentList, FlagList, Pod, SingletonPolicy};
 pub use component::{EntityBuilder, EntityModifier};
-pub use entity::{Entity, IndexedEntity, EntityIter};
+pub use entity::{Entity, EntityRef, EntityRefs, IndexedEntity, EntityIter};
+pub use error::{Error, Result};
+pub use extract::Extract;
+pub use query::{CachedQuery, InterestTracker, QueryId};
+pub use registry::{ComponentRegistry, UnknownComponent};
+pub use send::MaybeSend;
 pub use system::{System, Process};
-pub use world::{ComponentManager, ServiceManager, SystemManager, DataHelper, World};
+pub use world::{ChildSpawner, ComponentManager, ServiceManager, SystemManager, DataHelper, DeterminismConfig, Despawning, EntityEvent, EntitySpawner, World, WorldBuilder};
 
 use std::ops::Deref;
 
+#[macro_use]
+pub mod send;
+#[macro_use]
+mod trace;
+
 pub mod aspect;
+pub mod column;
 pub mod component;
+#[cfg(feature = "compress")]
+pub mod compress;
+#[cfg(feature = "serde")]
+pub mod console;
 pub mod entity;
+pub mod error;
+pub mod extract;
+pub mod history;
+pub mod inspect;
+pub mod manager;
+pub mod metrics;
+#[cfg(feature = "serde")]
+pub mod plugin;
+pub mod predict;
+pub mod query;
+pub mod reflect;
+pub mod registry;
+pub mod replay;
+pub mod replication;
+#[cfg(feature = "serde")]
+pub mod scene;
+#[cfg(feature = "serde")]
+pub mod script;
+#[cfg(feature = "serde")]
+pub mod stream;
 pub mod system;
 pub mod world;
 
@@ -55,7 +103,40 @@ impl<'a, T: ComponentManager> Deref for EntityData<'a, T>
     type Target = IndexedEntity<T>;
     fn deref(&self) -> &IndexedEntity<T>
     {
-        &self.0
+        self.0
+    }
+}
+
+impl<'a, T: ComponentManager> BuildData<'a, T>
+{
+    /// The entity, for adding the components of a `#[nested]` component manager.
+    pub fn nested<N: ComponentManager>(&self) -> BuildData<'a, N> where T: component::Nests<N>
+    {
+        BuildData(unsafe { self.0.cast() })
+    }
+}
+
+impl<'a, T: ComponentManager> ModifyData<'a, T>
+{
+    /// The entity, for changing the components of a `#[nested]` component manager.
+    pub fn nested<N: ComponentManager>(&self) -> ModifyData<'a, N> where T: component::Nests<N>
+    {
+        ModifyData(unsafe { self.0.cast() })
+    }
+}
+
+impl<'a, T: ComponentManager> EntityData<'a, T>
+{
+    /// Which component fields the entity has.
+    pub fn mask(&self, components: &T) -> world::ComponentMask
+    {
+        components.component_mask(self.0)
+    }
+
+    /// The entity, for reading the components of a `#[nested]` component manager.
+    pub fn nested<N: ComponentManager>(&self) -> EntityData<'a, N> where T: component::Nests<N>
+    {
+        EntityData(unsafe { self.0.cast() })
     }
 }
 
@@ -67,10 +148,12 @@ impl<'a, T: ComponentManager> Clone for BuildData<'a, T> {fn clone(&self) -> Bui
 impl<'a, T: ComponentManager> Clone for ModifyData<'a, T> {fn clone(&self) -> ModifyData<'a, T> {*self}}
 impl<'a, T: ComponentManager> Clone for EntityData<'a, T> {fn clone(&self) -> EntityData<'a, T> {*self}}
 
-#[doc(hidden)]
+/// Implemented by `BuildData`, `ModifyData` and `EntityData`, so helpers that read or set
+/// components can be written once, eg: `fn heal<U: EditData<MyComponents>>(e: &U, c: &mut MyComponents)`.
 pub unsafe trait EditData<T: ComponentManager> { fn entity(&self) -> &IndexedEntity<T>; }
-unsafe impl<'a, T: ComponentManager> EditData<T> for ModifyData<'a, T> { fn entity(&self) -> &IndexedEntity<T> { &self.0 } }
-unsafe impl<'a, T: ComponentManager> EditData<T> for EntityData<'a, T> { fn entity(&self) -> &IndexedEntity<T> { &self.0 } }
+unsafe impl<'a, T: ComponentManager> EditData<T> for BuildData<'a, T> { fn entity(&self) -> &IndexedEntity<T> { self.0 } }
+unsafe impl<'a, T: ComponentManager> EditData<T> for ModifyData<'a, T> { fn entity(&self) -> &IndexedEntity<T> { self.0 } }
+unsafe impl<'a, T: ComponentManager> EditData<T> for EntityData<'a, T> { fn entity(&self) -> &IndexedEntity<T> { self.0 } }
 
 #[macro_use]
 mod macros
@@ -78,9 +161,176 @@ mod macros
     #[macro_export]
     macro_rules! process {
         {
-            $world:expr, $system:ident
+            $world:expr, [$system:ident $(.$path:ident)* $(, $rest:ident $(.$rest_path:ident)*)* $(,)?]
+        } => {
+            {
+                process!($world, $system $(.$path)*);
+                $(
+                    $world.flush();
+                    process!($world, $rest $(.$rest_path)*);
+                )*
+            }
+        };
+        {
+            $world:expr, $system:ident $(.$path:ident)*
         } => {
-            $crate::Process::process(&mut $world.systems.$system, &mut $world.data)
+            $crate::Process::process(&mut $world.systems.$system $(.$path)*, &mut $world.data)
+        };
+    }
+
+    /// Implements `InspectComponent` for a struct by listing its fields, which must all implement it.
+    #[macro_export]
+    macro_rules! inspect_struct {
+        ($ty:ty { $($field:ident),* $(,)? }) => {
+            impl $crate::inspect::InspectComponent for $ty
+            {
+                fn inspect(&self) -> $crate::inspect::InspectValue
+                {
+                    $crate::inspect::InspectValue::Struct(vec![
+                        $((stringify!($field), $crate::inspect::InspectComponent::inspect(&self.$field))),*
+                    ])
+                }
+            }
+        };
+    }
+
+    /// Implements `Reflect` for a struct by listing its fields, which must all implement `ReflectValue`.
+    #[macro_export]
+    macro_rules! reflect_struct {
+        ($ty:ty { $($field:ident),* $(,)? }) => {
+            impl $crate::reflect::Reflect for $ty
+            {
+                fn field_names(&self) -> &'static [&'static str]
+                {
+                    &[$(stringify!($field)),*]
+                }
+
+                fn get_field(&self, name: &str) -> Option<String>
+                {
+                    match name {
+                        $(stringify!($field) => Some($crate::reflect::ReflectValue::to_text(&self.$field)),)*
+                        _ => None,
+                    }
+                }
+
+                fn set_field(&mut self, name: &str, value: &str) -> $crate::Result<()>
+                {
+                    match name {
+                        $(stringify!($field) => {
+                            self.$field = $crate::reflect::ReflectValue::from_text(value)
+                                .ok_or_else(|| $crate::reflect::invalid_value(name, value))?;
+                            Ok(())
+                        },)*
+                        _ => Err($crate::reflect::unknown_field(name)),
+                    }
+                }
+
+                fn get_f64(&self, name: &str) -> Option<f64>
+                {
+                    match name {
+                        $(stringify!($field) => $crate::reflect::ReflectValue::to_f64(&self.$field),)*
+                        _ => None,
+                    }
+                }
+
+                fn set_f64(&mut self, name: &str, value: f64) -> $crate::Result<()>
+                {
+                    match name {
+                        $(stringify!($field) => {
+                            self.$field = $crate::reflect::ReflectValue::from_f64(value)
+                                .ok_or_else(|| $crate::reflect::invalid_value(name, &value.to_string()))?;
+                            Ok(())
+                        },)*
+                        _ => Err($crate::reflect::unknown_field(name)),
+                    }
+                }
+            }
+        };
+    }
+
+    /// Implements `Delta` for a struct by listing its fields, which must all implement `Delta`:
+    /// `delta_struct!(Position { x, y })`. Each field is written only if it changed.
+    #[macro_export]
+    macro_rules! delta_struct {
+        ($ty:ident { $($field:ident),+ $(,)? }) => {
+            impl $crate::replication::Delta for $ty
+            {
+                fn write_delta(&self, old: Option<&$ty>, out: &mut $crate::replication::BitWriter)
+                {
+                    $($crate::replication::Delta::write_delta(&self.$field, old.map(|old| &old.$field), out);)+
+                }
+
+                fn read_delta(old: Option<&$ty>, input: &mut $crate::replication::BitReader) -> $crate::Result<$ty>
+                {
+                    Ok($ty { $($field: $crate::replication::Delta::read_delta(old.map(|old| &old.$field), input)?,)+ })
+                }
+            }
+        };
+    }
+
+    /// Declares the columns of a `#[soa]` component: `soa_struct!(Position => PositionColumns { x: f32, y: f32 })`
+    /// defines `PositionColumns` with a `Vec` for each listed field. The fields must be `Clone`.
+    #[macro_export]
+    macro_rules! soa_struct {
+        ($ty:ident => $columns:ident { $($field:ident : $field_ty:ty),+ $(,)? }) => {
+            #[derive(Clone, Debug, Default)]
+            pub struct $columns
+            {
+                $(pub $field: Vec<$field_ty>,)+
+            }
+
+            impl $crate::component::SoaComponent for $ty
+            {
+                type Columns = $columns;
+            }
+
+            impl $crate::component::SoaColumns<$ty> for $columns
+            {
+                fn push(&mut self, value: $ty)
+                {
+                    $(self.$field.push(value.$field);)+
+                }
+
+                fn swap_remove(&mut self, pos: usize) -> $ty
+                {
+                    $ty { $($field: self.$field.swap_remove(pos),)+ }
+                }
+
+                fn get(&self, pos: usize) -> $ty
+                {
+                    $ty { $($field: self.$field[pos].clone(),)+ }
+                }
+
+                fn replace(&mut self, pos: usize, value: $ty) -> $ty
+                {
+                    $ty { $($field: ::std::mem::replace(&mut self.$field[pos], value.$field),)+ }
+                }
+
+                fn len(&self) -> usize
+                {
+                    [$(self.$field.len()),+][0]
+                }
+
+                fn capacity(&self) -> usize
+                {
+                    [$(self.$field.capacity()),+].iter().cloned().min().unwrap_or(0)
+                }
+
+                fn reserve(&mut self, additional: usize)
+                {
+                    $(self.$field.reserve(additional);)+
+                }
+
+                fn shrink_to_fit(&mut self)
+                {
+                    $(self.$field.shrink_to_fit();)+
+                }
+
+                fn memory_bytes(&self) -> usize
+                {
+                    0 $(+ self.$field.capacity() * ::std::mem::size_of::<$field_ty>())+
+                }
+            }
         };
     }
 
@@ -89,29 +339,145 @@ mod macros
         {
             $Name:ident;
         } => {
-            pub struct $Name;
+            pub struct $Name {
+                __layers: $crate::component::LayerList<$Name>,
+                __despawning: $crate::FlagList<$Name, $crate::Despawning>,
+            }
 
             unsafe impl $crate::ComponentManager for $Name
             {
                 unsafe fn new() -> $Name
                 {
-                    $Name
+                    $Name {
+                        __layers: $crate::component::LayerList::new(),
+                        __despawning: $crate::FlagList::flag(),
+                    }
+                }
+
+                unsafe fn remove_all(&mut self, entity: &$crate::IndexedEntity<$Name>)
+                {
+                    self.__layers.clear(entity);
+                    self.__despawning.clear(entity);
+                }
+
+                unsafe fn transfer(&mut self, from: &$crate::IndexedEntity<$Name>, to: &mut $Name, into: &$crate::IndexedEntity<$Name>)
+                {
+                    self.__layers.transfer(from, &mut to.__layers, into);
+                    self.__despawning.clear(from);
+                }
+
+                unsafe fn take_changed(&mut self, changed: &mut Vec<$crate::Entity>)
+                {
+                    self.__layers.take_changed(changed);
+                    self.__despawning.take_changed(changed);
+                }
+
+                unsafe fn take_detached_changed(&mut self, _: &mut Vec<$crate::Entity>)
+                {
+
+                }
+
+                unsafe fn remap(&mut self, from: &$crate::IndexedEntity<$Name>, to: &$crate::IndexedEntity<$Name>)
+                {
+                    self.__layers.remap(from, to);
+                    self.__despawning.remap(from, to);
+                }
+
+                fn shrink_to_fit(&mut self)
+                {
+                    self.__layers.shrink_to_fit();
+                    self.__despawning.shrink_to_fit();
+                }
+
+                fn layers(&self) -> &$crate::component::LayerList<$Name>
+                {
+                    &self.__layers
+                }
+
+                fn layers_mut(&mut self) -> &mut $crate::component::LayerList<$Name>
+                {
+                    &mut self.__layers
+                }
+
+                fn despawning_flags(&self) -> &$crate::FlagList<$Name, $crate::Despawning>
+                {
+                    &self.__despawning
+                }
+
+                fn despawning_flags_mut(&mut self) -> &mut $crate::FlagList<$Name, $crate::Despawning>
+                {
+                    &mut self.__despawning
+                }
+
+                fn names() -> &'static [&'static str]
+                {
+                    &[]
+                }
+
+                fn memory_report(&self) -> $crate::world::MemoryReport
+                {
+                    $crate::world::MemoryReport::default()
+                }
+
+                fn component_mask(&self, _: &$crate::IndexedEntity<$Name>) -> $crate::world::ComponentMask
+                {
+                    $crate::world::ComponentMask::default()
+                }
+
+                fn reserve(&mut self, _: usize)
+                {
+
+                }
+
+                fn hash_entity<H: ::std::hash::Hasher>(&self, _: &$crate::IndexedEntity<$Name>, _: &mut H)
+                {
+
+                }
+
+                fn clear_refs(&mut self, _: &dyn Fn(&$crate::Entity) -> bool)
+                {
+
+                }
+
+                fn inspect_components(&self, _: &$crate::IndexedEntity<$Name>) -> Vec<(&'static str, $crate::inspect::InspectValue)>
+                {
+                    Vec::new()
                 }
 
-                unsafe fn remove_all(&mut self, _: &$crate::IndexedEntity<$Name>)
+                fn reflect_component(&self, _: &$crate::IndexedEntity<$Name>, _: &str) -> Option<&dyn $crate::reflect::Reflect>
                 {
+                    None
+                }
 
+                fn reflect_component_mut(&mut self, _: &$crate::IndexedEntity<$Name>, _: &str) -> Option<&mut dyn $crate::reflect::Reflect>
+                {
+                    None
                 }
+
+                __component_persist_impl!($Name);
             }
         };
         {
-            $Name:ident {
-                $(#[$kind:ident] $field_name:ident : $field_ty:ty),+
+            $(#[$default:ident])* $Name:ident {
+                $(#[$kind:ident $(($storage:ty))?] $(#[$attr:ident])* $field_name:ident : $field_ty:ty),+
             }
         } => {
             pub struct $Name {
                 $(
-                    pub $field_name : $crate::ComponentList<$Name, $field_ty>,
+                    pub $field_name : __component_list!($kind, $Name, $field_ty),
+                )+
+                __layers: $crate::component::LayerList<$Name>,
+                __despawning: $crate::FlagList<$Name, $crate::Despawning>,
+            }
+
+            $(
+                __component_nests!($kind, $Name, $field_ty);
+            )+
+
+            impl $Name
+            {
+                $(
+                    __component_field!($kind, $Name, $field_name, $field_ty);
                 )+
             }
 
@@ -121,8 +487,10 @@ mod macros
                 {
                     $Name {
                         $(
-                            $field_name : $crate::ComponentList::$kind(),
+                            $field_name : __component_new!($kind, [$($storage)?], $Name, $field_ty).named(stringify!($field_name)),
                         )+
+                        __layers: $crate::component::LayerList::new(),
+                        __despawning: $crate::FlagList::flag(),
                     }
                 }
 
@@ -131,28 +499,400 @@ mod macros
                     $(
                         self.$field_name.clear(entity);
                     )+
+                    self.__layers.clear(entity);
+                    self.__despawning.clear(entity);
+                }
+
+                unsafe fn transfer(&mut self, from: &$crate::IndexedEntity<$Name>, to: &mut $Name, into: &$crate::IndexedEntity<$Name>)
+                {
+                    $(
+                        self.$field_name.transfer(from, &mut to.$field_name, into);
+                    )+
+                    self.__layers.transfer(from, &mut to.__layers, into);
+                    self.__despawning.clear(from);
+                }
+
+                unsafe fn remap(&mut self, from: &$crate::IndexedEntity<$Name>, to: &$crate::IndexedEntity<$Name>)
+                {
+                    $(
+                        self.$field_name.remap(from, to);
+                    )+
+                    self.__layers.remap(from, to);
+                    self.__despawning.remap(from, to);
+                }
+
+                fn shrink_to_fit(&mut self)
+                {
+                    $(
+                        self.$field_name.shrink_to_fit();
+                    )+
+                    self.__layers.shrink_to_fit();
+                    self.__despawning.shrink_to_fit();
+                }
+
+                unsafe fn take_changed(&mut self, changed: &mut Vec<$crate::Entity>)
+                {
+                    $(
+                        __component_changed!((self.$field_name), changed, $($attr)*);
+                    )+
+                    self.__layers.take_changed(changed);
+                    self.__despawning.take_changed(changed);
                 }
+
+                unsafe fn take_detached_changed(&mut self, changed: &mut Vec<$crate::Entity>)
+                {
+                    $(
+                        __component_detached_changed!((self.$field_name), changed, $($attr)*);
+                    )+
+                }
+
+                fn layers(&self) -> &$crate::component::LayerList<$Name>
+                {
+                    &self.__layers
+                }
+
+                fn layers_mut(&mut self) -> &mut $crate::component::LayerList<$Name>
+                {
+                    &mut self.__layers
+                }
+
+                fn despawning_flags(&self) -> &$crate::FlagList<$Name, $crate::Despawning>
+                {
+                    &self.__despawning
+                }
+
+                fn despawning_flags_mut(&mut self) -> &mut $crate::FlagList<$Name, $crate::Despawning>
+                {
+                    &mut self.__despawning
+                }
+
+                fn names() -> &'static [&'static str]
+                {
+                    &[$(stringify!($field_name)),+]
+                }
+
+                fn memory_report(&self) -> $crate::world::MemoryReport
+                {
+                    $crate::world::MemoryReport
+                    {
+                        components: vec![$(
+                            $crate::world::ComponentMemory
+                            {
+                                name: self.$field_name.name(),
+                                len: self.$field_name.len(),
+                                capacity: self.$field_name.capacity(),
+                                bytes: self.$field_name.memory_bytes(),
+                            },
+                        )+],
+                    }
+                }
+
+                fn component_mask(&self, entity: &$crate::IndexedEntity<$Name>) -> $crate::world::ComponentMask
+                {
+                    let mut mask = $crate::world::ComponentMask::default();
+                    let has = [$(self.$field_name.contains(entity)),+];
+                    for (field, &has) in has.iter().enumerate() {
+                        if has {
+                            mask.insert(field);
+                        }
+                    }
+                    mask
+                }
+
+                fn reserve(&mut self, additional: usize)
+                {
+                    $(
+                        self.$field_name.reserve(additional);
+                    )+
+                }
+
+                fn hash_entity<H: ::std::hash::Hasher>(&self, entity: &$crate::IndexedEntity<$Name>, state: &mut H)
+                {
+                    $(
+                        __component_hash!((self.$field_name), entity, state, $($attr)*);
+                    )+
+                }
+
+                fn clear_refs(&mut self, removed: &dyn Fn(&$crate::Entity) -> bool)
+                {
+                    $(
+                        __component_refs!((self.$field_name), removed, $($attr)*);
+                    )+
+                }
+
+                fn inspect_components(&self, entity: &$crate::IndexedEntity<$Name>) -> Vec<(&'static str, $crate::inspect::InspectValue)>
+                {
+                    let mut components = Vec::new();
+                    $(
+                        __component_inspect!((self.$field_name), stringify!($field_name), entity, components, $($attr)*);
+                    )+
+                    components
+                }
+
+                fn reflect_component(&self, entity: &$crate::IndexedEntity<$Name>, name: &str) -> Option<&dyn $crate::reflect::Reflect>
+                {
+                    $(
+                        __component_reflect!(name == stringify!($field_name), self.$field_name.reflect(entity), $($attr)*);
+                    )+
+                    None
+                }
+
+                fn reflect_component_mut(&mut self, entity: &$crate::IndexedEntity<$Name>, name: &str) -> Option<&mut dyn $crate::reflect::Reflect>
+                {
+                    $(
+                        __component_reflect!(name == stringify!($field_name), self.$field_name.reflect_mut(entity), $($attr)*);
+                    )+
+                    None
+                }
+
+                __component_persist_impl!($Name, [$($default)*], $($field_name [$($attr)*]),+);
             }
         };
         {
-            $Name:ident {
-                $(#[$kind:ident] $field_name:ident : $field_ty:ty),+,
+            $(#[$default:ident])* $Name:ident {
+                $(#[$kind:ident $(($storage:ty))?] $(#[$attr:ident])* $field_name:ident : $field_ty:ty),+,
             }
         } => {
-            components! { $Name { $(#[$kind] $field_name : $field_ty),+ } }
+            components! { $(#[$default])* $Name { $(#[$kind $(($storage))?] $(#[$attr])* $field_name : $field_ty),+ } }
         };
     }
 
+    /// Storage type for each kind of field in `components!`.
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __component_list {
+        (flag, $Name:ty, $field_ty:ty) => { $crate::FlagList<$Name, $field_ty> };
+        (nested, $Name:ty, $field_ty:ty) => { $crate::component::NestedList<$Name, $field_ty> };
+        (shared, $Name:ty, $field_ty:ty) => { $crate::component::SharedList<$Name, $field_ty> };
+        (soa, $Name:ty, $field_ty:ty) => { $crate::component::SoaList<$Name, $field_ty> };
+        ($kind:ident, $Name:ty, $field_ty:ty) => { $crate::ComponentList<$Name, $field_ty> };
+    }
+
+    /// Creates the storage for a field, passing a default `ComponentStorage` to `#[storage(..)]`.
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __component_new {
+        ($kind:ident, [$storage:ty], $Name:ty, $field_ty:ty) => {
+            <__component_list!($kind, $Name, $field_ty)>::$kind(<$storage as ::std::default::Default>::default())
+        };
+        ($kind:ident, [], $Name:ty, $field_ty:ty) => {
+            <__component_list!($kind, $Name, $field_ty)>::$kind()
+        };
+    }
+
+    /// Lets entities of `components!` be used with the components of its `#[nested]` fields.
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __component_nests {
+        (nested, $Name:ty, $field_ty:ty) => {
+            unsafe impl $crate::component::Nests<$field_ty> for $Name {}
+        };
+        ($kind:ident, $Name:ty, $field_ty:ty) => {};
+    }
+
+    /// Pairs a component with its field for building entities from tuples, except for `#[nested]` fields.
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __component_field {
+        (nested, $Name:ty, $field_name:ident, $field_ty:ty) => {};
+        ($kind:ident, $Name:ty, $field_name:ident, $field_ty:ty) => {
+            pub fn $field_name(value: $field_ty) -> $crate::component::Field<$Name, $field_ty>
+            {
+                $crate::component::Field::new(value, |c: &mut $Name, e: &$crate::BuildData<$Name>, value: $field_ty| {
+                    c.$field_name.add(e, value);
+                })
+            }
+        };
+    }
+
+    /// Hashes a field's component for `World::state_hash()` if it is marked `#[hashable]`.
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __component_hash {
+        ($list:expr, $entity:expr, $state:expr, ) => {};
+        ($list:expr, $entity:expr, $state:expr, hashable $($attr:ident)*) => {
+            $list.hash_entity($entity, $state);
+        };
+        ($list:expr, $entity:expr, $state:expr, $other:ident $($attr:ident)*) => {
+            __component_hash!($list, $entity, $state, $($attr)*);
+        };
+    }
+
+    /// Collects the entities that gained or lost a field's component, unless it is marked
+    /// `#[detached]`, in which case changes are kept for `World::flush_detached()`.
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __component_changed {
+        ($list:expr, $changed:expr, ) => {
+            $list.take_changed($changed);
+        };
+        ($list:expr, $changed:expr, detached $($attr:ident)*) => {};
+        ($list:expr, $changed:expr, $other:ident $($attr:ident)*) => {
+            __component_changed!($list, $changed, $($attr)*);
+        };
+    }
+
+    /// Collects the entities that gained or lost a field's component if it is marked `#[detached]`.
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __component_detached_changed {
+        ($list:expr, $changed:expr, ) => {};
+        ($list:expr, $changed:expr, detached $($attr:ident)*) => {
+            $list.take_changed($changed);
+        };
+        ($list:expr, $changed:expr, $other:ident $($attr:ident)*) => {
+            __component_detached_changed!($list, $changed, $($attr)*);
+        };
+    }
+
+    /// Adds a field's component to an inspector's model if it is marked `#[inspect]`.
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __component_inspect {
+        ($list:expr, $field:expr, $entity:expr, $out:expr, ) => {};
+        ($list:expr, $field:expr, $entity:expr, $out:expr, inspect $($attr:ident)*) => {
+            if let Some(value) = $list.inspect($entity) {
+                $out.push(($field, value));
+            }
+        };
+        ($list:expr, $field:expr, $entity:expr, $out:expr, $other:ident $($attr:ident)*) => {
+            __component_inspect!($list, $field, $entity, $out, $($attr)*);
+        };
+    }
+
+    /// Returns a field's component for reflection if it is marked `#[reflect]` and has the wanted name.
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __component_reflect {
+        ($matches:expr, $component:expr, ) => {};
+        ($matches:expr, $component:expr, reflect $($attr:ident)*) => {
+            if $matches {
+                return $component;
+            }
+        };
+        ($matches:expr, $component:expr, $other:ident $($attr:ident)*) => {
+            __component_reflect!($matches, $component, $($attr)*);
+        };
+    }
+
+    /// Clears a field's references to removed entities if it is marked `#[refs]`.
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __component_refs {
+        ($list:expr, $removed:expr, ) => {};
+        ($list:expr, $removed:expr, refs $($attr:ident)*) => {
+            $list.clear_refs($removed);
+        };
+        ($list:expr, $removed:expr, $other:ident $($attr:ident)*) => {
+            __component_refs!($list, $removed, $($attr)*);
+        };
+    }
+
+    /// Deserializes a field's component by name if it is persistent.
+    ///
+    /// A field is persistent if it is marked `#[persist]`, or if the struct is and the field isn't
+    /// marked `#[transient]`. The bracketed ident is `persist` for the latter.
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __component_persist {
+        ($list:expr, $field:expr, $name:expr, $entity:expr, $value:expr, [persist]) => {
+            if $name == $field {
+                return Some($list.deserialize($entity, $value));
+            }
+        };
+        ($list:expr, $field:expr, $name:expr, $entity:expr, $value:expr, [$($default:ident)*]) => {};
+        ($list:expr, $field:expr, $name:expr, $entity:expr, $value:expr, [$($default:ident)*] transient $($attr:ident)*) => {};
+        ($list:expr, $field:expr, $name:expr, $entity:expr, $value:expr, [$($default:ident)*] persist $($attr:ident)*) => {
+            __component_persist!($list, $field, $name, $entity, $value, [persist] $($attr)*);
+        };
+        ($list:expr, $field:expr, $name:expr, $entity:expr, $value:expr, [$($default:ident)*] $other:ident $($attr:ident)*) => {
+            __component_persist!($list, $field, $name, $entity, $value, [$($default)*] $($attr)*);
+        };
+    }
+
+    /// Serializes a field's component into `$out` if it is persistent.
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __component_save {
+        ($list:expr, $field:expr, $entity:expr, $out:expr, [persist]) => {
+            if let Some(value) = $list.serialize($entity) {
+                $out.push(($field, value?));
+            }
+        };
+        ($list:expr, $field:expr, $entity:expr, $out:expr, [$($default:ident)*]) => {};
+        ($list:expr, $field:expr, $entity:expr, $out:expr, [$($default:ident)*] transient $($attr:ident)*) => {};
+        ($list:expr, $field:expr, $entity:expr, $out:expr, [$($default:ident)*] persist $($attr:ident)*) => {
+            __component_save!($list, $field, $entity, $out, [persist] $($attr)*);
+        };
+        ($list:expr, $field:expr, $entity:expr, $out:expr, [$($default:ident)*] $other:ident $($attr:ident)*) => {
+            __component_save!($list, $field, $entity, $out, [$($default)*] $($attr)*);
+        };
+    }
+
+    /// The `ComponentManager` methods that only exist with the `serde` feature.
+    #[cfg(feature = "serde")]
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __component_persist_impl {
+        ($Name:ident) => {
+            unsafe fn deserialize_component(&mut self, _: &str, _: &$crate::IndexedEntity<$Name>, _: $crate::scene::Value) -> Option<$crate::Result<()>>
+            {
+                None
+            }
+
+            fn serialize_components(&self, _: &$crate::IndexedEntity<$Name>) -> $crate::Result<Vec<(&'static str, $crate::scene::Value)>>
+            {
+                Ok(Vec::new())
+            }
+        };
+        ($Name:ident, $default:tt, $($field_name:ident [$($attr:ident)*]),+) => {
+            unsafe fn deserialize_component(&mut self, name: &str, entity: &$crate::IndexedEntity<$Name>, value: $crate::scene::Value) -> Option<$crate::Result<()>>
+            {
+                $(
+                    __component_persist!((self.$field_name), stringify!($field_name), name, entity, value, $default $($attr)*);
+                )+
+                None
+            }
+
+            fn serialize_components(&self, entity: &$crate::IndexedEntity<$Name>) -> $crate::Result<Vec<(&'static str, $crate::scene::Value)>>
+            {
+                let mut components = Vec::new();
+                $(
+                    __component_save!((self.$field_name), stringify!($field_name), entity, components, $default $($attr)*);
+                )+
+                Ok(components)
+            }
+        };
+    }
+
+    #[cfg(not(feature = "serde"))]
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __component_persist_impl {
+        ($($t:tt)*) => {};
+    }
+
     #[macro_export]
     macro_rules! services {
         {
             $Name:ident {
-                $($field_name:ident : $field_ty:ty = $field_init:expr),+
+                $($fields:tt)+
             }
+        } => {
+            __service_fields! { $Name [] $($fields)+ }
+        };
+    }
+
+    /// Reads the fields of `services!` one at a time, filling in their visibility.
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __service_fields {
+        {
+            $Name:ident [$([$($attr:ident)*] [$($vis:tt)*] $field_name:ident : $field_ty:ty = [$($field_init:tt)*])+]
         } => {
             pub struct $Name {
                 $(
-                    pub $field_name : $field_ty,
+                    $($vis)* $field_name : $field_ty,
                 )+
             }
 
@@ -162,19 +902,36 @@ mod macros
                 {
                     $Name {
                         $(
-                            $field_name : $field_init,
+                            $field_name : __service_init!([$($attr)*] [$($field_init)*] $field_ty),
                         )+
                     }
                 }
             }
         };
         {
-            $Name:ident {
-                $($field_name:ident : $field_ty:ty = $field_init:expr),+,
-            }
+            $Name:ident [$($done:tt)*] $(#[$attr:ident])* pub($($restrict:tt)+) $field_name:ident : $field_ty:ty $(= $field_init:expr)? $(, $($rest:tt)*)?
         } => {
-            services! { $Name { $($field_name : $field_ty = $field_init),+ } }
-        }
+            __service_fields! { $Name [$($done)* [$($attr)*] [pub($($restrict)+)] $field_name : $field_ty = [$($field_init)?]] $($($rest)*)? }
+        };
+        {
+            $Name:ident [$($done:tt)*] $(#[$attr:ident])* pub $field_name:ident : $field_ty:ty $(= $field_init:expr)? $(, $($rest:tt)*)?
+        } => {
+            __service_fields! { $Name [$($done)* [$($attr)*] [pub] $field_name : $field_ty = [$($field_init)?]] $($($rest)*)? }
+        };
+        {
+            $Name:ident [$($done:tt)*] $(#[$attr:ident])* $field_name:ident : $field_ty:ty $(= $field_init:expr)? $(, $($rest:tt)*)?
+        } => {
+            __service_fields! { $Name [$($done)* [$($attr)*] [pub] $field_name : $field_ty = [$($field_init)?]] $($($rest)*)? }
+        };
+    }
+
+    /// Initial value of a field in `services!`.
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __service_init {
+        ([] [] $field_ty:ty) => { <$field_ty as ::std::default::Default>::default() };
+        ([services] [] $field_ty:ty) => { <$field_ty as $crate::ServiceManager>::new() };
+        ([$($attr:ident)*] [$field_init:expr] $field_ty:ty) => { $field_init };
     }
 
     #[macro_export]
@@ -182,61 +939,115 @@ mod macros
         {
             $Name:ident<$components:ty, $services:ty>;
         } => {
-            pub struct $Name;
+            pub struct $Name {
+                __dynamic: $crate::system::DynamicSystems<$components, $services>,
+            }
+
+            impl $Name
+            {
+                /// Adds a named system to be updated after all the others, replacing and returning
+                /// any system previously added with the same name.
+                ///
+                /// Existing entities aren't activated for the new system. Use
+                /// `World::add_dynamic_system()` to add systems to a running world.
+                pub fn add_dynamic(&mut self, name: &str, system: $crate::system::BoxedProcess<$components, $services>)
+                    -> Option<$crate::system::BoxedProcess<$components, $services>>
+                {
+                    self.__dynamic.insert(name, system)
+                }
+
+                /// Removes a system added with `add_dynamic()`, returning it.
+                pub fn remove_dynamic(&mut self, name: &str) -> Option<$crate::system::BoxedProcess<$components, $services>>
+                {
+                    self.__dynamic.remove(name)
+                }
+            }
 
             unsafe impl $crate::SystemManager for $Name
             {
                 type Components = $components;
                 type Services = $services;
-                #[allow(unused_unsafe)] // The aspect macro is probably going to be used here and it also expands to an unsafe block.
                 unsafe fn new() -> $Name
                 {
-                    $Name
+                    $Name {
+                        __dynamic: $crate::system::DynamicSystems::new(),
+                    }
                 }
 
-                unsafe fn activated(&mut self, _: $crate::EntityData<$components>, _: &$components)
+                unsafe fn activated(&mut self, en: $crate::EntityData<$components>, co: &$components)
                 {
-
+                    $crate::System::activated(&mut self.__dynamic, &en, co);
                 }
 
-                unsafe fn reactivated(&mut self, _: $crate::EntityData<$components>, _: &$components)
+                unsafe fn activated_batch(&mut self, en: &[$crate::EntityData<$components>], co: &$components)
                 {
-
+                    $crate::System::activated_batch(&mut self.__dynamic, en, co);
                 }
 
-                unsafe fn deactivated(&mut self, _: $crate::EntityData<$components>, _: &$components)
+                unsafe fn reactivated(&mut self, en: $crate::EntityData<$components>, co: &$components)
                 {
+                    $crate::System::reactivated(&mut self.__dynamic, &en, co);
+                }
 
+                unsafe fn deactivated(&mut self, en: $crate::EntityData<$components>, co: &$components)
+                {
+                    $crate::System::deactivated(&mut self.__dynamic, &en, co);
                 }
 
-                unsafe fn update(&mut self, _: &mut $crate::DataHelper<$components, $services>)
+                unsafe fn update(&mut self, co: &mut $crate::DataHelper<$components, $services>)
                 {
+                    $crate::Process::process(&mut self.__dynamic, co);
+                }
 
+                fn dynamic_systems(&mut self) -> &mut $crate::system::DynamicSystems<$components, $services>
+                {
+                    &mut self.__dynamic
                 }
             }
         };
         {
             $Name:ident<$components:ty, $services:ty> {
-                $($field_name:ident : $field_ty:ty = $field_init:expr),+
+                $($(#[group($group:ident)])* $field_name:ident : $field_ty:ty = $field_init:expr),+
             }
         } => {
             pub struct $Name {
                 $(
                     pub $field_name : $field_ty,
                 )+
+                __dynamic: $crate::system::DynamicSystems<$components, $services>,
+            }
+
+            impl $Name
+            {
+                /// Adds a named system to be updated after all the others, replacing and returning
+                /// any system previously added with the same name.
+                ///
+                /// Existing entities aren't activated for the new system. Use
+                /// `World::add_dynamic_system()` to add systems to a running world.
+                pub fn add_dynamic(&mut self, name: &str, system: $crate::system::BoxedProcess<$components, $services>)
+                    -> Option<$crate::system::BoxedProcess<$components, $services>>
+                {
+                    self.__dynamic.insert(name, system)
+                }
+
+                /// Removes a system added with `add_dynamic()`, returning it.
+                pub fn remove_dynamic(&mut self, name: &str) -> Option<$crate::system::BoxedProcess<$components, $services>>
+                {
+                    self.__dynamic.remove(name)
+                }
             }
 
             unsafe impl $crate::SystemManager for $Name
             {
                 type Components = $components;
                 type Services = $services;
-                #[allow(unused_unsafe)] // The aspect macro is probably going to be used here and it also expands to an unsafe block.
                 unsafe fn new() -> $Name
                 {
                     $Name {
                         $(
                             $field_name : $field_init,
                         )+
+                        __dynamic: $crate::system::DynamicSystems::new(),
                     }
                 }
 
@@ -245,6 +1056,15 @@ mod macros
                     $(
                         self.$field_name.activated(&en, co);
                     )+
+                    $crate::System::activated(&mut self.__dynamic, &en, co);
+                }
+
+                unsafe fn activated_batch(&mut self, en: &[$crate::EntityData<$components>], co: &$components)
+                {
+                    $(
+                        self.$field_name.activated_batch(en, co);
+                    )+
+                    $crate::System::activated_batch(&mut self.__dynamic, en, co);
                 }
 
                 unsafe fn reactivated(&mut self, en: $crate::EntityData<$components>, co: &$components)
@@ -252,6 +1072,7 @@ mod macros
                     $(
                         self.$field_name.reactivated(&en, co);
                     )+
+                    $crate::System::reactivated(&mut self.__dynamic, &en, co);
                 }
 
                 unsafe fn deactivated(&mut self, en: $crate::EntityData<$components>, co: &$components)
@@ -259,24 +1080,31 @@ mod macros
                     $(
                         self.$field_name.deactivated(&en, co);
                     )+
+                    $crate::System::deactivated(&mut self.__dynamic, &en, co);
                 }
 
                 unsafe fn update(&mut self, co: &mut $crate::DataHelper<$components, $services>)
                 {
                     $(
-                        if self.$field_name.is_active() {
-                            $crate::Process::process(&mut self.$field_name, co);
+                        if !co.any_group_paused(&[$(stringify!($group)),*]) {
+                            $crate::system::failures::process_isolated(&mut self.$field_name, stringify!($field_name), co);
                         }
                     )+
+                    $crate::Process::process(&mut self.__dynamic, co);
+                }
+
+                fn dynamic_systems(&mut self) -> &mut $crate::system::DynamicSystems<$components, $services>
+                {
+                    &mut self.__dynamic
                 }
             }
         };
         {
             $Name:ident<$components:ty, $services:ty> {
-                $($field_name:ident : $field_ty:ty = $field_init:expr),+,
+                $($(#[group($group:ident)])* $field_name:ident : $field_ty:ty = $field_init:expr),+,
             }
         } => {
-            systems! { $Name<$components, $services> { $($field_name : $field_ty = $field_init),+ } }
+            systems! { $Name<$components, $services> { $($(#[group($group)])* $field_name : $field_ty = $field_init),+ } }
         }
     }
 
@@ -284,35 +1112,40 @@ mod macros
     macro_rules! aspect {
         {
             <$components:ty>
-            all: [$($all_field:ident),*]
-            none: [$($none_field:ident),*]
+            $(all: [$($all_field:ident $(.$all_field_path:ident)*),*])?
+            $(none: [$($none_field:ident $(.$none_field_path:ident)*),*])?
+            $(layers: [$($layer:expr),*])?
+            $(where: [$($predicate:expr),*])?
         } => {
-            unsafe {
-                $crate::Aspect::new(Box::new(|_en: &$crate::EntityData<$components>, _co: &$components| {
-                    ($(_co.$all_field.has(_en) &&)* true) &&
-                    !($(_co.$none_field.has(_en) ||)* false)
-                }))
+            {
+                let _predicates: Vec<$crate::aspect::Predicate<$components>> = vec![
+                    $($($crate::aspect::predicate::<$components, _>($predicate)),*)?
+                ];
+                let _layers: u32 = 0 $($(| $layer)*)?;
+                let _info = $crate::aspect::AspectInfo::new(vec![
+                    $($(concat!(stringify!($all_field) $(, ".", stringify!($all_field_path))*),)*)?
+                    $($(concat!(stringify!($none_field) $(, ".", stringify!($none_field_path))*),)*)?
+                ], _layers != 0, !_predicates.is_empty());
+                $crate::Aspect::<$components>::with_info(Box::new(move |_en: &$crate::EntityData<$components>, _co: &$components| {
+                    ($($(__aspect_has!(_co, _en, $all_field $(.$all_field_path)*) &&)*)? true) &&
+                    !($($(__aspect_has!(_co, _en, $none_field $(.$none_field_path)*) ||)*)? false) &&
+                    (_layers == 0 || $crate::ComponentManager::layers(_co).has_any(_en, _layers)) &&
+                    _predicates.iter().all(|predicate| predicate(_en, _co))
+                }), _info)
             }
         };
-        {
-            <$components:ty>
-            all: [$($field:ident),*]
-        } => {
-            aspect!(
-                <$components>
-                all: [$($field),*]
-                none: []
-            )
+    }
+
+    /// Checks whether an entity has a component, reaching into `#[nested]` fields for paths like
+    /// `physics.body`.
+    #[doc(hidden)]
+    #[macro_export]
+    macro_rules! __aspect_has {
+        ($co:expr, $en:expr, $field:ident) => {
+            $co.$field.has($en)
         };
-        {
-            <$components:ty>
-            none: [$($field:ident),*]
-        } => {
-            aspect!(
-                <$components>
-                all: []
-                none: [$($field),*]
-            )
+        ($co:expr, $en:expr, $field:ident . $($path:tt)+) => {
+            __aspect_has!($co.$field, &$en.nested(), $($path)+)
         };
     }
 }
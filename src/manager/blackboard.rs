@@ -0,0 +1,86 @@
+//! Shared scratch space holding one value of each type.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+use MaybeSend;
+use ServiceManager;
+
+/// Stores at most one value per Rust type, eg: `set(PlayerScore(0))` and `get::<PlayerScore>()`.
+///
+/// It can be a field of `services!`, or the whole service manager of a world.
+pub struct Blackboard
+{
+    values: HashMap<TypeId, boxed!(Any)>,
+}
+
+impl Blackboard
+{
+    pub fn new() -> Blackboard
+    {
+        Blackboard
+        {
+            values: HashMap::new(),
+        }
+    }
+
+    /// Stores a value, returning the previous value of the same type.
+    pub fn set<T: Any + MaybeSend>(&mut self, value: T) -> Option<T>
+    {
+        self.values.insert(TypeId::of::<T>(), Box::new(value)).map(|old| *old.downcast::<T>().ok().unwrap())
+    }
+
+    pub fn get<T: Any>(&self) -> Option<&T>
+    {
+        self.values.get(&TypeId::of::<T>()).and_then(|value| value.downcast_ref::<T>())
+    }
+
+    pub fn get_mut<T: Any>(&mut self) -> Option<&mut T>
+    {
+        self.values.get_mut(&TypeId::of::<T>()).and_then(|value| value.downcast_mut::<T>())
+    }
+
+    /// Returns the value of a type, storing one made by `default` first if there isn't one.
+    pub fn get_or_insert_with<T, F>(&mut self, default: F) -> &mut T
+        where T: Any + MaybeSend, F: FnOnce() -> T
+    {
+        self.values.entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(default()))
+            .downcast_mut::<T>()
+            .unwrap()
+    }
+
+    /// Removes the value of a type, returning it.
+    pub fn remove<T: Any>(&mut self) -> Option<T>
+    {
+        self.values.remove(&TypeId::of::<T>()).map(|old| *old.downcast::<T>().ok().unwrap())
+    }
+
+    pub fn contains<T: Any>(&self) -> bool
+    {
+        self.values.contains_key(&TypeId::of::<T>())
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.values.is_empty()
+    }
+
+    pub fn clear(&mut self)
+    {
+        self.values.clear();
+    }
+}
+
+impl ServiceManager for Blackboard
+{
+    fn new() -> Blackboard
+    {
+        Blackboard::new()
+    }
+}
@@ -0,0 +1,164 @@
+//! Messages passed between worlds, eg: a headless server and its clients.
+
+use std::collections::{HashMap, VecDeque};
+use std::collections::vec_deque::Drain;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use MaybeSend;
+use ServiceManager;
+use {Error, Result};
+
+/// Identifies a world on the other end of a transport.
+pub type Peer = u32;
+
+/// Carries serialized messages between worlds, eg: over sockets or in-process channels.
+pub trait Transport
+{
+    fn send(&mut self, peer: Peer, bytes: Vec<u8>) -> Result<()>;
+
+    /// Returns the next message that arrived, and who sent it, without waiting for one.
+    fn receive(&mut self) -> Result<Option<(Peer, Vec<u8>)>>;
+}
+
+/// Sends messages to other worlds and keeps the ones they send back, by peer.
+///
+/// Messages are serialized as JSON. Until a transport is set, nothing can be sent or received.
+pub struct MessageBus<T>
+{
+    transport: Option<boxed!(Transport)>,
+    inbox: HashMap<Peer, VecDeque<T>>,
+    _message: PhantomData<fn(T)>,
+}
+
+impl<T: Serialize + DeserializeOwned> MessageBus<T>
+{
+    pub fn new() -> MessageBus<T>
+    {
+        MessageBus
+        {
+            transport: None,
+            inbox: HashMap::new(),
+            _message: PhantomData,
+        }
+    }
+
+    pub fn with_transport<R: Transport + MaybeSend + 'static>(transport: R) -> MessageBus<T>
+    {
+        MessageBus
+        {
+            transport: Some(Box::new(transport)),
+            ..MessageBus::new()
+        }
+    }
+
+    /// Replaces the transport. Messages already received are kept.
+    pub fn set_transport<R: Transport + MaybeSend + 'static>(&mut self, transport: R)
+    {
+        self.transport = Some(Box::new(transport));
+    }
+
+    pub fn send_to(&mut self, peer: Peer, message: &T) -> Result<()>
+    {
+        let bytes = ::serde_json::to_vec(message).map_err(|err| Error::Parse(err.to_string()))?;
+        self.transport()?.send(peer, bytes)
+    }
+
+    /// Takes every message that has arrived from a peer, in the order they were sent.
+    ///
+    /// If a message can't be read, the error is returned and the messages before it are kept.
+    pub fn drain_from(&mut self, peer: Peer) -> Result<Drain<'_, T>>
+    {
+        self.receive()?;
+        Ok(self.inbox.entry(peer).or_default().drain(..))
+    }
+
+    /// Moves every message waiting in the transport into the inbox of its sender.
+    pub fn receive(&mut self) -> Result<()>
+    {
+        while let Some((peer, bytes)) = self.transport()?.receive()?
+        {
+            let message = ::serde_json::from_slice(&bytes).map_err(|err| Error::Parse(err.to_string()))?;
+            self.inbox.entry(peer).or_default().push_back(message);
+        }
+        Ok(())
+    }
+
+    /// The peers with messages in the inbox, in no particular order.
+    pub fn peers(&self) -> Vec<Peer>
+    {
+        self.inbox.iter().filter(|&(_, messages)| !messages.is_empty()).map(|(peer, _)| *peer).collect()
+    }
+
+    fn transport(&mut self) -> Result<&mut boxed!(Transport)>
+    {
+        self.transport.as_mut().ok_or_else(|| Error::Transport("no transport set".to_string()))
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + 'static> ServiceManager for MessageBus<T>
+{
+    fn new() -> MessageBus<T>
+    {
+        MessageBus::new()
+    }
+}
+
+/// Mailboxes of the peers connected to a `LocalNetwork`.
+type Mailboxes = HashMap<Peer, VecDeque<(Peer, Vec<u8>)>>;
+
+/// Connects worlds in the same process, eg: a server and a client in tests or single player.
+#[derive(Clone, Default)]
+pub struct LocalNetwork
+{
+    mailboxes: Arc<Mutex<Mailboxes>>,
+}
+
+impl LocalNetwork
+{
+    pub fn new() -> LocalNetwork
+    {
+        LocalNetwork::default()
+    }
+
+    /// Joins the network as `peer`, dropping anything sent to an earlier `peer`.
+    pub fn connect(&self, peer: Peer) -> LocalTransport
+    {
+        self.mailboxes.lock().unwrap().insert(peer, VecDeque::new());
+        LocalTransport
+        {
+            peer: peer,
+            network: self.clone(),
+        }
+    }
+}
+
+/// A peer's end of a `LocalNetwork`.
+pub struct LocalTransport
+{
+    peer: Peer,
+    network: LocalNetwork,
+}
+
+impl Transport for LocalTransport
+{
+    fn send(&mut self, peer: Peer, bytes: Vec<u8>) -> Result<()>
+    {
+        match self.network.mailboxes.lock().unwrap().get_mut(&peer)
+        {
+            Some(mailbox) => {
+                mailbox.push_back((self.peer, bytes));
+                Ok(())
+            },
+            None => Err(Error::Transport(format!("no peer {} is connected", peer))),
+        }
+    }
+
+    fn receive(&mut self) -> Result<Option<(Peer, Vec<u8>)>>
+    {
+        Ok(self.network.mailboxes.lock().unwrap().get_mut(&self.peer).and_then(|mailbox| mailbox.pop_front()))
+    }
+}
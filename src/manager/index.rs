@@ -0,0 +1,147 @@
+//! Lookup of entities by a key read from their components.
+
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::Keys;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use {ComponentManager, ServiceManager};
+use MaybeSend;
+use DataHelper;
+use Entity;
+use EntityData;
+use {Process, System};
+
+/// Keeps track of which entities have each key, eg: the entities on each team.
+///
+/// Keys are read with a user-provided extractor when an entity is activated or reactivated.
+/// Entities for which the extractor returns `None` are left out. Changing a component in place
+/// doesn't reactivate its entity, so call `refresh()` afterwards if the key depends on it.
+pub struct IndexManager<K, C: ComponentManager, M: ServiceManager = ()>
+{
+    entities: HashMap<K, HashSet<Entity>>,
+    keys: HashMap<Entity, K>,
+    key: boxed!(Fn(&EntityData<C>, &C) -> Option<K> + 'static),
+    _services: PhantomData<fn(M)>,
+}
+
+impl<K: Hash + Eq + Clone, C: ComponentManager, M: ServiceManager> IndexManager<K, C, M>
+{
+    pub fn new<F>(key: F) -> IndexManager<K, C, M>
+        where F: Fn(&EntityData<C>, &C) -> Option<K> + MaybeSend + 'static
+    {
+        IndexManager
+        {
+            entities: HashMap::new(),
+            keys: HashMap::new(),
+            key: Box::new(key),
+            _services: PhantomData,
+        }
+    }
+
+    /// The entities with a key, if there are any.
+    pub fn get(&self, key: &K) -> Option<&HashSet<Entity>>
+    {
+        self.entities.get(key)
+    }
+
+    /// Counts the entities with a key.
+    pub fn count(&self, key: &K) -> usize
+    {
+        self.entities.get(key).map(|set| set.len()).unwrap_or(0)
+    }
+
+    /// Returns the key an entity was last given.
+    pub fn key(&self, entity: &Entity) -> Option<&K>
+    {
+        self.keys.get(entity)
+    }
+
+    /// Iterates over the keys that at least one entity has.
+    pub fn keys(&self) -> Keys<'_, K, HashSet<Entity>>
+    {
+        self.entities.keys()
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.keys.is_empty()
+    }
+
+    /// Reads an entity's key again, eg: after changing a component in place.
+    pub fn refresh(&mut self, entity: &EntityData<C>, components: &C)
+    {
+        match (self.key)(entity, components)
+        {
+            Some(key) => self.insert(***entity, key),
+            None => self.remove(entity),
+        }
+    }
+
+    fn insert(&mut self, entity: Entity, key: K)
+    {
+        if self.keys.get(&entity) == Some(&key)
+        {
+            return;
+        }
+        self.remove(&entity);
+        self.entities.entry(key.clone()).or_default().insert(entity);
+        self.keys.insert(entity, key);
+    }
+
+    fn remove(&mut self, entity: &Entity)
+    {
+        if let Some(key) = self.keys.remove(entity)
+        {
+            let empty = match self.entities.get_mut(&key)
+            {
+                Some(set) => {
+                    set.remove(entity);
+                    set.is_empty()
+                },
+                None => false,
+            };
+            if empty
+            {
+                self.entities.remove(&key);
+            }
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, C: ComponentManager, M: ServiceManager> System for IndexManager<K, C, M>
+{
+    type Components = C;
+    type Services = M;
+    fn activated(&mut self, entity: &EntityData<C>, components: &C)
+    {
+        if let Some(key) = (self.key)(entity, components)
+        {
+            self.insert(***entity, key);
+        }
+    }
+
+    fn reactivated(&mut self, entity: &EntityData<C>, components: &C)
+    {
+        self.refresh(entity, components);
+    }
+
+    fn deactivated(&mut self, entity: &EntityData<C>, _: &C)
+    {
+        self.remove(entity);
+    }
+}
+
+impl<K: Hash + Eq + Clone, C: ComponentManager, M: ServiceManager> Process for IndexManager<K, C, M>
+{
+    /// Keys are only read when entities change, so there's nothing to do.
+    fn process(&mut self, _: &mut DataHelper<C, M>)
+    {
+
+    }
+}
@@ -0,0 +1,27 @@
+
+//! Managers observe entities on behalf of the world and help keep track of them, without defining
+//! data or logic themselves.
+
+pub use self::blackboard::Blackboard;
+#[cfg(feature = "serde")]
+pub use self::bus::{LocalNetwork, LocalTransport, MessageBus, Peer, Transport};
+pub use self::index::IndexManager;
+pub use self::name::NameManager;
+pub use self::player::{PlayerId, PlayerManager};
+pub use self::queue::{OverflowPolicy, QueueManager};
+pub use self::removed::RemovedComponents;
+pub use self::rng::Rng;
+pub use self::schedule::{ScheduleManager, TaskId};
+pub use self::spatial::{GridBroadPhase, SpatialGrid, SpatialGridManager};
+
+pub mod blackboard;
+#[cfg(feature = "serde")]
+pub mod bus;
+pub mod index;
+pub mod name;
+pub mod player;
+pub mod queue;
+pub mod removed;
+pub mod rng;
+pub mod schedule;
+pub mod spatial;
@@ -0,0 +1,81 @@
+
+//! Human-readable names for entities.
+
+use std::collections::HashMap;
+use std::collections::hash_map::Iter;
+
+use Entity;
+
+/// Two-way mapping between entities and unique names.
+pub struct NameManager
+{
+    entities: HashMap<String, Entity>,
+    names: HashMap<Entity, String>,
+}
+
+impl NameManager
+{
+    pub fn new() -> NameManager
+    {
+        NameManager
+        {
+            entities: HashMap::new(),
+            names: HashMap::new(),
+        }
+    }
+
+    /// Names an entity, replacing any name it had before.
+    ///
+    /// Names are unique, so if another entity already has the name it loses it, and that entity is
+    /// returned.
+    pub fn set(&mut self, entity: Entity, name: String) -> Option<Entity>
+    {
+        self.remove(&entity);
+        let prev = self.entities.insert(name.clone(), entity);
+        if let Some(ref prev) = prev
+        {
+            self.names.remove(prev);
+        }
+        self.names.insert(entity, name);
+        prev
+    }
+
+    /// Removes the name of an entity, returning it.
+    pub fn remove(&mut self, entity: &Entity) -> Option<String>
+    {
+        let name = self.names.remove(entity);
+        if let Some(ref name) = name
+        {
+            self.entities.remove(name);
+        }
+        name
+    }
+
+    /// Returns the entity with the given name.
+    pub fn entity(&self, name: &str) -> Option<Entity>
+    {
+        self.entities.get(name).cloned()
+    }
+
+    /// Returns the name of an entity.
+    pub fn name(&self, entity: &Entity) -> Option<&str>
+    {
+        self.names.get(entity).map(|name| &name[..])
+    }
+
+    /// Iterates over all named entities.
+    pub fn iter(&self) -> Iter<'_, Entity, String>
+    {
+        self.names.iter()
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.names.is_empty()
+    }
+}
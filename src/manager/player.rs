@@ -0,0 +1,171 @@
+//! Entities controlled by players, eg: the avatar of each client in a networked game.
+
+use std::collections::{BTreeMap, HashMap};
+use std::collections::btree_map::Entry;
+use std::marker::PhantomData;
+use std::vec::Drain;
+
+use {ComponentManager, ServiceManager};
+use Aspect;
+use CachedQuery;
+use DataHelper;
+use Entity;
+use EntityData;
+use {Process, System};
+
+/// Identifies a player, eg: a client's connection number.
+pub type PlayerId = u32;
+
+/// Keeps track of the entity each player controls.
+///
+/// Only entities matching the manager's aspect can be assigned to a player. An entity is evicted
+/// from its player when it stops matching or is removed, and evictions pile up until they're taken
+/// with `drain_evicted()`, eg: to respawn the player.
+pub struct PlayerManager<C: ComponentManager, M: ServiceManager = ()>
+{
+    query: CachedQuery<C>,
+    entities: BTreeMap<PlayerId, Entity>,
+    players: HashMap<Entity, PlayerId>,
+    evicted: Vec<(PlayerId, Entity)>,
+    _services: PhantomData<fn(M)>,
+}
+
+impl<C: ComponentManager, M: ServiceManager> PlayerManager<C, M>
+{
+    pub fn new(aspect: Aspect<C>) -> PlayerManager<C, M>
+    {
+        PlayerManager
+        {
+            query: CachedQuery::new(aspect),
+            entities: BTreeMap::new(),
+            players: HashMap::new(),
+            evicted: Vec::new(),
+            _services: PhantomData,
+        }
+    }
+
+    /// Gives an entity to a player, replacing the player's previous entity and taking it from any
+    /// other player. Returns false, leaving everything as it was, if the entity doesn't match the
+    /// aspect.
+    pub fn assign(&mut self, player: PlayerId, entity: &EntityData<C>) -> bool
+    {
+        if !self.query.contains(entity)
+        {
+            return false;
+        }
+        self.release(player);
+        self.unassign(entity);
+        self.entities.insert(player, ***entity);
+        self.players.insert(***entity, player);
+        true
+    }
+
+    /// Takes the entity away from a player, returning it.
+    pub fn release(&mut self, player: PlayerId) -> Option<Entity>
+    {
+        let entity = self.entities.remove(&player)?;
+        self.players.remove(&entity);
+        Some(entity)
+    }
+
+    /// The entity a player controls.
+    pub fn entity(&self, player: PlayerId) -> Option<Entity>
+    {
+        self.entities.get(&player).cloned()
+    }
+
+    /// The player controlling an entity.
+    pub fn player(&self, entity: &Entity) -> Option<PlayerId>
+    {
+        self.players.get(entity).cloned()
+    }
+
+    /// Every player with an entity, in order of player id.
+    pub fn iter(&self) -> impl Iterator<Item = (PlayerId, Entity)> + '_
+    {
+        self.entities.iter().map(|(&player, &entity)| (player, entity))
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.entities.is_empty()
+    }
+
+    /// Takes every eviction recorded so far, in the order they happened.
+    pub fn drain_evicted(&mut self) -> Drain<'_, (PlayerId, Entity)>
+    {
+        self.evicted.drain(..)
+    }
+
+    fn unassign(&mut self, entity: &Entity) -> Option<PlayerId>
+    {
+        let player = self.players.remove(entity)?;
+        self.entities.remove(&player);
+        Some(player)
+    }
+
+    fn evict(&mut self, entity: &Entity)
+    {
+        if let Some(player) = self.unassign(entity)
+        {
+            self.evicted.push((player, *entity));
+        }
+    }
+}
+
+impl<C: ComponentManager, M: ServiceManager> System for PlayerManager<C, M>
+{
+    type Components = C;
+    type Services = M;
+    /// Entities are deactivated and activated again without being removed when the world is
+    /// compacted, so those get their players back.
+    fn activated_batch(&mut self, entities: &[EntityData<C>], components: &C)
+    {
+        for entity in self.query.activated_batch(entities, components)
+        {
+            let entity = **entity;
+            if let Some(i) = self.evicted.iter().position(|&(_, e)| e == entity)
+            {
+                let (player, _) = self.evicted.remove(i);
+                if let Entry::Vacant(slot) = self.entities.entry(player)
+                {
+                    slot.insert(entity);
+                    self.players.insert(entity, player);
+                }
+            }
+        }
+    }
+
+    fn activated(&mut self, entity: &EntityData<C>, components: &C)
+    {
+        self.activated_batch(::std::slice::from_ref(entity), components);
+    }
+
+    fn reactivated(&mut self, entity: &EntityData<C>, components: &C)
+    {
+        if let (true, false) = self.query.reactivated(entity, components)
+        {
+            self.evict(entity);
+        }
+    }
+
+    fn deactivated(&mut self, entity: &EntityData<C>, _: &C)
+    {
+        self.query.deactivated(entity);
+        self.evict(entity);
+    }
+}
+
+impl<C: ComponentManager, M: ServiceManager> Process for PlayerManager<C, M>
+{
+    /// Players are only evicted when entities change, so there's nothing to do.
+    fn process(&mut self, _: &mut DataHelper<C, M>)
+    {
+
+    }
+}
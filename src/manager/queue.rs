@@ -0,0 +1,152 @@
+//! Queues of events passed between systems.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use ServiceManager;
+
+/// What a full `QueueManager` does with a new event.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy
+{
+    /// Discard the event that was pushed first to make room.
+    DropOldest,
+    /// Discard the new event.
+    DropNewest,
+    /// Give the new event back from `push()`.
+    Reject,
+}
+
+/// A queue of events, popped highest priority first and in the order they were pushed within a
+/// priority.
+pub struct QueueManager<E>
+{
+    /// Events by descending priority, then by when they were pushed.
+    events: BTreeMap<(Reverse<i32>, u64), E>,
+    next: u64,
+    capacity: Option<(usize, OverflowPolicy)>,
+}
+
+impl<E> QueueManager<E>
+{
+    pub fn new() -> QueueManager<E>
+    {
+        QueueManager
+        {
+            events: BTreeMap::new(),
+            next: 0,
+            capacity: None,
+        }
+    }
+
+    /// Creates a queue that holds at most `capacity` events.
+    pub fn bounded(capacity: usize, policy: OverflowPolicy) -> QueueManager<E>
+    {
+        QueueManager
+        {
+            capacity: Some((capacity, policy)),
+            ..QueueManager::new()
+        }
+    }
+
+    /// Pushes an event with priority 0.
+    pub fn push(&mut self, event: E) -> Result<(), E>
+    {
+        self.push_with_priority(event, 0)
+    }
+
+    /// Pushes an event that will be popped before any event with a lower priority.
+    ///
+    /// Returns the event if the queue is full and rejects new events.
+    pub fn push_with_priority(&mut self, event: E, priority: i32) -> Result<(), E>
+    {
+        if let Some((capacity, policy)) = self.capacity
+        {
+            if self.events.len() >= capacity
+            {
+                match policy
+                {
+                    OverflowPolicy::DropOldest => {
+                        let oldest = self.events.keys().min_by_key(|&&(_, order)| order).cloned();
+                        match oldest
+                        {
+                            Some(oldest) => { self.events.remove(&oldest); },
+                            None => return Ok(()),
+                        }
+                    },
+                    OverflowPolicy::DropNewest => return Ok(()),
+                    OverflowPolicy::Reject => return Err(event),
+                }
+            }
+        }
+        self.events.insert((Reverse(priority), self.next), event);
+        self.next += 1;
+        Ok(())
+    }
+
+    /// Removes the next event.
+    pub fn pop(&mut self) -> Option<E>
+    {
+        self.events.pop_first().map(|(_, event)| event)
+    }
+
+    /// Returns the next event without removing it.
+    pub fn peek(&self) -> Option<&E>
+    {
+        self.events.values().next()
+    }
+
+    /// Removes every event, in the order they would be popped.
+    pub fn drain(&mut self) -> Drain<'_, E>
+    {
+        Drain(self)
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.events.is_empty()
+    }
+
+    pub fn clear(&mut self)
+    {
+        self.events.clear();
+    }
+}
+
+impl<E: 'static> ServiceManager for QueueManager<E>
+{
+    fn new() -> QueueManager<E>
+    {
+        QueueManager::new()
+    }
+}
+
+/// Iterator popping every event of a `QueueManager`.
+pub struct Drain<'a, E: 'a>(&'a mut QueueManager<E>);
+
+impl<'a, E> Iterator for Drain<'a, E>
+{
+    type Item = E;
+    fn next(&mut self) -> Option<E>
+    {
+        self.0.pop()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>)
+    {
+        (self.0.len(), Some(self.0.len()))
+    }
+}
+
+impl<'a, E> Drop for Drain<'a, E>
+{
+    fn drop(&mut self)
+    {
+        self.0.clear();
+    }
+}
@@ -0,0 +1,109 @@
+//! Components of removed entities, for code that reacts to removals after an update.
+
+use std::collections::HashSet;
+use std::marker::PhantomData;
+use std::vec::Drain;
+
+use {ComponentManager, ServiceManager};
+use MaybeSend;
+use DataHelper;
+use Entity;
+use EntityData;
+use {Process, System};
+
+/// Keeps a component of each entity as it's removed, eg: so the audio layer can play a death sound
+/// at the entity's last position once `update()` returns.
+///
+/// The component is read with a user-provided extractor just before the entity's components are
+/// dropped. Entities for which the extractor returns `None` are left out. Removals pile up until
+/// they're taken with `drain()`.
+pub struct RemovedComponents<T, C: ComponentManager, M: ServiceManager = ()>
+{
+    removed: Vec<(Entity, T)>,
+    read: boxed!(Fn(&EntityData<C>, &C) -> Option<T> + 'static),
+    _services: PhantomData<fn(M)>,
+}
+
+impl<T, C: ComponentManager, M: ServiceManager> RemovedComponents<T, C, M>
+{
+    pub fn new<F>(read: F) -> RemovedComponents<T, C, M>
+        where F: Fn(&EntityData<C>, &C) -> Option<T> + MaybeSend + 'static
+    {
+        RemovedComponents
+        {
+            removed: Vec::new(),
+            read: Box::new(read),
+            _services: PhantomData,
+        }
+    }
+
+    /// The removed entities and their components, in the order they were removed.
+    pub fn iter(&self) -> ::std::slice::Iter<'_, (Entity, T)>
+    {
+        self.removed.iter()
+    }
+
+    /// Takes every removal recorded so far.
+    pub fn drain(&mut self) -> Drain<'_, (Entity, T)>
+    {
+        self.removed.drain(..)
+    }
+
+    pub fn clear(&mut self)
+    {
+        self.removed.clear();
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.removed.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.removed.is_empty()
+    }
+}
+
+impl<T, C: ComponentManager, M: ServiceManager> System for RemovedComponents<T, C, M>
+{
+    type Components = C;
+    type Services = M;
+    /// Entities are deactivated and activated again without being removed when the world is
+    /// compacted, so those aren't removals.
+    fn activated_batch(&mut self, entities: &[EntityData<C>], _: &C)
+    {
+        if !self.removed.is_empty()
+        {
+            let activated: HashSet<Entity> = entities.iter().map(|e| ***e).collect();
+            self.removed.retain(|&(entity, _)| !activated.contains(&entity));
+        }
+    }
+
+    fn activated(&mut self, entity: &EntityData<C>, components: &C)
+    {
+        self.activated_batch(::std::slice::from_ref(entity), components);
+    }
+
+    fn reactivated(&mut self, _: &EntityData<C>, _: &C)
+    {
+
+    }
+
+    fn deactivated(&mut self, entity: &EntityData<C>, components: &C)
+    {
+        if let Some(component) = (self.read)(entity, components)
+        {
+            self.removed.push((***entity, component));
+        }
+    }
+}
+
+impl<T, C: ComponentManager, M: ServiceManager> Process for RemovedComponents<T, C, M>
+{
+    /// Components are only read when entities are removed, so there's nothing to do.
+    fn process(&mut self, _: &mut DataHelper<C, M>)
+    {
+
+    }
+}
@@ -0,0 +1,102 @@
+//! Seeded random numbers, for simulations that have to play out the same way every time.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use ServiceManager;
+
+/// A small, fast random number generator (SplitMix64) that gives the same numbers for the same
+/// seed on every platform.
+///
+/// Every world has one, see `DataHelper::rng()`. It can also be a field of `services!`, where it
+/// starts with seed 0. Cloning it saves its position in the sequence, eg: for a snapshot that
+/// should draw the same numbers when restored.
+///
+/// Systems that draw from their own `stream()` get the same numbers however many other systems
+/// draw from the generator, or from streams of their own.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Rng
+{
+    seed: u64,
+    state: u64,
+    streams: BTreeMap<String, Rng>,
+}
+
+impl Rng
+{
+    pub fn seeded(seed: u64) -> Rng
+    {
+        Rng
+        {
+            seed: seed,
+            state: seed,
+            streams: BTreeMap::new(),
+        }
+    }
+
+    /// Starts the sequence again from `seed`, along with every stream.
+    pub fn reseed(&mut self, seed: u64)
+    {
+        *self = Rng::seeded(seed);
+    }
+
+    pub fn seed(&self) -> u64
+    {
+        self.seed
+    }
+
+    /// A generator of its own for `name`, eg: the name of a system, seeded from this generator's
+    /// seed and the name. It's created the first time it's asked for.
+    pub fn stream(&mut self, name: &str) -> &mut Rng
+    {
+        if !self.streams.contains_key(name)
+        {
+            // FNV-1a, so that streams get the same seeds on every platform and in every run.
+            let mut hash = 0xcbf29ce484222325u64;
+            for &byte in name.as_bytes()
+            {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+            let seed = Rng::seeded(self.seed ^ hash).next_u64();
+            self.streams.insert(name.to_string(), Rng::seeded(seed));
+        }
+        self.streams.get_mut(name).unwrap()
+    }
+
+    pub fn next_u64(&mut self) -> u64
+    {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn next_u32(&mut self) -> u32
+    {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// A number from 0 up to, but not including, 1.
+    pub fn next_f32(&mut self) -> f32
+    {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// A number in `range`, which must not be empty.
+    pub fn range(&mut self, range: Range<i32>) -> i32
+    {
+        assert!(range.start < range.end, "empty range {:?}", range);
+        let span = (range.end as i64 - range.start as i64) as u64;
+        (range.start as i64 + (self.next_u64() % span) as i64) as i32
+    }
+}
+
+impl ServiceManager for Rng
+{
+    fn new() -> Rng
+    {
+        Rng::seeded(0)
+    }
+}
@@ -0,0 +1,174 @@
+//! Work scheduled to happen a number of updates (or seconds) from now.
+
+use std::collections::BTreeMap;
+
+use {ComponentManager, ServiceManager};
+use DataHelper;
+use Entity;
+use MaybeSend;
+use {Process, System};
+use manager::QueueManager;
+
+/// A call made by a `ScheduleManager` once its task is due.
+pub type ScheduledCall<C, M> = boxed!(FnOnce(&mut DataHelper<C, M>));
+
+/// Identifies a scheduled task so it can be cancelled.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct TaskId(u64);
+
+enum Task<C: ComponentManager, M: ServiceManager>
+{
+    Call(ScheduledCall<C, M>),
+    Despawn(Entity),
+}
+
+/// Runs tasks after a delay, eg: removing an explosion a second after it's created.
+///
+/// It counts the updates it's processed for as ticks, so it should be a field of `systems!` (or a
+/// dynamic system). Tasks that are due run in the order they were due, then in the order they were
+/// scheduled.
+pub struct ScheduleManager<C: ComponentManager, M: ServiceManager = ()>
+{
+    tick: u64,
+    tick_length: f32,
+    next_id: u64,
+    /// Tasks by the tick they're due, then by id.
+    tasks: BTreeMap<(u64, u64), Task<C, M>>,
+}
+
+impl<C: ComponentManager, M: ServiceManager> ScheduleManager<C, M>
+{
+    /// Creates a schedule for a world updated every `tick_length` seconds.
+    pub fn new(tick_length: f32) -> ScheduleManager<C, M>
+    {
+        assert!(tick_length > 0.0, "Tick length must be positive");
+        ScheduleManager
+        {
+            tick: 0,
+            tick_length: tick_length,
+            next_id: 0,
+            tasks: BTreeMap::new(),
+        }
+    }
+
+    /// Number of updates processed so far.
+    pub fn tick(&self) -> u64
+    {
+        self.tick
+    }
+
+    /// Calls `call` during the update `ticks` updates from now (or the next one, for 0).
+    pub fn after_ticks<F>(&mut self, ticks: u64, call: F) -> TaskId
+        where F: FnOnce(&mut DataHelper<C, M>) + MaybeSend + 'static
+    {
+        self.schedule(ticks, Task::Call(Box::new(call)))
+    }
+
+    /// Calls `call` during the first update at least `seconds` from now.
+    pub fn after_seconds<F>(&mut self, seconds: f32, call: F) -> TaskId
+        where F: FnOnce(&mut DataHelper<C, M>) + MaybeSend + 'static
+    {
+        let ticks = self.ticks(seconds);
+        self.after_ticks(ticks, call)
+    }
+
+    /// Removes an entity `ticks` updates from now, unless it has already been removed.
+    pub fn despawn_after_ticks(&mut self, ticks: u64, entity: Entity) -> TaskId
+    {
+        self.schedule(ticks, Task::Despawn(entity))
+    }
+
+    /// Removes an entity at least `seconds` from now, unless it has already been removed.
+    pub fn despawn_after_seconds(&mut self, seconds: f32, entity: Entity) -> TaskId
+    {
+        let ticks = self.ticks(seconds);
+        self.despawn_after_ticks(ticks, entity)
+    }
+
+    /// Pushes an event onto a queue of the services `ticks` updates from now.
+    ///
+    /// `queue` picks the queue out of the services, eg: `|s| &mut s.damage`.
+    pub fn deliver_after_ticks<E>(&mut self, ticks: u64, queue: fn(&mut M) -> &mut QueueManager<E>, event: E) -> TaskId
+        where E: MaybeSend + 'static
+    {
+        self.after_ticks(ticks, move |data: &mut DataHelper<C, M>| {
+            let _ = queue(&mut data.services).push(event);
+        })
+    }
+
+    /// Pushes an event onto a queue of the services at least `seconds` from now.
+    pub fn deliver_after_seconds<E>(&mut self, seconds: f32, queue: fn(&mut M) -> &mut QueueManager<E>, event: E) -> TaskId
+        where E: MaybeSend + 'static
+    {
+        let ticks = self.ticks(seconds);
+        self.deliver_after_ticks(ticks, queue, event)
+    }
+
+    /// Cancels a task that hasn't run yet, returning whether it was found.
+    pub fn cancel(&mut self, task: TaskId) -> bool
+    {
+        let key = self.tasks.keys().find(|&&(_, id)| id == task.0).cloned();
+        key.map(|key| self.tasks.remove(&key)).is_some()
+    }
+
+    /// Number of tasks that haven't run yet.
+    pub fn len(&self) -> usize
+    {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.tasks.is_empty()
+    }
+
+    /// Cancels every task.
+    pub fn clear(&mut self)
+    {
+        self.tasks.clear();
+    }
+
+    fn ticks(&self, seconds: f32) -> u64
+    {
+        (seconds / self.tick_length).ceil().max(0.0) as u64
+    }
+
+    fn schedule(&mut self, ticks: u64, task: Task<C, M>) -> TaskId
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.tasks.insert((self.tick + ticks, id), task);
+        TaskId(id)
+    }
+}
+
+impl<C: ComponentManager, M: ServiceManager> System for ScheduleManager<C, M>
+{
+    type Components = C;
+    type Services = M;
+}
+
+impl<C: ComponentManager, M: ServiceManager> Process for ScheduleManager<C, M>
+{
+    fn process(&mut self, data: &mut DataHelper<C, M>)
+    {
+        self.tick += 1;
+        while let Some(entry) = self.tasks.first_entry()
+        {
+            if entry.key().0 > self.tick
+            {
+                break;
+            }
+            match entry.remove()
+            {
+                Task::Call(call) => call(data),
+                Task::Despawn(entity) => {
+                    if data.with_entity_data(&entity, |_, _| ()).is_some()
+                    {
+                        data.remove_entity(entity);
+                    }
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,401 @@
+
+//! Bucketing of entities by position for neighbourhood queries.
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+use {ComponentManager, ServiceManager};
+use MaybeSend;
+use DataHelper;
+use {Entity, IndexedEntity};
+use EntityData;
+use EntityIter;
+use {Process, System};
+use system::BroadPhase;
+
+/// A uniform grid of square cells, each holding the entities positioned inside it.
+pub struct SpatialGrid<C: ComponentManager>
+{
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<Entity>>,
+    entries: HashMap<Entity, GridEntry<C>>,
+}
+
+struct GridEntry<C: ComponentManager>
+{
+    entity: IndexedEntity<C>,
+    position: (f32, f32),
+    cell: (i32, i32),
+}
+
+impl<C: ComponentManager> SpatialGrid<C>
+{
+    /// Creates an empty grid whose cells are `cell_size` units across.
+    pub fn new(cell_size: f32) -> SpatialGrid<C>
+    {
+        assert!(cell_size > 0.0, "Cell size must be positive");
+        SpatialGrid
+        {
+            cell_size: cell_size,
+            cells: HashMap::new(),
+            entries: HashMap::new(),
+        }
+    }
+
+    #[inline]
+    pub fn cell_size(&self) -> f32
+    {
+        self.cell_size
+    }
+
+    /// Returns the cell containing a position.
+    pub fn cell(&self, position: (f32, f32)) -> (i32, i32)
+    {
+        ((position.0 / self.cell_size).floor() as i32, (position.1 / self.cell_size).floor() as i32)
+    }
+
+    /// Places an entity at a position, moving it if it is already in the grid.
+    pub fn insert(&mut self, entity: &EntityData<C>, position: (f32, f32))
+    {
+        if !self.move_to(entity, position)
+        {
+            let cell = self.cell(position);
+            self.cells.entry(cell).or_default().push(***entity);
+            self.entries.insert(***entity, GridEntry
+            {
+                entity: unsafe { (**entity).clone() },
+                position: position,
+                cell: cell,
+            });
+        }
+    }
+
+    /// Removes an entity from the grid, returning whether it was there.
+    pub fn remove(&mut self, entity: &Entity) -> bool
+    {
+        match self.entries.remove(entity)
+        {
+            Some(entry) => {
+                self.remove_from_cell(entity, entry.cell);
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn clear(&mut self)
+    {
+        self.cells.clear();
+        self.entries.clear();
+    }
+
+    /// Returns the position an entity was last placed at.
+    pub fn position(&self, entity: &Entity) -> Option<(f32, f32)>
+    {
+        self.entries.get(entity).map(|entry| entry.position)
+    }
+
+    pub fn contains(&self, entity: &Entity) -> bool
+    {
+        self.entries.contains_key(entity)
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.entries.is_empty()
+    }
+
+    /// Iterates over the entities inside an axis-aligned box (inclusive).
+    pub fn query_aabb(&self, min: (f32, f32), max: (f32, f32)) -> SpatialQuery<'_, C>
+    {
+        SpatialQuery::new(self, Shape::Aabb(min, max), min, max)
+    }
+
+    /// Iterates over the entities within `radius` of `center` (inclusive).
+    pub fn query_radius(&self, center: (f32, f32), radius: f32) -> SpatialQuery<'_, C>
+    {
+        SpatialQuery::new(self, Shape::Circle(center, radius * radius),
+            (center.0 - radius, center.1 - radius),
+            (center.0 + radius, center.1 + radius))
+    }
+
+    /// Moves an entity that is already in the grid, returning false if it isn't.
+    fn move_to(&mut self, entity: &Entity, position: (f32, f32)) -> bool
+    {
+        let cell = self.cell(position);
+        let old_cell = match self.entries.get_mut(entity)
+        {
+            Some(entry) => {
+                entry.position = position;
+                ::std::mem::replace(&mut entry.cell, cell)
+            },
+            None => return false,
+        };
+        if old_cell != cell
+        {
+            self.remove_from_cell(entity, old_cell);
+            self.cells.entry(cell).or_default().push(*entity);
+        }
+        true
+    }
+
+    fn remove_from_cell(&mut self, entity: &Entity, cell: (i32, i32))
+    {
+        let empty = match self.cells.get_mut(&cell)
+        {
+            Some(bucket) => {
+                if let Some(i) = bucket.iter().position(|e| e == entity)
+                {
+                    bucket.swap_remove(i);
+                }
+                bucket.is_empty()
+            },
+            None => false,
+        };
+        if empty
+        {
+            self.cells.remove(&cell);
+        }
+    }
+}
+
+enum Shape
+{
+    Aabb((f32, f32), (f32, f32)),
+    Circle((f32, f32), f32),
+}
+
+impl Shape
+{
+    fn contains(&self, p: (f32, f32)) -> bool
+    {
+        match *self
+        {
+            Shape::Aabb(min, max) => min.0 <= p.0 && p.0 <= max.0 && min.1 <= p.1 && p.1 <= max.1,
+            Shape::Circle(c, r2) => (p.0 - c.0) * (p.0 - c.0) + (p.1 - c.1) * (p.1 - c.1) <= r2,
+        }
+    }
+}
+
+/// Iterator over the entities of a `SpatialGrid` inside a region.
+pub struct SpatialQuery<'a, C: ComponentManager + 'a>
+{
+    grid: &'a SpatialGrid<C>,
+    shape: Shape,
+    min_cell: (i32, i32),
+    max_cell: (i32, i32),
+    cell: (i32, i32),
+    index: usize,
+}
+
+impl<'a, C: ComponentManager> SpatialQuery<'a, C>
+{
+    fn new(grid: &'a SpatialGrid<C>, shape: Shape, min: (f32, f32), max: (f32, f32)) -> SpatialQuery<'a, C>
+    {
+        let min_cell = grid.cell(min);
+        SpatialQuery
+        {
+            grid: grid,
+            shape: shape,
+            min_cell: min_cell,
+            max_cell: grid.cell(max),
+            cell: min_cell,
+            index: 0,
+        }
+    }
+}
+
+impl<'a, C: ComponentManager> Iterator for SpatialQuery<'a, C>
+{
+    type Item = EntityData<'a, C>;
+    fn next(&mut self) -> Option<EntityData<'a, C>>
+    {
+        while self.cell.1 <= self.max_cell.1
+        {
+            if let Some(bucket) = self.grid.cells.get(&self.cell)
+            {
+                while self.index < bucket.len()
+                {
+                    let entry = &self.grid.entries[&bucket[self.index]];
+                    self.index += 1;
+                    if self.shape.contains(entry.position)
+                    {
+                        return Some(EntityData(&entry.entity));
+                    }
+                }
+            }
+            self.index = 0;
+            self.cell.0 += 1;
+            if self.cell.0 > self.max_cell.0
+            {
+                self.cell.0 = self.min_cell.0;
+                self.cell.1 += 1;
+            }
+        }
+        None
+    }
+}
+
+/// Keeps a `SpatialGrid` of all entities that have a position.
+///
+/// Positions are read with a user-provided extractor when an entity is activated or reactivated,
+/// and refreshed for every tracked entity each time the manager is processed. Entities for which
+/// the extractor returns `None` are left out of the grid.
+pub struct SpatialGridManager<C: ComponentManager, M: ServiceManager = ()>
+{
+    grid: SpatialGrid<C>,
+    position: boxed!(Fn(&EntityData<C>, &C) -> Option<(f32, f32)> + 'static),
+    _services: PhantomData<fn(M)>,
+}
+
+impl<C: ComponentManager, M: ServiceManager> SpatialGridManager<C, M>
+{
+    pub fn new<F>(cell_size: f32, position: F) -> SpatialGridManager<C, M>
+        where F: Fn(&EntityData<C>, &C) -> Option<(f32, f32)> + MaybeSend + 'static
+    {
+        SpatialGridManager
+        {
+            grid: SpatialGrid::new(cell_size),
+            position: Box::new(position),
+            _services: PhantomData,
+        }
+    }
+}
+
+impl<C: ComponentManager, M: ServiceManager> Deref for SpatialGridManager<C, M>
+{
+    type Target = SpatialGrid<C>;
+    fn deref(&self) -> &SpatialGrid<C>
+    {
+        &self.grid
+    }
+}
+
+impl<C: ComponentManager, M: ServiceManager> DerefMut for SpatialGridManager<C, M>
+{
+    fn deref_mut(&mut self) -> &mut SpatialGrid<C>
+    {
+        &mut self.grid
+    }
+}
+
+impl<C: ComponentManager, M: ServiceManager> System for SpatialGridManager<C, M>
+{
+    type Components = C;
+    type Services = M;
+    fn activated(&mut self, entity: &EntityData<C>, components: &C)
+    {
+        if let Some(position) = (self.position)(entity, components)
+        {
+            self.grid.insert(entity, position);
+        }
+    }
+
+    fn reactivated(&mut self, entity: &EntityData<C>, components: &C)
+    {
+        match (self.position)(entity, components)
+        {
+            Some(position) => self.grid.insert(entity, position),
+            None => { self.grid.remove(entity); },
+        }
+    }
+
+    fn deactivated(&mut self, entity: &EntityData<C>, _: &C)
+    {
+        self.grid.remove(entity);
+    }
+}
+
+impl<C: ComponentManager, M: ServiceManager> Process for SpatialGridManager<C, M>
+{
+    fn process(&mut self, data: &mut DataHelper<C, M>)
+    {
+        let positions: Vec<(Entity, Option<(f32, f32)>)> = self.grid.entries.iter()
+            .map(|(e, entry)| (*e, (self.position)(&EntityData(&entry.entity), &data.components)))
+            .collect();
+        for (entity, position) in positions
+        {
+            match position
+            {
+                Some(position) => { self.grid.move_to(&entity, position); },
+                None => { self.grid.remove(&entity); },
+            }
+        }
+    }
+}
+
+/// Broad phase that pairs up entities within a fixed distance of each other.
+///
+/// Both sets are bucketed into a grid with cells as wide as the distance, so only entities in
+/// neighbouring cells are ever compared. An entity is never paired with itself.
+pub struct GridBroadPhase<C: ComponentManager>
+{
+    radius: f32,
+    position: boxed!(Fn(&EntityData<C>, &C) -> Option<(f32, f32)> + 'static),
+}
+
+impl<C: ComponentManager> GridBroadPhase<C>
+{
+    pub fn new<F>(radius: f32, position: F) -> GridBroadPhase<C>
+        where F: Fn(&EntityData<C>, &C) -> Option<(f32, f32)> + MaybeSend + 'static
+    {
+        assert!(radius > 0.0, "Radius must be positive");
+        GridBroadPhase
+        {
+            radius: radius,
+            position: Box::new(position),
+        }
+    }
+
+    fn cell(&self, position: (f32, f32)) -> (i32, i32)
+    {
+        ((position.0 / self.radius).floor() as i32, (position.1 / self.radius).floor() as i32)
+    }
+}
+
+impl<C: ComponentManager> BroadPhase<C> for GridBroadPhase<C>
+{
+    fn pairs<'a>(&mut self, a: EntityIter<'a, C>, b: EntityIter<'a, C>, c: &C) -> Vec<(EntityData<'a, C>, EntityData<'a, C>)>
+    {
+        let mut cells: HashMap<(i32, i32), Vec<(EntityData<'a, C>, (f32, f32))>> = HashMap::new();
+        for e in b
+        {
+            if let Some(p) = (self.position)(&e, c)
+            {
+                cells.entry(self.cell(p)).or_default().push((e, p));
+            }
+        }
+
+        let r2 = self.radius * self.radius;
+        let mut pairs = Vec::new();
+        for e in a
+        {
+            let p = match (self.position)(&e, c)
+            {
+                Some(p) => p,
+                None => continue,
+            };
+            let (cx, cy) = self.cell(p);
+            for x in cx - 1..cx + 2
+            {
+                for y in cy - 1..cy + 2
+                {
+                    for &(other, q) in cells.get(&(x, y)).into_iter().flat_map(|bucket| bucket.iter())
+                    {
+                        if **other != **e && (p.0 - q.0) * (p.0 - q.0) + (p.1 - q.1) * (p.1 - q.1) <= r2
+                        {
+                            pairs.push((e, other));
+                        }
+                    }
+                }
+            }
+        }
+        pairs
+    }
+}
@@ -0,0 +1,23 @@
+//! Counters, gauges and timings reported by a world, for exporting to statsd, Prometheus and the
+//! like.
+//!
+//! Give a world a sink with `World::set_metrics()`. It then reports:
+//!
+//! - `ecs.update` and `ecs.flush` timings, and `ecs.system.<name>` timings while
+//!   `World::profile_systems()` is on.
+//! - `ecs.entities` gauge with the number of entities after each update.
+//! - `ecs.entities.created`, `ecs.entities.removed` and `ecs.entities.reactivated` counters.
+//! - `ecs.components.<name>.capacity` gauge whenever a component field's storage grows.
+
+use std::time::Duration;
+
+/// Receives a world's metrics. Every method does nothing unless overridden.
+pub trait Metrics
+{
+    /// Adds to a count.
+    fn counter(&mut self, _name: &str, _value: u64) {}
+    /// Records the current value of something.
+    fn gauge(&mut self, _name: &str, _value: f64) {}
+    /// Records how long something took.
+    fn timing(&mut self, _name: &str, _duration: Duration) {}
+}
@@ -0,0 +1,147 @@
+//! Systems loaded from plugins, eg: mods compiled to WebAssembly.
+//!
+//! A plugin only sees the world through `PluginHost`, which deals in plain integers and bytes so
+//! that it can be exposed across an FFI or WebAssembly boundary by the runtime that loads the
+//! plugin. Entities are referred to by id, components by their position in `components!`, and
+//! component values are JSON, as in the `scene` module. Only `#[persist]` components can be read
+//! or written.
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use {ComponentManager, ServiceManager};
+use DataHelper;
+use Entity;
+use {Process, System};
+use script::ScriptView;
+
+/// What a plugin can do to the world while it's being processed.
+pub trait PluginHost
+{
+    /// Number of entities in the world.
+    fn entity_count(&self) -> u32;
+    /// Id of the `i`th entity, in order of creation.
+    fn entity(&self, i: u32) -> u64;
+    /// Looks up a component by field name, returning its id.
+    fn component_id(&self, name: &str) -> Option<u32>;
+    /// Reads an entity's component as JSON, if it has one.
+    fn get(&mut self, entity: u64, component: u32) -> Option<Vec<u8>>;
+    /// Gives an entity a component read from JSON. Returns false if that failed.
+    fn set(&mut self, entity: u64, component: u32, value: &[u8]) -> bool;
+    /// Removes an entity at the next flush.
+    fn remove(&mut self, entity: u64);
+}
+
+/// A system implemented outside of Rust's type system, eg: a WebAssembly module.
+pub trait Plugin
+{
+    fn update(&mut self, host: &mut dyn PluginHost);
+}
+
+/// Runs a plugin as a system, eg: with `World::add_dynamic_system()`.
+pub struct PluginSystem<C: ComponentManager, M: ServiceManager>
+{
+    plugin: boxed!(Plugin),
+    _marker: PhantomData<fn(C, M)>,
+}
+
+impl<C: ComponentManager, M: ServiceManager> PluginSystem<C, M>
+{
+    pub fn new(plugin: boxed!(Plugin)) -> PluginSystem<C, M>
+    {
+        PluginSystem
+        {
+            plugin: plugin,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C: ComponentManager, M: ServiceManager> System for PluginSystem<C, M>
+{
+    type Components = C;
+    type Services = M;
+}
+
+impl<C: ComponentManager, M: ServiceManager> Process for PluginSystem<C, M>
+{
+    fn process(&mut self, data: &mut DataHelper<C, M>)
+    {
+        let mut entities: Vec<Entity> = data.entities().map(|e| **e).collect();
+        entities.sort_by_key(|e| e.id());
+        let ids = entities.iter().map(|e| (e.id(), *e)).collect();
+        let mut host = Host
+        {
+            entities: entities,
+            ids: ids,
+            view: ScriptView::new(data),
+        };
+        self.plugin.update(&mut host);
+    }
+}
+
+struct Host<'a, C: ComponentManager + 'a, M: ServiceManager + 'a>
+{
+    entities: Vec<Entity>,
+    ids: HashMap<u64, Entity>,
+    view: ScriptView<'a, C, M>,
+}
+
+impl<'a, C: ComponentManager, M: ServiceManager> Host<'a, C, M>
+{
+    fn lookup(&self, entity: u64, component: u32) -> Option<(Entity, &'static str)>
+    {
+        let name = self.view.names().get(component as usize)?;
+        self.ids.get(&entity).map(|entity| (*entity, *name))
+    }
+}
+
+impl<'a, C: ComponentManager, M: ServiceManager> PluginHost for Host<'a, C, M>
+{
+    fn entity_count(&self) -> u32
+    {
+        self.entities.len() as u32
+    }
+
+    fn entity(&self, i: u32) -> u64
+    {
+        self.entities[i as usize].id()
+    }
+
+    fn component_id(&self, name: &str) -> Option<u32>
+    {
+        self.view.names().iter().position(|n| *n == name).map(|i| i as u32)
+    }
+
+    fn get(&mut self, entity: u64, component: u32) -> Option<Vec<u8>>
+    {
+        let (entity, name) = self.lookup(entity, component)?;
+        match self.view.get(&entity, name)
+        {
+            Ok(Some(value)) => Some(value.to_string().into_bytes()),
+            _ => None,
+        }
+    }
+
+    fn set(&mut self, entity: u64, component: u32, value: &[u8]) -> bool
+    {
+        let (entity, name) = match self.lookup(entity, component)
+        {
+            Some(found) => found,
+            None => return false,
+        };
+        match ::serde_json::from_slice(value)
+        {
+            Ok(value) => self.view.set(&entity, name, value).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    fn remove(&mut self, entity: u64)
+    {
+        if let Some(entity) = self.ids.get(&entity)
+        {
+            self.view.despawn(*entity);
+        }
+    }
+}
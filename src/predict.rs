@@ -0,0 +1,211 @@
+//! Client-side prediction for networked games.
+//!
+//! A client applies its own inputs as soon as they happen instead of waiting for the server, and
+//! keeps them until the server says it has simulated their frame. When a server snapshot arrives,
+//! `Predictor::reconcile()` restores it, applies the inputs the server hasn't seen yet again, and
+//! reports every predicted component that ended up different from what was predicted, so that the
+//! game can smooth the correction out instead of snapping.
+//!
+//! Resimulated frames run every system again, so anything else the systems do, such as creating
+//! entities or playing sounds, happens again too.
+
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::mem;
+
+use {Component, ComponentList, ComponentManager};
+use {Entity, EntityData, IndexedEntity};
+use {SystemManager, World};
+
+/// Copies an entity's component from one predicted field.
+type Capture<C> = Box<dyn Fn(&mut C, &IndexedEntity<C>) -> Option<Box<dyn Any>>>;
+
+/// Compares two components copied from the same predicted field.
+type Differs = Box<dyn Fn(&dyn Any, &dyn Any) -> bool>;
+
+struct Predicted<C: ComponentManager>
+{
+    name: &'static str,
+    capture: Capture<C>,
+    differs: Differs,
+}
+
+/// A predicted component that was corrected by a server snapshot.
+pub struct Misprediction
+{
+    pub entity: Entity,
+    /// Name given to the field by `Predictor::predict()`.
+    pub component: &'static str,
+    predicted: Option<Box<dyn Any>>,
+    corrected: Option<Box<dyn Any>>,
+}
+
+impl Misprediction
+{
+    /// The component as it was predicted, if the entity had one.
+    pub fn predicted<T: Any>(&self) -> Option<&T>
+    {
+        self.predicted.as_ref().and_then(|value| value.downcast_ref())
+    }
+
+    /// The component after reconciling, if the entity still has one.
+    pub fn corrected<T: Any>(&self) -> Option<&T>
+    {
+        self.corrected.as_ref().and_then(|value| value.downcast_ref())
+    }
+}
+
+/// Applies local inputs ahead of the server and reconciles with its snapshots.
+pub struct Predictor<C: ComponentManager, E>
+{
+    fields: Vec<Predicted<C>>,
+    /// Inputs of each frame the server hasn't acknowledged, oldest first.
+    unacknowledged: VecDeque<(u64, Vec<E>)>,
+    inputs: Vec<E>,
+    frame: u64,
+}
+
+impl<C: ComponentManager, E> Predictor<C, E>
+{
+    pub fn new() -> Predictor<C, E>
+    {
+        Predictor
+        {
+            fields: Vec::new(),
+            unacknowledged: VecDeque::new(),
+            inputs: Vec::new(),
+            frame: 0,
+        }
+    }
+
+    /// Tags a component field as predicted, so that corrections to it are reported by
+    /// `reconcile()` under `name`.
+    ///
+    /// `field` picks the list out of the components, eg: `|c| &mut c.position`.
+    pub fn predict<T: Component + Clone + PartialEq>(&mut self, name: &'static str, field: fn(&mut C) -> &mut ComponentList<C, T>)
+    {
+        self.fields.push(Predicted
+        {
+            name: name,
+            capture: Box::new(move |c: &mut C, entity: &IndexedEntity<C>| {
+                field(c).get(&EntityData(entity)).map(|value| Box::new(value) as Box<dyn Any>)
+            }),
+            differs: Box::new(|a: &dyn Any, b: &dyn Any| a.downcast_ref::<T>() != b.downcast_ref::<T>()),
+        });
+    }
+
+    /// Queues a local input to be applied at the next update.
+    pub fn input(&mut self, input: E)
+    {
+        self.inputs.push(input);
+    }
+
+    /// The frame the next update will simulate. Frames are counted from 0.
+    pub fn frame(&self) -> u64
+    {
+        self.frame
+    }
+
+    /// Number of frames whose inputs the server hasn't acknowledged yet.
+    pub fn pending(&self) -> usize
+    {
+        self.unacknowledged.len()
+    }
+
+    /// Applies the queued inputs with `apply`, updates the world, and keeps the inputs until the
+    /// server acknowledges the frame.
+    pub fn update<S, F>(&mut self, world: &mut World<S>, mut apply: F)
+        where S: SystemManager<Components = C>, F: FnMut(&mut World<S>, &E)
+    {
+        let inputs = mem::take(&mut self.inputs);
+        for input in &inputs
+        {
+            apply(world, input);
+        }
+        world.update();
+        self.unacknowledged.push_back((self.frame, inputs));
+        self.frame += 1;
+    }
+
+    /// Reconciles with a server snapshot of the world after simulating `frame`.
+    ///
+    /// `restore` sets the world to the server's state, then the inputs of the frames after
+    /// `frame` are applied with `apply` and simulated again. Returns the predicted components
+    /// that differ from what they were before reconciling.
+    pub fn reconcile<S, R, F>(&mut self, world: &mut World<S>, frame: u64, restore: R, mut apply: F) -> Vec<Misprediction>
+        where S: SystemManager<Components = C>, R: FnOnce(&mut World<S>), F: FnMut(&mut World<S>, &E)
+    {
+        while self.unacknowledged.front().is_some_and(|&(pending, _)| pending <= frame)
+        {
+            self.unacknowledged.pop_front();
+        }
+        let predicted = self.capture(world);
+        restore(world);
+        for (_, inputs) in &self.unacknowledged
+        {
+            for input in inputs
+            {
+                apply(world, input);
+            }
+            world.update();
+        }
+        let mut corrected: HashMap<Entity, _> = self.capture(world).into_iter().collect();
+
+        let mut mispredictions = Vec::new();
+        for (entity, mut predicted) in predicted
+        {
+            let mut corrected = corrected.remove(&entity).unwrap_or_else(|| self.fields.iter().map(|_| None).collect());
+            for (i, field) in self.fields.iter().enumerate()
+            {
+                let (predicted, corrected) = (predicted[i].take(), corrected[i].take());
+                let differs = match (&predicted, &corrected)
+                {
+                    (Some(a), Some(b)) => (field.differs)(&**a, &**b),
+                    (None, None) => false,
+                    _ => true,
+                };
+                if differs
+                {
+                    mispredictions.push(Misprediction
+                    {
+                        entity: entity,
+                        component: field.name,
+                        predicted: predicted,
+                        corrected: corrected,
+                    });
+                }
+            }
+        }
+        let mut appeared: Vec<_> = corrected.into_iter().collect();
+        appeared.sort_by_key(|&(entity, _)| entity.id());
+        for (entity, corrected) in appeared
+        {
+            for (field, corrected) in self.fields.iter().zip(corrected)
+            {
+                if corrected.is_some()
+                {
+                    mispredictions.push(Misprediction
+                    {
+                        entity: entity,
+                        component: field.name,
+                        predicted: None,
+                        corrected: corrected,
+                    });
+                }
+            }
+        }
+        mispredictions
+    }
+
+    /// Copies the predicted components of every entity, by field.
+    fn capture<S>(&self, world: &mut World<S>) -> Vec<(Entity, Vec<Option<Box<dyn Any>>>)>
+        where S: SystemManager<Components = C>
+    {
+        let mut entities: Vec<Entity> = world.entities().map(|e| **e).collect();
+        entities.sort_by_key(|entity| entity.id());
+        let fields = &self.fields;
+        world.with_entity_datas(&entities, |e, c| {
+            (**e, fields.iter().map(|field| (field.capture)(c, &e)).collect())
+        })
+    }
+}
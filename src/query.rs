@@ -0,0 +1,249 @@
+//! Sets of entities matching an aspect, kept up to date as entities change.
+
+use std::ops::Deref;
+
+use Aspect;
+use {ComponentManager, ServiceManager};
+use DataHelper;
+use IndexedEntity;
+use EntityData;
+use EntityIter;
+use System;
+
+/// Identifies a query added with `DataHelper::add_query()`.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct QueryId(usize);
+
+/// The entities matching an aspect, sorted by index.
+pub struct CachedQuery<C: ComponentManager>
+{
+    aspect: Aspect<C>,
+    entities: Vec<IndexedEntity<C>>,
+}
+
+/// The entities a system wrapping another is interested in, eg: the ones an `EntitySystem`
+/// processes.
+///
+/// Each `on_*` method updates the set and calls whichever of the wrapped system's `activated()`,
+/// `reactivated()` or `deactivated()` describes the change, if any. The set can be read through
+/// the `CachedQuery` it dereferences to.
+pub struct InterestTracker<C: ComponentManager>
+{
+    query: CachedQuery<C>,
+}
+
+impl QueryId
+{
+    #[doc(hidden)]
+    pub fn new(index: usize) -> QueryId
+    {
+        QueryId(index)
+    }
+
+    #[doc(hidden)]
+    pub fn index(&self) -> usize
+    {
+        self.0
+    }
+}
+
+impl<C: ComponentManager> CachedQuery<C>
+{
+    pub fn new(aspect: Aspect<C>) -> CachedQuery<C>
+    {
+        CachedQuery
+        {
+            aspect: aspect,
+            entities: Vec::new(),
+        }
+    }
+
+    /// The matching entities, in index order.
+    pub fn iter(&self) -> EntityIter<'_, C>
+    {
+        EntityIter::Slice(self.entities.iter())
+    }
+
+    /// The matching entities in index order, split into runs of at most `size`.
+    pub fn chunks(&self, size: usize) -> impl Iterator<Item = EntityIter<'_, C>>
+    {
+        self.entities.chunks(size).map(|chunk| EntityIter::Slice(chunk.iter()))
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.entities.is_empty()
+    }
+
+    pub fn contains(&self, entity: &IndexedEntity<C>) -> bool
+    {
+        self.position(entity).is_some()
+    }
+
+    /// Adds the entity if it matches, returning whether it was added. An entity that's already
+    /// matched isn't added again.
+    pub fn activated(&mut self, entity: &EntityData<C>, components: &C) -> bool
+    {
+        if !self.aspect.check(entity, components)
+        {
+            return false;
+        }
+        let indexed = unsafe { (**entity).clone() };
+        match self.entities.binary_search_by_key(&indexed.index(), |e| e.index())
+        {
+            Ok(i) if *self.entities[i] == *indexed => return false,
+            Ok(i) => self.entities[i] = indexed,
+            Err(i) => self.entities.insert(i, indexed),
+        }
+        true
+    }
+
+    /// Adds the entities that match, returning them.
+    pub fn activated_batch<'a>(&mut self, entities: &[EntityData<'a, C>], components: &C) -> Vec<EntityData<'a, C>>
+    {
+        let mut matched: Vec<_> = entities.iter()
+            .filter(|e| self.position(e).is_none() && self.aspect.check(e, components))
+            .cloned()
+            .collect();
+        matched.sort_by_key(|e| e.index());
+        matched.dedup_by_key(|e| e.index());
+
+        // Merge the sorted batch in, replacing stale entities that held the same index.
+        let capacity = self.entities.len() + matched.len();
+        let old = ::std::mem::replace(&mut self.entities, Vec::with_capacity(capacity));
+        let mut old = old.into_iter().peekable();
+        for e in &matched
+        {
+            while let Some(existing) = old.next_if(|existing| existing.index() < e.index())
+            {
+                self.entities.push(existing);
+            }
+            old.next_if(|existing| existing.index() == e.index());
+            self.entities.push(unsafe { (**e).clone() });
+        }
+        self.entities.extend(old);
+        matched
+    }
+
+    /// Rechecks an entity whose components changed, returning whether it matched before and
+    /// whether it matches now.
+    pub fn reactivated(&mut self, entity: &EntityData<C>, components: &C) -> (bool, bool)
+    {
+        let was = self.position(entity).is_some();
+        if was && !self.aspect.check(entity, components)
+        {
+            self.deactivated(entity);
+            (true, false)
+        }
+        else if was
+        {
+            (true, true)
+        }
+        else
+        {
+            (false, self.activated(entity, components))
+        }
+    }
+
+    /// Removes the entity, returning whether it had matched.
+    pub fn deactivated(&mut self, entity: &EntityData<C>) -> bool
+    {
+        match self.position(entity)
+        {
+            Some(i) => {
+                self.entities.remove(i);
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn position(&self, entity: &IndexedEntity<C>) -> Option<usize>
+    {
+        match self.entities.binary_search_by_key(&entity.index(), |e| e.index())
+        {
+            Ok(i) if *self.entities[i] == **entity => Some(i),
+            _ => None,
+        }
+    }
+}
+
+impl<C: ComponentManager> InterestTracker<C>
+{
+    pub fn new(aspect: Aspect<C>) -> InterestTracker<C>
+    {
+        InterestTracker
+        {
+            query: CachedQuery::new(aspect),
+        }
+    }
+
+    pub fn on_activated<S>(&mut self, entity: &EntityData<C>, components: &C, inner: &mut S)
+        where S: System<Components = C> + ?Sized
+    {
+        if self.query.activated(entity, components)
+        {
+            inner.activated(entity, components);
+        }
+    }
+
+    /// Passes the entities that match on to `inner.activated_batch()`.
+    pub fn on_activated_batch<S>(&mut self, entities: &[EntityData<C>], components: &C, inner: &mut S)
+        where S: System<Components = C> + ?Sized
+    {
+        let matched = self.query.activated_batch(entities, components);
+        inner.activated_batch(&matched, components);
+    }
+
+    pub fn on_reactivated<S>(&mut self, entity: &EntityData<C>, components: &C, inner: &mut S)
+        where S: System<Components = C> + ?Sized
+    {
+        match self.query.reactivated(entity, components)
+        {
+            (true, true) => inner.reactivated(entity, components),
+            (true, false) => inner.deactivated(entity, components),
+            (false, true) => inner.activated(entity, components),
+            (false, false) => {},
+        }
+    }
+
+    pub fn on_deactivated<S>(&mut self, entity: &EntityData<C>, components: &C, inner: &mut S)
+        where S: System<Components = C> + ?Sized
+    {
+        if self.query.deactivated(entity)
+        {
+            inner.deactivated(entity, components);
+        }
+    }
+
+    /// Replaces the aspect and rechecks every entity against it, calling `inner.activated()` or
+    /// `inner.deactivated()` for the ones that join or leave the set.
+    pub fn set_aspect<M, S>(&mut self, aspect: Aspect<C>, data: &DataHelper<C, M>, inner: &mut S)
+        where M: ServiceManager, S: System<Components = C> + ?Sized
+    {
+        self.query.aspect = aspect;
+        let query = &mut self.query;
+        data.rescan(|entity, components| {
+            match query.reactivated(entity, components)
+            {
+                (false, true) => inner.activated(entity, components),
+                (true, false) => inner.deactivated(entity, components),
+                _ => {},
+            }
+        });
+    }
+}
+
+impl<C: ComponentManager> Deref for InterestTracker<C>
+{
+    type Target = CachedQuery<C>;
+    fn deref(&self) -> &CachedQuery<C>
+    {
+        &self.query
+    }
+}
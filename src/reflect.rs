@@ -0,0 +1,124 @@
+//! Reading and writing the fields of components by name, as text or numbers.
+//!
+//! Fields marked `#[reflect]` in `components!` can be reached with
+//! `ComponentManager::reflect_component()`. Their types must implement `Reflect`, which can be
+//! done for structs with `reflect_struct!`:
+//!
+//! ```ignore
+//! reflect_struct!(Position { x, y });
+//! ```
+
+use {Error, Result};
+
+/// Implemented by component types whose fields can be read and written by name.
+pub trait Reflect
+{
+    /// Names of the fields, in declaration order.
+    fn field_names(&self) -> &'static [&'static str];
+    /// A field as text, or `None` if there's no field with that name.
+    fn get_field(&self, name: &str) -> Option<String>;
+    /// Sets a field from text.
+    fn set_field(&mut self, name: &str, value: &str) -> Result<()>;
+    /// A field as a number, or `None` if there's no such field or it isn't numeric.
+    fn get_f64(&self, name: &str) -> Option<f64>;
+    /// Sets a numeric field.
+    fn set_f64(&mut self, name: &str, value: f64) -> Result<()>;
+}
+
+/// Implemented by the types of fields that `reflect_struct!` can expose.
+pub trait ReflectValue: Sized
+{
+    fn to_text(&self) -> String;
+    fn from_text(text: &str) -> Option<Self>;
+    fn to_f64(&self) -> Option<f64>;
+    fn from_f64(value: f64) -> Option<Self>;
+}
+
+/// The error for a field that doesn't exist.
+pub fn unknown_field(name: &str) -> Error
+{
+    Error::Parse(format!("unknown field `{}`", name))
+}
+
+/// The error for a value that doesn't fit a field.
+pub fn invalid_value(name: &str, value: &str) -> Error
+{
+    Error::Parse(format!("`{}` isn't a valid value for field `{}`", value, name))
+}
+
+macro_rules! reflect_number {
+    ($($ty:ty),+) => {
+        $(
+            impl ReflectValue for $ty
+            {
+                fn to_text(&self) -> String
+                {
+                    self.to_string()
+                }
+
+                fn from_text(text: &str) -> Option<$ty>
+                {
+                    text.parse().ok()
+                }
+
+                fn to_f64(&self) -> Option<f64>
+                {
+                    Some(*self as f64)
+                }
+
+                fn from_f64(value: f64) -> Option<$ty>
+                {
+                    Some(value as $ty)
+                }
+            }
+        )+
+    };
+}
+
+reflect_number!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64);
+
+impl ReflectValue for bool
+{
+    fn to_text(&self) -> String
+    {
+        self.to_string()
+    }
+
+    fn from_text(text: &str) -> Option<bool>
+    {
+        text.parse().ok()
+    }
+
+    fn to_f64(&self) -> Option<f64>
+    {
+        Some(if *self { 1.0 } else { 0.0 })
+    }
+
+    fn from_f64(value: f64) -> Option<bool>
+    {
+        Some(value != 0.0)
+    }
+}
+
+impl ReflectValue for String
+{
+    fn to_text(&self) -> String
+    {
+        self.clone()
+    }
+
+    fn from_text(text: &str) -> Option<String>
+    {
+        Some(text.to_string())
+    }
+
+    fn to_f64(&self) -> Option<f64>
+    {
+        None
+    }
+
+    fn from_f64(_: f64) -> Option<String>
+    {
+        None
+    }
+}
@@ -0,0 +1,109 @@
+//! Lookup of component fields by name.
+//!
+//! Anything that refers to components with strings (data files, scripts, consoles) should resolve
+//! those names through a `ComponentRegistry` so that mistakes are reported the same way everywhere.
+
+use std::error::Error;
+use std::fmt;
+use std::marker::PhantomData;
+
+use ComponentManager;
+
+/// Resolves the names of the fields declared in `components!`.
+pub struct ComponentRegistry<C: ComponentManager>(PhantomData<fn(C)>);
+
+/// A component name that doesn't match any field of the component manager.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnknownComponent
+{
+    /// The name that was looked up.
+    pub name: String,
+    /// Known names that are close to `name`, closest first.
+    pub suggestions: Vec<&'static str>,
+}
+
+impl<C: ComponentManager> ComponentRegistry<C>
+{
+    pub fn new() -> ComponentRegistry<C>
+    {
+        ComponentRegistry(PhantomData)
+    }
+
+    /// Names of all known components, in declaration order.
+    pub fn names(&self) -> &'static [&'static str]
+    {
+        C::names()
+    }
+
+    /// Returns the position of the named component in declaration order.
+    pub fn lookup(&self, name: &str) -> Result<usize, UnknownComponent>
+    {
+        match C::names().iter().position(|&n| n == name)
+        {
+            Some(index) => Ok(index),
+            None => Err(UnknownComponent::new(name, C::names())),
+        }
+    }
+
+    /// Returns the statically allocated version of a component name.
+    pub fn resolve(&self, name: &str) -> Result<&'static str, UnknownComponent>
+    {
+        self.lookup(name).map(|index| C::names()[index])
+    }
+}
+
+impl UnknownComponent
+{
+    /// Creates the error for `name`, suggesting candidates from `known`.
+    pub fn new(name: &str, known: &[&'static str]) -> UnknownComponent
+    {
+        let max_distance = ::std::cmp::max(1, name.len() / 3);
+        let mut close: Vec<(usize, &'static str)> = known.iter()
+            .map(|&k| (edit_distance(name, k), k))
+            .filter(|&(d, _)| d <= max_distance)
+            .collect();
+        close.sort_by_key(|&(d, _)| d);
+        UnknownComponent
+        {
+            name: name.to_string(),
+            suggestions: close.into_iter().map(|(_, k)| k).collect(),
+        }
+    }
+}
+
+impl fmt::Display for UnknownComponent
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self.suggestions.len()
+        {
+            0 => write!(f, "unknown component `{}`", self.name),
+            1 => write!(f, "unknown component `{}` (did you mean `{}`?)", self.name, self.suggestions[0]),
+            _ => {
+                let list: Vec<String> = self.suggestions.iter().map(|s| format!("`{}`", s)).collect();
+                write!(f, "unknown component `{}` (did you mean one of {}?)", self.name, list.join(", "))
+            }
+        }
+    }
+}
+
+impl Error for UnknownComponent {}
+
+/// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize
+{
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..b.len() + 1).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, ca) in a.chars().enumerate()
+    {
+        cur[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate()
+        {
+            let cost = if ca == cb { 0 } else { 1 };
+            cur[j + 1] = ::std::cmp::min(::std::cmp::min(prev[j + 1] + 1, cur[j] + 1), prev[j] + cost);
+        }
+        ::std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
@@ -0,0 +1,153 @@
+//! Recording a simulation and playing it back.
+//!
+//! A `Recorder` stores the inputs applied before each update, along with the entities created and
+//! removed and the `state_hash()` afterwards. A `Replay` applies the same inputs to a fresh world
+//! and checks that it ends up in the same state each frame, which makes bugs reproducible as long
+//! as the systems are deterministic.
+//!
+//! Inputs are whatever the application uses to drive the simulation (key presses, network
+//! commands, ...). Anything that changes the world other than inputs and systems, such as setting
+//! up a level, has to be done the same way before recording and before replaying.
+
+use std::mem;
+
+use {Error, Result};
+use {EntityEvent, SystemManager, World};
+
+/// What happened during one update.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame<E>
+{
+    /// Inputs applied before the update, in order.
+    pub inputs: Vec<E>,
+    /// Entities created and removed during the update.
+    pub events: Vec<EntityEvent>,
+    /// `World::state_hash()` after the update.
+    pub hash: u64,
+}
+
+/// A recorded sequence of frames.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recording<E>
+{
+    pub frames: Vec<Frame<E>>,
+}
+
+/// Records the inputs and results of each update.
+pub struct Recorder<E>
+{
+    frames: Vec<Frame<E>>,
+    inputs: Vec<E>,
+}
+
+impl<E: Clone> Recorder<E>
+{
+    pub fn new() -> Recorder<E>
+    {
+        Recorder
+        {
+            frames: Vec::new(),
+            inputs: Vec::new(),
+        }
+    }
+
+    /// Queues an input to be applied at the next update.
+    pub fn input(&mut self, input: E)
+    {
+        self.inputs.push(input);
+    }
+
+    /// Applies the queued inputs with `apply`, updates the world, and records the frame.
+    pub fn update<S, F>(&mut self, world: &mut World<S>, mut apply: F)
+        where S: SystemManager, F: FnMut(&mut World<S>, &E)
+    {
+        let inputs = mem::take(&mut self.inputs);
+        for input in &inputs
+        {
+            apply(world, input);
+        }
+        world.update();
+        self.frames.push(Frame
+        {
+            inputs: inputs,
+            events: world.entity_events().to_vec(),
+            hash: world.state_hash(),
+        });
+    }
+
+    /// Number of frames recorded so far.
+    pub fn len(&self) -> usize
+    {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.frames.is_empty()
+    }
+
+    /// Stops recording. Inputs queued since the last update are dropped.
+    pub fn finish(self) -> Recording<E>
+    {
+        Recording
+        {
+            frames: self.frames,
+        }
+    }
+}
+
+/// Plays a recording back into a world.
+pub struct Replay<E>
+{
+    recording: Recording<E>,
+    next: usize,
+}
+
+impl<E> Replay<E>
+{
+    pub fn new(recording: Recording<E>) -> Replay<E>
+    {
+        Replay
+        {
+            recording: recording,
+            next: 0,
+        }
+    }
+
+    /// Index of the next frame to be played.
+    pub fn position(&self) -> usize
+    {
+        self.next
+    }
+
+    pub fn is_finished(&self) -> bool
+    {
+        self.next >= self.recording.frames.len()
+    }
+
+    /// Plays the next frame: applies its inputs with `apply`, then updates the world.
+    ///
+    /// Returns `Ok(false)` without doing anything once every frame has been played, and
+    /// `Error::Desync` if the world didn't end up as it did when recorded.
+    pub fn update<S, F>(&mut self, world: &mut World<S>, mut apply: F) -> Result<bool>
+        where S: SystemManager, F: FnMut(&mut World<S>, &E)
+    {
+        let frame = match self.recording.frames.get(self.next)
+        {
+            Some(frame) => frame,
+            None => return Ok(false),
+        };
+        for input in &frame.inputs
+        {
+            apply(world, input);
+        }
+        world.update();
+        let index = self.next;
+        self.next += 1;
+        if world.entity_events() != &frame.events[..] || world.state_hash() != frame.hash
+        {
+            return Err(Error::Desync { frame: index });
+        }
+        Ok(true)
+    }
+}
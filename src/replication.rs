@@ -0,0 +1,301 @@
+//! Compact encoding of components sent to other worlds, eg: positions sent by a server to its
+//! clients every update.
+//!
+//! A `DeltaEncoder` remembers the last value it encoded for each entity and only writes what
+//! changed since then, packed bit by bit. The `DeltaDecoder` on the other end remembers the same
+//! values, so every encoded value has to be decoded, in order. How a value is written is up to a
+//! `DeltaCodec`. The default one writes each field that changed, for types implementing `Delta`,
+//! which can be done for plain structs with `delta_struct!`:
+//!
+//! ```ignore
+//! delta_struct!(Position { x, y });
+//! ```
+
+use std::collections::HashMap;
+
+use Entity;
+use {Error, Result};
+
+/// Writes values as a sequence of bits.
+#[derive(Clone, Debug, Default)]
+pub struct BitWriter
+{
+    bytes: Vec<u8>,
+    bits: usize,
+}
+
+impl BitWriter
+{
+    pub fn new() -> BitWriter
+    {
+        BitWriter::default()
+    }
+
+    pub fn write_bit(&mut self, bit: bool)
+    {
+        if self.bits.is_multiple_of(8)
+        {
+            self.bytes.push(0);
+        }
+        if bit
+        {
+            *self.bytes.last_mut().unwrap() |= 1 << (self.bits % 8);
+        }
+        self.bits += 1;
+    }
+
+    /// Writes the lowest `count` bits of `value`.
+    pub fn write_bits(&mut self, value: u64, count: u32)
+    {
+        for bit in 0..count
+        {
+            self.write_bit(value >> bit & 1 == 1);
+        }
+    }
+
+    /// Number of bits written so far.
+    pub fn len(&self) -> usize
+    {
+        self.bits
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.bits == 0
+    }
+
+    /// The bits written, padded with zeroes to a whole number of bytes.
+    pub fn into_bytes(self) -> Vec<u8>
+    {
+        self.bytes
+    }
+}
+
+/// Reads values written by a `BitWriter`.
+pub struct BitReader<'a>
+{
+    bytes: &'a [u8],
+    bits: usize,
+}
+
+impl<'a> BitReader<'a>
+{
+    pub fn new(bytes: &'a [u8]) -> BitReader<'a>
+    {
+        BitReader
+        {
+            bytes: bytes,
+            bits: 0,
+        }
+    }
+
+    pub fn read_bit(&mut self) -> Result<bool>
+    {
+        match self.bytes.get(self.bits / 8)
+        {
+            Some(byte) => {
+                let bit = byte >> (self.bits % 8) & 1 == 1;
+                self.bits += 1;
+                Ok(bit)
+            },
+            None => Err(Error::Parse("unexpected end of encoded value".to_string())),
+        }
+    }
+
+    /// Reads `count` bits written by `BitWriter::write_bits()`.
+    pub fn read_bits(&mut self, count: u32) -> Result<u64>
+    {
+        let mut value = 0;
+        for bit in 0..count
+        {
+            if self.read_bit()?
+            {
+                value |= 1 << bit;
+            }
+        }
+        Ok(value)
+    }
+}
+
+/// Implemented by values that can be written as the difference from an older value.
+pub trait Delta: Sized
+{
+    /// Writes the value, leaving out what is the same in `old`. Without `old`, the whole value is
+    /// written.
+    fn write_delta(&self, old: Option<&Self>, out: &mut BitWriter);
+    /// Reads a value written by `write_delta()` with the same `old`.
+    fn read_delta(old: Option<&Self>, input: &mut BitReader) -> Result<Self>;
+}
+
+macro_rules! delta_number {
+    ($($ty:ty => $bits:expr, $to:expr, $from:expr);+ $(;)?) => {
+        $(
+            impl Delta for $ty
+            {
+                fn write_delta(&self, old: Option<&$ty>, out: &mut BitWriter)
+                {
+                    if let Some(old) = old
+                    {
+                        let changed = $to(*old) != $to(*self);
+                        out.write_bit(changed);
+                        if !changed
+                        {
+                            return;
+                        }
+                    }
+                    out.write_bits($to(*self), $bits);
+                }
+
+                fn read_delta(old: Option<&$ty>, input: &mut BitReader) -> Result<$ty>
+                {
+                    if let Some(old) = old
+                    {
+                        if !input.read_bit()?
+                        {
+                            return Ok(*old);
+                        }
+                    }
+                    Ok($from(input.read_bits($bits)?))
+                }
+            }
+        )+
+    };
+}
+
+delta_number! {
+    i8 => 8, |v: i8| v as u64, |v: u64| v as i8;
+    i16 => 16, |v: i16| v as u64, |v: u64| v as i16;
+    i32 => 32, |v: i32| v as u64, |v: u64| v as i32;
+    i64 => 64, |v: i64| v as u64, |v: u64| v as i64;
+    u8 => 8, |v: u8| v as u64, |v: u64| v as u8;
+    u16 => 16, |v: u16| v as u64, |v: u64| v as u16;
+    u32 => 32, |v: u32| v as u64, |v: u64| v as u32;
+    u64 => 64, |v: u64| v, |v: u64| v;
+    f32 => 32, |v: f32| v.to_bits() as u64, |v: u64| f32::from_bits(v as u32);
+    f64 => 64, |v: f64| v.to_bits(), f64::from_bits;
+}
+
+impl Delta for bool
+{
+    /// A single bit: whether it flipped, or its value without `old`.
+    fn write_delta(&self, old: Option<&bool>, out: &mut BitWriter)
+    {
+        out.write_bit(*self != old.cloned().unwrap_or(false));
+    }
+
+    fn read_delta(old: Option<&bool>, input: &mut BitReader) -> Result<bool>
+    {
+        Ok(input.read_bit()? != old.cloned().unwrap_or(false))
+    }
+}
+
+/// Writes and reads values of type `T` for a `DeltaEncoder` and `DeltaDecoder`, eg: a codec that
+/// quantizes positions to a grid.
+pub trait DeltaCodec<T>
+{
+    /// Writes `new`, leaving out what is the same in `old`, if there is an `old` value.
+    fn encode(&self, old: Option<&T>, new: &T, out: &mut BitWriter);
+    /// Reads a value written by `encode()` with the same `old`.
+    fn decode(&self, old: Option<&T>, input: &mut BitReader) -> Result<T>;
+}
+
+/// The codec for types implementing `Delta`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DefaultCodec;
+
+impl<T: Delta> DeltaCodec<T> for DefaultCodec
+{
+    fn encode(&self, old: Option<&T>, new: &T, out: &mut BitWriter)
+    {
+        new.write_delta(old, out);
+    }
+
+    fn decode(&self, old: Option<&T>, input: &mut BitReader) -> Result<T>
+    {
+        T::read_delta(old, input)
+    }
+}
+
+/// Encodes each entity's component against the last value encoded for it.
+pub struct DeltaEncoder<T, D = DefaultCodec>
+{
+    codec: D,
+    sent: HashMap<Entity, T>,
+}
+
+impl<T: Clone + Delta> DeltaEncoder<T>
+{
+    pub fn new() -> DeltaEncoder<T>
+    {
+        DeltaEncoder::with_codec(DefaultCodec)
+    }
+}
+
+impl<T: Clone, D: DeltaCodec<T>> DeltaEncoder<T, D>
+{
+    pub fn with_codec(codec: D) -> DeltaEncoder<T, D>
+    {
+        DeltaEncoder
+        {
+            codec: codec,
+            sent: HashMap::new(),
+        }
+    }
+
+    /// Encodes an entity's component, in full the first time.
+    pub fn encode(&mut self, entity: Entity, value: &T) -> Vec<u8>
+    {
+        let mut out = BitWriter::new();
+        self.codec.encode(self.sent.get(&entity), value, &mut out);
+        self.sent.insert(entity, value.clone());
+        out.into_bytes()
+    }
+
+    /// Forgets the last value encoded for an entity, eg: when it's removed or a client joins.
+    /// The decoder has to forget it too.
+    pub fn forget(&mut self, entity: Entity)
+    {
+        self.sent.remove(&entity);
+    }
+}
+
+/// Decodes the output of a `DeltaEncoder` using the same codec.
+pub struct DeltaDecoder<T, D = DefaultCodec>
+{
+    codec: D,
+    received: HashMap<Entity, T>,
+}
+
+impl<T: Clone + Delta> DeltaDecoder<T>
+{
+    pub fn new() -> DeltaDecoder<T>
+    {
+        DeltaDecoder::with_codec(DefaultCodec)
+    }
+}
+
+impl<T: Clone, D: DeltaCodec<T>> DeltaDecoder<T, D>
+{
+    pub fn with_codec(codec: D) -> DeltaDecoder<T, D>
+    {
+        DeltaDecoder
+        {
+            codec: codec,
+            received: HashMap::new(),
+        }
+    }
+
+    /// Decodes an entity's component from the output of `DeltaEncoder::encode()`.
+    pub fn decode(&mut self, entity: Entity, bytes: &[u8]) -> Result<T>
+    {
+        let value = self.codec.decode(self.received.get(&entity), &mut BitReader::new(bytes))?;
+        self.received.insert(entity, value.clone());
+        Ok(value)
+    }
+
+    /// Forgets the last value decoded for an entity.
+    pub fn forget(&mut self, entity: Entity)
+    {
+        self.received.remove(&entity);
+    }
+}
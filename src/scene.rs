@@ -0,0 +1,423 @@
+//! Entities described in data files, eg: levels authored outside of Rust code.
+//!
+//! Only fields marked `#[persist]` in `components!` can be loaded, and their types must implement
+//! `serde::Serialize` and `serde::Deserialize`. A scene document looks like:
+//!
+//! ```json
+//! {
+//!     "version": 2,
+//!     "entities": [
+//!         { "name": "player", "components": { "position": { "x": 0.0, "y": 1.0 }, "health": 10 } },
+//!         { "components": { "position": { "x": 4.0, "y": 0.0 } } }
+//!     ]
+//! }
+//! ```
+//!
+//! With the `ron` feature, the same document can be written in RON, where structs can be written
+//! without quoting their field names, eg: `(name: "player", components: { "position": (x: 0.0, y: 1.0) })`.
+//!
+//! `version` is the schema version of the components, chosen by the application. When a component
+//! struct changes, bump it and register a conversion from the old struct with
+//! `Migrations::register_migration()` so that older documents can still be loaded.
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+#[cfg(feature = "compress")]
+use std::io::{Read, Write};
+
+pub use serde_json::Value;
+
+use {ComponentManager, ComponentRegistry, DataHelper, ServiceManager};
+use BuildData;
+use Entity;
+use {Error, Result};
+
+/// A list of entities to create, with their components by field name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scene
+{
+    /// Schema version the components were saved with.
+    pub version: u32,
+    pub entities: Vec<SceneEntity>,
+}
+
+/// An entity of a `Scene`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SceneEntity
+{
+    /// Name given to the entity with `DataHelper::set_name()`.
+    pub name: Option<String>,
+    /// Serialized components, by field name.
+    pub components: Vec<(String, Value)>,
+}
+
+/// An entity taken out of a world by `World::archive_entity()`, eg: to stream part of a large
+/// world out of memory. Holds its name and `#[persist]` components as bytes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArchivedEntity
+{
+    bytes: Vec<u8>,
+}
+
+/// Converts a component's serialized value from an older schema version.
+type Convert = Box<dyn Fn(Value) -> ::std::result::Result<Value, String>>;
+
+struct Migration
+{
+    component: &'static str,
+    /// First version that has the new form of the component.
+    version: u32,
+    convert: Convert,
+}
+
+/// Conversions that bring scenes saved with older schema versions up to date.
+pub struct Migrations
+{
+    version: u32,
+    migrations: Vec<Migration>,
+}
+
+impl Scene
+{
+    pub fn new(version: u32) -> Scene
+    {
+        Scene
+        {
+            version: version,
+            entities: Vec::new(),
+        }
+    }
+
+    /// Parses a scene from a JSON document.
+    pub fn from_json(json: &str) -> Result<Scene>
+    {
+        match serde_json::from_str(json)
+        {
+            Ok(value) => Scene::from_value(value),
+            Err(err) => Err(Error::Parse(err.to_string())),
+        }
+    }
+
+    /// Reads a scene from an already parsed document.
+    pub fn from_value(value: Value) -> Result<Scene>
+    {
+        let mut root = match value
+        {
+            Value::Object(root) => root,
+            _ => return Err(Error::Parse("expected an object".to_string())),
+        };
+        let version = match root.remove("version")
+        {
+            Some(version) => match version.as_u64()
+            {
+                Some(version) if version <= u32::MAX as u64 => version as u32,
+                _ => return Err(Error::Parse("expected the version to be a number".to_string())),
+            },
+            None => 0,
+        };
+        let entities = match root.remove("entities")
+        {
+            Some(Value::Array(entities)) => entities,
+            _ => return Err(Error::Parse("expected an `entities` array".to_string())),
+        };
+        let mut scene = Scene
+        {
+            version: version,
+            entities: Vec::with_capacity(entities.len()),
+        };
+        for entity in entities
+        {
+            scene.entities.push(SceneEntity::from_value(entity)?);
+        }
+        Ok(scene)
+    }
+
+    /// Writes the scene as a JSON document.
+    pub fn to_json(&self) -> String
+    {
+        self.to_value().to_string()
+    }
+
+    /// Parses a scene from a RON document, which has the same layout as a JSON one.
+    #[cfg(feature = "ron")]
+    pub fn from_ron(ron: &str) -> Result<Scene>
+    {
+        // Read as RON's own values first, which keep structs' field names as keys.
+        let value: ::ron::Value = ::ron::from_str(ron).map_err(|err| Error::Parse(err.to_string()))?;
+        match serde_json::to_value(value)
+        {
+            Ok(value) => Scene::from_value(value),
+            Err(err) => Err(Error::Parse(err.to_string())),
+        }
+    }
+
+    /// Writes the scene as a RON document.
+    #[cfg(feature = "ron")]
+    pub fn to_ron(&self) -> String
+    {
+        ::ron::ser::to_string_pretty(&self.to_value(), ::ron::ser::PrettyConfig::default())
+            .expect("a JSON value can always be written as RON")
+    }
+
+    /// Writes the scene as a compressed JSON document, returning the writer.
+    #[cfg(feature = "compress")]
+    pub fn write_compressed<W: Write>(&self, writer: W) -> Result<W>
+    {
+        let mut encoder = ::compress::Encoder::new(writer);
+        serde_json::to_writer(&mut encoder, &self.to_value()).map_err(|err| Error::Io(err.to_string()))?;
+        encoder.finish().map_err(|err| Error::Io(err.to_string()))
+    }
+
+    /// Reads a scene written by `write_compressed()`.
+    #[cfg(feature = "compress")]
+    pub fn read_compressed<R: Read>(reader: R) -> Result<Scene>
+    {
+        match serde_json::from_reader(::compress::Decoder::new(reader))
+        {
+            Ok(value) => Scene::from_value(value),
+            Err(err) => Err(Error::Parse(err.to_string())),
+        }
+    }
+
+    /// Writes the scene as a document that can be read back by `from_value()`.
+    pub fn to_value(&self) -> Value
+    {
+        let entities = self.entities.iter().map(SceneEntity::to_value).collect();
+        let mut root = serde_json::Map::new();
+        root.insert("version".to_string(), Value::from(self.version));
+        root.insert("entities".to_string(), Value::Array(entities));
+        Value::Object(root)
+    }
+
+    /// Creates the scene's entities, returning them in order.
+    ///
+    /// If any component can't be loaded, no entities are created.
+    pub fn spawn<C, M>(&self, data: &mut DataHelper<C, M>) -> Result<Vec<Entity>>
+        where C: ComponentManager, M: ServiceManager
+    {
+        let registry = ComponentRegistry::<C>::new();
+        for entity in &self.entities
+        {
+            for (name, _) in &entity.components
+            {
+                registry.lookup(name)?;
+            }
+        }
+        let mut spawned = Vec::with_capacity(self.entities.len());
+        for entity in &self.entities
+        {
+            match entity.spawn(data)
+            {
+                Ok(created) => spawned.push(created),
+                Err(err) => {
+                    for entity in spawned
+                    {
+                        data.remove_entity(entity);
+                    }
+                    return Err(err);
+                },
+            }
+        }
+        Ok(spawned)
+    }
+}
+
+impl SceneEntity
+{
+    /// Reads an entity from its part of a scene document.
+    pub fn from_value(value: Value) -> Result<SceneEntity>
+    {
+        let mut entity = match value
+        {
+            Value::Object(entity) => entity,
+            _ => return Err(Error::Parse("expected each entity to be an object".to_string())),
+        };
+        let name = match entity.remove("name")
+        {
+            Some(Value::String(name)) => Some(name),
+            None | Some(Value::Null) => None,
+            Some(_) => return Err(Error::Parse("expected an entity's name to be a string".to_string())),
+        };
+        let components = match entity.remove("components")
+        {
+            Some(Value::Object(components)) => components.into_iter().collect(),
+            None => Vec::new(),
+            Some(_) => return Err(Error::Parse("expected an entity's components to be an object".to_string())),
+        };
+        Ok(SceneEntity
+        {
+            name: name,
+            components: components,
+        })
+    }
+
+    /// Writes the entity as its part of a scene document.
+    pub fn to_value(&self) -> Value
+    {
+        let mut object = serde_json::Map::new();
+        if let Some(ref name) = self.name
+        {
+            object.insert("name".to_string(), Value::String(name.clone()));
+        }
+        let components = self.components.iter().cloned().collect();
+        object.insert("components".to_string(), Value::Object(components));
+        Value::Object(object)
+    }
+
+    /// Creates the entity. If any component can't be loaded, it isn't created.
+    pub fn spawn<C, M>(&self, data: &mut DataHelper<C, M>) -> Result<Entity>
+        where C: ComponentManager, M: ServiceManager
+    {
+        let registry = ComponentRegistry::<C>::new();
+        for (name, _) in &self.components
+        {
+            registry.lookup(name)?;
+        }
+        let mut result = Ok(());
+        let created = data.create_entity(|e: BuildData<C>, c: &mut C| {
+            for (name, value) in &self.components
+            {
+                let loaded = unsafe { c.deserialize_component(name, e.0, value.clone()) };
+                result = match loaded
+                {
+                    Some(result) => result,
+                    None => Err(Error::Deserialize
+                    {
+                        component_name: registry.resolve(name).unwrap_or(""),
+                        message: "the field isn't marked #[persist]".to_string(),
+                    }),
+                };
+                if result.is_err()
+                {
+                    return;
+                }
+            }
+        });
+        if let Err(err) = result
+        {
+            data.remove_entity(created);
+            return Err(err);
+        }
+        if let Some(ref name) = self.name
+        {
+            data.set_name(&created, name);
+        }
+        Ok(created)
+    }
+}
+
+impl ArchivedEntity
+{
+    /// Archives an entity's name and components.
+    pub fn new(entity: &SceneEntity) -> Result<ArchivedEntity>
+    {
+        let bytes = serde_json::to_vec(&entity.to_value()).map_err(|err| Error::Parse(err.to_string()))?;
+        Ok(ArchivedEntity
+        {
+            bytes: bytes,
+        })
+    }
+
+    /// Reads back an archive saved with `as_bytes()`. It isn't checked until it's unarchived.
+    pub fn from_bytes(bytes: Vec<u8>) -> ArchivedEntity
+    {
+        ArchivedEntity
+        {
+            bytes: bytes,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8]
+    {
+        &self.bytes
+    }
+
+    /// The entity as it was archived.
+    pub fn entity(&self) -> Result<SceneEntity>
+    {
+        let value = serde_json::from_slice(&self.bytes).map_err(|err| Error::Parse(err.to_string()))?;
+        SceneEntity::from_value(value)
+    }
+}
+
+impl Migrations
+{
+    /// Creates an empty set of migrations to the current schema `version`.
+    pub fn new(version: u32) -> Migrations
+    {
+        Migrations
+        {
+            version: version,
+            migrations: Vec::new(),
+        }
+    }
+
+    /// The schema version that scenes are migrated to.
+    pub fn version(&self) -> u32
+    {
+        self.version
+    }
+
+    /// Registers a conversion of the named component from `Old`, used before `version`, to `New`.
+    ///
+    /// eg: `migrations.register_migration::<PositionV1, Position>("position", 2)`. Conversions of
+    /// a component are chained in version order, so a scene can be several versions behind.
+    pub fn register_migration<Old, New>(&mut self, component: &'static str, version: u32)
+        where Old: DeserializeOwned, New: From<Old> + Serialize
+    {
+        let position = self.migrations.iter().position(|m| m.version > version).unwrap_or(self.migrations.len());
+        self.migrations.insert(position, Migration
+        {
+            component: component,
+            version: version,
+            convert: Box::new(|value| {
+                let old: Old = serde_json::from_value(value).map_err(|err| err.to_string())?;
+                serde_json::to_value(New::from(old)).map_err(|err| err.to_string())
+            }),
+        });
+    }
+
+    /// Brings a scene up to the current schema version.
+    pub fn migrate(&self, scene: &mut Scene) -> Result<()>
+    {
+        if scene.version > self.version
+        {
+            return Err(Error::UnsupportedVersion { version: scene.version, supported: self.version });
+        }
+        let saved = scene.version;
+        for migration in self.migrations.iter().filter(|m| m.version > saved && m.version <= self.version)
+        {
+            for entity in &mut scene.entities
+            {
+                for &mut (ref name, ref mut value) in &mut entity.components
+                {
+                    if *name != migration.component
+                    {
+                        continue;
+                    }
+                    let old = ::std::mem::replace(value, Value::Null);
+                    *value = match (migration.convert)(old)
+                    {
+                        Ok(new) => new,
+                        Err(message) => return Err(Error::Deserialize
+                        {
+                            component_name: migration.component,
+                            message: format!("migrating to version {}: {}", migration.version, message),
+                        }),
+                    };
+                }
+            }
+        }
+        scene.version = self.version;
+        Ok(())
+    }
+
+    /// Parses a scene from a JSON document, then brings it up to the current schema version.
+    pub fn load_json(&self, json: &str) -> Result<Scene>
+    {
+        let mut scene = Scene::from_json(json)?;
+        self.migrate(&mut scene)?;
+        Ok(scene)
+    }
+}
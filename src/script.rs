@@ -0,0 +1,78 @@
+//! Access to components by name and as JSON values, for scripting languages and consoles.
+//!
+//! Only fields marked `#[persist]` can be read or written, since they're the ones that know how
+//! to convert to and from values. A scripting bridge exposes a `ScriptView` to its scripts, eg: as
+//! `get(entity, "health")` and `set(entity, "health", 10)`.
+
+use {ComponentManager, ComponentRegistry, DataHelper, ServiceManager};
+use Entity;
+use {Error, Result};
+use scene::{SceneEntity, Value};
+
+/// A view of a world's components that doesn't depend on their Rust types.
+pub struct ScriptView<'a, C: ComponentManager + 'a, M: ServiceManager + 'a>
+{
+    data: &'a mut DataHelper<C, M>,
+    registry: ComponentRegistry<C>,
+}
+
+impl<'a, C: ComponentManager, M: ServiceManager> ScriptView<'a, C, M>
+{
+    pub fn new(data: &'a mut DataHelper<C, M>) -> ScriptView<'a, C, M>
+    {
+        ScriptView
+        {
+            data: data,
+            registry: ComponentRegistry::new(),
+        }
+    }
+
+    /// Names of every component field.
+    pub fn names(&self) -> &'static [&'static str]
+    {
+        self.registry.names()
+    }
+
+    /// Reads an entity's component, or `None` if it doesn't have one.
+    pub fn get(&mut self, entity: &Entity, name: &str) -> Result<Option<Value>>
+    {
+        let name = self.registry.resolve(name)?;
+        let components = self.data.with_entity_data(entity, |e, c| c.serialize_components(&e))
+            .ok_or(Error::NoSuchEntity(*entity))??;
+        Ok(components.into_iter().find(|&(field, _)| field == name).map(|(_, value)| value))
+    }
+
+    /// Gives an entity a component, replacing any it had. The entity is reactivated at the next
+    /// flush if it didn't have one.
+    pub fn set(&mut self, entity: &Entity, name: &str, value: Value) -> Result<()>
+    {
+        let name = self.registry.resolve(name)?;
+        let loaded = self.data.with_entity_data(entity, |e, c| unsafe { c.deserialize_component(name, &e, value.clone()) })
+            .ok_or(Error::NoSuchEntity(*entity))?;
+        match loaded
+        {
+            Some(result) => result,
+            None => Err(Error::Deserialize
+            {
+                component_name: name,
+                message: "the field isn't marked #[persist]".to_string(),
+            }),
+        }
+    }
+
+    /// Creates an entity with components by name.
+    pub fn spawn(&mut self, components: Vec<(String, Value)>) -> Result<Entity>
+    {
+        SceneEntity
+        {
+            name: None,
+            components: components,
+        }.spawn(self.data)
+    }
+
+    /// Removes an entity at the next flush.
+    pub fn despawn(&mut self, entity: Entity)
+    {
+        self.data.remove_entity(entity);
+    }
+}
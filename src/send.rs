@@ -0,0 +1,29 @@
+//! Optional `Send` bounds for everything the library stores in a box.
+//!
+//! With the `send` feature enabled, closures, hooks and systems handed to the library must be
+//! `Send`, which makes a `World` `Send` as long as its components and services are. Without the
+//! feature there is no extra requirement.
+
+/// `Send` with the `send` feature, and no bound at all without it.
+#[cfg(feature = "send")]
+pub trait MaybeSend: Send {}
+#[cfg(feature = "send")]
+impl<T: Send + ?Sized> MaybeSend for T {}
+
+/// `Send` with the `send` feature, and no bound at all without it.
+#[cfg(not(feature = "send"))]
+pub trait MaybeSend {}
+#[cfg(not(feature = "send"))]
+impl<T: ?Sized> MaybeSend for T {}
+
+/// A boxed trait object that is also `Send` when the `send` feature is enabled.
+#[cfg(feature = "send")]
+macro_rules! boxed {
+    ($($bound:tt)+) => { Box<dyn $($bound)+ + Send> };
+}
+
+/// A boxed trait object that is also `Send` when the `send` feature is enabled.
+#[cfg(not(feature = "send"))]
+macro_rules! boxed {
+    ($($bound:tt)+) => { Box<dyn $($bound)+> };
+}
@@ -0,0 +1,186 @@
+//! Streaming parts of a large world in and out of memory by chunk.
+//!
+//! Each entity belongs to the chunk returned by a user-provided function, usually from its
+//! position. As the player moves, `ChunkStreamer::update()` archives the entities of chunks that
+//! are too far away and brings back the ones that come into range. Only `#[persist]` components
+//! survive being unloaded, see `World::archive_entity()`.
+
+use std::collections::HashMap;
+
+use {SystemManager, World};
+use Entity;
+use EntityData;
+use MaybeSend;
+use Result;
+use scene::ArchivedEntity;
+
+/// Coordinates of a chunk.
+pub type Chunk = (i32, i32);
+
+/// Returns the chunk containing a point, for chunks `size` units wide.
+pub fn chunk_at(x: f32, y: f32, size: f32) -> Chunk
+{
+    ((x / size).floor() as i32, (y / size).floor() as i32)
+}
+
+/// Something that happened to a chunk during `ChunkStreamer::update()`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ChunkEvent
+{
+    /// The chunk's entities were brought back into the world.
+    Loaded(Chunk, Vec<Entity>),
+    /// The chunk's entities were archived and removed from the world.
+    Unloaded(Chunk),
+}
+
+/// Called after a chunk is loaded or unloaded, eg: to let systems build or drop per-chunk state.
+type ChunkCallback<S> = boxed!(FnMut(&mut World<S>, &ChunkEvent));
+
+/// Archives and restores the entities of chunks as they go out of and come into range.
+pub struct ChunkStreamer<S: SystemManager>
+{
+    chunk_of: boxed!(Fn(&EntityData<S::Components>, &S::Components) -> Option<Chunk> + 'static),
+    unloaded: HashMap<Chunk, Vec<ArchivedEntity>>,
+    callbacks: Vec<ChunkCallback<S>>,
+}
+
+impl<S: SystemManager> ChunkStreamer<S>
+{
+    /// Creates a streamer that places entities with `chunk_of`. Entities it returns `None` for,
+    /// such as the player, are never unloaded.
+    pub fn new<F>(chunk_of: F) -> ChunkStreamer<S>
+        where F: Fn(&EntityData<S::Components>, &S::Components) -> Option<Chunk> + MaybeSend + 'static
+    {
+        ChunkStreamer
+        {
+            chunk_of: Box::new(chunk_of),
+            unloaded: HashMap::new(),
+            callbacks: Vec::new(),
+        }
+    }
+
+    /// Adds a callback for every chunk that is loaded or unloaded.
+    pub fn on_event<F>(&mut self, callback: F)
+        where F: FnMut(&mut World<S>, &ChunkEvent) + MaybeSend + 'static
+    {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    /// Returns whether a chunk's entities are archived.
+    pub fn is_unloaded(&self, chunk: Chunk) -> bool
+    {
+        self.unloaded.contains_key(&chunk)
+    }
+
+    /// Chunks whose entities are archived.
+    pub fn unloaded_chunks(&self) -> Vec<Chunk>
+    {
+        let mut chunks: Vec<_> = self.unloaded.keys().cloned().collect();
+        chunks.sort();
+        chunks
+    }
+
+    /// Archives the entities in a chunk. Returns how many there were.
+    pub fn unload(&mut self, world: &mut World<S>, chunk: Chunk) -> Result<usize>
+    {
+        let entities = self.chunk_entities(world).remove(&chunk).unwrap_or_default();
+        let count = self.archive(world, chunk, entities)?;
+        self.notify(world, ChunkEvent::Unloaded(chunk));
+        Ok(count)
+    }
+
+    /// Brings back the entities of an unloaded chunk. Does nothing if it isn't unloaded.
+    pub fn load(&mut self, world: &mut World<S>, chunk: Chunk) -> Result<Vec<Entity>>
+    {
+        if !self.is_unloaded(chunk)
+        {
+            return Ok(Vec::new());
+        }
+        let entities = self.load_quietly(world, chunk)?;
+        self.notify(world, ChunkEvent::Loaded(chunk, entities.clone()));
+        Ok(entities)
+    }
+
+    /// Loads the unloaded chunks within `radius` chunks of `center`, and unloads the chunks
+    /// outside of it that have entities. Returns what happened, in order.
+    pub fn update(&mut self, world: &mut World<S>, center: Chunk, radius: i32) -> Result<Vec<ChunkEvent>>
+    {
+        let in_range = |chunk: &Chunk| (chunk.0 - center.0).abs() <= radius && (chunk.1 - center.1).abs() <= radius;
+        let mut events = Vec::new();
+
+        let mut far: Vec<_> = self.chunk_entities(world).into_iter().filter(|&(chunk, _)| !in_range(&chunk)).collect();
+        far.sort_by_key(|&(chunk, _)| chunk);
+        for (chunk, entities) in far
+        {
+            self.archive(world, chunk, entities)?;
+            events.push(ChunkEvent::Unloaded(chunk));
+        }
+
+        let mut near: Vec<_> = self.unloaded.keys().cloned().filter(|chunk| in_range(chunk)).collect();
+        near.sort();
+        for chunk in near
+        {
+            let entities = self.load_quietly(world, chunk)?;
+            events.push(ChunkEvent::Loaded(chunk, entities));
+        }
+
+        for event in &events
+        {
+            self.notify(world, event.clone());
+        }
+        Ok(events)
+    }
+
+    /// The active entities of each chunk, sorted by id.
+    fn chunk_entities(&self, world: &World<S>) -> HashMap<Chunk, Vec<Entity>>
+    {
+        let mut chunks: HashMap<Chunk, Vec<Entity>> = HashMap::new();
+        for entity in world.entities()
+        {
+            if let Some(chunk) = (self.chunk_of)(&entity, &world.data.components)
+            {
+                chunks.entry(chunk).or_default().push(**entity);
+            }
+        }
+        for entities in chunks.values_mut()
+        {
+            entities.sort_by_key(|e| e.id());
+        }
+        chunks
+    }
+
+    fn archive(&mut self, world: &mut World<S>, chunk: Chunk, entities: Vec<Entity>) -> Result<usize>
+    {
+        let count = entities.len();
+        // Archived entities are kept as they're removed, so a failure part way loses nothing
+        let archived = self.unloaded.entry(chunk).or_default();
+        let result = entities.into_iter().try_for_each(|entity| {
+            archived.push(world.archive_entity(entity)?);
+            Ok(())
+        });
+        world.flush();
+        result.map(|_| count)
+    }
+
+    /// Unarchives a chunk's entities without calling the callbacks.
+    fn load_quietly(&mut self, world: &mut World<S>, chunk: Chunk) -> Result<Vec<Entity>>
+    {
+        let archived = self.unloaded.remove(&chunk).unwrap_or_default();
+        let mut entities = Vec::with_capacity(archived.len());
+        for entity in &archived
+        {
+            entities.push(world.unarchive(entity)?);
+        }
+        world.flush();
+        Ok(entities)
+    }
+
+    fn notify(&mut self, world: &mut World<S>, event: ChunkEvent)
+    {
+        for callback in &mut self.callbacks
+        {
+            callback(world, &event);
+        }
+    }
+}
+
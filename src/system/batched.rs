@@ -0,0 +1,118 @@
+//! Entity systems that process their entities a chunk at a time.
+
+use std::ops::{Deref, DerefMut};
+
+use Aspect;
+use DataHelper;
+use EntityData;
+use EntityIter;
+use query::InterestTracker;
+use {System, Process};
+
+/// Processes entities in chunks, eg: to bind a physics context once per chunk, or to work on
+/// runs of neighbouring components with SIMD.
+pub trait BatchedEntityProcess: System
+{
+    /// Optional method called before each chunk is processed.
+    fn begin_chunk(&mut self, _: &mut DataHelper<Self::Components, Self::Services>)
+    {
+
+    }
+
+    fn process_chunk<'a>(&mut self, entities: EntityIter<'a, Self::Components>, data: &mut DataHelper<Self::Components, Self::Services>);
+
+    /// Optional method called after each chunk is processed.
+    fn end_chunk(&mut self, _: &mut DataHelper<Self::Components, Self::Services>)
+    {
+
+    }
+}
+
+/// Like `EntitySystem`, but hands its entities to the process at most `chunk_size` at a time.
+pub struct BatchedEntitySystem<T: BatchedEntityProcess>
+{
+    interest: InterestTracker<T::Components>,
+    chunk_size: usize,
+    pub inner: T,
+}
+
+impl<T: BatchedEntityProcess> BatchedEntitySystem<T>
+{
+    /// Panics if `chunk_size` is zero.
+    pub fn new(inner: T, aspect: Aspect<T::Components>, chunk_size: usize) -> BatchedEntitySystem<T>
+    {
+        assert!(chunk_size > 0, "chunk size must be at least 1");
+        BatchedEntitySystem
+        {
+            interest: InterestTracker::new(aspect),
+            chunk_size: chunk_size,
+            inner: inner,
+        }
+    }
+
+    /// The entities that will be processed next update, in index order.
+    pub fn interested(&self) -> EntityIter<'_, T::Components>
+    {
+        self.interest.iter()
+    }
+}
+
+impl<T: BatchedEntityProcess> Deref for BatchedEntitySystem<T>
+{
+    type Target = T;
+    fn deref(&self) -> &T
+    {
+        &self.inner
+    }
+}
+
+impl<T: BatchedEntityProcess> DerefMut for BatchedEntitySystem<T>
+{
+    fn deref_mut(&mut self) -> &mut T
+    {
+        &mut self.inner
+    }
+}
+
+impl<T: BatchedEntityProcess> System for BatchedEntitySystem<T>
+{
+    type Components = T::Components;
+    type Services = T::Services;
+    fn activated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
+    {
+        self.interest.on_activated(entity, world, &mut self.inner);
+    }
+
+    fn activated_batch(&mut self, entities: &[EntityData<T::Components>], world: &T::Components)
+    {
+        self.interest.on_activated_batch(entities, world, &mut self.inner);
+    }
+
+    fn reactivated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
+    {
+        self.interest.on_reactivated(entity, world, &mut self.inner);
+    }
+
+    fn deactivated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
+    {
+        self.interest.on_deactivated(entity, world, &mut self.inner);
+    }
+
+    fn is_active(&self) -> bool
+    {
+        self.inner.is_active()
+    }
+}
+
+impl<T: BatchedEntityProcess> Process for BatchedEntitySystem<T>
+{
+    fn process(&mut self, c: &mut DataHelper<T::Components, T::Services>)
+    {
+        for chunk in self.interest.chunks(self.chunk_size)
+        {
+            self.inner.begin_chunk(c);
+            self.inner.process_chunk(chunk, c);
+            self.inner.end_chunk(c);
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Removal of every entity matching an aspect, eg: entities marked dead.
+
+use std::collections::HashSet;
+use std::marker::PhantomData;
+
+use {ComponentManager, ServiceManager};
+use Aspect;
+use DataHelper;
+use Entity;
+use EntityData;
+use {Process, System};
+use query::CachedQuery;
+
+/// Removes every entity matching an aspect, eg: `aspect!(<MyComponents> all: [dead])`.
+///
+/// Processed last, the entities are removed by the flush at the end of the same update. With a
+/// delay, they're removed with `DataHelper::remove_entity_after()` instead, so systems can still
+/// see them as `despawning()` until then. Once an entity has been scheduled for removal it stays
+/// scheduled, even if it stops matching.
+pub struct CleanupSystem<C: ComponentManager, M: ServiceManager = ()>
+{
+    matching: CachedQuery<C>,
+    scheduled: HashSet<Entity>,
+    delay: u32,
+    _services: PhantomData<fn(M)>,
+}
+
+impl<C: ComponentManager, M: ServiceManager> CleanupSystem<C, M>
+{
+    pub fn new(aspect: Aspect<C>) -> CleanupSystem<C, M>
+    {
+        CleanupSystem::with_delay(aspect, 0)
+    }
+
+    /// Removes matching entities `ticks` updates after they're first seen.
+    pub fn with_delay(aspect: Aspect<C>, ticks: u32) -> CleanupSystem<C, M>
+    {
+        CleanupSystem
+        {
+            matching: CachedQuery::new(aspect),
+            scheduled: HashSet::new(),
+            delay: ticks,
+            _services: PhantomData,
+        }
+    }
+
+    /// Number of matching entities, including ones already scheduled for removal.
+    pub fn len(&self) -> usize
+    {
+        self.matching.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.matching.is_empty()
+    }
+
+    /// Returns whether the entity has been scheduled for removal.
+    pub fn is_scheduled(&self, entity: &Entity) -> bool
+    {
+        self.scheduled.contains(entity)
+    }
+}
+
+impl<C: ComponentManager, M: ServiceManager> System for CleanupSystem<C, M>
+{
+    type Components = C;
+    type Services = M;
+    fn activated(&mut self, entity: &EntityData<C>, components: &C)
+    {
+        self.matching.activated(entity, components);
+    }
+
+    fn activated_batch(&mut self, entities: &[EntityData<C>], components: &C)
+    {
+        self.matching.activated_batch(entities, components);
+    }
+
+    fn reactivated(&mut self, entity: &EntityData<C>, components: &C)
+    {
+        self.matching.reactivated(entity, components);
+    }
+
+    fn deactivated(&mut self, entity: &EntityData<C>, _: &C)
+    {
+        self.matching.deactivated(entity);
+        self.scheduled.remove(entity);
+    }
+}
+
+impl<C: ComponentManager, M: ServiceManager> Process for CleanupSystem<C, M>
+{
+    fn process(&mut self, data: &mut DataHelper<C, M>)
+    {
+        for entity in self.matching.iter()
+        {
+            if !self.scheduled.insert(**entity)
+            {
+                continue;
+            }
+            if self.delay == 0
+            {
+                data.remove_entity(**entity);
+            }
+            else
+            {
+                data.remove_entity_after(**entity, self.delay);
+            }
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! Systems that keep some state for each of their entities.
+
+use std::slice;
+
+use Aspect;
+use ComponentManager;
+use DataHelper;
+use IndexedEntity;
+use EntityData;
+use {System, Process};
+
+pub trait DataProcess: System
+{
+    /// State kept for each entity, eg: a path or cached lookup.
+    type Data;
+
+    /// Creates the state for an entity when it starts matching the system's aspect.
+    fn create(&mut self, entity: &EntityData<Self::Components>, components: &Self::Components) -> Self::Data;
+
+    fn process<'a>(&mut self, entities: DataIter<'a, Self::Components, Self::Data>, data: &mut DataHelper<Self::Components, Self::Services>);
+}
+
+/// Like `EntitySystem`, but each entity is given to the process along with its state.
+///
+/// The state is dropped when the entity is deactivated or stops matching the aspect.
+pub struct DataSystem<T: DataProcess>
+{
+    /// Matched entities and their state, sorted by index.
+    entries: Vec<(IndexedEntity<T::Components>, T::Data)>,
+    aspect: Aspect<T::Components>,
+    pub inner: T,
+}
+
+impl<T: DataProcess> DataSystem<T>
+{
+    pub fn new(inner: T, aspect: Aspect<T::Components>) -> DataSystem<T>
+    {
+        DataSystem
+        {
+            entries: Vec::new(),
+            aspect: aspect,
+            inner: inner,
+        }
+    }
+
+    /// Returns the state kept for an entity.
+    pub fn get(&self, entity: &IndexedEntity<T::Components>) -> Option<&T::Data>
+    {
+        self.position(entity).map(|i| &self.entries[i].1)
+    }
+
+    pub fn get_mut(&mut self, entity: &IndexedEntity<T::Components>) -> Option<&mut T::Data>
+    {
+        self.position(entity).map(move |i| &mut self.entries[i].1)
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.entries.is_empty()
+    }
+
+    fn position(&self, entity: &IndexedEntity<T::Components>) -> Option<usize>
+    {
+        match self.entries.binary_search_by_key(&entity.index(), |entry| entry.0.index())
+        {
+            Ok(i) if *self.entries[i].0 == **entity => Some(i),
+            _ => None,
+        }
+    }
+
+    fn insert(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
+    {
+        let data = self.inner.create(entity, world);
+        let entry = (unsafe { (**entity).clone() }, data);
+        match self.entries.binary_search_by_key(&entity.index(), |entry| entry.0.index())
+        {
+            Ok(i) => self.entries[i] = entry,
+            Err(i) => self.entries.insert(i, entry),
+        }
+    }
+}
+
+impl<T: DataProcess> System for DataSystem<T>
+{
+    type Components = T::Components;
+    type Services = T::Services;
+    fn activated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
+    {
+        if self.aspect.check(entity, world)
+        {
+            self.insert(entity, world);
+            self.inner.activated(entity, world);
+        }
+    }
+
+    fn reactivated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
+    {
+        match (self.position(entity), self.aspect.check(entity, world))
+        {
+            (Some(_), true) => self.inner.reactivated(entity, world),
+            (Some(i), false) => {
+                self.entries.remove(i);
+                self.inner.deactivated(entity, world);
+            },
+            (None, true) => {
+                self.insert(entity, world);
+                self.inner.activated(entity, world);
+            },
+            (None, false) => {},
+        }
+    }
+
+    fn deactivated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
+    {
+        if let Some(i) = self.position(entity)
+        {
+            self.entries.remove(i);
+            self.inner.deactivated(entity, world);
+        }
+    }
+
+    fn is_active(&self) -> bool
+    {
+        self.inner.is_active()
+    }
+}
+
+impl<T: DataProcess> Process for DataSystem<T>
+{
+    fn process(&mut self, c: &mut DataHelper<T::Components, T::Services>)
+    {
+        self.inner.process(DataIter { inner: self.entries.iter_mut() }, c);
+    }
+}
+
+/// Iterator over the entities of a `DataSystem` and their state, in index order.
+pub struct DataIter<'a, C: ComponentManager + 'a, D: 'a>
+{
+    inner: slice::IterMut<'a, (IndexedEntity<C>, D)>,
+}
+
+impl<'a, C: ComponentManager, D> Iterator for DataIter<'a, C, D>
+{
+    type Item = (EntityData<'a, C>, &'a mut D);
+    fn next(&mut self) -> Option<(EntityData<'a, C>, &'a mut D)>
+    {
+        self.inner.next().map(|&mut (ref e, ref mut d)| (EntityData(e), d))
+    }
+}
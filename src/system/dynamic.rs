@@ -0,0 +1,173 @@
+
+//! Systems chosen at runtime rather than listed in `systems!`.
+
+use {ComponentManager, ServiceManager, SystemManager};
+use DataHelper;
+use EntityData;
+use {Process, System};
+use system::failures::process_isolated;
+
+/// A boxed system, as loaded from a plugin or picked at runtime.
+pub type BoxedProcess<C, M> = boxed!(Process<Components = C, Services = M>);
+
+/// An ordered list of boxed systems.
+///
+/// It can be used as the system manager of a `World` (see `WorldBuilder`), or as a field in
+/// `systems!` to extend a static set of systems.
+pub struct DynamicSystems<C: ComponentManager, M: ServiceManager>
+{
+    systems: Vec<(Option<String>, BoxedProcess<C, M>)>,
+}
+
+impl<C: ComponentManager, M: ServiceManager> DynamicSystems<C, M>
+{
+    pub fn new() -> DynamicSystems<C, M>
+    {
+        DynamicSystems
+        {
+            systems: Vec::new(),
+        }
+    }
+
+    /// Adds a system to be processed after all the existing ones.
+    ///
+    /// The system is only told about entities activated after it was added.
+    pub fn push(&mut self, system: BoxedProcess<C, M>)
+    {
+        self.systems.push((None, system));
+    }
+
+    /// Adds a named system to be processed after all the existing ones.
+    ///
+    /// If there is already a system with the same name, it is replaced in place and returned.
+    pub fn insert(&mut self, name: &str, system: BoxedProcess<C, M>) -> Option<BoxedProcess<C, M>>
+    {
+        match self.position(name)
+        {
+            Some(i) => Some(::std::mem::replace(&mut self.systems[i].1, system)),
+            None => {
+                self.systems.push((Some(name.to_string()), system));
+                None
+            }
+        }
+    }
+
+    /// Removes the named system, returning it.
+    pub fn remove(&mut self, name: &str) -> Option<BoxedProcess<C, M>>
+    {
+        self.position(name).map(|i| self.systems.remove(i).1)
+    }
+
+    pub fn contains(&self, name: &str) -> bool
+    {
+        self.position(name).is_some()
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.systems.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.systems.is_empty()
+    }
+
+    fn position(&self, name: &str) -> Option<usize>
+    {
+        self.systems.iter().position(|(n, _)| n.as_ref().map(|n| &n[..]) == Some(name))
+    }
+}
+
+impl<C: ComponentManager, M: ServiceManager> System for DynamicSystems<C, M>
+{
+    type Components = C;
+    type Services = M;
+    fn activated(&mut self, e: &EntityData<C>, c: &C)
+    {
+        for &mut (_, ref mut sys) in self.systems.iter_mut()
+        {
+            sys.activated(e, c);
+        }
+    }
+
+    fn activated_batch(&mut self, entities: &[EntityData<C>], c: &C)
+    {
+        for &mut (_, ref mut sys) in self.systems.iter_mut()
+        {
+            sys.activated_batch(entities, c);
+        }
+    }
+
+    fn reactivated(&mut self, e: &EntityData<C>, c: &C)
+    {
+        for &mut (_, ref mut sys) in self.systems.iter_mut()
+        {
+            sys.reactivated(e, c);
+        }
+    }
+
+    fn deactivated(&mut self, e: &EntityData<C>, c: &C)
+    {
+        for &mut (_, ref mut sys) in self.systems.iter_mut()
+        {
+            sys.deactivated(e, c);
+        }
+    }
+}
+
+impl<C: ComponentManager, M: ServiceManager> Process for DynamicSystems<C, M>
+{
+    /// Processes every active system, in the order they were added.
+    fn process(&mut self, c: &mut DataHelper<C, M>)
+    {
+        for (i, &mut (ref name, ref mut sys)) in self.systems.iter_mut().enumerate()
+        {
+            match *name
+            {
+                Some(ref name) => process_isolated(sys, name, c),
+                None => process_isolated(sys, &format!("#{}", i), c),
+            }
+        }
+    }
+}
+
+unsafe impl<C: ComponentManager, M: ServiceManager> SystemManager for DynamicSystems<C, M>
+{
+    type Components = C;
+    type Services = M;
+    unsafe fn new() -> DynamicSystems<C, M>
+    {
+        DynamicSystems::new()
+    }
+
+    unsafe fn activated(&mut self, en: EntityData<C>, co: &C)
+    {
+        System::activated(self, &en, co);
+    }
+
+    unsafe fn activated_batch(&mut self, en: &[EntityData<C>], co: &C)
+    {
+        System::activated_batch(self, en, co);
+    }
+
+    unsafe fn reactivated(&mut self, en: EntityData<C>, co: &C)
+    {
+        System::reactivated(self, &en, co);
+    }
+
+    unsafe fn deactivated(&mut self, en: EntityData<C>, co: &C)
+    {
+        System::deactivated(self, &en, co);
+    }
+
+    unsafe fn update(&mut self, co: &mut DataHelper<C, M>)
+    {
+        Process::process(self, co);
+    }
+
+    fn dynamic_systems(&mut self) -> &mut DynamicSystems<C, M>
+    {
+        self
+    }
+}
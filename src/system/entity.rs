@@ -1,25 +1,31 @@
 
 //! Systems to specifically deal with entities.
 
-use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
 
 use Aspect;
 use DataHelper;
-use {Entity, IndexedEntity};
 use EntityData;
 use EntityIter;
+use query::InterestTracker;
 use {System, Process};
 
 pub trait EntityProcess: System
 {
-    fn process<'a>(&mut self, EntityIter<'a, Self::Components>, &mut DataHelper<Self::Components, Self::Services>);
+    fn process<'a>(&mut self, entities: EntityIter<'a, Self::Components>, data: &mut DataHelper<Self::Components, Self::Services>);
+}
+
+impl<T: EntityProcess + ?Sized> EntityProcess for Box<T>
+{
+    fn process<'a>(&mut self, en: EntityIter<'a, T::Components>, c: &mut DataHelper<T::Components, T::Services>)
+    {
+        (**self).process(en, c);
+    }
 }
 
 pub struct EntitySystem<T: EntityProcess>
 {
-    interested: HashMap<Entity, IndexedEntity<T::Components>>,
-    aspect: Aspect<T::Components>,
+    interest: InterestTracker<T::Components>,
     pub inner: T,
 }
 
@@ -29,11 +35,26 @@ impl<T: EntityProcess> EntitySystem<T>
     {
         EntitySystem
         {
-            interested: HashMap::new(),
-            aspect: aspect,
+            interest: InterestTracker::new(aspect),
             inner: inner,
         }
     }
+
+    /// The entities that will be processed next update, in index order.
+    pub fn interested(&self) -> EntityIter<'_, T::Components>
+    {
+        self.interest.iter()
+    }
+
+    /// Changes which entities the system is interested in, eg: to widen an activation radius when
+    /// a setting changes.
+    ///
+    /// Every entity of `data` is checked again straight away, and the inner system is told about
+    /// the ones that start or stop matching.
+    pub fn set_aspect(&mut self, aspect: Aspect<T::Components>, data: &DataHelper<T::Components, T::Services>)
+    {
+        self.interest.set_aspect(aspect, data, &mut self.inner);
+    }
 }
 
 impl<T: EntityProcess> Deref for EntitySystem<T>
@@ -59,40 +80,22 @@ impl<T: EntityProcess> System for EntitySystem<T>
     type Services = T::Services;
     fn activated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
     {
-        if self.aspect.check(entity, world)
-        {
-            self.interested.insert(***entity, unsafe { (**entity).clone() });
-            self.inner.activated(entity, world);
-        }
+        self.interest.on_activated(entity, world, &mut self.inner);
+    }
+
+    fn activated_batch(&mut self, entities: &[EntityData<T::Components>], world: &T::Components)
+    {
+        self.interest.on_activated_batch(entities, world, &mut self.inner);
     }
 
     fn reactivated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
     {
-        if self.interested.contains_key(entity)
-        {
-            if self.aspect.check(entity, world)
-            {
-                self.inner.reactivated(entity, world);
-            }
-            else
-            {
-                self.interested.remove(entity);
-                self.inner.deactivated(entity, world);
-            }
-        }
-        else if self.aspect.check(entity, world)
-        {
-            self.interested.insert(***entity, unsafe { (**entity).clone() });
-            self.inner.activated(entity, world);
-        }
+        self.interest.on_reactivated(entity, world, &mut self.inner);
     }
 
     fn deactivated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
     {
-        if self.interested.remove(entity).is_some()
-        {
-            self.inner.deactivated(entity, world);
-        }
+        self.interest.on_deactivated(entity, world, &mut self.inner);
     }
 
     fn is_active(&self) -> bool
@@ -105,6 +108,85 @@ impl<T: EntityProcess> Process for EntitySystem<T>
 {
     fn process(&mut self, c: &mut DataHelper<T::Components, T::Services>)
     {
-        self.inner.process(EntityIter::Map(self.interested.values()), c);
+        self.inner.process(self.interest.iter(), c);
+    }
+}
+
+/// An `EntitySystem` that isn't processed by `world.update()`, only when called manually, eg: with
+/// `process!`.
+pub struct PassiveEntitySystem<T: EntityProcess>
+{
+    system: EntitySystem<T>,
+}
+
+impl<T: EntityProcess> PassiveEntitySystem<T>
+{
+    pub fn new(inner: T, aspect: Aspect<T::Components>) -> PassiveEntitySystem<T>
+    {
+        PassiveEntitySystem
+        {
+            system: EntitySystem::new(inner, aspect),
+        }
+    }
+
+    /// The entities that will be processed when it's next called, in index order.
+    pub fn interested(&self) -> EntityIter<'_, T::Components>
+    {
+        self.system.interested()
+    }
+}
+
+impl<T: EntityProcess> Deref for PassiveEntitySystem<T>
+{
+    type Target = T;
+    fn deref(&self) -> &T
+    {
+        &self.system.inner
+    }
+}
+
+impl<T: EntityProcess> DerefMut for PassiveEntitySystem<T>
+{
+    fn deref_mut(&mut self) -> &mut T
+    {
+        &mut self.system.inner
+    }
+}
+
+impl<T: EntityProcess> System for PassiveEntitySystem<T>
+{
+    type Components = T::Components;
+    type Services = T::Services;
+    fn activated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
+    {
+        self.system.activated(entity, world);
+    }
+
+    fn activated_batch(&mut self, entities: &[EntityData<T::Components>], world: &T::Components)
+    {
+        self.system.activated_batch(entities, world);
+    }
+
+    fn reactivated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
+    {
+        self.system.reactivated(entity, world);
+    }
+
+    fn deactivated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
+    {
+        self.system.deactivated(entity, world);
+    }
+
+    fn is_active(&self) -> bool
+    {
+        false
+    }
+}
+
+impl<T: EntityProcess> Process for PassiveEntitySystem<T>
+{
+    fn process(&mut self, c: &mut DataHelper<T::Components, T::Services>)
+    {
+        self.system.process(c);
     }
 }
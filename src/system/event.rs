@@ -0,0 +1,249 @@
+//! Systems that are woken up by events, eg: a sound system that only runs when something asks for
+//! a sound to be played, or a health system that only looks at entities that took damage.
+
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+
+use ServiceManager;
+use Aspect;
+use DataHelper;
+use {Entity, IndexedEntity};
+use EntityData;
+use {Process, System};
+use manager::QueueManager;
+
+/// Gives an `EventSystem` access to the services' queue of events of type `E`.
+///
+/// eg: for a `sounds: QueueManager<Sound>` service,
+/// `fn events(&mut self) -> &mut QueueManager<Sound> { &mut self.sounds }`.
+pub trait EventServices<E>: ServiceManager
+{
+    fn events(&mut self) -> &mut QueueManager<E>;
+}
+
+/// Handles events taken from a queue by an `EventSystem`.
+pub trait EventProcess: System
+{
+    type Event;
+    fn process(&mut self, events: &[Self::Event], data: &mut DataHelper<Self::Components, Self::Services>);
+}
+
+/// Takes every event from a queue once per update and hands them to the process, in the order
+/// they would be popped. The process isn't called when the queue is empty.
+///
+/// Events pushed while the process runs, including by the process itself, wait for the next
+/// update.
+pub struct EventSystem<T: EventProcess>
+{
+    pub inner: T,
+}
+
+impl<T: EventProcess> EventSystem<T>
+{
+    pub fn new(inner: T) -> EventSystem<T>
+    {
+        EventSystem
+        {
+            inner: inner,
+        }
+    }
+}
+
+impl<T: EventProcess> Deref for EventSystem<T>
+{
+    type Target = T;
+    fn deref(&self) -> &T
+    {
+        &self.inner
+    }
+}
+
+impl<T: EventProcess> DerefMut for EventSystem<T>
+{
+    fn deref_mut(&mut self) -> &mut T
+    {
+        &mut self.inner
+    }
+}
+
+impl<T: EventProcess> System for EventSystem<T>
+{
+    type Components = T::Components;
+    type Services = T::Services;
+    fn activated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
+    {
+        self.inner.activated(entity, world);
+    }
+
+    fn activated_batch(&mut self, entities: &[EntityData<T::Components>], world: &T::Components)
+    {
+        self.inner.activated_batch(entities, world);
+    }
+
+    fn reactivated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
+    {
+        self.inner.reactivated(entity, world);
+    }
+
+    fn deactivated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
+    {
+        self.inner.deactivated(entity, world);
+    }
+
+    fn is_active(&self) -> bool
+    {
+        self.inner.is_active()
+    }
+}
+
+impl<T: EventProcess> Process for EventSystem<T> where T::Services: EventServices<T::Event>
+{
+    fn process(&mut self, data: &mut DataHelper<T::Components, T::Services>)
+    {
+        let queue = data.services.events();
+        if queue.is_empty()
+        {
+            return;
+        }
+        let events: Vec<T::Event> = queue.drain().collect();
+        self.inner.process(&events, data);
+    }
+}
+
+/// An event addressed to an entity, eg: `DamageEvent { target, amount }`.
+pub trait TargetedEvent
+{
+    fn target(&self) -> Entity;
+}
+
+/// Handles the events addressed to one entity at a time, for an `EntityEventSystem`.
+pub trait EntityEventProcess: System
+{
+    type Event: TargetedEvent;
+    fn process(&mut self, entity: &EntityData<Self::Components>, events: &[Self::Event], data: &mut DataHelper<Self::Components, Self::Services>);
+}
+
+/// Like `EventSystem`, but groups the events by their target and hands the process each target
+/// along with its events, in the order each target was first addressed.
+///
+/// Only entities matching the aspect receive events. Events addressed to any other entity,
+/// including ones that have been removed, are discarded.
+pub struct EntityEventSystem<T: EntityEventProcess>
+{
+    aspect: Aspect<T::Components>,
+    interested: HashMap<Entity, IndexedEntity<T::Components>>,
+    pub inner: T,
+}
+
+impl<T: EntityEventProcess> EntityEventSystem<T>
+{
+    pub fn new(inner: T, aspect: Aspect<T::Components>) -> EntityEventSystem<T>
+    {
+        EntityEventSystem
+        {
+            aspect: aspect,
+            interested: HashMap::new(),
+            inner: inner,
+        }
+    }
+}
+
+impl<T: EntityEventProcess> Deref for EntityEventSystem<T>
+{
+    type Target = T;
+    fn deref(&self) -> &T
+    {
+        &self.inner
+    }
+}
+
+impl<T: EntityEventProcess> DerefMut for EntityEventSystem<T>
+{
+    fn deref_mut(&mut self) -> &mut T
+    {
+        &mut self.inner
+    }
+}
+
+impl<T: EntityEventProcess> System for EntityEventSystem<T>
+{
+    type Components = T::Components;
+    type Services = T::Services;
+    fn activated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
+    {
+        if self.aspect.check(entity, world)
+        {
+            self.interested.insert(***entity, unsafe { (**entity).clone() });
+            self.inner.activated(entity, world);
+        }
+    }
+
+    fn reactivated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
+    {
+        let was = self.interested.contains_key(entity);
+        let is = self.aspect.check(entity, world);
+        if is
+        {
+            self.interested.insert(***entity, unsafe { (**entity).clone() });
+        }
+        else
+        {
+            self.interested.remove(entity);
+        }
+        match (was, is)
+        {
+            (true, true) => self.inner.reactivated(entity, world),
+            (true, false) => self.inner.deactivated(entity, world),
+            (false, true) => self.inner.activated(entity, world),
+            (false, false) => {},
+        }
+    }
+
+    fn deactivated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
+    {
+        if self.interested.remove(entity).is_some()
+        {
+            self.inner.deactivated(entity, world);
+        }
+    }
+
+    fn is_active(&self) -> bool
+    {
+        self.inner.is_active()
+    }
+}
+
+impl<T: EntityEventProcess> Process for EntityEventSystem<T> where T::Services: EventServices<T::Event>
+{
+    fn process(&mut self, data: &mut DataHelper<T::Components, T::Services>)
+    {
+        let queue = data.services.events();
+        if queue.is_empty()
+        {
+            return;
+        }
+        let mut targets: Vec<(Entity, Vec<T::Event>)> = Vec::new();
+        let mut positions = HashMap::new();
+        for event in queue.drain()
+        {
+            let target = event.target();
+            if !self.interested.contains_key(&target)
+            {
+                continue;
+            }
+            let pos = *positions.entry(target).or_insert_with(|| {
+                targets.push((target, Vec::new()));
+                targets.len() - 1
+            });
+            targets[pos].1.push(event);
+        }
+        for (target, events) in targets
+        {
+            // An earlier target's events may have removed it, but removals wait for the next flush.
+            if let Some(entity) = self.interested.get(&target)
+            {
+                self.inner.process(&EntityData(entity), &events, data);
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Keeping the world running when a system panics.
+
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Instant;
+
+use {ComponentManager, ServiceManager};
+use DataHelper;
+use Process;
+use world::SystemTime;
+
+/// A system that panicked while being processed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SystemFailure
+{
+    /// Field name of the system, or name of the dynamic system.
+    pub system: String,
+    /// The panic message.
+    pub message: String,
+}
+
+/// Systems that have panicked since panic isolation was enabled.
+///
+/// Failed systems are skipped until they are reset.
+#[derive(Clone, Debug, Default)]
+pub struct SystemFailures
+{
+    failures: Vec<SystemFailure>,
+}
+
+impl SystemFailures
+{
+    pub fn new() -> SystemFailures
+    {
+        SystemFailures
+        {
+            failures: Vec::new(),
+        }
+    }
+
+    /// Every recorded failure, oldest first.
+    pub fn failures(&self) -> &[SystemFailure]
+    {
+        &self.failures
+    }
+
+    pub fn has_failed(&self, system: &str) -> bool
+    {
+        self.failures.iter().any(|f| f.system == system)
+    }
+
+    /// Forgets a system's failure so that it runs again, returning whether it had failed.
+    pub fn reset(&mut self, system: &str) -> bool
+    {
+        let len = self.failures.len();
+        self.failures.retain(|f| f.system != system);
+        self.failures.len() != len
+    }
+
+    pub fn clear(&mut self)
+    {
+        self.failures.clear();
+    }
+}
+
+/// Processes a system, catching and recording any panic if isolation is enabled, and timing it if
+/// profiling is.
+#[doc(hidden)]
+pub fn process_isolated<P, C, M>(system: &mut P, name: &str, data: &mut DataHelper<C, M>)
+    where P: Process<Components = C, Services = M> + ?Sized, C: ComponentManager, M: ServiceManager
+{
+    if !system.is_active()
+    {
+        return;
+    }
+    trace_span!("ecs::process", system = name);
+    if data.system_times().is_none()
+    {
+        return process_guarded(system, name, data);
+    }
+    let start = Instant::now();
+    process_guarded(system, name, data);
+    let duration = start.elapsed();
+    if let Some(times) = data.system_times_mut()
+    {
+        times.push(SystemTime
+        {
+            system: name.to_string(),
+            duration: duration,
+        });
+    }
+}
+
+fn process_guarded<P, C, M>(system: &mut P, name: &str, data: &mut DataHelper<C, M>)
+    where P: Process<Components = C, Services = M> + ?Sized, C: ComponentManager, M: ServiceManager
+{
+    match data.system_failures()
+    {
+        None => return system.process(data),
+        Some(failures) if failures.has_failed(name) => return,
+        Some(_) => {},
+    }
+    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| system.process(data)))
+    {
+        trace_event!(system = name, "ecs: system panicked");
+        if let Some(failures) = data.system_failures_mut()
+        {
+            failures.failures.push(SystemFailure
+            {
+                system: name.to_string(),
+                message: panic_message(&*payload),
+            });
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String
+{
+    match payload.downcast_ref::<&str>()
+    {
+        Some(message) => message.to_string(),
+        None => match payload.downcast_ref::<String>()
+        {
+            Some(message) => message.clone(),
+            None => "unknown panic".to_string(),
+        },
+    }
+}
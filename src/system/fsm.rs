@@ -0,0 +1,186 @@
+//! Per-entity finite state machines, eg: enemies that patrol, chase and attack.
+
+use std::mem;
+use std::ops::{Deref, DerefMut};
+
+use {ComponentList, ComponentManager};
+use Aspect;
+use DataHelper;
+use EntityData;
+use {Process, System};
+use query::InterestTracker;
+
+/// A component holding an entity's state, and the state it's been told to switch to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateMachine<S>
+{
+    state: S,
+    next: Option<S>,
+    entered: bool,
+}
+
+impl<S: PartialEq> StateMachine<S>
+{
+    pub fn new(initial: S) -> StateMachine<S>
+    {
+        StateMachine
+        {
+            state: initial,
+            next: None,
+            entered: false,
+        }
+    }
+
+    pub fn state(&self) -> &S
+    {
+        &self.state
+    }
+
+    /// Switches to `state` the next time the entity is processed by an `FsmSystem`. Switching to
+    /// the current state does nothing, and a later call replaces an earlier one.
+    pub fn transition(&mut self, state: S)
+    {
+        self.next = Some(state);
+    }
+
+    /// The state that will be switched to, if there is one.
+    pub fn pending(&self) -> Option<&S>
+    {
+        self.next.as_ref()
+    }
+}
+
+/// Gives an `FsmSystem` access to the field of the components holding state machines.
+///
+/// eg: for a `#[hot] ai: StateMachine<Ai>` field,
+/// `fn state_machines(&self) -> &ComponentList<Self, StateMachine<Ai>> { &self.ai }`.
+pub trait FsmComponents<S: 'static>: ComponentManager
+{
+    fn state_machines(&self) -> &ComponentList<Self, StateMachine<S>>;
+    fn state_machines_mut(&mut self) -> &mut ComponentList<Self, StateMachine<S>>;
+}
+
+/// The behaviour of each state, called by an `FsmSystem` for every entity with a state machine.
+pub trait FsmProcess: System
+{
+    type State: PartialEq + Clone + 'static;
+
+    /// Optional method called when an entity enters a state, including its first one.
+    fn enter(&mut self, _: &Self::State, _: &EntityData<Self::Components>, _: &mut DataHelper<Self::Components, Self::Services>)
+    {
+
+    }
+
+    /// Optional method called when an entity leaves a state for another.
+    fn exit(&mut self, _: &Self::State, _: &EntityData<Self::Components>, _: &mut DataHelper<Self::Components, Self::Services>)
+    {
+
+    }
+
+    /// Called once per update for each entity, after any transition.
+    fn update(&mut self, state: &Self::State, entity: &EntityData<Self::Components>, data: &mut DataHelper<Self::Components, Self::Services>);
+}
+
+/// Processes every entity with a `StateMachine`, calling `exit()` and `enter()` when its state
+/// changes and then `update()`, in index order.
+///
+/// Entities are only seen when processed, so an entity removed in the middle of a state doesn't
+/// get an `exit()`.
+pub struct FsmSystem<T: FsmProcess>
+{
+    interest: InterestTracker<T::Components>,
+    pub inner: T,
+}
+
+impl<T: FsmProcess> FsmSystem<T> where T::Components: FsmComponents<T::State>
+{
+    pub fn new(inner: T) -> FsmSystem<T>
+    {
+        FsmSystem
+        {
+            interest: InterestTracker::new(Aspect::from_fn(|e, c: &T::Components| c.state_machines().has(e))),
+            inner: inner,
+        }
+    }
+}
+
+impl<T: FsmProcess> Deref for FsmSystem<T>
+{
+    type Target = T;
+    fn deref(&self) -> &T
+    {
+        &self.inner
+    }
+}
+
+impl<T: FsmProcess> DerefMut for FsmSystem<T>
+{
+    fn deref_mut(&mut self) -> &mut T
+    {
+        &mut self.inner
+    }
+}
+
+impl<T: FsmProcess> System for FsmSystem<T>
+{
+    type Components = T::Components;
+    type Services = T::Services;
+    fn activated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
+    {
+        self.interest.on_activated(entity, world, &mut self.inner);
+    }
+
+    fn activated_batch(&mut self, entities: &[EntityData<T::Components>], world: &T::Components)
+    {
+        self.interest.on_activated_batch(entities, world, &mut self.inner);
+    }
+
+    fn reactivated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
+    {
+        self.interest.on_reactivated(entity, world, &mut self.inner);
+    }
+
+    fn deactivated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
+    {
+        self.interest.on_deactivated(entity, world, &mut self.inner);
+    }
+
+    fn is_active(&self) -> bool
+    {
+        self.inner.is_active()
+    }
+}
+
+impl<T: FsmProcess> Process for FsmSystem<T> where T::Components: FsmComponents<T::State>
+{
+    fn process(&mut self, data: &mut DataHelper<T::Components, T::Services>)
+    {
+        for entity in self.interest.iter()
+        {
+            let (exited, entered, state) = match data.components.state_machines_mut().borrow(&entity)
+            {
+                Some(machine) => {
+                    let first = !mem::replace(&mut machine.entered, true);
+                    let exited = match machine.next.take()
+                    {
+                        Some(next) if next != machine.state => Some(mem::replace(&mut machine.state, next)),
+                        _ => None,
+                    };
+                    let entered = first || exited.is_some();
+                    // The state it started in was never entered, so it isn't exited either.
+                    (exited.filter(|_| !first), entered, machine.state.clone())
+                },
+                None => continue,
+            };
+            if let Some(exited) = exited
+            {
+                self.inner.exit(&exited, &entity, data);
+            }
+            if entered
+            {
+                self.inner.enter(&state, &entity, data);
+            }
+            self.inner.update(&state, &entity, data);
+        }
+    }
+}
@@ -1,27 +1,89 @@
 
 //! System to specifically deal with interactions between two types of entity.
 
-use std::collections::HashMap;
-
 use Aspect;
+use ComponentManager;
 use DataHelper;
-use {Entity, IndexedEntity};
 use EntityData;
 use EntityIter;
+use MaybeSend;
+use query::CachedQuery;
 use {Process, System};
 
 pub trait InteractProcess: System
 {
-    fn process<'a>(&self, EntityIter<'a, Self::Components>, EntityIter<'a, Self::Components>, &mut DataHelper<Self::Components, Self::Services>);
+    fn process<'a>(&self, a: EntityIter<'a, Self::Components>, b: EntityIter<'a, Self::Components>, data: &mut DataHelper<Self::Components, Self::Services>);
+
+    /// Process the pairs of entities left over by a broad phase.
+    ///
+    /// By default `process()` is called once for each pair.
+    fn process_pairs<'a>(&self, pairs: Vec<(EntityData<'a, Self::Components>, EntityData<'a, Self::Components>)>, c: &mut DataHelper<Self::Components, Self::Services>)
+    {
+        for (a, b) in pairs
+        {
+            self.process(EntityIter::List(vec![a].into_iter()), EntityIter::List(vec![b].into_iter()), c);
+        }
+    }
+
+    /// Optional method called when an entity joins or leaves either set, after `activated()`,
+    /// `reactivated()` or `deactivated()`.
+    ///
+    /// Those are called once per change, even for entities in both sets.
+    fn membership_changed(&mut self, _: &EntityData<Self::Components>, _old: Membership, _new: Membership, _: &Self::Components)
+    {
+
+    }
+}
+
+/// Which sets of an `InteractSystem` an entity is in.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Membership
+{
+    pub a: bool,
+    pub b: bool,
+}
+
+impl Membership
+{
+    pub fn any(&self) -> bool
+    {
+        self.a || self.b
+    }
+}
+
+/// Narrows down which entities of an `InteractSystem` may interact with each other.
+pub trait BroadPhase<C: ComponentManager>
+{
+    /// Returns the pairs of entities, one from each set, that may interact.
+    fn pairs<'a>(&mut self, a: EntityIter<'a, C>, b: EntityIter<'a, C>, components: &C) -> Vec<(EntityData<'a, C>, EntityData<'a, C>)>;
+}
+
+impl<C: ComponentManager, F> BroadPhase<C> for F where F: FnMut(&EntityData<C>, &EntityData<C>, &C) -> bool
+{
+    fn pairs<'a>(&mut self, a: EntityIter<'a, C>, b: EntityIter<'a, C>, c: &C) -> Vec<(EntityData<'a, C>, EntityData<'a, C>)>
+    {
+        let b: Vec<_> = b.collect();
+        let mut pairs = Vec::new();
+        for x in a
+        {
+            for y in b.iter()
+            {
+                if (*self)(&x, y, c)
+                {
+                    pairs.push((x, *y));
+                }
+            }
+        }
+        pairs
+    }
 }
 
 pub struct InteractSystem<T: InteractProcess>
 {
-    interested_a: HashMap<Entity, IndexedEntity<T::Components>>,
-    interested_b: HashMap<Entity, IndexedEntity<T::Components>>,
-    aspect_a: Aspect<T::Components>,
-    aspect_b: Aspect<T::Components>,
-    inner: T,
+    interested_a: CachedQuery<T::Components>,
+    interested_b: CachedQuery<T::Components>,
+    broadphase: Option<boxed!(BroadPhase<T::Components>)>,
+    pub inner: T,
 }
 
 impl<T: InteractProcess> InteractSystem<T>
@@ -30,13 +92,63 @@ impl<T: InteractProcess> InteractSystem<T>
     {
         InteractSystem
         {
-            interested_a: HashMap::new(),
-            interested_b: HashMap::new(),
-            aspect_a: aspect_a,
-            aspect_b: aspect_b,
+            interested_a: CachedQuery::new(aspect_a),
+            interested_b: CachedQuery::new(aspect_b),
+            broadphase: None,
             inner: inner,
         }
     }
+
+    /// Create an interact system that only processes the pairs of entities let through by a
+    /// broad phase, instead of every entity from one set against every entity from the other.
+    pub fn new_with_broadphase<B>(inner: T, aspect_a: Aspect<T::Components>, aspect_b: Aspect<T::Components>, broadphase: B) -> InteractSystem<T>
+        where B: BroadPhase<T::Components> + MaybeSend + 'static
+    {
+        InteractSystem
+        {
+            broadphase: Some(Box::new(broadphase)),
+            ..InteractSystem::new(inner, aspect_a, aspect_b)
+        }
+    }
+
+    /// The entities matching `aspect_a` that will be processed next update, in index order.
+    pub fn interested_a(&self) -> EntityIter<'_, T::Components>
+    {
+        self.interested_a.iter()
+    }
+
+    /// The entities matching `aspect_b` that will be processed next update, in index order.
+    pub fn interested_b(&self) -> EntityIter<'_, T::Components>
+    {
+        self.interested_b.iter()
+    }
+}
+
+impl<T: InteractProcess> InteractSystem<T>
+{
+    /// Tells the inner process how the entity's membership changed.
+    fn notify(&mut self, entity: &EntityData<T::Components>, old: Membership, new: Membership, world: &T::Components)
+    {
+        match (old.any(), new.any())
+        {
+            (false, true) => self.inner.activated(entity, world),
+            (true, true) => self.inner.reactivated(entity, world),
+            (true, false) => self.inner.deactivated(entity, world),
+            (false, false) => {},
+        }
+        if old != new
+        {
+            self.inner.membership_changed(entity, old, new, world);
+        }
+    }
+
+    /// Rechecks both sets, adding the entity to the ones it now matches.
+    fn recheck(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
+    {
+        let (was_a, is_a) = self.interested_a.reactivated(entity, world);
+        let (was_b, is_b) = self.interested_b.reactivated(entity, world);
+        self.notify(entity, Membership { a: was_a, b: was_b }, Membership { a: is_a, b: is_b }, world);
+    }
 }
 
 impl<T: InteractProcess> System for InteractSystem<T>
@@ -45,66 +157,22 @@ impl<T: InteractProcess> System for InteractSystem<T>
     type Services = T::Services;
     fn activated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
     {
-        if self.aspect_a.check(entity, world)
-        {
-            self.interested_a.insert(***entity, unsafe { (**entity).clone() });
-            self.inner.activated(entity, world);
-        }
-        if self.aspect_b.check(entity, world)
-        {
-            self.interested_b.insert(***entity, unsafe { (**entity).clone() });
-            self.inner.activated(entity, world);
-        }
+        self.recheck(entity, world);
     }
 
     fn reactivated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
     {
-        if self.interested_a.contains_key(entity)
-        {
-            if self.aspect_a.check(entity, world)
-            {
-                self.inner.reactivated(entity, world);
-            }
-            else
-            {
-                self.interested_a.remove(entity);
-                self.inner.deactivated(entity, world);
-            }
-        }
-        else if self.aspect_a.check(entity, world)
-        {
-            self.interested_a.insert(***entity, unsafe { (**entity).clone() });
-            self.inner.activated(entity, world);
-        }
-        if self.interested_b.contains_key(entity)
-        {
-            if self.aspect_b.check(entity, world)
-            {
-                self.inner.reactivated(entity, world);
-            }
-            else
-            {
-                self.interested_b.remove(entity);
-                self.inner.deactivated(entity, world);
-            }
-        }
-        else if self.aspect_b.check(entity, world)
-        {
-            self.interested_b.insert(***entity, unsafe { (**entity).clone() });
-            self.inner.activated(entity, world);
-        }
+        self.recheck(entity, world);
     }
 
     fn deactivated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
     {
-        if self.interested_a.remove(entity).is_some()
+        let old = Membership
         {
-            self.inner.deactivated(entity, world);
-        }
-        if self.interested_b.remove(entity).is_some()
-        {
-            self.inner.deactivated(entity, world);
-        }
+            a: self.interested_a.deactivated(entity),
+            b: self.interested_b.deactivated(entity),
+        };
+        self.notify(entity, old, Membership::default(), world);
     }
 
     fn is_active(&self) -> bool
@@ -115,8 +183,21 @@ impl<T: InteractProcess> System for InteractSystem<T>
 
 impl<T: InteractProcess> Process for InteractSystem<T>
 {
+    /// In deterministic mode, the pairs left by a broad phase are sorted by index too.
     fn process(&mut self, c: &mut DataHelper<T::Components, T::Services>)
     {
-        self.inner.process(EntityIter::Map(self.interested_a.values()), EntityIter::Map(self.interested_b.values()), c);
+        let (a, b) = (self.interested_a.iter(), self.interested_b.iter());
+        match self.broadphase
+        {
+            Some(ref mut broadphase) => {
+                let mut pairs = broadphase.pairs(a, b, &c.components);
+                if c.is_deterministic()
+                {
+                    pairs.sort_by_key(|pair| (pair.0.index(), pair.1.index()));
+                }
+                self.inner.process_pairs(pairs, c);
+            },
+            None => self.inner.process(a, b, c),
+        }
     }
 }
@@ -47,6 +47,11 @@ impl<T: Process> System for IntervalSystem<T>
         self.inner.activated(e, w);
     }
 
+    fn activated_batch(&mut self, entities: &[EntityData<T::Components>], w: &T::Components)
+    {
+        self.inner.activated_batch(entities, w);
+    }
+
     fn reactivated(&mut self, e: &EntityData<T::Components>, w: &T::Components)
     {
         self.inner.reactivated(e, w);
@@ -66,17 +66,34 @@ impl<T: Process> System for LazySystem<T>
     type Services = T::Services;
     fn activated(&mut self, e: &EntityData<T::Components>, w: &T::Components)
     {
-        self.inner.as_mut().map(|sys| sys.activated(e, w));
+        if let Some(ref mut sys) = self.inner
+        {
+            sys.activated(e, w);
+        }
+    }
+
+    fn activated_batch(&mut self, entities: &[EntityData<T::Components>], w: &T::Components)
+    {
+        if let Some(ref mut sys) = self.inner
+        {
+            sys.activated_batch(entities, w);
+        }
     }
 
     fn reactivated(&mut self, e: &EntityData<T::Components>, w: &T::Components)
     {
-        self.inner.as_mut().map(|sys| sys.reactivated(e, w));
+        if let Some(ref mut sys) = self.inner
+        {
+            sys.reactivated(e, w);
+        }
     }
 
     fn deactivated(&mut self, e: &EntityData<T::Components>, w: &T::Components)
     {
-        self.inner.as_mut().map(|sys| sys.deactivated(e, w));
+        if let Some(ref mut sys) = self.inner
+        {
+            sys.deactivated(e, w);
+        }
     }
 
     fn is_active(&self) -> bool
@@ -0,0 +1,112 @@
+
+//! Removal of entities after a number of updates, eg: for bullets, particles and popups.
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use {ComponentList, ComponentManager, ServiceManager};
+use DataHelper;
+use {Entity, IndexedEntity};
+use EntityData;
+use {Process, System};
+
+/// Number of updates left before an entity is removed by a `LifetimeSystem`.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Lifetime(pub u32);
+
+/// Gives a `LifetimeSystem` access to the field of the components holding lifetimes.
+///
+/// eg: for a `#[hot] lifetime: Lifetime` field,
+/// `fn lifetimes(&self) -> &ComponentList<Self, Lifetime> { &self.lifetime }`.
+pub trait LifetimeComponents: ComponentManager
+{
+    fn lifetimes(&self) -> &ComponentList<Self, Lifetime>;
+    fn lifetimes_mut(&mut self) -> &mut ComponentList<Self, Lifetime>;
+}
+
+/// Counts down every entity's `Lifetime` once per update, removing those that reach 0.
+pub struct LifetimeSystem<C: LifetimeComponents, M: ServiceManager = ()>
+{
+    interested: HashMap<Entity, IndexedEntity<C>>,
+    expired: Vec<Entity>,
+    services: PhantomData<fn(M)>,
+}
+
+impl<C: LifetimeComponents, M: ServiceManager> LifetimeSystem<C, M>
+{
+    pub fn new() -> LifetimeSystem<C, M>
+    {
+        LifetimeSystem
+        {
+            interested: HashMap::new(),
+            expired: Vec::new(),
+            services: PhantomData,
+        }
+    }
+
+    /// Number of entities being counted down.
+    pub fn len(&self) -> usize
+    {
+        self.interested.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.interested.is_empty()
+    }
+}
+
+impl<C: LifetimeComponents, M: ServiceManager> System for LifetimeSystem<C, M>
+{
+    type Components = C;
+    type Services = M;
+    fn activated(&mut self, entity: &EntityData<C>, c: &C)
+    {
+        if c.lifetimes().has(entity)
+        {
+            self.interested.insert(***entity, unsafe { (**entity).clone() });
+        }
+    }
+
+    fn reactivated(&mut self, entity: &EntityData<C>, c: &C)
+    {
+        if c.lifetimes().has(entity)
+        {
+            self.interested.insert(***entity, unsafe { (**entity).clone() });
+        }
+        else
+        {
+            self.interested.remove(entity);
+        }
+    }
+
+    fn deactivated(&mut self, entity: &EntityData<C>, _: &C)
+    {
+        self.interested.remove(entity);
+    }
+}
+
+impl<C: LifetimeComponents, M: ServiceManager> Process for LifetimeSystem<C, M>
+{
+    fn process(&mut self, data: &mut DataHelper<C, M>)
+    {
+        let lifetimes = data.components.lifetimes_mut();
+        for entity in self.interested.values()
+        {
+            if let Some(&mut Lifetime(ref mut remaining)) = lifetimes.borrow(&EntityData(entity))
+            {
+                *remaining = remaining.saturating_sub(1);
+                if *remaining == 0
+                {
+                    self.expired.push(**entity);
+                }
+            }
+        }
+        // Sorted so that removals happen in the same order every run
+        self.expired.sort_by_key(|e| e.id());
+        for entity in self.expired.drain(..)
+        {
+            data.remove_entity(entity);
+        }
+    }
+}
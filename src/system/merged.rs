@@ -0,0 +1,82 @@
+//! Systems defined by another `systems!` block.
+
+use std::ops::{Deref, DerefMut};
+
+use DataHelper;
+use EntityData;
+use SystemManager;
+use {Process, System};
+
+/// Mounts the systems of another `systems!` block as a single system, eg: a bundle of physics
+/// systems shipped by a library.
+///
+/// The bundle must be defined with the same components and services. Its systems are told about
+/// entities and updated in order, where the field is, and can be reached through the field like
+/// any other system, eg: `world.systems.physics.gravity` or `process!(world, physics.gravity)`.
+pub struct MergedSystemManager<S: SystemManager>
+{
+    pub inner: S,
+}
+
+impl<S: SystemManager> MergedSystemManager<S>
+{
+    pub fn new() -> MergedSystemManager<S>
+    {
+        MergedSystemManager
+        {
+            // Entities are passed on by the outer manager, the same as for any other system.
+            inner: unsafe { S::new() },
+        }
+    }
+}
+
+impl<S: SystemManager> Deref for MergedSystemManager<S>
+{
+    type Target = S;
+    fn deref(&self) -> &S
+    {
+        &self.inner
+    }
+}
+
+impl<S: SystemManager> DerefMut for MergedSystemManager<S>
+{
+    fn deref_mut(&mut self) -> &mut S
+    {
+        &mut self.inner
+    }
+}
+
+impl<S: SystemManager> System for MergedSystemManager<S>
+{
+    type Components = S::Components;
+    type Services = S::Services;
+    fn activated(&mut self, entity: &EntityData<S::Components>, world: &S::Components)
+    {
+        unsafe { self.inner.activated(*entity, world) }
+    }
+
+    fn activated_batch(&mut self, entities: &[EntityData<S::Components>], world: &S::Components)
+    {
+        unsafe { self.inner.activated_batch(entities, world) }
+    }
+
+    fn reactivated(&mut self, entity: &EntityData<S::Components>, world: &S::Components)
+    {
+        unsafe { self.inner.reactivated(*entity, world) }
+    }
+
+    fn deactivated(&mut self, entity: &EntityData<S::Components>, world: &S::Components)
+    {
+        unsafe { self.inner.deactivated(*entity, world) }
+    }
+}
+
+impl<S: SystemManager> Process for MergedSystemManager<S>
+{
+    /// Updates every system of the bundle, including its dynamic systems.
+    fn process(&mut self, data: &mut DataHelper<S::Components, S::Services>)
+    {
+        unsafe { self.inner.update(data) }
+    }
+}
@@ -1,20 +1,44 @@
 
 //! Types to process the world and entities.
 
-pub use self::entity::{EntitySystem, EntityProcess};
-pub use self::interact::{InteractSystem, InteractProcess};
+pub use self::batched::{BatchedEntityProcess, BatchedEntitySystem};
+pub use self::cleanup::{CleanupSystem};
+pub use self::data::{DataIter, DataProcess, DataSystem};
+pub use self::dynamic::{BoxedProcess, DynamicSystems};
+pub use self::entity::{EntitySystem, EntityProcess, PassiveEntitySystem};
+pub use self::event::{EntityEventProcess, EntityEventSystem, EventProcess, EventServices, EventSystem, TargetedEvent};
+pub use self::failures::{SystemFailure, SystemFailures};
+pub use self::fsm::{FsmComponents, FsmProcess, FsmSystem, StateMachine};
+pub use self::interact::{InteractSystem, InteractProcess, BroadPhase, Membership};
 pub use self::interval::{IntervalSystem};
 pub use self::lazy::{LazySystem};
+pub use self::lifetime::{Lifetime, LifetimeComponents, LifetimeSystem};
+pub use self::merged::{MergedSystemManager};
+pub use self::parallel::{CommandBuffer, ParEntityProcess, ParEntitySystem};
+pub use self::reload::{DynSystem};
+pub use self::transform::{Parent, Transform, TransformComponents, TransformSystem};
 
 use EntityData;
 use ComponentManager;
 use ServiceManager;
 use DataHelper;
 
+pub mod batched;
+pub mod cleanup;
+pub mod data;
+pub mod dynamic;
 pub mod entity;
+pub mod event;
+pub mod failures;
+pub mod fsm;
 pub mod interact;
 pub mod interval;
 pub mod lazy;
+pub mod lifetime;
+pub mod merged;
+pub mod parallel;
+pub mod reload;
+pub mod transform;
 
 /// Generic base system type.
 pub trait System
@@ -27,6 +51,17 @@ pub trait System
 
     }
 
+    /// Optional method called when several entities are activated at once.
+    ///
+    /// By default it calls activated() for each entity
+    fn activated_batch(&mut self, entities: &[EntityData<Self::Components>], c: &Self::Components)
+    {
+        for e in entities
+        {
+            self.activated(e, c);
+        }
+    }
+
     /// Optional method called when an entity is reactivated.
     ///
     /// By default it calls deactivated() followed by activated()
@@ -51,5 +86,43 @@ pub trait System
 pub trait Process: System
 {
     /// Process the world.
-    fn process(&mut self, &mut DataHelper<Self::Components, Self::Services>);
+    fn process(&mut self, data: &mut DataHelper<Self::Components, Self::Services>);
+}
+
+impl<T: System + ?Sized> System for Box<T>
+{
+    type Components = T::Components;
+    type Services = T::Services;
+    fn activated(&mut self, e: &EntityData<T::Components>, c: &T::Components)
+    {
+        (**self).activated(e, c);
+    }
+
+    fn activated_batch(&mut self, entities: &[EntityData<T::Components>], c: &T::Components)
+    {
+        (**self).activated_batch(entities, c);
+    }
+
+    fn reactivated(&mut self, e: &EntityData<T::Components>, c: &T::Components)
+    {
+        (**self).reactivated(e, c);
+    }
+
+    fn deactivated(&mut self, e: &EntityData<T::Components>, c: &T::Components)
+    {
+        (**self).deactivated(e, c);
+    }
+
+    fn is_active(&self) -> bool
+    {
+        (**self).is_active()
+    }
+}
+
+impl<T: Process + ?Sized> Process for Box<T>
+{
+    fn process(&mut self, c: &mut DataHelper<T::Components, T::Services>)
+    {
+        (**self).process(c);
+    }
 }
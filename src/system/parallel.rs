@@ -0,0 +1,275 @@
+//! Entity systems that process their entities on several threads.
+
+use std::ops::{Deref, DerefMut};
+use std::iter;
+use std::sync::Mutex;
+#[cfg(not(feature = "rayon"))]
+use std::{panic, thread};
+
+use {ComponentManager, ServiceManager};
+use Aspect;
+use DataHelper;
+use Entity;
+use EntityBuilder;
+use EntityData;
+use EntityIter;
+use query::InterestTracker;
+use {System, Process};
+
+/// A change to the world recorded by a worker thread, applied once every thread has finished.
+pub type Command<C, M> = Box<dyn FnOnce(&mut DataHelper<C, M>) + Send>;
+
+/// Changes to make to the world after a parallel process has finished.
+pub struct CommandBuffer<C: ComponentManager, M: ServiceManager>
+{
+    commands: Vec<Command<C, M>>,
+}
+
+impl<C: ComponentManager, M: ServiceManager> CommandBuffer<C, M>
+{
+    pub fn new() -> CommandBuffer<C, M>
+    {
+        CommandBuffer
+        {
+            commands: Vec::new(),
+        }
+    }
+
+    /// Runs `command` with the world's data once the process has finished.
+    pub fn defer<F>(&mut self, command: F) where F: FnOnce(&mut DataHelper<C, M>) + Send + 'static
+    {
+        self.commands.push(Box::new(command));
+    }
+
+    pub fn create_entity<B>(&mut self, builder: B) where B: EntityBuilder<C> + Send + 'static
+    {
+        self.defer(move |data| { data.create_entity(builder); });
+    }
+
+    pub fn remove_entity(&mut self, entity: Entity)
+    {
+        self.defer(move |data| data.remove_entity(entity));
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.commands.is_empty()
+    }
+
+    /// Runs every command in the order they were recorded.
+    pub fn apply(self, data: &mut DataHelper<C, M>)
+    {
+        for command in self.commands
+        {
+            command(data);
+        }
+    }
+}
+
+/// Processes a slice of entities with read-only access to the components.
+///
+/// Each thread works on its own clone of the process, which is handed back with `join()` when the
+/// thread is done.
+pub trait ParEntityProcess: System + Clone + Send
+{
+    fn process_slice<'a>(&mut self, entities: EntityIter<'a, Self::Components>, components: &Self::Components, commands: &mut CommandBuffer<Self::Components, Self::Services>);
+
+    /// Optional method called with each thread's clone after processing, eg: to merge statistics.
+    fn join(&mut self, _: Self)
+    {
+
+    }
+}
+
+/// Like `EntitySystem`, but divides its entities into chunks of `chunk_size` and processes them on
+/// as many threads as there are CPUs, from rayon's pool with the `rayon` feature. Idle threads take
+/// the next unprocessed chunk, so uneven chunks don't hold up the rest.
+///
+/// Commands are applied in the order of the chunks they were recorded for, so the result doesn't
+/// depend on how the chunks were shared out.
+pub struct ParEntitySystem<T: ParEntityProcess>
+{
+    interest: InterestTracker<T::Components>,
+    chunk_size: usize,
+    pub inner: T,
+}
+
+impl<T: ParEntityProcess> ParEntitySystem<T>
+{
+    /// Panics if `chunk_size` is zero.
+    pub fn new(inner: T, aspect: Aspect<T::Components>, chunk_size: usize) -> ParEntitySystem<T>
+    {
+        assert!(chunk_size > 0, "chunk size must be at least 1");
+        ParEntitySystem
+        {
+            interest: InterestTracker::new(aspect),
+            chunk_size: chunk_size,
+            inner: inner,
+        }
+    }
+
+    /// The entities that will be processed next update, in index order.
+    pub fn interested(&self) -> EntityIter<'_, T::Components>
+    {
+        self.interest.iter()
+    }
+}
+
+impl<T: ParEntityProcess> Deref for ParEntitySystem<T>
+{
+    type Target = T;
+    fn deref(&self) -> &T
+    {
+        &self.inner
+    }
+}
+
+impl<T: ParEntityProcess> DerefMut for ParEntitySystem<T>
+{
+    fn deref_mut(&mut self) -> &mut T
+    {
+        &mut self.inner
+    }
+}
+
+impl<T: ParEntityProcess> System for ParEntitySystem<T>
+{
+    type Components = T::Components;
+    type Services = T::Services;
+    fn activated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
+    {
+        self.interest.on_activated(entity, world, &mut self.inner);
+    }
+
+    fn activated_batch(&mut self, entities: &[EntityData<T::Components>], world: &T::Components)
+    {
+        self.interest.on_activated_batch(entities, world, &mut self.inner);
+    }
+
+    fn reactivated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
+    {
+        self.interest.on_reactivated(entity, world, &mut self.inner);
+    }
+
+    fn deactivated(&mut self, entity: &EntityData<T::Components>, world: &T::Components)
+    {
+        self.interest.on_deactivated(entity, world, &mut self.inner);
+    }
+
+    fn is_active(&self) -> bool
+    {
+        self.inner.is_active()
+    }
+}
+
+/// The chunks still to be processed, with their positions.
+type ChunkQueue<'a, C> = Mutex<iter::Enumerate<Box<dyn Iterator<Item = EntityIter<'a, C>> + Send + 'a>>>;
+
+/// Processes chunks from the queue until it's empty, returning the commands recorded for each.
+fn drain_chunks<'a, T>(worker: &mut T, queue: &ChunkQueue<'a, T::Components>, components: &T::Components) -> Vec<(usize, CommandBuffer<T::Components, T::Services>)>
+    where T: ParEntityProcess
+{
+    let mut buffers = Vec::new();
+    loop
+    {
+        let next = queue.lock().unwrap().next();
+        let (index, chunk) = match next
+        {
+            Some(next) => next,
+            None => break,
+        };
+        let mut commands = CommandBuffer::new();
+        worker.process_slice(chunk, components, &mut commands);
+        buffers.push((index, commands));
+    }
+    buffers
+}
+
+/// Number of threads chunks can be shared out to.
+#[cfg(feature = "rayon")]
+fn worker_threads() -> usize
+{
+    rayon::current_num_threads()
+}
+
+#[cfg(not(feature = "rayon"))]
+fn worker_threads() -> usize
+{
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+/// Runs each worker on rayon's thread pool.
+#[cfg(feature = "rayon")]
+fn run_workers<'a, T>(workers: Vec<T>, queue: &ChunkQueue<'a, T::Components>, components: &T::Components) -> Vec<(T, Vec<(usize, CommandBuffer<T::Components, T::Services>)>)>
+    where T: ParEntityProcess, T::Components: Sync
+{
+    let mut results: Vec<_> = workers.into_iter().map(|worker| (worker, Vec::new())).collect();
+    rayon::scope(|scope| {
+        for &mut (ref mut worker, ref mut buffers) in results.iter_mut()
+        {
+            scope.spawn(move |_| *buffers = drain_chunks(worker, queue, components));
+        }
+    });
+    results
+}
+
+/// Runs each worker on a thread of its own, spawned for this update.
+#[cfg(not(feature = "rayon"))]
+fn run_workers<'a, T>(workers: Vec<T>, queue: &ChunkQueue<'a, T::Components>, components: &T::Components) -> Vec<(T, Vec<(usize, CommandBuffer<T::Components, T::Services>)>)>
+    where T: ParEntityProcess, T::Components: Sync
+{
+    thread::scope(|scope| {
+        let handles: Vec<_> = workers.into_iter().map(|mut worker| {
+            scope.spawn(move || {
+                let buffers = drain_chunks(&mut worker, queue, components);
+                (worker, buffers)
+            })
+        }).collect();
+        handles.into_iter().map(|handle| match handle.join()
+        {
+            Ok(result) => result,
+            Err(payload) => panic::resume_unwind(payload),
+        }).collect()
+    })
+}
+
+impl<T: ParEntityProcess> Process for ParEntitySystem<T> where T::Components: Sync
+{
+    /// Shares the chunks out to rayon's thread pool with the `rayon` feature, or to threads
+    /// spawned for the update otherwise. A single chunk is processed on the calling thread.
+    fn process(&mut self, data: &mut DataHelper<T::Components, T::Services>)
+    {
+        let chunks = self.interest.len().div_ceil(self.chunk_size);
+        let threads = worker_threads().min(chunks);
+        let chunk_iter: Box<dyn Iterator<Item = EntityIter<T::Components>> + Send> = Box::new(self.interest.chunks(self.chunk_size));
+        let queue = Mutex::new(chunk_iter.enumerate());
+        let workers: Vec<T> = (0..threads).map(|_| self.inner.clone()).collect();
+        let results = if threads <= 1
+        {
+            workers.into_iter().map(|mut worker| {
+                let buffers = drain_chunks(&mut worker, &queue, &data.components);
+                (worker, buffers)
+            }).collect()
+        }
+        else
+        {
+            run_workers(workers, &queue, &data.components)
+        };
+        let mut buffers = Vec::with_capacity(chunks);
+        for (worker, worker_buffers) in results
+        {
+            self.inner.join(worker);
+            buffers.extend(worker_buffers);
+        }
+        buffers.sort_by_key(|&(index, _)| index);
+        for (_, commands) in buffers
+        {
+            commands.apply(data);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Systems that can be replaced while the world is running.
+
+use {ComponentManager, ServiceManager};
+use Aspect;
+use DataHelper;
+use EntityData;
+use EntityIter;
+use {Process, System};
+use query::CachedQuery;
+use system::BoxedProcess;
+
+/// A boxed system that can be swapped for another at runtime, eg: one reloaded from a dynamic
+/// library after its code changed.
+///
+/// It remembers every active entity, and tells the new system about them when swapped, so systems
+/// that keep track of their entities carry on as if they had been there all along. Loading the
+/// library (eg: with `libloading`) is left to the application.
+pub struct DynSystem<C: ComponentManager, M: ServiceManager>
+{
+    system: BoxedProcess<C, M>,
+    entities: CachedQuery<C>,
+}
+
+impl<C: ComponentManager, M: ServiceManager> DynSystem<C, M>
+{
+    pub fn new(system: BoxedProcess<C, M>) -> DynSystem<C, M>
+    {
+        DynSystem
+        {
+            system: system,
+            entities: CachedQuery::new(Aspect::all()),
+        }
+    }
+
+    /// Replaces the system, activating every current entity in the new one. Returns the old system.
+    pub fn swap(&mut self, system: BoxedProcess<C, M>, components: &C) -> BoxedProcess<C, M>
+    {
+        let old = ::std::mem::replace(&mut self.system, system);
+        let entities: Vec<_> = self.entities.iter().collect();
+        self.system.activated_batch(&entities, components);
+        old
+    }
+
+    /// Every active entity, in index order.
+    pub fn entities(&self) -> EntityIter<'_, C>
+    {
+        self.entities.iter()
+    }
+
+    pub fn system(&self) -> &BoxedProcess<C, M>
+    {
+        &self.system
+    }
+}
+
+impl<C: ComponentManager, M: ServiceManager> System for DynSystem<C, M>
+{
+    type Components = C;
+    type Services = M;
+    fn activated(&mut self, entity: &EntityData<C>, components: &C)
+    {
+        self.entities.activated(entity, components);
+        self.system.activated(entity, components);
+    }
+
+    fn activated_batch(&mut self, entities: &[EntityData<C>], components: &C)
+    {
+        self.entities.activated_batch(entities, components);
+        self.system.activated_batch(entities, components);
+    }
+
+    fn reactivated(&mut self, entity: &EntityData<C>, components: &C)
+    {
+        self.system.reactivated(entity, components);
+    }
+
+    fn deactivated(&mut self, entity: &EntityData<C>, components: &C)
+    {
+        self.entities.deactivated(entity);
+        self.system.deactivated(entity, components);
+    }
+
+    fn is_active(&self) -> bool
+    {
+        self.system.is_active()
+    }
+}
+
+impl<C: ComponentManager, M: ServiceManager> Process for DynSystem<C, M>
+{
+    fn process(&mut self, data: &mut DataHelper<C, M>)
+    {
+        self.system.process(data);
+    }
+}
@@ -0,0 +1,153 @@
+//! Propagation of transforms from parent entities to their children, eg: a gun held by a
+//! character moves with the character.
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use {ComponentList, ComponentManager, ServiceManager};
+use DataHelper;
+use {Entity, IndexedEntity};
+use EntityData;
+use {Process, System};
+
+/// The entity whose transform an entity's local transform is relative to.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Parent(pub Entity);
+
+/// A transform that can be placed within another, eg: a 2D affine matrix, or a position and an
+/// angle.
+pub trait Transform: Clone
+{
+    /// The global transform of a child with this local transform, under a parent with the global
+    /// transform `parent`.
+    fn within(&self, parent: &Self) -> Self;
+}
+
+/// Gives a `TransformSystem` access to the fields of the components holding transforms and
+/// parents.
+///
+/// eg: for `#[hot] local: Affine`, `#[hot] global: Affine` and `#[cold] parent: Parent` fields,
+/// `fn local_transforms(&self) -> &ComponentList<Self, Affine> { &self.local }`, and so on.
+pub trait TransformComponents: ComponentManager
+{
+    type Transform: Transform;
+    fn local_transforms(&self) -> &ComponentList<Self, Self::Transform>;
+    fn global_transforms_mut(&mut self) -> &mut ComponentList<Self, Self::Transform>;
+    fn parents(&self) -> &ComponentList<Self, Parent>;
+}
+
+/// Sets the global transform of every entity with a local transform once per update, working
+/// from parents down to their children.
+///
+/// An entity whose parent doesn't exist or has no local transform is placed at the root, with its
+/// local transform as its global one. So is one whose parents loop back to it.
+pub struct TransformSystem<C: TransformComponents, M: ServiceManager = ()>
+{
+    interested: HashMap<Entity, IndexedEntity<C>>,
+    services: PhantomData<fn(M)>,
+}
+
+impl<C: TransformComponents, M: ServiceManager> TransformSystem<C, M>
+{
+    pub fn new() -> TransformSystem<C, M>
+    {
+        TransformSystem
+        {
+            interested: HashMap::new(),
+            services: PhantomData,
+        }
+    }
+
+    /// Number of entities with a local transform.
+    pub fn len(&self) -> usize
+    {
+        self.interested.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.interested.is_empty()
+    }
+
+    /// The interested entities, parents before children.
+    fn order(&self, parents: &HashMap<Entity, Entity>) -> Vec<Entity>
+    {
+        let depth = |entity: &Entity| {
+            let mut depth = 0;
+            let mut current = entity;
+            // Stopping at the number of entities breaks loops.
+            while let Some(parent) = parents.get(current)
+            {
+                if depth == parents.len()
+                {
+                    break;
+                }
+                depth += 1;
+                current = parent;
+            }
+            depth
+        };
+        let mut order: Vec<(usize, Entity)> = self.interested.keys().map(|entity| (depth(entity), *entity)).collect();
+        order.sort_by_key(|&(depth, entity)| (depth, entity.id()));
+        order.into_iter().map(|(_, entity)| entity).collect()
+    }
+}
+
+impl<C: TransformComponents, M: ServiceManager> System for TransformSystem<C, M>
+{
+    type Components = C;
+    type Services = M;
+    fn activated(&mut self, entity: &EntityData<C>, c: &C)
+    {
+        if c.local_transforms().has(entity)
+        {
+            self.interested.insert(***entity, unsafe { (**entity).clone() });
+        }
+    }
+
+    fn reactivated(&mut self, entity: &EntityData<C>, c: &C)
+    {
+        if c.local_transforms().has(entity)
+        {
+            self.interested.insert(***entity, unsafe { (**entity).clone() });
+        }
+        else
+        {
+            self.interested.remove(entity);
+        }
+    }
+
+    fn deactivated(&mut self, entity: &EntityData<C>, _: &C)
+    {
+        self.interested.remove(entity);
+    }
+}
+
+impl<C: TransformComponents, M: ServiceManager> Process for TransformSystem<C, M>
+{
+    fn process(&mut self, data: &mut DataHelper<C, M>)
+    {
+        let c = &mut data.components;
+        let parents: HashMap<Entity, Entity> = self.interested.iter()
+            .filter_map(|(entity, indexed)| c.parents().get(&EntityData(indexed)).map(|Parent(parent)| (*entity, parent)))
+            .filter(|(_, parent)| self.interested.contains_key(parent))
+            .collect();
+        let mut globals: HashMap<Entity, C::Transform> = HashMap::new();
+        for entity in self.order(&parents)
+        {
+            let indexed = EntityData(&self.interested[&entity]);
+            let local = match c.local_transforms().get(&indexed)
+            {
+                Some(local) => local,
+                None => continue,
+            };
+            let global = match parents.get(&entity).and_then(|parent| globals.get(parent))
+            {
+                Some(parent) => local.within(parent),
+                None => local,
+            };
+            c.global_transforms_mut().set(&indexed, global.clone());
+            globals.insert(entity, global);
+        }
+    }
+}
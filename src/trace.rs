@@ -0,0 +1,29 @@
+//! Instrumentation with `tracing`, when the `tracing` feature is enabled.
+//!
+//! Without the feature these macros expand to nothing.
+
+/// Enters a span that lasts until the end of the enclosing block.
+#[cfg(feature = "tracing")]
+macro_rules! trace_span {
+    ($name:expr $(, $field:ident = $value:expr)*) => {
+        let _span = ::tracing::trace_span!($name $(, $field = $value)*).entered();
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_span {
+    ($($t:tt)*) => {};
+}
+
+/// Emits an event.
+#[cfg(feature = "tracing")]
+macro_rules! trace_event {
+    ($($t:tt)*) => {
+        ::tracing::trace!($($t)*);
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_event {
+    ($($t:tt)*) => {};
+}
@@ -1,11 +1,24 @@
 
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::mem;
 use std::ops::{Deref, DerefMut};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
 
 use {BuildData, EntityData, ModifyData};
+use {Error, Result};
 use {Entity, IndexedEntity, EntityIter};
-use {EntityBuilder, EntityModifier};
+use {Aspect, EntityBuilder, EntityModifier, Extract};
 use {System};
-use entity::EntityManager;
+use component::{ComponentAccess, ComponentRef, FlagList, LayerList, NestedList};
+use system::{BoxedProcess, DynamicSystems, EntityProcess, EntitySystem, SystemFailures};
+use entity::{EntityManager, FilteredEntityIter};
+use manager::{NameManager, Rng};
+use metrics::Metrics;
+use query::{CachedQuery, QueryId};
+use reflect::Reflect;
 
 enum Event
 {
@@ -13,10 +26,147 @@ enum Event
     RemoveEntity(Entity),
 }
 
+/// Something that happened to an entity during an update.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum EntityEvent
+{
+    /// The entity was created and activated.
+    Created(Entity),
+    /// The entity was deactivated and removed.
+    Removed(Entity),
+}
+
+/// Marks an entity given to `DataHelper::remove_entity_after()`, which systems can still see
+/// until it is removed. Aspects can match these entities with `Aspect::despawning()`.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Despawning
+{
+    /// Updates left before the entity is removed, counting the one that removes it.
+    pub remaining: u32,
+}
+
+/// Memory used by each component field, as returned by `World::memory_report()`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemoryReport
+{
+    pub components: Vec<ComponentMemory>,
+}
+
+/// Memory used by a single component field.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ComponentMemory
+{
+    pub name: &'static str,
+    /// Number of entities with the component.
+    pub len: usize,
+    pub capacity: usize,
+    /// Approximate bytes allocated on the heap.
+    pub bytes: usize,
+}
+
+/// Which component fields an entity has, by their position in `components!`.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct ComponentMask
+{
+    words: Vec<u64>,
+}
+
+impl ComponentMask
+{
+    pub fn insert(&mut self, field: usize)
+    {
+        let word = field / 64;
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        self.words[word] |= 1 << (field % 64);
+    }
+
+    pub fn contains(&self, field: usize) -> bool
+    {
+        self.words.get(field / 64).is_some_and(|word| word & (1 << (field % 64)) != 0)
+    }
+
+    /// Number of fields in the mask.
+    pub fn len(&self) -> usize
+    {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.len() == 0
+    }
+
+    /// Positions of the fields in the mask, in declaration order.
+    pub fn fields(&self) -> Vec<usize>
+    {
+        (0..self.words.len() * 64).filter(|&field| self.contains(field)).collect()
+    }
+
+    /// Names of the fields in the mask, in declaration order.
+    pub fn names<C: ComponentManager>(&self) -> Vec<&'static str>
+    {
+        self.fields().into_iter().map(|field| C::names()[field]).collect()
+    }
+}
+
+impl MemoryReport
+{
+    /// Sum of the bytes used by all fields.
+    pub fn total_bytes(&self) -> usize
+    {
+        self.components.iter().map(|c| c.bytes).sum()
+    }
+
+    /// Returns the usage of the named field.
+    pub fn get(&self, name: &str) -> Option<&ComponentMemory>
+    {
+        self.components.iter().find(|c| c.name == name)
+    }
+}
+
+/// What happened during one `World::update()`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrameReport
+{
+    /// Entities created and activated.
+    pub created: usize,
+    /// Entities deactivated and removed.
+    pub removed: usize,
+    /// Entity events queued since the last update, before the systems were processed.
+    pub queued_before_systems: usize,
+    /// Entity events queued by the systems while they were processed.
+    pub queued_by_systems: usize,
+    /// Time each system took, in the order they were processed, if profiling is enabled.
+    pub system_times: Vec<SystemTime>,
+    /// Time the whole update took.
+    pub duration: Duration,
+}
+
+/// Settings of deterministic mode, see `World::set_determinism()`.
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct DeterminismConfig
+{
+    /// Seed of the world's random number generator.
+    pub seed: u64,
+}
+
+/// Time a system took to process.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SystemTime
+{
+    /// Field name of the system, or name of the dynamic system.
+    pub system: String,
+    pub duration: Duration,
+}
+
 pub struct World<S> where S: SystemManager
 {
     pub systems: S,
     pub data: DataHelper<S::Components, S::Services>,
+    entity_events: Vec<EntityEvent>,
+    immediate: bool,
 }
 
 pub struct DataHelper<C, M> where C: ComponentManager, M: ServiceManager
@@ -24,13 +174,143 @@ pub struct DataHelper<C, M> where C: ComponentManager, M: ServiceManager
     pub components: C,
     pub services: M,
     entities: EntityManager<C>,
+    names: NameManager,
     event_queue: Vec<Event>,
+    changed: Vec<Entity>,
+    spawn_sender: Sender<SpawnBuilder<C>>,
+    spawn_receiver: Receiver<SpawnBuilder<C>>,
+    failures: Option<SystemFailures>,
+    paused: HashSet<String>,
+    times: Option<Vec<SystemTime>>,
+    queries: Vec<Option<CachedQuery<C>>>,
+    despawning: HashMap<Entity, u32>,
+    metrics: Option<boxed!(Metrics)>,
+    capacities: Vec<usize>,
+    determinism: Option<DeterminismConfig>,
+    rng: Rng,
+}
+
+type SpawnBuilder<C> = Box<dyn FnOnce(BuildData<C>, &mut C) + Send>;
+
+/// A handle for creating entities from other threads.
+///
+/// Builders sent through a spawner are run on the world's thread the next time it is updated.
+pub struct EntitySpawner<C: ComponentManager>
+{
+    sender: Sender<SpawnBuilder<C>>,
+}
+
+impl<C: ComponentManager> EntitySpawner<C>
+{
+    /// Queues an entity to be created during the next update.
+    ///
+    /// Returns false if the world no longer exists.
+    pub fn spawn<B>(&self, builder: B) -> bool where B: EntityBuilder<C> + Send + 'static
+    {
+        self.sender.send(Box::new(move |e: BuildData<C>, c: &mut C| builder.build(e, c))).is_ok()
+    }
+}
+
+/// A builder given to `ChildSpawner::spawn_with()`, which can spawn children of its own.
+type ChildBuilder<C> = Box<dyn FnOnce(BuildData<C>, &mut C, &mut ChildSpawner<C>)>;
+
+/// Creates entities from inside a builder passed to `DataHelper::create_entity_with_children()`,
+/// eg: a gun created along with its owner.
+///
+/// Children get their `Entity` right away, so the builder can store it in a component, but they
+/// are built after their parent. Each entity is activated after its parent and before its
+/// parent's later children.
+pub struct ChildSpawner<'a, C: ComponentManager>
+{
+    entities: &'a mut EntityManager<C>,
+    parent: Entity,
+    queued: Vec<(Entity, ChildBuilder<C>)>,
+}
+
+impl<'a, C: ComponentManager> ChildSpawner<'a, C>
+{
+    /// The entity being built.
+    pub fn parent(&self) -> Entity
+    {
+        self.parent
+    }
+
+    /// Queues a child entity to be built once the current builder returns.
+    pub fn spawn<B>(&mut self, builder: B) -> Entity where B: EntityBuilder<C> + 'static
+    {
+        self.spawn_with(move |e: BuildData<C>, c: &mut C, _: &mut ChildSpawner<C>| builder.build(e, c))
+    }
+
+    /// Like `spawn()`, but the child's builder can spawn children too.
+    pub fn spawn_with<F>(&mut self, builder: F) -> Entity
+        where F: FnOnce(BuildData<C>, &mut C, &mut ChildSpawner<C>) + 'static
+    {
+        let entity = self.entities.create();
+        self.queued.push((entity, Box::new(builder)));
+        entity
+    }
 }
 
-pub unsafe trait ComponentManager: 'static
+impl<C: ComponentManager> Clone for EntitySpawner<C>
+{
+    fn clone(&self) -> EntitySpawner<C>
+    {
+        EntitySpawner
+        {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+pub unsafe trait ComponentManager: Sized + 'static
 {
     unsafe fn new() -> Self;
     unsafe fn remove_all(&mut self, en: &IndexedEntity<Self>);
+    /// Moves all of an entity's components to an entity of another world.
+    unsafe fn transfer(&mut self, from: &IndexedEntity<Self>, to: &mut Self, into: &IndexedEntity<Self>);
+    /// Collects the entities that gained or lost a component since the last call.
+    unsafe fn take_changed(&mut self, changed: &mut Vec<Entity>);
+    /// Collects the entities whose `#[detached]` fields gained or lost a component since the last
+    /// call, see `World::flush_detached()`.
+    unsafe fn take_detached_changed(&mut self, changed: &mut Vec<Entity>);
+    /// Moves all of an entity's components to another index, see `World::compact()`.
+    unsafe fn remap(&mut self, from: &IndexedEntity<Self>, to: &IndexedEntity<Self>);
+    /// Frees as much unused storage as possible in every component field.
+    fn shrink_to_fit(&mut self);
+    /// Names of the component fields, in declaration order.
+    fn names() -> &'static [&'static str];
+    /// Reports the memory used by each component field, in declaration order.
+    fn memory_report(&self) -> MemoryReport;
+    /// Makes room in every component field for `additional` more entities.
+    fn reserve(&mut self, additional: usize);
+    /// Hashes the entity's `#[hashable]` components.
+    fn hash_entity<H: Hasher>(&self, entity: &IndexedEntity<Self>, state: &mut H);
+    /// Which fields the entity has a component in. A `#[nested]` field counts if the entity has
+    /// any of its components.
+    fn component_mask(&self, entity: &IndexedEntity<Self>) -> ComponentMask;
+    /// Layer masks of the entities.
+    fn layers(&self) -> &LayerList<Self>;
+    fn layers_mut(&mut self) -> &mut LayerList<Self>;
+    /// Flags the entities waiting to be removed by `DataHelper::remove_entity_after()`, see
+    /// `Aspect::despawning()`.
+    fn despawning_flags(&self) -> &FlagList<Self, Despawning>;
+    #[doc(hidden)]
+    fn despawning_flags_mut(&mut self) -> &mut FlagList<Self, Despawning>;
+    /// The entity's `#[inspect]` components, by field name.
+    fn inspect_components(&self, entity: &IndexedEntity<Self>) -> Vec<(&'static str, ::inspect::InspectValue)>;
+    /// The entity's `#[reflect]` component with the given field name, if it has one.
+    fn reflect_component(&self, entity: &IndexedEntity<Self>, name: &str) -> Option<&dyn Reflect>;
+    fn reflect_component_mut(&mut self, entity: &IndexedEntity<Self>, name: &str) -> Option<&mut dyn Reflect>;
+    /// Clears the `EntityRef`s of `#[refs]` components that refer to removed entities.
+    fn clear_refs(&mut self, removed: &dyn Fn(&Entity) -> bool);
+    /// Gives an entity the named `#[persist]` component, read from `value`.
+    ///
+    /// Returns `None` if there's no `#[persist]` field with that name.
+    #[cfg(feature = "serde")]
+    unsafe fn deserialize_component(&mut self, name: &str, entity: &IndexedEntity<Self>, value: ::scene::Value) -> Option<Result<()>>;
+    /// Serializes the entity's `#[persist]` components, by field name.
+    #[cfg(feature = "serde")]
+    fn serialize_components(&self, entity: &IndexedEntity<Self>) -> Result<Vec<(&'static str, ::scene::Value)>>;
 }
 
 pub trait ServiceManager: 'static
@@ -46,9 +326,18 @@ pub unsafe trait SystemManager
     type Services: ServiceManager;
     unsafe fn new() -> Self;
     unsafe fn activated(&mut self, en: EntityData<Self::Components>, co: &Self::Components);
+    unsafe fn activated_batch(&mut self, en: &[EntityData<Self::Components>], co: &Self::Components)
+    {
+        for e in en
+        {
+            self.activated(*e, co);
+        }
+    }
     unsafe fn reactivated(&mut self, en: EntityData<Self::Components>, co: &Self::Components);
     unsafe fn deactivated(&mut self, en: EntityData<Self::Components>, co: &Self::Components);
     unsafe fn update(&mut self, co: &mut DataHelper<Self::Components, Self::Services>);
+    /// Systems added at runtime, which are updated after all the others.
+    fn dynamic_systems(&mut self) -> &mut DynamicSystems<Self::Components, Self::Services>;
 }
 
 impl<S: SystemManager> Deref for World<S>
@@ -92,13 +381,84 @@ impl<C: ComponentManager, M: ServiceManager> DataHelper<C, M>
     {
         // TODO cleanup
         if self.entities.is_valid(entity) {
-            Some(call(EntityData(unsafe { &self.entities.indexed(&entity).clone() }), self))
+            Some(call(EntityData(unsafe { &self.entities.indexed(entity).clone() }), self))
         } else {
             None
         }
     }
 
-    pub fn create_entity<B>(&mut self, mut builder: B) -> Entity where B: EntityBuilder<C>
+    /// Like `with_entity_data()`, but with shared access, eg: for UI code that only has a `&World`.
+    pub fn with_entity_data_ref<F, R>(&self, entity: &Entity, mut call: F) -> Option<R>
+        where F: FnMut(EntityData<C>, &C) -> R
+    {
+        self.entities.try_indexed(entity).map(|indexed| call(EntityData(indexed), &self.components))
+    }
+
+    /// Like `with_entity_data()`, but calls `call` for each of several entities in turn.
+    ///
+    /// The entities are all looked up before the first call. Ones that aren't valid are skipped,
+    /// so the results are for the valid entities, in order.
+    pub fn with_entity_datas<F, R>(&mut self, entities: &[Entity], mut call: F) -> Vec<R>
+        where F: FnMut(EntityData<C>, &mut C) -> R
+    {
+        let indexed: Vec<IndexedEntity<C>> = entities.iter()
+            .filter_map(|entity| self.entities.try_indexed(entity).map(|indexed| unsafe { indexed.clone() }))
+            .collect();
+        indexed.iter().map(|entity| call(EntityData(entity), &mut self.components)).collect()
+    }
+
+    /// Like `with_entity_data()`, but for working with two entities at once.
+    ///
+    /// Returns `None` without calling `call` if either entity isn't valid.
+    pub fn with_entity_pair<F, R>(&mut self, a: &Entity, b: &Entity, mut call: F) -> Option<R>
+        where F: FnMut(EntityData<C>, EntityData<C>, &mut C) -> R
+    {
+        match (self.entities.try_indexed(a), self.entities.try_indexed(b)) {
+            (Some(a), Some(b)) => Some(call(EntityData(a), EntityData(b), &mut self.components)),
+            _ => None,
+        }
+    }
+
+    /// Drops every component of a `#[nested]` field, eg: `data.reset_nested(|c| &mut c.render)`
+    /// to rebuild presentation data from scratch without touching the rest of the world.
+    ///
+    /// The entities that had any of its components are reactivated at the next flush.
+    pub fn reset_nested<N, F>(&mut self, field: F)
+        where N: ComponentManager, F: FnOnce(&mut C) -> &mut NestedList<C, N>
+    {
+        let list = field(&mut self.components);
+        for entity in self.entities.iter() {
+            if list.contains(&entity) {
+                self.changed.push(**entity);
+            }
+        }
+        unsafe { list.reset(); }
+    }
+
+    /// Returns whether the entity exists and hasn't been removed.
+    pub fn is_alive(&self, entity: &Entity) -> bool
+    {
+        self.entities.is_valid(entity)
+    }
+
+    /// Returns whether the entity has a component, eg: `world.has_component(&e, |c| &c.position)`.
+    ///
+    /// False if the entity doesn't exist.
+    pub fn has_component<F, L>(&self, entity: &Entity, field: F) -> bool
+        where F: FnOnce(&C) -> &L, L: ComponentAccess<C>
+    {
+        self.entities.try_indexed(entity).is_some_and(|indexed| field(&self.components).contains(indexed))
+    }
+
+    /// An entity's component, eg: `world.component(&e, |c| &c.position)`, or `None` if the entity
+    /// doesn't exist or doesn't have one.
+    pub fn component<'a, F, L>(&'a self, entity: &Entity, field: F) -> Option<&'a L::Component>
+        where F: FnOnce(&'a C) -> &'a L, L: ComponentRef<C> + 'a
+    {
+        self.entities.try_indexed(entity).and_then(|indexed| field(&self.components).component(indexed))
+    }
+
+    pub fn create_entity<B>(&mut self, builder: B) -> Entity where B: EntityBuilder<C>
     {
         let entity = self.entities.create();
         builder.build(BuildData(self.entities.indexed(&entity)), &mut self.components);
@@ -110,58 +470,906 @@ impl<C: ComponentManager, M: ServiceManager> DataHelper<C, M>
     {
         self.event_queue.push(Event::RemoveEntity(entity));
     }
+
+    /// Like `create_entity()`, but the builder is also given a `ChildSpawner` for creating other
+    /// entities along with this one.
+    pub fn create_entity_with_children<F>(&mut self, builder: F) -> Entity
+        where F: FnOnce(BuildData<C>, &mut C, &mut ChildSpawner<C>)
+    {
+        let entity = self.entities.create();
+        self.build_with_children(entity, builder);
+        entity
+    }
+
+    /// Builds an entity, then each child it spawned in turn.
+    fn build_with_children<F>(&mut self, entity: Entity, builder: F)
+        where F: FnOnce(BuildData<C>, &mut C, &mut ChildSpawner<C>)
+    {
+        // Copied so that the spawner can borrow the entity manager while the entity is built.
+        let indexed = unsafe { self.entities.indexed(&entity).clone() };
+        let mut children = ChildSpawner {
+            entities: &mut self.entities,
+            parent: entity,
+            queued: Vec::new(),
+        };
+        builder(BuildData(&indexed), &mut self.components, &mut children);
+        let queued = children.queued;
+        self.event_queue.push(Event::BuildEntity(entity));
+        for (child, builder) in queued {
+            self.build_with_children(child, builder);
+        }
+    }
+
+    /// Removes an entity at the start of the `ticks`th update from now, eg: after a death animation.
+    ///
+    /// Until then, `despawning()` returns how long it has left, and the entity matches
+    /// `Aspect::despawning()` from the next flush. If the entity was already going to be removed
+    /// sooner, that is kept. Returns false if the entity doesn't exist.
+    pub fn remove_entity_after(&mut self, entity: Entity, ticks: u32) -> bool
+    {
+        if !self.entities.is_valid(&entity) {
+            return false;
+        }
+        if ticks == 0 {
+            self.remove_entity(entity);
+            return true;
+        }
+        let remaining = self.despawning.entry(entity).or_insert(ticks);
+        *remaining = (*remaining).min(ticks);
+        let flag = Despawning { remaining: *remaining };
+        let indexed = self.entities.indexed(&entity);
+        self.components.despawning_flags_mut().set(&EntityData(indexed), flag);
+        true
+    }
+
+    /// Returns whether an entity is waiting to be removed by `remove_entity_after()`.
+    pub fn despawning(&self, entity: &Entity) -> Option<Despawning>
+    {
+        self.despawning.get(entity).map(|&remaining| Despawning { remaining: remaining })
+    }
+
+    /// One more than the highest entity index in use since the world was created or compacted.
+    ///
+    /// Hot component storage grows to this size, so a mark much higher than the number of
+    /// entities means `World::compact()` would free memory.
+    pub fn high_water_mark(&self) -> usize
+    {
+        self.entities.high_water_mark()
+    }
+
+    /// Which component fields the entity has, if it exists.
+    pub fn component_mask(&self, entity: &Entity) -> Option<ComponentMask>
+    {
+        self.entities.try_indexed(entity).map(|indexed| self.components.component_mask(indexed))
+    }
+
+    /// Every entity, in index order in deterministic mode and in no particular order otherwise.
+    pub fn entities(&self) -> EntityIter<'_, C>
+    {
+        if self.determinism.is_none() {
+            return self.entities.iter();
+        }
+        let mut entities: Vec<_> = self.entities.iter().collect();
+        entities.sort_by_key(|e| e.index());
+        EntityIter::List(entities.into_iter())
+    }
+
+    /// The settings of deterministic mode, if it's on. See `World::set_determinism()`.
+    pub fn determinism(&self) -> Option<&DeterminismConfig>
+    {
+        self.determinism.as_ref()
+    }
+
+    pub fn is_deterministic(&self) -> bool
+    {
+        self.determinism.is_some()
+    }
+
+    /// The world's random number generator. Seeded from `DeterminismConfig::seed` in
+    /// deterministic mode, and differently for every world otherwise.
+    pub fn rng(&mut self) -> &mut Rng
+    {
+        &mut self.rng
+    }
+
+    /// The current time, or `None` in deterministic mode, where systems mustn't depend on the
+    /// wall clock. Systems that need the time should take it from here so that they can't.
+    pub fn wall_clock(&self) -> Option<Instant>
+    {
+        match self.determinism {
+            Some(_) => None,
+            None => Some(Instant::now()),
+        }
+    }
+
+    /// Returns a handle that other threads can use to create entities in this world.
+    pub fn spawner(&self) -> EntitySpawner<C>
+    {
+        EntitySpawner
+        {
+            sender: self.spawn_sender.clone(),
+        }
+    }
+
+    /// Gives an entity a unique name, taking it away from any other entity that had it.
+    ///
+    /// Returns false if the entity doesn't exist. Names are forgotten when their entity is removed.
+    pub fn set_name(&mut self, entity: &Entity, name: &str) -> bool
+    {
+        if self.entities.is_valid(entity) {
+            self.names.set(*entity, name.to_string());
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Removes the name of an entity, returning it.
+    pub fn remove_name(&mut self, entity: &Entity) -> Option<String>
+    {
+        self.names.remove(entity)
+    }
+
+    /// Stops processing the systems marked `#[group(name)]` in `systems!`, eg: for a pause menu.
+    ///
+    /// Paused systems are still told about entities being activated and deactivated.
+    pub fn pause_group(&mut self, name: &str)
+    {
+        self.paused.insert(name.to_string());
+    }
+
+    /// Resumes processing the systems of a paused group.
+    pub fn resume_group(&mut self, name: &str)
+    {
+        self.paused.remove(name);
+    }
+
+    pub fn is_group_paused(&self, name: &str) -> bool
+    {
+        self.paused.contains(name)
+    }
+
+    /// Returns whether any of the groups is paused.
+    pub fn any_group_paused(&self, groups: &[&str]) -> bool
+    {
+        !self.paused.is_empty() && groups.iter().any(|group| self.paused.contains(*group))
+    }
+
+    /// Systems that have panicked, if panic isolation is enabled.
+    pub fn system_failures(&self) -> Option<&SystemFailures>
+    {
+        self.failures.as_ref()
+    }
+
+    /// Mutable version of `system_failures()`, eg: to reset a failed system.
+    pub fn system_failures_mut(&mut self) -> Option<&mut SystemFailures>
+    {
+        self.failures.as_mut()
+    }
+
+    /// Time each system has taken so far in this update, if profiling is enabled.
+    pub fn system_times(&self) -> Option<&[SystemTime]>
+    {
+        self.times.as_ref().map(|times| &times[..])
+    }
+
+    #[doc(hidden)]
+    pub fn system_times_mut(&mut self) -> Option<&mut Vec<SystemTime>>
+    {
+        self.times.as_mut()
+    }
+
+    /// The world's metrics sink, if it has one, eg: for systems to report their own metrics.
+    pub fn metrics_mut(&mut self) -> Option<&mut boxed!(Metrics)>
+    {
+        self.metrics.as_mut()
+    }
+
+    /// Names given to entities, for looking them up either way.
+    pub fn names(&self) -> &NameManager
+    {
+        &self.names
+    }
+
+    /// Starts keeping track of the entities matching an aspect, for code outside of systems.
+    pub fn add_query(&mut self, aspect: Aspect<C>) -> QueryId
+    {
+        let mut query = CachedQuery::new(aspect);
+        self.rescan(|e, c| { query.activated(e, c); });
+        match self.queries.iter().position(Option::is_none) {
+            Some(i) => {
+                self.queries[i] = Some(query);
+                QueryId::new(i)
+            },
+            None => {
+                self.queries.push(Some(query));
+                QueryId::new(self.queries.len() - 1)
+            },
+        }
+    }
+
+    /// The entities matching a query as of the last update.
+    pub fn query(&self, id: QueryId) -> Option<&CachedQuery<C>>
+    {
+        self.queries.get(id.index()).and_then(Option::as_ref)
+    }
+
+    /// Stops keeping track of a query, returning whether it existed.
+    pub fn remove_query(&mut self, id: QueryId) -> bool
+    {
+        match self.queries.get_mut(id.index()) {
+            Some(query) => query.take().is_some(),
+            None => false,
+        }
+    }
+
+    /// Calls `recheck` with every entity that systems have been told about, skipping the ones
+    /// still waiting to be activated, eg: to check them against an aspect that has changed.
+    pub fn rescan<F>(&self, mut recheck: F)
+        where F: FnMut(&EntityData<C>, &C)
+    {
+        let pending = self.pending_entities();
+        for e in self.entities() {
+            if !pending.contains(&**e) {
+                recheck(&e, &self.components);
+            }
+        }
+    }
+
+    /// Whether the entity is queued to be removed at the next flush.
+    fn removal_pending(&self, entity: &Entity) -> bool
+    {
+        self.event_queue.iter().any(|e| matches!(*e, Event::RemoveEntity(removed) if removed == *entity))
+    }
+
+    /// Entities still waiting in the queue, which are activated when it is flushed.
+    fn pending_entities(&self) -> HashSet<Entity>
+    {
+        self.event_queue.iter().filter_map(|e| match *e {
+            Event::BuildEntity(entity) => Some(entity),
+            Event::RemoveEntity(_) => None,
+        }).collect()
+    }
 }
 
 impl<S: SystemManager> World<S>
 {
     pub fn new() -> World<S>
     {
+        let (spawn_sender, spawn_receiver) = mpsc::channel();
         World {
             systems: unsafe { S::new() },
             data: DataHelper {
                 components: unsafe { S::Components::new() },
                 services: S::Services::new(),
                 entities: EntityManager::new(),
+                names: NameManager::new(),
                 event_queue: Vec::new(),
+                changed: Vec::new(),
+                spawn_sender: spawn_sender,
+                spawn_receiver: spawn_receiver,
+                failures: None,
+                paused: HashSet::new(),
+                times: None,
+                queries: Vec::new(),
+                despawning: HashMap::new(),
+                metrics: None,
+                capacities: Vec::new(),
+                determinism: None,
+                rng: Rng::seeded(RandomState::new().build_hasher().finish()),
             },
+            entity_events: Vec::new(),
+            immediate: false,
         }
     }
 
-    pub fn entities(&self) -> EntityIter<S::Components>
+    /// Creates a world with room for `capacity` entities before anything needs to reallocate.
+    pub fn with_capacity(capacity: usize) -> World<S>
     {
-        self.data.entities.iter()
+        let mut world: World<S> = World::new();
+        world.data.entities = EntityManager::with_capacity(capacity);
+        world.data.components.reserve(capacity);
+        world.data.event_queue.reserve(capacity);
+        world.data.changed.reserve(capacity);
+        world
     }
 
-    pub fn modify_entity<M>(&mut self, entity: Entity, mut modifier: M) where M: EntityModifier<S::Components>
+    /// Iterates over the entities matching an aspect, checking every entity.
+    ///
+    /// For sets of entities that are looked at often, `add_query()` is faster.
+    pub fn matching(&self, aspect: Aspect<S::Components>) -> FilteredEntityIter<'_, S::Components>
+    {
+        self.data.entities().filter(aspect, &self.data.components)
+    }
+
+    /// Returns an entity matching an aspect, if there are any. Which one isn't specified.
+    pub fn find_first(&self, aspect: Aspect<S::Components>) -> Option<Entity>
+    {
+        self.matching(aspect).next().map(|e| **e)
+    }
+
+    /// Counts the entities matching an aspect.
+    pub fn count(&self, aspect: Aspect<S::Components>) -> usize
+    {
+        self.matching(aspect).count()
+    }
+
+    /// Returns a hash of every entity and its `#[hashable]` components.
+    ///
+    /// Entities are visited in order of creation and the hash function is fixed, so two worlds
+    /// that ran the same simulation give the same hash, even in different processes. This makes
+    /// it suitable for detecting desyncs between lockstep clients on the same platform.
+    pub fn state_hash(&self) -> u64
+    {
+        let mut entities: Vec<_> = self.data.entities.iter().collect();
+        entities.sort_by_key(|e| e.id());
+        let mut state = StateHasher::new();
+        for entity in entities {
+            state.write_u64(entity.id());
+            self.data.components.hash_entity(&entity, &mut state);
+        }
+        state.finish()
+    }
+
+    /// Captures every entity's `#[persist]` components and name as a scene, tagged with the
+    /// schema `version` of the components.
+    #[cfg(feature = "serde")]
+    pub fn snapshot(&self, version: u32) -> Result<::scene::Scene>
+    {
+        let mut entities: Vec<_> = self.data.entities.iter().collect();
+        entities.sort_by_key(|e| e.id());
+        let mut scene = ::scene::Scene::new(version);
+        for entity in entities {
+            let components = self.data.components.serialize_components(&entity)?;
+            let name = self.data.names.name(&entity).map(|name| name.to_string());
+            if components.is_empty() && name.is_none() {
+                continue;
+            }
+            scene.entities.push(::scene::SceneEntity {
+                name: name,
+                components: components.into_iter().map(|(field, value)| (field.to_string(), value)).collect(),
+            });
+        }
+        Ok(scene)
+    }
+
+    /// Serializes one entity's `#[persist]` components, eg: to send it over the network or to
+    /// copy and paste it in an editor.
+    #[cfg(feature = "serde")]
+    pub fn serialize_entity(&self, entity: Entity) -> Result<Vec<u8>>
+    {
+        if !self.data.entities.is_valid(&entity) {
+            return Err(Error::NoSuchEntity(entity));
+        }
+        let components = self.data.components.serialize_components(self.data.entities.indexed(&entity))?;
+        let entity = ::scene::SceneEntity {
+            name: None,
+            components: components.into_iter().map(|(field, value)| (field.to_string(), value)).collect(),
+        };
+        Ok(entity.to_value().to_string().into_bytes())
+    }
+
+    /// Creates an entity from the output of `serialize_entity()`.
+    #[cfg(feature = "serde")]
+    pub fn deserialize_entity(&mut self, bytes: &[u8]) -> Result<Entity>
+    {
+        let value = ::serde_json::from_slice(bytes).map_err(|err| Error::Parse(err.to_string()))?;
+        ::scene::SceneEntity::from_value(value)?.spawn(&mut self.data)
+    }
+
+    /// Creates a copy of an entity with its `#[persist]` components, eg: to spawn more of an
+    /// entity set up in an editor.
+    ///
+    /// `#[transient]` components, and any others that aren't persisted, are left out, and the
+    /// copy isn't given the entity's name.
+    #[cfg(feature = "serde")]
+    pub fn clone_entity(&mut self, entity: Entity) -> Result<Entity>
+    {
+        if !self.data.entities.is_valid(&entity) {
+            return Err(Error::NoSuchEntity(entity));
+        }
+        let components = self.data.components.serialize_components(self.data.entities.indexed(&entity))?;
+        let copy = ::scene::SceneEntity {
+            name: None,
+            components: components.into_iter().map(|(field, value)| (field.to_string(), value)).collect(),
+        };
+        let copy = copy.spawn(&mut self.data)?;
+        if self.immediate {
+            self.flush_queue();
+        }
+        Ok(copy)
+    }
+
+    /// Removes an entity, keeping its name and `#[persist]` components in a compact archive that
+    /// `unarchive()` can bring back, eg: when streaming parts of a large world out of memory.
+    ///
+    /// Components that aren't `#[persist]` are dropped. The entity is removed at the next flush,
+    /// and comes back with a new id.
+    #[cfg(feature = "serde")]
+    pub fn archive_entity(&mut self, entity: Entity) -> Result<::scene::ArchivedEntity>
+    {
+        if !self.data.entities.is_valid(&entity) {
+            return Err(Error::NoSuchEntity(entity));
+        }
+        let components = self.data.components.serialize_components(self.data.entities.indexed(&entity))?;
+        let archived = ::scene::ArchivedEntity::new(&::scene::SceneEntity {
+            name: self.data.names.name(&entity).map(|name| name.to_string()),
+            components: components.into_iter().map(|(field, value)| (field.to_string(), value)).collect(),
+        })?;
+        self.data.remove_entity(entity);
+        Ok(archived)
+    }
+
+    /// Creates an entity from one taken out by `archive_entity()`.
+    #[cfg(feature = "serde")]
+    pub fn unarchive(&mut self, archived: &::scene::ArchivedEntity) -> Result<Entity>
+    {
+        archived.entity()?.spawn(&mut self.data)
+    }
+
+    /// Moves an entity and all of its components and name to another world with the same
+    /// components, eg: when handing a player off to another server shard.
+    ///
+    /// The entity gets a new id in the other world, which is returned. It is removed from this
+    /// world at the next flush, or straight away in immediate mode, and can't be transferred or
+    /// removed again until then. `EntityRef`s to or from it aren't remapped.
+    pub fn transfer_entity<T>(&mut self, entity: Entity, other: &mut World<T>) -> Result<Entity>
+        where T: SystemManager<Components = S::Components>
+    {
+        if !self.data.entities.is_valid(&entity) || self.data.removal_pending(&entity) {
+            return Err(Error::NoSuchEntity(entity));
+        }
+        let from = self.data.entities.indexed(&entity);
+        let source = &mut self.data.components;
+        let moved = other.create_entity(|e: BuildData<S::Components>, c: &mut S::Components| unsafe {
+            source.transfer(from, c, e.0);
+        });
+        if let Some(name) = self.data.names.remove(&entity) {
+            other.data.set_name(&moved, &name);
+        }
+        self.remove_entity(entity);
+        Ok(moved)
+    }
+
+    /// Turns panic isolation on or off.
+    ///
+    /// While it's on, a system that panics during `update()` is recorded in `system_failures()`
+    /// and skipped from then on, instead of the panic unwinding out of `update()`. Whatever the
+    /// system changed before panicking is left as it was.
+    pub fn isolate_panics(&mut self, enabled: bool)
+    {
+        if !enabled {
+            self.data.failures = None;
+        } else if self.data.failures.is_none() {
+            self.data.failures = Some(SystemFailures::new());
+        }
+    }
+
+    /// Turns timing of each system on or off, for `FrameReport::system_times`.
+    pub fn profile_systems(&mut self, enabled: bool)
+    {
+        if !enabled {
+            self.data.times = None;
+        } else if self.data.times.is_none() {
+            self.data.times = Some(Vec::new());
+        }
+    }
+
+    /// Turns immediate mode on or off.
+    ///
+    /// While it's on, `World::create_entity()` and `World::remove_entity()` flush the world right
+    /// away, so systems see the change before the call returns instead of at the next `update()`
+    /// or `flush()`. This suits turn-based games that act on entities between updates. Entities
+    /// created or removed through a `DataHelper`, eg: by systems, are still queued.
+    pub fn set_immediate(&mut self, enabled: bool)
+    {
+        self.immediate = enabled;
+    }
+
+    pub fn is_immediate(&self) -> bool
+    {
+        self.immediate
+    }
+
+    /// Turns deterministic mode on with `Some` config, or off with `None`.
+    ///
+    /// While it's on, the same inputs always lead to the same state, as long as the systems
+    /// themselves are deterministic: entities are visited in index order by `entities()`,
+    /// `matching()`, `extract()` and the library's systems, `DataHelper::rng()` is seeded from
+    /// the config, and `DataHelper::wall_clock()` is withheld. This suits lockstep networking and
+    /// replays.
+    pub fn set_determinism(&mut self, config: Option<DeterminismConfig>)
+    {
+        if let Some(ref config) = config {
+            self.data.rng.reseed(config.seed);
+        }
+        self.data.determinism = config;
+    }
+
+    /// Same as `DataHelper::create_entity()`, but activates the entity right away in immediate mode.
+    pub fn create_entity<B>(&mut self, builder: B) -> Entity where B: EntityBuilder<S::Components>
+    {
+        let entity = self.data.create_entity(builder);
+        if self.immediate {
+            self.flush_queue();
+        }
+        entity
+    }
+
+    /// Same as `DataHelper::create_entity_with_children()`, but activates the entities right away
+    /// in immediate mode.
+    pub fn create_entity_with_children<F>(&mut self, builder: F) -> Entity
+        where F: FnOnce(BuildData<S::Components>, &mut S::Components, &mut ChildSpawner<S::Components>)
+    {
+        let entity = self.data.create_entity_with_children(builder);
+        if self.immediate {
+            self.flush_queue();
+        }
+        entity
+    }
+
+    /// Same as `DataHelper::remove_entity()`, but removes the entity right away in immediate mode.
+    pub fn remove_entity(&mut self, entity: Entity)
+    {
+        self.data.remove_entity(entity);
+        if self.immediate {
+            self.flush_queue();
+        }
+    }
+
+    /// Reports metrics to `metrics` from now on. See the `metrics` module for what's reported.
+    pub fn set_metrics(&mut self, metrics: boxed!(Metrics))
+    {
+        self.data.metrics = Some(metrics);
+    }
+
+    /// Stops reporting metrics, returning the sink.
+    pub fn take_metrics(&mut self) -> Option<boxed!(Metrics)>
+    {
+        self.data.metrics.take()
+    }
+
+    /// Fills `target` from every entity in the world.
+    ///
+    /// Call it after `update()` to take a consistent snapshot of the world's state.
+    pub fn extract<R>(&self, target: &mut R) where R: Extract<S::Components>
+    {
+        target.begin();
+        for entity in self.data.entities() {
+            target.extract(entity, &self.data.components);
+        }
+    }
+
+    /// Reports how much memory each component field is using.
+    pub fn memory_report(&self) -> MemoryReport
+    {
+        self.data.components.memory_report()
+    }
+
+    /// Entities created and removed during the last update, in the order it happened.
+    pub fn entity_events(&self) -> &[EntityEvent]
+    {
+        &self.entity_events
+    }
+
+    pub fn modify_entity<M>(&mut self, entity: Entity, modifier: M) where M: EntityModifier<S::Components>
     {
         let indexed = self.data.entities.indexed(&entity);
         modifier.modify(ModifyData(indexed), &mut self.data.components);
-        unsafe { self.systems.reactivated(EntityData(indexed), &mut self.data.components); }
+        unsafe {
+            self.systems.reactivated(EntityData(indexed), &self.data.components);
+            for query in self.data.queries.iter_mut().flatten() {
+                query.reactivated(&EntityData(indexed), &self.data.components);
+            }
+            self.data.components.take_changed(&mut self.data.changed);
+        }
+        self.data.changed.retain(|e| *e != entity);
+    }
+
+    /// Like `modify_entity()`, but returns an error instead of panicking if the entity doesn't
+    /// exist.
+    pub fn try_modify_entity<M>(&mut self, entity: Entity, modifier: M) -> Result<()> where M: EntityModifier<S::Components>
+    {
+        if self.data.entities.is_valid(&entity) {
+            self.modify_entity(entity, modifier);
+            Ok(())
+        } else {
+            Err(Error::NoSuchEntity(entity))
+        }
+    }
+
+    /// Adds a named system while the world is running, and activates every existing entity for it.
+    ///
+    /// Returns the system previously added under the same name, after deactivating every entity
+    /// for it.
+    pub fn add_dynamic_system(&mut self, name: &str, mut system: BoxedProcess<S::Components, S::Services>)
+        -> Option<BoxedProcess<S::Components, S::Services>>
+    {
+        self.data.rescan(|e, c| system.activated(e, c));
+        self.systems.dynamic_systems().insert(name, system).map(|old| self.deactivate_all(old))
+    }
+
+    /// Removes a system added with `add_dynamic_system()`, deactivating every entity for it.
+    pub fn remove_dynamic_system(&mut self, name: &str) -> Option<BoxedProcess<S::Components, S::Services>>
+    {
+        self.systems.dynamic_systems().remove(name).map(|old| self.deactivate_all(old))
+    }
+
+    fn deactivate_all(&self, mut system: BoxedProcess<S::Components, S::Services>) -> BoxedProcess<S::Components, S::Services>
+    {
+        for e in self.data.entities.iter() {
+            system.deactivated(&e, &self.data.components);
+        }
+        system
+    }
+
+    fn activate_batch(&mut self, built: &mut Vec<Entity>)
+    {
+        if built.is_empty() {
+            return;
+        }
+        let entities = &self.data.entities;
+        let batch: Vec<_> = built.iter().map(|entity| EntityData(entities.indexed(entity))).collect();
+        unsafe { self.systems.activated_batch(&batch, &self.data.components); }
+        for query in self.data.queries.iter_mut().flatten() {
+            query.activated_batch(&batch, &self.data.components);
+        }
+        built.clear();
     }
 
     fn flush_queue(&mut self)
     {
-        for e in self.data.event_queue.drain(..) {
+        trace_span!("ecs::flush_queue");
+        let start = Instant::now();
+        let events = self.entity_events.len();
+        let mut reactivated = 0;
+        while let Ok(builder) = self.data.spawn_receiver.try_recv() {
+            self.data.create_entity(builder);
+        }
+        unsafe { self.data.components.take_changed(&mut self.data.changed); }
+        let mut changed = mem::take(&mut self.data.changed);
+        let mut handled = HashSet::new();
+        let mut removed = HashSet::new();
+        // Consecutive activations are passed to the systems together.
+        let mut built = Vec::new();
+        for e in mem::take(&mut self.data.event_queue) {
             match e {
                 Event::BuildEntity(entity) => {
-                    unsafe { self.systems.activated(EntityData(self.data.entities.indexed(&entity)), &mut self.data.components); }
+                    handled.insert(entity);
+                    trace_event!(entity = entity.id(), "ecs: entity activated");
+                    built.push(entity);
+                    self.entity_events.push(EntityEvent::Created(entity));
                 },
                 Event::RemoveEntity(entity) => {
+                    self.activate_batch(&mut built);
+                    // The same entity can be queued for removal more than once, eg: by a system
+                    // and by `remove_entity_after()`.
+                    let indexed = match self.data.entities.try_indexed(&entity) {
+                        Some(indexed) if !removed.contains(&entity) => indexed,
+                        _ => continue,
+                    };
+                    handled.insert(entity);
+                    removed.insert(entity);
+                    trace_event!(entity = entity.id(), "ecs: entity removed");
                     unsafe {
-                        let indexed = self.data.entities.indexed(&entity);
-                        self.systems.deactivated(EntityData(indexed), &mut self.data.components);
+                        self.systems.deactivated(EntityData(indexed), &self.data.components);
+                        for query in self.data.queries.iter_mut().flatten() {
+                            query.deactivated(&EntityData(indexed));
+                        }
                         self.data.components.remove_all(indexed);
                     }
                     self.data.entities.remove(&entity);
+                    self.data.names.remove(&entity);
+                    self.data.despawning.remove(&entity);
+                    self.entity_events.push(EntityEvent::Removed(entity));
+                }
+            }
+        }
+        self.activate_batch(&mut built);
+        if !removed.is_empty() {
+            self.data.components.clear_refs(&|entity| removed.contains(entity));
+        }
+
+        // Entities that gained or lost components outside of `modify_entity`
+        changed.sort_by_key(|e| e.id());
+        changed.dedup();
+        for entity in changed {
+            if !handled.contains(&entity) && self.data.entities.is_valid(&entity) {
+                let indexed = self.data.entities.indexed(&entity);
+                unsafe { self.systems.reactivated(EntityData(indexed), &self.data.components); }
+                for query in self.data.queries.iter_mut().flatten() {
+                    query.reactivated(&EntityData(indexed), &self.data.components);
+                }
+                reactivated += 1;
+            }
+        }
+
+        if let Some(ref mut metrics) = self.data.metrics {
+            let events = &self.entity_events[events..];
+            let created = events.iter().filter(|e| matches!(e, EntityEvent::Created(_))).count();
+            metrics.counter("ecs.entities.created", created as u64);
+            metrics.counter("ecs.entities.removed", (events.len() - created) as u64);
+            metrics.counter("ecs.entities.reactivated", reactivated);
+            let report = self.data.components.memory_report();
+            self.data.capacities.resize(report.components.len(), 0);
+            for (component, capacity) in report.components.iter().zip(self.data.capacities.iter_mut()) {
+                if component.capacity > *capacity {
+                    metrics.gauge(&format!("ecs.components.{}.capacity", component.name), component.capacity as f64);
+                }
+                *capacity = component.capacity;
+            }
+            metrics.timing("ecs.flush", start.elapsed());
+        }
+    }
+
+    /// Counts down the entities given to `remove_entity_after()`, queueing the ones whose time is up.
+    fn tick_despawning(&mut self)
+    {
+        let mut expired = Vec::new();
+        self.data.despawning.retain(|&entity, remaining| {
+            *remaining -= 1;
+            if *remaining == 0 {
+                expired.push(entity);
+            }
+            *remaining > 0
+        });
+        // Sorted so that removals happen in the same order every run
+        expired.sort_by_key(|e| e.id());
+        self.data.event_queue.extend(expired.into_iter().map(Event::RemoveEntity));
+    }
+
+    /// Renumbers entity indices so they run from zero up to the number of entities, then frees
+    /// the unused component storage. Returns the number of entities that were moved.
+    ///
+    /// Pending changes are flushed first. Systems and queries see each moved entity deactivated
+    /// and then activated again with its new index.
+    pub fn compact(&mut self) -> usize
+    {
+        self.flush_queue();
+        let moved = self.data.entities.compact();
+        unsafe {
+            for (old, _) in &moved {
+                self.systems.deactivated(EntityData(old), &self.data.components);
+                for query in self.data.queries.iter_mut().flatten() {
+                    query.deactivated(&EntityData(old));
                 }
             }
+            for (old, new) in &moved {
+                self.data.components.remap(old, new);
+            }
+            let batch: Vec<_> = moved.iter().map(|(_, new)| EntityData(new)).collect();
+            self.systems.activated_batch(&batch, &self.data.components);
+            for query in self.data.queries.iter_mut().flatten() {
+                query.activated_batch(&batch, &self.data.components);
+            }
         }
+        self.data.components.shrink_to_fit();
+        moved.len()
     }
 
-    pub fn update(&mut self)
+    /// Applies queued entity changes without processing any systems, eg: between systems
+    /// processed with `process!`, or after creating entities outside of an update.
+    pub fn flush(&mut self)
     {
         self.flush_queue();
+    }
+
+    /// Reactivates the entities whose `#[detached]` fields gained or lost a component since the
+    /// last call, eg: once per rendered frame. Returns the number of entities reactivated.
+    ///
+    /// Changes to `#[detached]` fields are kept until then, so call it regularly.
+    pub fn flush_detached(&mut self) -> usize
+    {
+        let mut changed = Vec::new();
+        unsafe { self.data.components.take_detached_changed(&mut changed); }
+        changed.sort_by_key(|e| e.id());
+        changed.dedup();
+        let mut reactivated = 0;
+        for entity in changed {
+            if let Some(indexed) = self.data.entities.try_indexed(&entity) {
+                unsafe { self.systems.reactivated(EntityData(indexed), &self.data.components); }
+                for query in self.data.queries.iter_mut().flatten() {
+                    query.reactivated(&EntityData(indexed), &self.data.components);
+                }
+                reactivated += 1;
+            }
+        }
+        reactivated
+    }
+
+    /// Processes every system once, returning a report of what happened.
+    pub fn update(&mut self) -> FrameReport
+    {
+        trace_span!("ecs::update");
+        let start = Instant::now();
+        self.entity_events.clear();
+        if let Some(ref mut times) = self.data.times {
+            times.clear();
+        }
+        self.tick_despawning();
+        let queued_before_systems = self.data.event_queue.len();
+        self.flush_queue();
         unsafe { self.systems.update(&mut self.data); }
+        let queued_by_systems = self.data.event_queue.len();
         self.flush_queue();
+        let report = FrameReport {
+            created: self.entity_events.iter().filter(|e| matches!(e, EntityEvent::Created(_))).count(),
+            removed: self.entity_events.iter().filter(|e| matches!(e, EntityEvent::Removed(_))).count(),
+            queued_before_systems: queued_before_systems,
+            queued_by_systems: queued_by_systems,
+            system_times: self.data.times.clone().unwrap_or_default(),
+            duration: start.elapsed(),
+        };
+        if let Some(ref mut metrics) = self.data.metrics {
+            for time in &report.system_times {
+                metrics.timing(&format!("ecs.system.{}", time.system), time.duration);
+            }
+            metrics.gauge("ecs.entities", self.data.entities.count() as f64);
+            metrics.timing("ecs.update", report.duration);
+        }
+        report
+    }
+}
+
+/// 64-bit FNV-1a, which unlike the standard hasher is the same everywhere.
+struct StateHasher(u64);
+
+impl StateHasher
+{
+    fn new() -> StateHasher
+    {
+        StateHasher(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for StateHasher
+{
+    fn finish(&self) -> u64
+    {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8])
+    {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+/// Assembles a `World` from systems chosen at runtime, such as ones loaded from plugins.
+pub struct WorldBuilder<C: ComponentManager, M: ServiceManager>
+{
+    systems: DynamicSystems<C, M>,
+}
+
+impl<C: ComponentManager, M: ServiceManager> WorldBuilder<C, M>
+{
+    pub fn new() -> WorldBuilder<C, M>
+    {
+        WorldBuilder
+        {
+            systems: DynamicSystems::new(),
+        }
+    }
+
+    /// Adds a system. Systems are processed in the order they are added.
+    pub fn add_system(&mut self, system: BoxedProcess<C, M>)
+    {
+        self.systems.push(system);
+    }
+
+    /// Adds an entity process, wrapped in an `EntitySystem` filtering entities with `aspect`.
+    pub fn add_entity_system(&mut self, process: boxed!(EntityProcess<Components = C, Services = M>), aspect: Aspect<C>)
+    {
+        self.systems.push(Box::new(EntitySystem::new(process, aspect)));
+    }
+
+    pub fn build(self) -> World<DynamicSystems<C, M>>
+    {
+        let mut world = World::new();
+        world.systems = self.systems;
+        world
     }
 }
@@ -1,14 +1,19 @@
 
 #[macro_use]
 extern crate ecs;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
 
 use ecs::{BuildData, ModifyData};
-use ecs::{World, DataHelper};
+use ecs::{Aspect, Entity, EntityData, World, DataHelper};
 use ecs::{Process, System};
-use ecs::system::{EntityProcess, EntitySystem};
+use ecs::system::{EntityProcess, EntitySystem, InteractProcess, InteractSystem};
 use ecs::EntityIter;
+use ecs::manager::{GridBroadPhase, SpatialGridManager};
 
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Position
 {
     pub x: f32,
@@ -41,6 +46,36 @@ systems! {
     }
 }
 
+systems! {
+    GridSystems<TestComponents, ()> {
+        grid: SpatialGridManager<TestComponents> = SpatialGridManager::new(1.0, |e, c: &TestComponents| {
+            c.position.get(e).map(|p| (p.x, p.y))
+        })
+    }
+}
+
+systems! {
+    LogSystems<TestComponents, ()> {
+        log: ReactivationLog = ReactivationLog(Vec::new())
+    }
+}
+
+services! {
+    ContactServices {
+        contacts: Vec<(Entity, Entity)> = Vec::new()
+    }
+}
+
+systems! {
+    ContactSystems<TestComponents, ContactServices> {
+        contacts: InteractSystem<Contacts> = InteractSystem::new_with_broadphase(Contacts,
+                aspect!(<TestComponents> all: [position, team]),
+                aspect!(<TestComponents> all: [position]),
+                GridBroadPhase::new(1.0, |e, c: &TestComponents| c.position.get(e).map(|p| (p.x, p.y)))
+            )
+    }
+}
+
 pub struct HelloWorld(&'static str);
 impl Process for HelloWorld
 {
@@ -68,6 +103,38 @@ impl System for PrintPosition {
     fn is_active(&self) -> bool { false }
 }
 
+pub struct Contacts;
+impl InteractProcess for Contacts
+{
+    fn process(&self, a: EntityIter<TestComponents>, b: EntityIter<TestComponents>, co: &mut DataHelper<TestComponents, ContactServices>)
+    {
+        let b: Vec<_> = b.collect();
+        for x in a
+        {
+            for y in b.iter()
+            {
+                co.services.contacts.push((**x, ***y));
+            }
+        }
+    }
+}
+impl System for Contacts { type Components = TestComponents; type Services = ContactServices; }
+
+pub struct ReactivationLog(Vec<Entity>);
+impl Process for ReactivationLog
+{
+    fn process(&mut self, _: &mut DataHelper<TestComponents, ()>) {}
+}
+impl System for ReactivationLog
+{
+    type Components = TestComponents;
+    type Services = ();
+    fn reactivated(&mut self, e: &EntityData<TestComponents>, _: &TestComponents)
+    {
+        self.0.push(***e);
+    }
+}
+
 #[test]
 fn test_general_1()
 {
@@ -120,3 +187,4427 @@ fn test_general_1()
     world.systems.hello_world.0 = "Goodbye, World!";
     world.update();
 }
+
+#[test]
+fn test_component_registry()
+{
+    use ecs::ComponentRegistry;
+
+    let registry = ComponentRegistry::<TestComponents>::new();
+    assert_eq!(&["blank_data", "position", "team", "feature"], registry.names());
+    assert_eq!(Ok(2), registry.lookup("team"));
+
+    let err = registry.lookup("positon").unwrap_err();
+    assert_eq!("positon", err.name);
+    assert_eq!(vec!["position"], err.suggestions);
+    assert_eq!("unknown component `positon` (did you mean `position`?)", err.to_string());
+    assert!(registry.lookup("velocity").unwrap_err().suggestions.is_empty());
+}
+
+#[test]
+fn test_parse_aspect()
+{
+    use ecs::Error;
+
+    let mut world = World::<TestSystems>::new();
+    let placed = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+    });
+    let team = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+        c.team.add(&e, Team(1));
+    });
+
+    let aspect = Aspect::<TestComponents>::parse("position, !team").unwrap();
+    assert_eq!(vec!["position", "team"], aspect.info().fields);
+    assert!(!aspect.info().opaque);
+    world.with_entity_data(&placed, |e, c| assert!(aspect.check(&e, c)));
+    world.with_entity_data(&team, |e, c| assert!(!aspect.check(&e, c)));
+
+    let err = Aspect::<TestComponents>::parse("position, teem").err().unwrap();
+    let source = std::error::Error::source(&err).map(|source| source.to_string());
+    assert_eq!(Some("unknown component `teem` (did you mean `team`?)".to_string()), source);
+    match err
+    {
+        Error::UnknownComponent(err) => {
+            assert_eq!("teem", err.name);
+            assert_eq!(vec!["team"], err.suggestions);
+        },
+        _ => panic!("expected an unknown component"),
+    }
+}
+
+#[test]
+fn test_entity_names()
+{
+    let mut world = World::<TestSystems>::new();
+
+    let player = world.create_entity(());
+    let enemy = world.create_entity(());
+    assert!(world.set_name(&player, "player"));
+    assert!(world.set_name(&enemy, "enemy"));
+    assert_eq!(Some(player), world.names().entity("player"));
+    assert_eq!(Some("enemy"), world.names().name(&enemy));
+
+    // Names are unique
+    assert!(world.set_name(&enemy, "player"));
+    assert_eq!(Some(enemy), world.names().entity("player"));
+    assert_eq!(None, world.names().name(&player));
+    assert_eq!(None, world.names().entity("enemy"));
+
+    // Names are forgotten when the entity is removed
+    world.remove_entity(enemy);
+    world.update();
+    assert_eq!(None, world.names().entity("player"));
+    assert!(!world.set_name(&enemy, "enemy"));
+    assert_eq!(0, world.names().len());
+}
+
+#[test]
+fn test_spatial_grid()
+{
+    let mut world = World::<GridSystems>::new();
+
+    let near = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.5, y: 0.7 });
+    });
+    let far = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 3.2, y: 3.4 });
+    });
+    let left = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: -2.0, y: 0.0 });
+    });
+    world.create_entity(());
+    world.update();
+    assert_eq!(3, world.systems.grid.len());
+
+    let mut found: Vec<_> = world.systems.grid.query_aabb((0.0, 0.0), (4.0, 4.0)).map(|e| **e).collect();
+    found.sort_by_key(|e| e.id());
+    assert_eq!(vec![near, far], found);
+
+    let mut found: Vec<_> = world.systems.grid.query_radius((0.0, 0.0), 2.5).map(|e| **e).collect();
+    found.sort_by_key(|e| e.id());
+    assert_eq!(vec![near, left], found);
+
+    // Positions are refreshed each update
+    world.with_entity_data(&far, |e, c| c.position[e] = Position { x: 1.0, y: -1.0 });
+    world.update();
+    assert_eq!(Some((1.0, -1.0)), world.systems.grid.position(&far));
+    assert_eq!(2, world.systems.grid.query_radius((0.0, 0.0), 1.5).count());
+
+    // Entities without a position leave the grid
+    world.modify_entity(left, |e: ModifyData<TestComponents>, c: &mut TestComponents| {
+        c.position.remove(&e);
+    });
+    assert!(!world.systems.grid.contains(&left));
+    world.remove_entity(near);
+    world.update();
+    assert_eq!(1, world.systems.grid.len());
+}
+
+#[test]
+fn test_interact_broadphase()
+{
+    let mut world = World::<ContactSystems>::new();
+
+    let player = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+        c.team.add(&e, Team(1));
+    });
+    let close = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.5, y: -0.5 });
+    });
+    world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 5.0, y: 5.0 });
+    });
+    world.update();
+
+    assert_eq!(vec![(player, close)], world.services.contacts);
+}
+
+#[test]
+fn test_component_hooks()
+{
+    use std::sync::{Arc, Mutex};
+
+    let mut world = World::<TestSystems>::new();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let (add_log, remove_log) = (log.clone(), log.clone());
+    world.team.set_hooks(
+        move |_, team: &mut Team| add_log.lock().unwrap().push(("add", team.0)),
+        move |_, team: &mut Team| remove_log.lock().unwrap().push(("remove", team.0))
+    );
+
+    let entity = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.team.add(&e, Team(1));
+    });
+    world.modify_entity(entity, |e: ModifyData<TestComponents>, c: &mut TestComponents| {
+        c.team.insert(&e, Team(2));
+    });
+    world.remove_entity(entity);
+    world.update();
+
+    assert_eq!(vec![("add", 1), ("remove", 1), ("add", 2), ("remove", 2)], *log.lock().unwrap());
+}
+
+#[test]
+fn test_automatic_reactivation()
+{
+    let mut world = World::<LogSystems>::new();
+
+    let entity = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+    });
+    world.update();
+    assert!(world.systems.log.0.is_empty());
+
+    // Adding a component outside of `modify_entity` reactivates the entity at the next flush
+    world.with_entity_data(&entity, |e, c| { c.feature.set(&e, SomeFeature); });
+    world.update();
+    assert_eq!(vec![entity], world.systems.log.0);
+
+    // Changing an existing component isn't a structural change
+    world.with_entity_data(&entity, |e, c| { c.position.set(&e, Position { x: 1.0, y: 1.0 }); });
+    world.update();
+    assert_eq!(1, world.systems.log.0.len());
+
+    // `modify_entity` reactivates straight away, and only once
+    world.modify_entity(entity, |e: ModifyData<TestComponents>, c: &mut TestComponents| {
+        c.feature.remove(&e);
+    });
+    world.update();
+    assert_eq!(2, world.systems.log.0.len());
+}
+
+#[test]
+fn test_entity_events()
+{
+    use ecs::EntityEvent::{Created, Removed};
+
+    let mut world = World::<TestSystems>::new();
+    let a = world.create_entity(());
+    let b = world.create_entity(());
+    assert!(world.entity_events().is_empty());
+    world.update();
+    assert_eq!(&[Created(a), Created(b)], world.entity_events());
+
+    world.remove_entity(a);
+    world.update();
+    assert_eq!(&[Removed(a)], world.entity_events());
+
+    world.update();
+    assert!(world.entity_events().is_empty());
+}
+
+#[test]
+fn test_error_accessors()
+{
+    use ecs::Error;
+
+    let mut world = World::<TestSystems>::new();
+    let entity = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 1.0, y: 2.0 });
+    });
+
+    world.with_entity_data(&entity, |e, c| {
+        assert_eq!(Ok(Position { x: 1.0, y: 2.0 }), c.position.try_get(&e));
+        assert_eq!(Err(Error::MissingComponent { entity, component_name: "team" }), c.team.try_index(&e));
+        assert!(c.team.try_index_mut(&e).is_err());
+    });
+
+    world.remove_entity(entity);
+    world.update();
+    let result = world.try_modify_entity(entity, |_: ModifyData<TestComponents>, _: &mut TestComponents| {});
+    assert_eq!(Err(Error::NoSuchEntity(entity)), result);
+}
+
+#[test]
+fn test_entity_pairs()
+{
+    let mut world = World::<TestSystems>::new();
+    let a = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.team.add(&e, Team(1));
+    });
+    let b = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.team.add(&e, Team(2));
+    });
+
+    world.with_entity_pair(&a, &b, |a, b, c| {
+        let (x, y) = c.team.get_pair_mut(&a, &b).unwrap();
+        ::std::mem::swap(x, y);
+        assert!(c.team.get_pair_mut(&a, &a).is_none());
+        assert!(c.position.get_pair_mut(&a, &b).is_none());
+    }).unwrap();
+    world.with_entity_data(&a, |e, c| assert_eq!(Team(2), c.team[e]));
+
+    world.remove_entity(b);
+    world.update();
+    assert!(world.with_entity_pair(&a, &b, |_, _, _| ()).is_none());
+}
+
+#[test]
+fn test_pair_storage()
+{
+    components! {
+        PairComponents {
+            #[hot] hot: u32,
+            #[cold] cold: u32,
+            #[adaptive] adaptive: u32,
+            #[dense] dense: u32,
+            #[singleton] single: u32
+        }
+    }
+
+    systems! {
+        PairSystems<PairComponents, ()>;
+    }
+
+    let mut world = World::<PairSystems>::new();
+    let spawn = |world: &mut World<PairSystems>, value: u32| world.create_entity(move |e: BuildData<PairComponents>, c: &mut PairComponents| {
+        c.hot.add(&e, value);
+        c.cold.add(&e, value);
+        c.adaptive.add(&e, value);
+        c.dense.add(&e, value);
+    });
+    let a = spawn(&mut world, 1);
+    let b = spawn(&mut world, 2);
+    let missing = world.create_entity(());
+
+    world.with_entity_pair(&a, &b, |a, b, c| {
+        assert_eq!(Some((&mut 1, &mut 2)), c.hot.get_pair_mut(&a, &b));
+        assert_eq!(Some((&mut 1, &mut 2)), c.cold.get_pair_mut(&a, &b));
+        assert_eq!(Some((&mut 1, &mut 2)), c.adaptive.get_pair_mut(&a, &b));
+        assert_eq!(Some((&mut 2, &mut 1)), c.dense.get_pair_mut(&b, &a));
+        assert_eq!(None, c.single.get_pair_mut(&a, &b));
+    }).unwrap();
+    world.with_entity_pair(&a, &missing, |a, missing, c| {
+        assert_eq!(None, c.hot.get_pair_mut(&a, &missing));
+        assert_eq!(None, c.cold.get_pair_mut(&missing, &a));
+        assert_eq!(None, c.adaptive.get_pair_mut(&a, &missing));
+        assert_eq!(None, c.dense.get_pair_mut(&a, &missing));
+    }).unwrap();
+}
+
+#[test]
+fn test_entity_data_variants()
+{
+    let mut world = World::<TestSystems>::new();
+    let entities: Vec<Entity> = (0..3).map(|i| world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.team.add(&e, Team(i));
+    })).collect();
+    world.remove_entity(entities[1]);
+    world.update();
+
+    let teams = world.with_entity_datas(&entities, |e, c| {
+        c.team[e].0 += 10;
+        c.team[e]
+    });
+    assert_eq!(vec![Team(10), Team(12)], teams);
+
+    let world = &world;
+    assert_eq!(Some(Team(12)), world.with_entity_data_ref(&entities[2], |e, c| c.team[e]));
+    assert_eq!(None, world.with_entity_data_ref(&entities[1], |e, c| c.team[e]));
+}
+
+#[test]
+fn test_component_getters()
+{
+    let mut world = World::<TestSystems>::new();
+    let entity = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 1.0, y: 2.0 });
+    });
+    world.update();
+
+    assert!(world.is_alive(&entity));
+    assert!(world.has_component(&entity, |c| &c.position));
+    assert!(!world.has_component(&entity, |c| &c.team));
+    assert_eq!(Some(&Position { x: 1.0, y: 2.0 }), world.component(&entity, |c| &c.position));
+    assert_eq!(None, world.component(&entity, |c| &c.team));
+
+    world.remove_entity(entity);
+    world.update();
+    assert!(!world.is_alive(&entity));
+    assert!(!world.has_component(&entity, |c| &c.position));
+    assert_eq!(None, world.component(&entity, |c| &c.position));
+}
+
+#[test]
+fn test_world_builder()
+{
+    use ecs::WorldBuilder;
+
+    services! {
+        CountServices {
+            positions: usize = 0,
+            updates: usize = 0
+        }
+    }
+
+    struct CountUpdates;
+    impl System for CountUpdates { type Components = TestComponents; type Services = CountServices; }
+    impl Process for CountUpdates
+    {
+        fn process(&mut self, co: &mut DataHelper<TestComponents, CountServices>)
+        {
+            co.services.updates += 1;
+        }
+    }
+
+    struct CountPositions;
+    impl System for CountPositions { type Components = TestComponents; type Services = CountServices; }
+    impl EntityProcess for CountPositions
+    {
+        fn process(&mut self, en: EntityIter<TestComponents>, co: &mut DataHelper<TestComponents, CountServices>)
+        {
+            co.services.positions += en.count();
+        }
+    }
+
+    let mut builder = WorldBuilder::new();
+    builder.add_system(Box::new(CountUpdates));
+    builder.add_entity_system(Box::new(CountPositions), aspect!(<TestComponents> all: [position]));
+    let mut world = builder.build();
+
+    world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+    });
+    world.create_entity(());
+    world.update();
+    world.update();
+
+    assert_eq!(2, world.systems.len());
+    assert_eq!(2, world.services.updates);
+    assert_eq!(2, world.services.positions);
+}
+
+#[test]
+fn test_dynamic_systems()
+{
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Seen(Arc<AtomicUsize>);
+    impl System for Seen { type Components = TestComponents; type Services = (); }
+    impl EntityProcess for Seen
+    {
+        fn process(&mut self, en: EntityIter<TestComponents>, _: &mut DataHelper<TestComponents, ()>)
+        {
+            self.0.store(en.count(), Ordering::SeqCst);
+        }
+    }
+
+    let mut world = World::<TestSystems>::new();
+    world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+    });
+    world.update();
+    world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 1.0, y: 0.0 });
+    });
+
+    // Both the active and the queued entity are seen, each once
+    let seen = Arc::new(AtomicUsize::new(0));
+    let system = EntitySystem::new(Seen(seen.clone()), aspect!(<TestComponents> all: [position]));
+    assert!(world.add_dynamic_system("seen", Box::new(system)).is_none());
+    world.update();
+    assert_eq!(2, seen.load(Ordering::SeqCst));
+
+    assert!(world.remove_dynamic_system("seen").is_some());
+    assert!(world.remove_dynamic_system("seen").is_none());
+
+    assert!(world.systems.add_dynamic("hello", Box::new(HelloWorld("Hello, Dynamic!"))).is_none());
+    world.update();
+    assert!(world.systems.remove_dynamic("hello").is_some());
+}
+
+#[test]
+fn test_flag_components()
+{
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    pub struct Frozen;
+
+    components! {
+        FlagComponents {
+            #[hot] position: Position,
+            #[flag] frozen: Frozen
+        }
+    }
+
+    systems! {
+        FlagSystems<FlagComponents, ()>;
+    }
+
+    let mut world = World::<FlagSystems>::new();
+    let entity = world.create_entity(|e: BuildData<FlagComponents>, c: &mut FlagComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+        assert!(!c.frozen.add(&e, Frozen));
+    });
+    world.create_entity(|e: BuildData<FlagComponents>, c: &mut FlagComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+    });
+    world.update();
+
+    let frozen = world.entities().filter(aspect!(<FlagComponents> all: [frozen]), &world).count();
+    assert_eq!(1, frozen);
+
+    world.modify_entity(entity, |e: ModifyData<FlagComponents>, c: &mut FlagComponents| {
+        assert!(c.frozen.has(&e));
+        assert!(c.frozen.insert(&e, Frozen));
+        assert!(c.frozen.remove(&e));
+        assert!(!c.frozen.remove(&e));
+    });
+    let frozen = world.entities().filter(aspect!(<FlagComponents> all: [frozen]), &world).count();
+    assert_eq!(0, frozen);
+}
+
+#[test]
+fn test_singleton_components()
+{
+    use ecs::{Error, SingletonPolicy};
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    pub struct Camera(f32);
+
+    components! {
+        CameraComponents {
+            #[singleton] camera: Camera
+        }
+    }
+
+    systems! {
+        CameraSystems<CameraComponents, ()>;
+    }
+
+    let mut world = World::<CameraSystems>::new();
+    assert_eq!(None, world.camera.get_owner());
+
+    let first = world.create_entity(|e: BuildData<CameraComponents>, c: &mut CameraComponents| {
+        c.camera.add(&e, Camera(1.0));
+    });
+    assert_eq!(Some(first), world.camera.get_owner());
+
+    // Replacing the component on the owner keeps ownership
+    world.modify_entity(first, |e: ModifyData<CameraComponents>, c: &mut CameraComponents| {
+        assert_eq!(Some(Camera(1.0)), c.camera.insert(&e, Camera(2.0)));
+    });
+
+    // Giving it to another entity takes it away from the first
+    let second = world.create_entity(|e: BuildData<CameraComponents>, c: &mut CameraComponents| {
+        assert_eq!(None, c.camera.add(&e, Camera(3.0)));
+    });
+    assert_eq!(Some(second), world.camera.get_owner());
+    world.with_entity_data(&first, |e, c| assert!(!c.camera.has(&e)));
+    world.with_entity_data(&second, |e, c| assert_eq!(Camera(3.0), c.camera[e]));
+
+    // With the panic policy, the owner keeps the component
+    world.camera.set_singleton_policy(SingletonPolicy::Panic);
+    world.with_entity_data(&first, |e, c| {
+        match c.camera.try_set(&e, Camera(4.0))
+        {
+            Err(Error::SingletonTaken { owner, .. }) => assert_eq!(second, owner),
+            other => panic!("expected SingletonTaken, got {:?}", other),
+        }
+    });
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        world.with_entity_data(&first, |e, c| { c.camera.set(&e, Camera(4.0)); });
+    }));
+    assert!(panicked.is_err());
+    assert_eq!(Some(second), world.camera.get_owner());
+    world.with_entity_data(&second, |e, c| assert_eq!(Camera(3.0), c.camera[e]));
+    world.with_entity_data(&second, |e, c| assert_eq!(Some(Camera(3.0)), c.camera.try_set(&e, Camera(5.0)).unwrap()));
+
+    world.remove_entity(second);
+    world.update();
+    assert_eq!(None, world.camera.get_owner());
+}
+
+#[test]
+fn test_dense_components()
+{
+    components! {
+        DenseComponents {
+            #[dense] position: Position
+        }
+    }
+
+    systems! {
+        DenseSystems<DenseComponents, ()>;
+    }
+
+    let mut world = World::<DenseSystems>::new();
+    let entities: Vec<_> = (0..3).map(|i| world.create_entity(move |e: BuildData<DenseComponents>, c: &mut DenseComponents| {
+        c.position.add(&e, Position { x: i as f32, y: 0.0 });
+    })).collect();
+    assert_eq!(3, world.position.as_slice().unwrap().len());
+
+    world.remove_entity(entities[0]);
+    world.update();
+    assert_eq!(2, world.position.as_slice().unwrap().len());
+    world.with_entity_data(&entities[1], |e, c| assert_eq!(1.0, c.position[e].x));
+    world.with_entity_data(&entities[2], |e, c| assert_eq!(2.0, c.position[e].x));
+
+    for p in world.position.as_mut_slice().unwrap()
+    {
+        p.y += 1.0;
+    }
+    world.with_entity_data(&entities[2], |e, c| assert_eq!(Position { x: 2.0, y: 1.0 }, c.position[e]));
+}
+
+#[test]
+fn test_sparse_vec()
+{
+    use ecs::component::SparseVec;
+
+    let mut v = SparseVec::new();
+    assert!(v.is_empty());
+    assert_eq!(None, v.insert(3, "c"));
+    assert_eq!(None, v.insert(130, "z"));
+    assert_eq!(None, v.insert(0, "a"));
+    assert_eq!(Some("c"), v.insert(3, "C"));
+    assert_eq!(3, v.len());
+    assert_eq!(vec![0, 3, 130], v.keys().collect::<Vec<_>>());
+
+    assert_eq!(Some("a"), v.remove(0));
+    assert_eq!(None, v.remove(0));
+    assert!(!v.contains_key(0));
+    assert_eq!(vec![(3, &"C"), (130, &"z")], v.iter().collect::<Vec<_>>());
+}
+
+#[test]
+fn test_adaptive_components()
+{
+    components! {
+        AdaptiveComponents {
+            #[adaptive] position: Position
+        }
+    }
+
+    systems! {
+        AdaptiveSystems<AdaptiveComponents, ()>;
+    }
+
+    let mut world = World::<AdaptiveSystems>::new();
+    let entities: Vec<_> = (0..200).map(|i| world.create_entity(move |e: BuildData<AdaptiveComponents>, c: &mut AdaptiveComponents| {
+        c.position.add(&e, Position { x: i as f32, y: 0.0 });
+    })).collect();
+
+    // Thin out the list enough to unpack, then fill it up again
+    for &entity in &entities[..190]
+    {
+        world.modify_entity(entity, |e: ModifyData<AdaptiveComponents>, c: &mut AdaptiveComponents| { c.position.remove(&e); });
+    }
+    for (i, entity) in entities.iter().enumerate()
+    {
+        world.modify_entity(*entity, |e: ModifyData<AdaptiveComponents>, c: &mut AdaptiveComponents| {
+            assert_eq!(i >= 190, c.position.has(&e));
+            c.position.insert(&e, Position { x: i as f32, y: 1.0 });
+        });
+    }
+    for (i, entity) in entities.iter().enumerate()
+    {
+        world.with_entity_data(entity, |e, c| assert_eq!(Position { x: i as f32, y: 1.0 }, c.position[e]));
+    }
+}
+
+#[test]
+fn test_component_entry()
+{
+    let mut world = World::<TestSystems>::new();
+    let entity = world.create_entity(());
+
+    world.with_entity_data(&entity, |e, c| {
+        assert!(!c.team.entry(&e).is_occupied());
+        c.team.entry(&e).or_insert(Team(1)).0 += 1;
+        assert_eq!(Team(2), c.team[e]);
+
+        c.team.entry(&e).and_modify(|t| t.0 *= 10).or_insert_with(|| panic!("already has a team"));
+        assert_eq!(Team(20), c.team[e]);
+    });
+}
+
+#[test]
+fn test_memory_report()
+{
+    let mut world = World::<TestSystems>::new();
+    for i in 0..100
+    {
+        world.create_entity(move |e: BuildData<TestComponents>, c: &mut TestComponents| {
+            c.position.add(&e, Position { x: i as f32, y: 0.0 });
+            if i % 10 == 0
+            {
+                c.team.add(&e, Team(0));
+            }
+        });
+    }
+
+    let report = world.memory_report();
+    assert_eq!(vec!["blank_data", "position", "team", "feature"], report.components.iter().map(|c| c.name).collect::<Vec<_>>());
+    assert_eq!(100, report.get("position").unwrap().len);
+    assert_eq!(10, report.get("team").unwrap().len);
+    assert_eq!(0, report.get("feature").unwrap().len);
+    assert!(report.get("position").unwrap().bytes >= 100 * std::mem::size_of::<Position>());
+    assert!(report.total_bytes() >= report.get("position").unwrap().bytes + report.get("team").unwrap().bytes);
+
+    world.team.reserve(1000);
+    assert!(world.team.capacity() >= 1010);
+    world.team.shrink_to_fit();
+    assert!(world.team.capacity() < 1000);
+    assert_eq!(10, world.team.len());
+}
+
+#[test]
+fn test_world_with_capacity()
+{
+    let mut world = World::<TestSystems>::with_capacity(500);
+    assert!(world.position.capacity() >= 500);
+    assert!(world.team.capacity() >= 500);
+
+    let entities: Vec<_> = (0..500).map(|_| world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+    })).collect();
+    world.update();
+    assert_eq!(500, world.entities().count());
+    assert!(entities.iter().all(|e| world.with_entity_data(e, |e, c| c.position.has(&e)).unwrap()));
+}
+
+#[test]
+fn test_entity_spawner()
+{
+    let mut world = World::<TestSystems>::new();
+    let spawner = world.spawner();
+
+    let threads: Vec<_> = (0..4).map(|i| {
+        let spawner = spawner.clone();
+        std::thread::spawn(move || {
+            for j in 0..10
+            {
+                spawner.spawn(move |e: BuildData<TestComponents>, c: &mut TestComponents| {
+                    c.position.add(&e, Position { x: i as f32, y: j as f32 });
+                });
+            }
+        })
+    }).collect();
+    for thread in threads
+    {
+        thread.join().unwrap();
+    }
+    assert_eq!(0, world.entities().count());
+
+    world.update();
+    assert_eq!(40, world.entities().count());
+    assert_eq!(40, world.position.len());
+    assert_eq!(40, world.entity_events().len());
+}
+
+#[cfg(feature = "send")]
+#[test]
+fn test_world_is_send()
+{
+    fn assert_send<T: Send>() {}
+    assert_send::<World<TestSystems>>();
+    assert_send::<World<GridSystems>>();
+    assert_send::<World<ContactSystems>>();
+}
+
+#[test]
+fn test_extract()
+{
+    use ecs::Extract;
+
+    #[derive(Default)]
+    struct Positions(Vec<(Entity, Position)>);
+    impl Extract<TestComponents> for Positions
+    {
+        fn begin(&mut self)
+        {
+            self.0.clear();
+        }
+
+        fn extract(&mut self, e: EntityData<TestComponents>, c: &TestComponents)
+        {
+            if let Some(p) = c.position.get(&e)
+            {
+                self.0.push((**e, p));
+            }
+        }
+    }
+
+    let mut world = World::<TestSystems>::new();
+    let entity = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 1.0, y: 2.0 });
+    });
+    world.create_entity(());
+    world.update();
+
+    let mut snapshot = Positions::default();
+    world.extract(&mut snapshot);
+    world.extract(&mut snapshot);
+    let snapshot = std::thread::spawn(move || snapshot).join().unwrap();
+    assert_eq!(vec![(entity, Position { x: 1.0, y: 2.0 })], snapshot.0);
+}
+
+#[test]
+fn test_panic_isolation()
+{
+    pub struct Counter(u32, bool);
+    impl System for Counter { type Components = TestComponents; type Services = (); }
+    impl Process for Counter
+    {
+        fn process(&mut self, _: &mut DataHelper<TestComponents, ()>)
+        {
+            self.0 += 1;
+            if self.1
+            {
+                panic!("boom");
+            }
+        }
+    }
+
+    systems! {
+        PanicSystems<TestComponents, ()> {
+            exploding: Counter = Counter(0, true),
+            after: Counter = Counter(0, false)
+        }
+    }
+
+    let mut world = World::<PanicSystems>::new();
+    assert!(world.system_failures().is_none());
+    world.isolate_panics(true);
+
+    world.update();
+    world.update();
+    assert_eq!(1, world.systems.exploding.0);
+    assert_eq!(2, world.systems.after.0);
+    assert_eq!("exploding", world.system_failures().unwrap().failures()[0].system);
+    assert_eq!("boom", world.system_failures().unwrap().failures()[0].message);
+
+    // A reset system runs again
+    world.systems.exploding.1 = false;
+    assert!(world.system_failures_mut().unwrap().reset("exploding"));
+    world.update();
+    assert_eq!(2, world.systems.exploding.0);
+    assert!(world.system_failures().unwrap().failures().is_empty());
+}
+
+#[test]
+fn test_state_hash()
+{
+    #[derive(Copy, Clone, Debug, Hash, PartialEq)]
+    pub struct Health(u32);
+
+    components! {
+        HashComponents {
+            #[hot] position: Position,
+            #[cold] #[hashable] health: Health,
+            #[flag] #[hashable] frozen: ()
+        }
+    }
+
+    systems! {
+        HashSystems<HashComponents, ()>;
+    }
+
+    fn simulate() -> (World<HashSystems>, Entity)
+    {
+        let mut world = World::<HashSystems>::new();
+        let entity = world.create_entity(|e: BuildData<HashComponents>, c: &mut HashComponents| {
+            c.position.add(&e, Position { x: 0.0, y: 0.0 });
+            c.health.add(&e, Health(10));
+        });
+        world.create_entity(|e: BuildData<HashComponents>, c: &mut HashComponents| {
+            c.frozen.add(&e, ());
+        });
+        world.update();
+        (world, entity)
+    }
+
+    let (mut a, entity) = simulate();
+    let (b, _) = simulate();
+    assert_eq!(a.state_hash(), b.state_hash());
+
+    // Only `#[hashable]` components count
+    a.with_entity_data(&entity, |e, c| c.position[e].x = 5.0);
+    assert_eq!(a.state_hash(), b.state_hash());
+    a.with_entity_data(&entity, |e, c| c.health[e].0 -= 1);
+    assert!(a.state_hash() != b.state_hash());
+}
+
+#[test]
+fn test_record_and_replay()
+{
+    use ecs::Error;
+    use ecs::replay::{Recorder, Replay};
+
+    components! {
+        ReplayComponents {
+            #[hot] #[hashable] health: u32
+        }
+    }
+
+    systems! {
+        ReplaySystems<ReplayComponents, ()>;
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Input
+    {
+        Spawn(u32),
+        Hit(Entity),
+    }
+
+    fn apply(world: &mut World<ReplaySystems>, input: &Input)
+    {
+        match *input
+        {
+            Input::Spawn(health) => { world.create_entity(move |e: BuildData<ReplayComponents>, c: &mut ReplayComponents| {
+                c.health.add(&e, health);
+            }); },
+            Input::Hit(entity) => { world.with_entity_data(&entity, |e, c| c.health[e] -= 1); },
+        }
+    }
+
+    let mut world = World::<ReplaySystems>::new();
+    let mut recorder = Recorder::new();
+    recorder.input(Input::Spawn(3));
+    recorder.input(Input::Spawn(5));
+    recorder.update(&mut world, apply);
+    let first = world.entities().map(|e| **e).min_by_key(|e| e.id()).unwrap();
+    recorder.input(Input::Hit(first));
+    recorder.update(&mut world, apply);
+    recorder.update(&mut world, apply);
+    let recording = recorder.finish();
+    assert_eq!(3, recording.frames.len());
+
+    let mut replay = Replay::new(recording.clone());
+    let mut fresh = World::<ReplaySystems>::new();
+    while replay.update(&mut fresh, apply).unwrap() {}
+    assert!(replay.is_finished());
+    assert_eq!(world.state_hash(), fresh.state_hash());
+
+    // Applying inputs differently is caught on the frame it happens
+    let mut replay = Replay::new(recording);
+    let mut fresh = World::<ReplaySystems>::new();
+    let double = |world: &mut World<ReplaySystems>, input: &Input| { apply(world, input); if let Input::Hit(_) = *input { apply(world, input); } };
+    assert_eq!(Ok(true), replay.update(&mut fresh, double));
+    assert_eq!(Err(Error::Desync { frame: 1 }), replay.update(&mut fresh, double));
+}
+
+#[test]
+fn test_history()
+{
+    use ecs::history::History;
+
+    let mut world = World::<TestSystems>::new();
+    let mut history = History::new();
+    history.track(|c: &mut TestComponents| &mut c.position);
+
+    let entity = history.create_entity(&mut world.data, |e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 1.0, y: 1.0 });
+    });
+    world.update();
+    assert!(history.set(&mut world.data, entity, |c| &mut c.position, Position { x: 2.0, y: 2.0 }));
+    assert!(history.set(&mut world.data, entity, |c| &mut c.team, Team(3)));
+    assert!(history.remove_entity(&mut world.data, entity));
+    world.update();
+    assert_eq!(0, world.entities().count());
+
+    // Undoing the removal brings back the tracked components only
+    assert!(history.undo(&mut world.data));
+    world.update();
+    let restored = history.entity(entity);
+    assert!(restored != entity);
+    world.with_entity_data(&restored, |e, c| {
+        assert_eq!(Position { x: 2.0, y: 2.0 }, c.position[e]);
+        assert!(!c.team.has(&e));
+    });
+
+    assert!(history.undo(&mut world.data));
+    assert!(history.undo(&mut world.data));
+    world.with_entity_data(&restored, |e, c| assert_eq!(Position { x: 1.0, y: 1.0 }, c.position[e]));
+    assert!(history.undo(&mut world.data));
+    assert!(!history.can_undo());
+    world.update();
+    assert_eq!(0, world.entities().count());
+
+    // Redo everything, still referring to the entity by its first id
+    while history.redo(&mut world.data) {}
+    world.update();
+    assert_eq!(0, world.entities().count());
+    assert!(history.undo(&mut world.data));
+    world.update();
+    world.with_entity_data(&history.entity(entity), |e, c| assert_eq!(Position { x: 2.0, y: 2.0 }, c.position[e]));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_scene()
+{
+    use ecs::Error;
+    use ecs::scene::Scene;
+
+    components! {
+        SceneComponents {
+            #[hot] #[persist] health: u32,
+            #[cold] #[persist] label: String,
+            #[flag] #[persist] frozen: (),
+            #[hot] position: Position
+        }
+    }
+
+    systems! {
+        SceneSystems<SceneComponents, ()>;
+    }
+
+    let mut world = World::<SceneSystems>::new();
+    let scene = Scene::from_json(r#"{
+        "entities": [
+            { "name": "player", "components": { "health": 10, "label": "hero" } },
+            { "components": { "frozen": null } }
+        ]
+    }"#).unwrap();
+    let entities = scene.spawn(&mut world.data).unwrap();
+    world.update();
+    assert_eq!(2, world.entities().count());
+    assert_eq!(Some(entities[0]), world.names().entity("player"));
+    world.with_entity_data(&entities[0], |e, c| {
+        assert_eq!(10, c.health[e]);
+        assert_eq!("hero", c.label[e]);
+    });
+    world.with_entity_data(&entities[1], |e, c| assert!(c.frozen.has(&e)));
+
+    // Nothing is created when any entity can't be loaded
+    for json in &[
+        r#"{ "entities": [{ "components": { "health": 1 } }, { "components": { "helth": 1 } }] }"#,
+        r#"{ "entities": [{ "components": { "health": 1 } }, { "components": { "health": "full" } }] }"#,
+        r#"{ "entities": [{ "components": { "health": 1 } }, { "components": { "position": {} } }] }"#,
+    ]
+    {
+        assert!(Scene::from_json(json).unwrap().spawn(&mut world.data).is_err());
+        world.update();
+        assert_eq!(2, world.entities().count());
+    }
+    match Scene::from_json(r#"{ "entities": {} }"#)
+    {
+        Err(Error::Parse(_)) => (),
+        other => panic!("expected a parse error, got {:?}", other),
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_snapshot_migrations()
+{
+    use ecs::Error;
+    use ecs::scene::Migrations;
+
+    #[derive(Deserialize)]
+    pub struct PositionV1(f32, f32);
+
+    impl From<PositionV1> for Position
+    {
+        fn from(old: PositionV1) -> Position
+        {
+            Position { x: old.0, y: old.1 }
+        }
+    }
+
+    components! {
+        SnapshotComponents {
+            #[hot] #[persist] position: Position,
+            #[cold] #[persist] health: u32,
+            #[hot] velocity: Position
+        }
+    }
+
+    systems! {
+        SnapshotSystems<SnapshotComponents, ()>;
+    }
+
+    let mut migrations = Migrations::new(2);
+    migrations.register_migration::<PositionV1, Position>("position", 2);
+
+    let old = migrations.load_json(r#"{
+        "version": 1,
+        "entities": [{ "name": "player", "components": { "position": [1.0, 2.0], "health": 5 } }]
+    }"#).unwrap();
+    assert_eq!(2, old.version);
+    let mut world = World::<SnapshotSystems>::new();
+    let player = old.spawn(&mut world.data).unwrap()[0];
+    world.create_entity(|e: BuildData<SnapshotComponents>, c: &mut SnapshotComponents| {
+        c.velocity.add(&e, Position { x: 1.0, y: 0.0 });
+    });
+    world.update();
+    world.with_entity_data(&player, |e, c| assert_eq!(Position { x: 1.0, y: 2.0 }, c.position[e]));
+
+    // Entities without names or `#[persist]` components are left out
+    let snapshot = world.snapshot(migrations.version()).unwrap();
+    assert_eq!(old.to_value(), snapshot.to_value());
+    assert_eq!(snapshot.to_value(), migrations.load_json(&snapshot.to_json()).unwrap().to_value());
+
+    match migrations.load_json(r#"{ "version": 3, "entities": [] }"#)
+    {
+        Err(Error::UnsupportedVersion { version: 3, supported: 2 }) => (),
+        other => panic!("expected an unsupported version, got {:?}", other),
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_entity_serialization()
+{
+    use ecs::Error;
+
+    components! {
+        CopyComponents {
+            #[hot] #[persist] position: Position,
+            #[flag] #[persist] selected: (),
+            #[hot] velocity: Position
+        }
+    }
+
+    systems! {
+        CopySystems<CopyComponents, ()>;
+    }
+
+    let mut world = World::<CopySystems>::new();
+    let original = world.create_entity(|e: BuildData<CopyComponents>, c: &mut CopyComponents| {
+        c.position.add(&e, Position { x: 3.0, y: 4.0 });
+        c.selected.add(&e, ());
+        c.velocity.add(&e, Position { x: 1.0, y: 0.0 });
+    });
+    world.update();
+    world.set_name(&original, "original");
+
+    let bytes = world.serialize_entity(original).unwrap();
+    let copy = world.deserialize_entity(&bytes).unwrap();
+    world.update();
+    assert!(copy != original);
+    assert_eq!(Some(original), world.names().entity("original"));
+    world.with_entity_data(&copy, |e, c| {
+        assert_eq!(Position { x: 3.0, y: 4.0 }, c.position[e]);
+        assert!(c.selected.has(&e));
+        assert!(!c.velocity.has(&e));
+    });
+
+    world.remove_entity(original);
+    world.update();
+    assert_eq!(Err(Error::NoSuchEntity(original)), world.serialize_entity(original));
+    assert!(world.deserialize_entity(b"not json").is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_chunk_streaming()
+{
+    use std::sync::{Arc, Mutex};
+    use ecs::stream::{chunk_at, ChunkEvent, ChunkStreamer};
+
+    components! {
+        #[persist]
+        StreamComponents {
+            #[hot] position: Position
+        }
+    }
+
+    systems! {
+        StreamSystems<StreamComponents, ()>;
+    }
+
+    let mut world = World::<StreamSystems>::new();
+    for x in [0.0, 5.0, 25.0, 45.0]
+    {
+        world.create_entity(move |e: BuildData<StreamComponents>, c: &mut StreamComponents| {
+            c.position.add(&e, Position { x, y: 0.0 });
+        });
+    }
+    world.update();
+
+    let mut streamer = ChunkStreamer::new(|e, c: &StreamComponents| {
+        c.position.get(e).map(|p| chunk_at(p.x, p.y, 10.0))
+    });
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = seen.clone();
+    streamer.on_event(move |_, event| log.lock().unwrap().push(event.clone()));
+
+    // Only the chunks next to (0, 0) stay loaded
+    let events = streamer.update(&mut world, (0, 0), 1).unwrap();
+    assert_eq!(vec![ChunkEvent::Unloaded((2, 0)), ChunkEvent::Unloaded((4, 0))], events);
+    assert_eq!(2, world.entities().count());
+    assert_eq!(vec![(2, 0), (4, 0)], streamer.unloaded_chunks());
+
+    // Moving brings back the far chunks and unloads the first
+    let events = streamer.update(&mut world, (3, 0), 1).unwrap();
+    assert_eq!(3, events.len());
+    assert_eq!(ChunkEvent::Unloaded((0, 0)), events[0]);
+    match events[2]
+    {
+        ChunkEvent::Loaded((4, 0), ref entities) => assert_eq!(1, entities.len()),
+        ref other => panic!("expected chunk (4, 0) to load, got {:?}", other),
+    }
+    assert!(streamer.is_unloaded((0, 0)));
+    assert_eq!(2, world.entities().count());
+    assert_eq!(5, seen.lock().unwrap().len());
+
+    assert_eq!(2, streamer.load(&mut world, (0, 0)).unwrap().len());
+    assert_eq!(1, streamer.unload(&mut world, (4, 0)).unwrap());
+    assert_eq!(3, world.entities().count());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_script_view()
+{
+    use ecs::script::ScriptView;
+    use ecs::scene::Value;
+
+    components! {
+        ScriptComponents {
+            #[hot] #[persist] position: Position,
+            #[hot] velocity: Position
+        }
+    }
+
+    systems! {
+        ScriptSystems<ScriptComponents, ()>;
+    }
+
+    let mut world = World::<ScriptSystems>::new();
+    let spawned = {
+        let mut view = ScriptView::new(&mut world.data);
+        let position: Value = r#"{ "x": 1.0, "y": 2.0 }"#.parse().unwrap();
+        view.spawn(vec![("position".to_string(), position)]).unwrap()
+    };
+    world.update();
+
+    let mut view = ScriptView::new(&mut world.data);
+    assert_eq!(&["position", "velocity"], view.names());
+    let position = view.get(&spawned, "position").unwrap().unwrap();
+    assert_eq!(Some(2.0), position["y"].as_f64());
+    assert_eq!(None, view.get(&spawned, "velocity").unwrap());
+    assert!(view.get(&spawned, "postion").is_err());
+
+    let mut moved = position.clone();
+    moved["x"] = Value::from(5.0);
+    view.set(&spawned, "position", moved).unwrap();
+    assert!(view.set(&spawned, "velocity", position).is_err());
+    view.despawn(spawned);
+    world.with_entity_data(&spawned, |e, c| assert_eq!(5.0, c.position[e].x));
+    world.update();
+    assert_eq!(0, world.entities().count());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_plugin_system()
+{
+    use ecs::plugin::{Plugin, PluginHost, PluginSystem};
+    use ecs::scene::Value;
+
+    components! {
+        #[persist]
+        PluginComponents {
+            #[hot] position: Position
+        }
+    }
+
+    systems! {
+        PluginSystems<PluginComponents, ()>;
+    }
+
+    // Stands in for a WebAssembly module, which would only see bytes
+    struct Drift;
+    impl Plugin for Drift
+    {
+        fn update(&mut self, host: &mut dyn PluginHost)
+        {
+            let position = host.component_id("position").unwrap();
+            assert_eq!(None, host.component_id("velocity"));
+            for i in 0..host.entity_count()
+            {
+                let entity = host.entity(i);
+                let mut value: Value = String::from_utf8(host.get(entity, position).unwrap()).unwrap().parse().unwrap();
+                value["x"] = Value::from(value["x"].as_f64().unwrap() + 1.0);
+                assert!(host.set(entity, position, value.to_string().as_bytes()));
+                assert!(!host.set(entity, position, b"not json"));
+            }
+        }
+    }
+
+    let mut world = World::<PluginSystems>::new();
+    let entity = world.create_entity(|e: BuildData<PluginComponents>, c: &mut PluginComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+    });
+    world.add_dynamic_system("drift", Box::new(PluginSystem::new(Box::new(Drift))));
+    world.update();
+    world.update();
+    world.with_entity_data(&entity, |e, c| assert_eq!(2.0, c.position[e].x));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_console()
+{
+    use ecs::console::Console;
+
+    components! {
+        #[persist]
+        ConsoleComponents {
+            #[hot] position: Position,
+            #[flag] selected: ()
+        }
+    }
+
+    systems! {
+        ConsoleSystems<ConsoleComponents, ()>;
+    }
+
+    let mut world = World::<ConsoleSystems>::new();
+    let mut console = Console::new();
+    console.register("count", Box::new(|_, data: &mut DataHelper<ConsoleComponents, ()>| Ok(data.entities().count().to_string())));
+
+    let id = console.run("spawn", &mut world.data).unwrap();
+    console.run("spawn", &mut world.data).unwrap();
+    world.update();
+    assert_eq!("", console.run(&format!("set {} position 1 2", id), &mut world.data).unwrap());
+    console.run(&format!("set {} selected null", id), &mut world.data).unwrap();
+    assert_eq!(r#"{"x":1.0,"y":2.0}"#, console.run(&format!("get {} position", id), &mut world.data).unwrap());
+    assert_eq!(id, console.run("list position selected", &mut world.data).unwrap());
+    assert_eq!("2", console.run("count", &mut world.data).unwrap());
+
+    console.run(&format!("destroy {}", id), &mut world.data).unwrap();
+    world.update();
+    assert_eq!("1", console.run("count", &mut world.data).unwrap());
+    assert!(console.run(&format!("get {} position", id), &mut world.data).is_err());
+    assert!(console.run("teleport 1", &mut world.data).is_err());
+    assert!(console.run("help", &mut world.data).unwrap().contains("count"));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_archive_entity()
+{
+    use ecs::scene::ArchivedEntity;
+
+    components! {
+        #[persist]
+        ArchiveComponents {
+            #[hot] position: Position,
+            #[hot] #[transient] velocity: Position
+        }
+    }
+
+    systems! {
+        ArchiveSystems<ArchiveComponents, ()>;
+    }
+
+    let mut world = World::<ArchiveSystems>::new();
+    let entity = world.create_entity(|e: BuildData<ArchiveComponents>, c: &mut ArchiveComponents| {
+        c.position.add(&e, Position { x: 1.0, y: 2.0 });
+        c.velocity.add(&e, Position { x: 1.0, y: 0.0 });
+    });
+    world.update();
+    world.set_name(&entity, "chest");
+
+    let archived = world.archive_entity(entity).unwrap();
+    world.update();
+    assert_eq!(0, world.entities().count());
+    assert_eq!(None, world.names().entity("chest"));
+
+    let stored = ArchivedEntity::from_bytes(archived.as_bytes().to_vec());
+    let restored = world.unarchive(&stored).unwrap();
+    world.update();
+    assert!(restored != entity);
+    assert_eq!(Some(restored), world.names().entity("chest"));
+    world.with_entity_data(&restored, |e, c| {
+        assert_eq!(Position { x: 1.0, y: 2.0 }, c.position[e]);
+        assert!(!c.velocity.has(&e));
+    });
+}
+
+#[test]
+fn test_blackboard()
+{
+    use ecs::manager::Blackboard;
+
+    #[derive(Debug, PartialEq)]
+    pub struct Score(u32);
+
+    pub struct AddScore;
+    impl System for AddScore { type Components = TestComponents; type Services = Blackboard; }
+    impl Process for AddScore
+    {
+        fn process(&mut self, data: &mut DataHelper<TestComponents, Blackboard>)
+        {
+            data.services.get_or_insert_with(|| Score(0)).0 += 10;
+        }
+    }
+
+    systems! {
+        BlackboardSystems<TestComponents, Blackboard> {
+            add_score: AddScore = AddScore
+        }
+    }
+
+    let mut world = World::<BlackboardSystems>::new();
+    assert!(world.services.is_empty());
+    world.update();
+    world.update();
+    assert_eq!(Some(&Score(20)), world.services.get::<Score>());
+
+    assert_eq!(None, world.services.set("level one"));
+    assert_eq!(Some("level one"), world.services.set("level two"));
+    assert_eq!(2, world.services.len());
+    assert_eq!(Some(Score(20)), world.services.remove::<Score>());
+    assert!(!world.services.contains::<Score>());
+}
+
+#[test]
+fn test_queue_manager()
+{
+    use ecs::manager::{OverflowPolicy, QueueManager};
+
+    let mut queue = QueueManager::new();
+    queue.push("footstep").unwrap();
+    queue.push_with_priority("damage", 10).unwrap();
+    queue.push("sparkle").unwrap();
+    assert_eq!(Some(&"damage"), queue.peek());
+    assert_eq!(vec!["damage", "footstep", "sparkle"], queue.drain().collect::<Vec<_>>());
+    assert!(queue.is_empty());
+
+    let mut queue = QueueManager::bounded(2, OverflowPolicy::DropOldest);
+    for i in 0..4
+    {
+        queue.push(i).unwrap();
+    }
+    assert_eq!(vec![2, 3], queue.drain().collect::<Vec<_>>());
+
+    let mut queue = QueueManager::bounded(2, OverflowPolicy::DropNewest);
+    for i in 0..4
+    {
+        queue.push(i).unwrap();
+    }
+    assert_eq!(vec![0, 1], queue.drain().collect::<Vec<_>>());
+
+    let mut queue = QueueManager::bounded(1, OverflowPolicy::Reject);
+    assert_eq!(Ok(()), queue.push(0));
+    assert_eq!(Err(1), queue.push(1));
+
+    // Dropping a drain part way through still empties the queue
+    queue.drain().next();
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn test_schedule_manager()
+{
+    use ecs::manager::{QueueManager, ScheduleManager};
+
+    services! {
+        ScheduleServices {
+            events: QueueManager<&'static str> = QueueManager::new()
+        }
+    }
+
+    systems! {
+        ScheduleSystems<TestComponents, ScheduleServices> {
+            schedule: ScheduleManager<TestComponents, ScheduleServices> = ScheduleManager::new(0.5)
+        }
+    }
+
+    let mut world = World::<ScheduleSystems>::new();
+    let entity = world.create_entity(());
+    world.update();
+
+    world.systems.schedule.despawn_after_seconds(1.0, entity);
+    world.systems.schedule.deliver_after_ticks(1, |s| &mut s.events, "ping");
+    world.systems.schedule.after_ticks(3, |data: &mut DataHelper<TestComponents, ScheduleServices>| {
+        let _ = data.services.events.push("late");
+    });
+    let cancelled = world.systems.schedule.deliver_after_ticks(1, |s| &mut s.events, "cancelled");
+    assert!(world.systems.schedule.cancel(cancelled));
+    assert_eq!(3, world.systems.schedule.len());
+
+    world.update();
+    assert_eq!(vec!["ping"], world.services.events.drain().collect::<Vec<_>>());
+    assert_eq!(1, world.entities().count());
+    world.update();
+    world.update();
+    assert_eq!(0, world.entities().count());
+    world.update();
+    assert_eq!(vec!["late"], world.services.events.drain().collect::<Vec<_>>());
+    assert!(world.systems.schedule.is_empty());
+}
+
+#[test]
+fn test_lifetime_system()
+{
+    use ecs::ComponentList;
+    use ecs::system::{Lifetime, LifetimeComponents, LifetimeSystem};
+
+    components! {
+        LifetimeTestComponents {
+            #[hot] lifetime: Lifetime,
+            #[hot] position: Position
+        }
+    }
+
+    impl LifetimeComponents for LifetimeTestComponents
+    {
+        fn lifetimes(&self) -> &ComponentList<Self, Lifetime> { &self.lifetime }
+        fn lifetimes_mut(&mut self) -> &mut ComponentList<Self, Lifetime> { &mut self.lifetime }
+    }
+
+    systems! {
+        LifetimeSystems<LifetimeTestComponents, ()> {
+            lifetime: LifetimeSystem<LifetimeTestComponents> = LifetimeSystem::new()
+        }
+    }
+
+    let mut world = World::<LifetimeSystems>::new();
+    let bullet = world.create_entity(|e: BuildData<LifetimeTestComponents>, c: &mut LifetimeTestComponents| {
+        c.lifetime.add(&e, Lifetime(2));
+    });
+    let wall = world.create_entity(|e: BuildData<LifetimeTestComponents>, c: &mut LifetimeTestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+    });
+    world.update();
+    assert_eq!(1, world.systems.lifetime.len());
+    world.with_entity_data(&bullet, |e, c| assert_eq!(Lifetime(1), c.lifetime[e]));
+
+    // Giving an entity a lifetime later on starts counting it down too
+    world.modify_entity(wall, |e: ModifyData<LifetimeTestComponents>, c: &mut LifetimeTestComponents| {
+        c.lifetime.insert(&e, Lifetime(1));
+    });
+    world.update();
+    world.update();
+    assert_eq!(0, world.entities().count());
+    assert!(world.systems.lifetime.is_empty());
+}
+
+#[test]
+fn test_entity_refs()
+{
+    use ecs::{EntityRef, EntityRefs};
+
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Follow
+    {
+        target: EntityRef,
+        speed: f32,
+    }
+
+    impl EntityRefs for Follow
+    {
+        fn clear_refs(&mut self, removed: &dyn Fn(&Entity) -> bool) -> bool
+        {
+            self.target.clear_refs(removed)
+        }
+    }
+
+    components! {
+        RefComponents {
+            #[hot] #[refs] follow: Follow,
+            #[cold] #[refs] children: Vec<EntityRef>,
+            #[hot] unchecked: Entity
+        }
+    }
+
+    systems! {
+        RefSystems<RefComponents, ()>;
+    }
+
+    let mut world = World::<RefSystems>::new();
+    let target = world.create_entity(());
+    let other = world.create_entity(());
+    let follower = world.create_entity(|e: BuildData<RefComponents>, c: &mut RefComponents| {
+        c.follow.add(&e, Follow { target: EntityRef::new(target), speed: 1.0 });
+        c.children.add(&e, vec![EntityRef::new(target), EntityRef::new(other)]);
+        c.unchecked.add(&e, target);
+    });
+    world.update();
+
+    world.remove_entity(target);
+    world.update();
+    world.with_entity_data(&follower, |e, c| {
+        assert!(c.follow[e].target.is_none());
+        assert_eq!(1.0, c.follow[e].speed);
+        assert_eq!(vec![EntityRef::new(other)], c.children[e]);
+        // Fields not marked `#[refs]` are left alone
+        assert_eq!(target, c.unchecked[e]);
+    });
+}
+
+#[test]
+fn test_layers()
+{
+    use ecs::ComponentManager;
+
+    const UI: u32 = 1 << 0;
+    const TEAM_A: u32 = 1 << 1;
+    const TEAM_B: u32 = 1 << 2;
+
+    pub struct CountEntities(usize);
+    impl System for CountEntities { type Components = TestComponents; type Services = (); }
+    impl EntityProcess for CountEntities
+    {
+        fn process(&mut self, entities: EntityIter<TestComponents>, _: &mut DataHelper<TestComponents, ()>)
+        {
+            self.0 = entities.count();
+        }
+    }
+
+    systems! {
+        LayerSystems<TestComponents, ()> {
+            teams: EntitySystem<CountEntities> = EntitySystem::new(CountEntities(0),
+                aspect!(<TestComponents> all: [position] layers: [TEAM_A, TEAM_B])
+            ),
+            ui: EntitySystem<CountEntities> = EntitySystem::new(CountEntities(0),
+                aspect!(<TestComponents> layers: [UI])
+            )
+        }
+    }
+
+    let mut world = World::<LayerSystems>::new();
+    world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+        c.layers_mut().add(&e, TEAM_A);
+    });
+    let button = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.layers_mut().add(&e, UI);
+    });
+    world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 1.0, y: 0.0 });
+    });
+    world.update();
+    world.update();
+    assert_eq!(1, world.systems.teams.0);
+    assert_eq!(1, world.systems.ui.0);
+
+    // Changing an entity's layers reactivates it
+    world.modify_entity(button, |e: ModifyData<TestComponents>, c: &mut TestComponents| {
+        assert_eq!(UI, c.layers_mut().set(&e, UI | TEAM_B));
+        c.position.insert(&e, Position { x: 2.0, y: 0.0 });
+    });
+    world.update();
+    world.update();
+    assert_eq!(2, world.systems.teams.0);
+    assert_eq!(1, world.systems.ui.0);
+    world.with_entity_data(&button, |e, c| assert!(c.layers().has_any(&e, TEAM_B)));
+}
+
+#[test]
+fn test_pause_groups()
+{
+    pub struct Counter(u32);
+    impl System for Counter { type Components = TestComponents; type Services = (); }
+    impl Process for Counter
+    {
+        fn process(&mut self, _: &mut DataHelper<TestComponents, ()>)
+        {
+            self.0 += 1;
+        }
+    }
+
+    systems! {
+        PausableSystems<TestComponents, ()> {
+            #[group(gameplay)] physics: Counter = Counter(0),
+            #[group(gameplay)] #[group(audio)] footsteps: Counter = Counter(0),
+            ui: Counter = Counter(0),
+        }
+    }
+
+    let mut world = World::<PausableSystems>::new();
+    world.update();
+    world.pause_group("gameplay");
+    assert!(world.is_group_paused("gameplay"));
+    world.update();
+    world.resume_group("gameplay");
+    world.pause_group("audio");
+    world.update();
+    assert_eq!(2, world.systems.physics.0);
+    assert_eq!(1, world.systems.footsteps.0);
+    assert_eq!(3, world.systems.ui.0);
+}
+
+#[test]
+fn test_frame_report()
+{
+    let mut world = World::<TestSystems>::new();
+    for _ in 0..3
+    {
+        world.create_entity(());
+    }
+    let report = world.update();
+    assert_eq!(3, report.created);
+    assert_eq!(0, report.removed);
+    assert_eq!(3, report.queued_before_systems);
+    assert!(report.system_times.is_empty());
+
+    world.profile_systems(true);
+    let entity = world.entities().next().map(|e| **e).unwrap();
+    world.remove_entity(entity);
+    let report = world.update();
+    assert_eq!((0, 1), (report.created, report.removed));
+    assert_eq!("hello_world", report.system_times[0].system);
+    assert!(report.system_times.iter().all(|t| t.duration <= report.duration));
+}
+
+#[test]
+fn test_activated_batch()
+{
+    pub struct Batches(Vec<usize>);
+    impl System for Batches
+    {
+        type Components = TestComponents;
+        type Services = ();
+        fn activated_batch(&mut self, entities: &[EntityData<TestComponents>], _: &TestComponents)
+        {
+            self.0.push(entities.len());
+        }
+    }
+    impl EntityProcess for Batches
+    {
+        fn process(&mut self, _: EntityIter<TestComponents>, _: &mut DataHelper<TestComponents, ()>) {}
+    }
+
+    systems! {
+        BatchSystems<TestComponents, ()> {
+            batches: EntitySystem<Batches> = EntitySystem::new(Batches(Vec::new()),
+                aspect!(<TestComponents> all: [position])
+            )
+        }
+    }
+
+    let mut world = World::<BatchSystems>::new();
+    let first = world.create_entity(());
+    for _ in 0..3
+    {
+        world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+            c.position.add(&e, Position { x: 0.0, y: 0.0 });
+        });
+    }
+    world.update();
+    assert_eq!(vec![3], world.systems.batches.0);
+
+    // Activations either side of a removal are batched separately, keeping their order
+    world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 1.0, y: 0.0 });
+    });
+    world.remove_entity(first);
+    world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 2.0, y: 0.0 });
+    });
+    world.update();
+    assert_eq!(vec![3, 1, 1], world.systems.batches.0);
+}
+
+#[test]
+fn test_entity_system_order()
+{
+    pub struct Order(Vec<usize>);
+    impl System for Order { type Components = TestComponents; type Services = (); }
+    impl EntityProcess for Order
+    {
+        fn process(&mut self, entities: EntityIter<TestComponents>, _: &mut DataHelper<TestComponents, ()>)
+        {
+            self.0 = entities.map(|e| e.index()).collect();
+        }
+    }
+
+    systems! {
+        OrderSystems<TestComponents, ()> {
+            order: EntitySystem<Order> = EntitySystem::new(Order(Vec::new()),
+                aspect!(<TestComponents> all: [position])
+            )
+        }
+    }
+
+    let mut world = World::<OrderSystems>::new();
+    let entities: Vec<_> = (0..6).map(|i| world.create_entity(move |e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: i as f32, y: 0.0 });
+    })).collect();
+    world.update();
+    world.remove_entity(entities[1]);
+    world.remove_entity(entities[4]);
+    world.update();
+    world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 6.0, y: 0.0 });
+    });
+    world.update();
+
+    // Entities are processed in index order, wherever they were inserted
+    let order = &world.systems.order.0;
+    assert_eq!(5, order.len());
+    assert!(order.windows(2).all(|w| w[0] < w[1]));
+}
+
+#[test]
+fn test_interested()
+{
+    let mut world = World::<ContactSystems>::new();
+    let player = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+        c.team.add(&e, Team(1));
+    });
+    let rock = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 2.0, y: 0.0 });
+    });
+    assert_eq!(0, world.systems.contacts.interested_a().count());
+    world.update();
+
+    let a: Vec<Entity> = world.systems.contacts.interested_a().map(|e| **e).collect();
+    let mut b: Vec<Entity> = world.systems.contacts.interested_b().map(|e| **e).collect();
+    b.sort_by_key(|e| e.id());
+    assert_eq!(vec![player], a);
+    assert_eq!(vec![player, rock], b);
+
+    world.remove_entity(rock);
+    world.update();
+    assert_eq!(1, world.systems.contacts.interested_b().count());
+}
+
+#[test]
+fn test_cached_query()
+{
+    let mut world = World::<TestSystems>::new();
+    let first = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+    });
+    world.update();
+
+    // Only entities that have been activated are matched
+    let second = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 1.0, y: 0.0 });
+    });
+    let positions = world.add_query(aspect!(<TestComponents> all: [position]));
+    assert_eq!(1, world.query(positions).unwrap().len());
+    world.update();
+    assert_eq!(2, world.query(positions).unwrap().len());
+
+    world.modify_entity(first, |e: ModifyData<TestComponents>, c: &mut TestComponents| {
+        c.position.remove(&e);
+    });
+    world.remove_entity(second);
+    world.update();
+    let query = world.query(positions).unwrap();
+    assert!(query.is_empty());
+    assert_eq!(Some(false), world.with_entity_data_ref(&first, |e, _| query.contains(&e)));
+
+    assert!(world.remove_query(positions));
+    assert!(world.query(positions).is_none());
+    assert!(!world.remove_query(positions));
+}
+
+#[test]
+fn test_cached_query_membership()
+{
+    use ecs::CachedQuery;
+
+    let mut world = World::<TestSystems>::new();
+    let entities: Vec<_> = (0..4).map(|i| world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: i as f32, y: 0.0 });
+    })).collect();
+
+    let mut query = CachedQuery::new(aspect!(<TestComponents> all: [position]));
+    world.with_entity_data_ref(&entities[2], |e, c| {
+        assert!(query.activated(&e, c));
+        // Already a member, so it isn't added or reported again
+        assert!(!query.activated(&e, c));
+    });
+    // Batches are matched out of order and merged in
+    let mut batch: Vec<_> = world.entities().collect();
+    batch.reverse();
+    let added: Vec<_> = query.activated_batch(&batch, &world.data).iter().map(|e| ***e).collect();
+    assert_eq!(vec![entities[0], entities[1], entities[3]], added);
+    assert_eq!(4, query.len());
+    assert_eq!(entities, query.iter().map(|e| **e).collect::<Vec<_>>());
+    for entity in &entities
+    {
+        assert_eq!(Some(true), world.with_entity_data_ref(entity, |e, _| query.contains(&e)));
+    }
+}
+
+#[test]
+fn test_matching()
+{
+    let mut world = World::<TestSystems>::new();
+    assert_eq!(None, world.find_first(aspect!(<TestComponents> all: [team])));
+    let player = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+        c.team.add(&e, Team(1));
+    });
+    world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 1.0, y: 0.0 });
+    });
+    world.update();
+
+    assert_eq!(Some(player), world.find_first(aspect!(<TestComponents> all: [team])));
+    assert_eq!(2, world.count(aspect!(<TestComponents> all: [position])));
+    assert_eq!(1, world.count(aspect!(<TestComponents> all: [position] none: [team])));
+
+    let mut buf = vec![player, player, player];
+    world.matching(aspect!(<TestComponents> all: [team])).collect_into(&mut buf);
+    assert_eq!(vec![player], buf);
+}
+
+#[test]
+fn test_index_manager()
+{
+    use ecs::manager::IndexManager;
+
+    systems! {
+        TeamSystems<TestComponents, ()> {
+            teams: IndexManager<u8, TestComponents> = IndexManager::new(|e, c: &TestComponents| {
+                c.team.get(e).map(|team| team.0)
+            })
+        }
+    }
+
+    let mut world = World::<TeamSystems>::new();
+    let a = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.team.add(&e, Team(3));
+    });
+    let b = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.team.add(&e, Team(3));
+    });
+    world.create_entity(());
+    world.update();
+    assert_eq!(2, world.systems.teams.count(&3));
+    assert_eq!(2, world.systems.teams.len());
+    assert!(world.systems.teams.get(&3).unwrap().contains(&a));
+
+    world.modify_entity(b, |e: ModifyData<TestComponents>, c: &mut TestComponents| {
+        c.team.insert(&e, Team(1));
+    });
+    assert_eq!(Some(&1), world.systems.teams.key(&b));
+    assert_eq!(1, world.systems.teams.count(&3));
+
+    world.remove_entity(a);
+    world.update();
+    assert!(world.systems.teams.get(&3).is_none());
+    assert_eq!(vec![&1], world.systems.teams.keys().collect::<Vec<_>>());
+}
+
+#[test]
+fn test_player_manager()
+{
+    use ecs::manager::PlayerManager;
+
+    systems! {
+        PlayerSystems<TestComponents, ()> {
+            players: PlayerManager<TestComponents> = PlayerManager::new(aspect!(<TestComponents> all: [position]))
+        }
+    }
+
+    let mut world = World::<PlayerSystems>::new();
+    let spectator = world.create_entity(());
+    let a = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+    });
+    let b = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 1.0, y: 0.0 });
+    });
+    world.update();
+
+    let assign = |world: &mut World<PlayerSystems>, player, entity| {
+        let players = &mut world.systems.players;
+        world.data.with_entity_data(&entity, |e, _| players.assign(player, &e)).unwrap()
+    };
+    assert!(!assign(&mut world, 1, spectator));
+    assert!(assign(&mut world, 2, b));
+    assert!(assign(&mut world, 1, a));
+    assert_eq!(vec![(1, a), (2, b)], world.systems.players.iter().collect::<Vec<_>>());
+    assert_eq!(Some(2), world.systems.players.player(&b));
+
+    // Compacting the world keeps players' entities
+    world.remove_entity(spectator);
+    world.update();
+    world.compact();
+    assert_eq!(vec![(1, a), (2, b)], world.systems.players.iter().collect::<Vec<_>>());
+    assert_eq!(0, world.systems.players.drain_evicted().count());
+
+    // Entities that stop matching or are removed are evicted
+    world.modify_entity(a, |e: ModifyData<TestComponents>, c: &mut TestComponents| {
+        c.position.remove(&e);
+    });
+    world.remove_entity(b);
+    world.update();
+    assert!(world.systems.players.is_empty());
+    assert_eq!(vec![(1, a), (2, b)], world.systems.players.drain_evicted().collect::<Vec<_>>());
+    assert_eq!(None, world.systems.players.entity(1));
+}
+
+#[test]
+fn test_interact_membership()
+{
+    use ecs::system::Membership;
+
+    pub struct Lifecycle(Vec<&'static str>, Vec<(Membership, Membership)>);
+    impl System for Lifecycle
+    {
+        type Components = TestComponents;
+        type Services = ();
+        fn activated(&mut self, _: &EntityData<TestComponents>, _: &TestComponents) { self.0.push("activated"); }
+        fn reactivated(&mut self, _: &EntityData<TestComponents>, _: &TestComponents) { self.0.push("reactivated"); }
+        fn deactivated(&mut self, _: &EntityData<TestComponents>, _: &TestComponents) { self.0.push("deactivated"); }
+    }
+    impl InteractProcess for Lifecycle
+    {
+        fn process(&self, _: EntityIter<TestComponents>, _: EntityIter<TestComponents>, _: &mut DataHelper<TestComponents, ()>) {}
+        fn membership_changed(&mut self, _: &EntityData<TestComponents>, old: Membership, new: Membership, _: &TestComponents)
+        {
+            self.1.push((old, new));
+        }
+    }
+
+    systems! {
+        LifecycleSystems<TestComponents, ()> {
+            lifecycle: InteractSystem<Lifecycle> = InteractSystem::new(Lifecycle(Vec::new(), Vec::new()),
+                aspect!(<TestComponents> all: [position, team]),
+                aspect!(<TestComponents> all: [position])
+            )
+        }
+    }
+
+    let both = Membership { a: true, b: true };
+    let only_b = Membership { a: false, b: true };
+
+    let mut world = World::<LifecycleSystems>::new();
+    let entity = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+        c.team.add(&e, Team(1));
+    });
+    world.update();
+    world.modify_entity(entity, |e: ModifyData<TestComponents>, c: &mut TestComponents| {
+        c.team.remove(&e);
+    });
+    world.remove_entity(entity);
+    world.update();
+
+    // An entity in both sets only gets one notification for each change
+    assert_eq!(vec!["activated", "reactivated", "deactivated"], world.systems.lifecycle.inner.0);
+    assert_eq!(vec![(Membership::default(), both), (both, only_b), (only_b, Membership::default())], world.systems.lifecycle.inner.1);
+}
+
+#[test]
+fn test_data_system()
+{
+    use ecs::system::{DataIter, DataProcess, DataSystem};
+
+    // Remembers where each entity started, and how many updates it has been processed for
+    pub struct Origins;
+    impl System for Origins { type Components = TestComponents; type Services = (); }
+    impl DataProcess for Origins
+    {
+        type Data = (Position, u32);
+        fn create(&mut self, e: &EntityData<TestComponents>, c: &TestComponents) -> (Position, u32)
+        {
+            (c.position[*e], 0)
+        }
+
+        fn process(&mut self, entities: DataIter<TestComponents, (Position, u32)>, _: &mut DataHelper<TestComponents, ()>)
+        {
+            for (_, data) in entities
+            {
+                data.1 += 1;
+            }
+        }
+    }
+
+    systems! {
+        OriginSystems<TestComponents, ()> {
+            origins: DataSystem<Origins> = DataSystem::new(Origins, aspect!(<TestComponents> all: [position]))
+        }
+    }
+
+    let mut world = World::<OriginSystems>::new();
+    let entity = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 1.0, y: 2.0 });
+    });
+    world.create_entity(());
+    world.update();
+    world.update();
+    assert_eq!(1, world.systems.origins.len());
+
+    world.modify_entity(entity, |e: ModifyData<TestComponents>, c: &mut TestComponents| {
+        c.position[e] = Position { x: 5.0, y: 5.0 };
+    });
+    let indexed = world.entities().find(|e| ***e == entity).map(|e| unsafe { (*e).clone() }).unwrap();
+    assert_eq!(Some(&(Position { x: 1.0, y: 2.0 }, 2)), world.systems.origins.get(&indexed));
+
+    world.modify_entity(entity, |e: ModifyData<TestComponents>, c: &mut TestComponents| {
+        c.position.remove(&e);
+    });
+    assert!(world.systems.origins.get(&indexed).is_none());
+}
+
+#[test]
+fn test_passive_entity_system()
+{
+    use ecs::system::PassiveEntitySystem;
+
+    pub struct CountPositions(usize);
+    impl System for CountPositions { type Components = TestComponents; type Services = (); }
+    impl EntityProcess for CountPositions
+    {
+        fn process(&mut self, entities: EntityIter<TestComponents>, _: &mut DataHelper<TestComponents, ()>)
+        {
+            self.0 += entities.count();
+        }
+    }
+
+    systems! {
+        PassiveSystems<TestComponents, ()> {
+            count: PassiveEntitySystem<CountPositions> = PassiveEntitySystem::new(CountPositions(0),
+                aspect!(<TestComponents> all: [position])
+            )
+        }
+    }
+
+    let mut world = World::<PassiveSystems>::new();
+    for _ in 0..2
+    {
+        world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+            c.position.add(&e, Position { x: 0.0, y: 0.0 });
+        });
+    }
+    world.update();
+    world.update();
+    assert_eq!(0, world.systems.count.0);
+    assert_eq!(2, world.systems.count.interested().count());
+
+    process!(world, count);
+    assert_eq!(2, world.systems.count.0);
+}
+
+#[test]
+fn test_process_list()
+{
+    use ecs::system::PassiveEntitySystem;
+
+    pub struct CountPositions(usize);
+    impl System for CountPositions { type Components = TestComponents; type Services = (); }
+    impl EntityProcess for CountPositions
+    {
+        fn process(&mut self, entities: EntityIter<TestComponents>, _: &mut DataHelper<TestComponents, ()>)
+        {
+            self.0 = entities.count();
+        }
+    }
+
+    pub struct Spawn;
+    impl System for Spawn { type Components = TestComponents; type Services = (); }
+    impl Process for Spawn
+    {
+        fn process(&mut self, data: &mut DataHelper<TestComponents, ()>)
+        {
+            data.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+                c.position.add(&e, Position { x: 0.0, y: 0.0 });
+            });
+        }
+    }
+
+    // A passive group of systems, processed by path
+    pub struct Stage { spawn: Spawn }
+    impl System for Stage
+    {
+        type Components = TestComponents;
+        type Services = ();
+        fn is_active(&self) -> bool { false }
+    }
+    impl Process for Stage
+    {
+        fn process(&mut self, _: &mut DataHelper<TestComponents, ()>) {}
+    }
+
+    systems! {
+        ManualSystems<TestComponents, ()> {
+            stage: Stage = Stage { spawn: Spawn },
+            count: PassiveEntitySystem<CountPositions> = PassiveEntitySystem::new(CountPositions(0),
+                aspect!(<TestComponents> all: [position])
+            )
+        }
+    }
+
+    let mut world = World::<ManualSystems>::new();
+    process!(world, [stage.spawn, count]);
+    assert_eq!(1, world.systems.count.0);
+    process!(world, [stage.spawn, stage.spawn, count,]);
+    assert_eq!(3, world.systems.count.0);
+
+    // A single system isn't followed by a flush
+    process!(world, stage.spawn);
+    process!(world, count);
+    assert_eq!(3, world.systems.count.0);
+}
+
+#[test]
+fn test_services_defaults_and_nesting()
+{
+    use ecs::ServiceManager;
+    use ecs::manager::Blackboard;
+
+    services! {
+        AudioServices {
+            volume: f32 = 1.0,
+            muted: bool
+        }
+    }
+
+    services! {
+        GameServices {
+            #[services] audio: AudioServices,
+            #[services] blackboard: Blackboard,
+            pub(crate) frames: Vec<u32>,
+            pub score: u32 = 10,
+        }
+    }
+
+    let services = GameServices::new();
+    assert_eq!(1.0, services.audio.volume);
+    assert!(!services.audio.muted);
+    assert!(services.blackboard.is_empty());
+    assert!(services.frames.is_empty());
+    assert_eq!(10, services.score);
+}
+
+#[test]
+fn test_nested_components()
+{
+    components! {
+        PhysicsComponents {
+            #[hot] velocity: Position,
+            #[flag] sleeping: SomeFeature
+        }
+    }
+
+    components! {
+        GameComponents {
+            #[hot] position: Position,
+            #[nested] physics: PhysicsComponents
+        }
+    }
+
+    pub struct Moving(usize);
+    impl System for Moving { type Components = GameComponents; type Services = (); }
+    impl EntityProcess for Moving
+    {
+        fn process(&mut self, entities: EntityIter<GameComponents>, c: &mut DataHelper<GameComponents, ()>)
+        {
+            self.0 = 0;
+            for e in entities
+            {
+                let velocity = c.physics.velocity[e.nested()];
+                c.position[e].x += velocity.x;
+                self.0 += 1;
+            }
+        }
+    }
+
+    systems! {
+        GameSystems<GameComponents, ()> {
+            moving: EntitySystem<Moving> = EntitySystem::new(Moving(0),
+                aspect!(<GameComponents> all: [position, physics.velocity] none: [physics.sleeping])
+            )
+        }
+    }
+
+    let mut world = World::<GameSystems>::new();
+    let entity = world.create_entity(|e: BuildData<GameComponents>, c: &mut GameComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+        c.physics.velocity.add(&e.nested(), Position { x: 1.0, y: 0.0 });
+    });
+    world.create_entity(|e: BuildData<GameComponents>, c: &mut GameComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+        c.physics.velocity.add(&e.nested(), Position { x: 1.0, y: 0.0 });
+        c.physics.sleeping.add(&e.nested(), SomeFeature);
+    });
+    world.update();
+    world.update();
+    assert_eq!(1, world.systems.moving.0);
+    world.with_entity_data(&entity, |e, c| assert_eq!(2.0, c.position[e].x));
+    assert!(world.memory_report().get("physics").is_some());
+
+    // Removing an entity also removes its nested components
+    world.remove_entity(entity);
+    world.update();
+    assert_eq!(1, world.physics.velocity.len());
+}
+
+#[test]
+fn test_aspect_where()
+{
+    let min_x = 1.0;
+    let right = aspect!(<TestComponents>
+        all: [position]
+        where: [move |en, co| co.position[*en].x >= min_x]
+    );
+    let right_team = aspect!(<TestComponents>
+        all: [position]
+        none: [feature]
+        where: [move |en, co| co.position[*en].x >= min_x, |en, co| co.team.get(en).is_some_and(|t| t.0 == 2)]
+    );
+
+    let mut world = World::<TestSystems>::new();
+    for (x, team) in [(0.0, 2), (1.0, 1), (2.0, 2)]
+    {
+        world.create_entity(move |e: BuildData<TestComponents>, c: &mut TestComponents| {
+            c.position.add(&e, Position { x, y: 0.0 });
+            c.team.add(&e, Team(team));
+        });
+    }
+    world.update();
+    assert_eq!(2, world.count(right));
+    assert_eq!(1, world.count(right_team));
+}
+
+#[test]
+fn test_aspect_combinators()
+{
+    let positioned = Aspect::from_fn(|en, co: &TestComponents| co.position.has(en));
+    let on_team = aspect!(<TestComponents> all: [team]);
+    let lone = positioned.and(!on_team);
+    let either = aspect!(<TestComponents> all: [position]).or(aspect!(<TestComponents> all: [team]));
+
+    let mut world = World::<TestSystems>::new();
+    world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+    });
+    world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+        c.team.add(&e, Team(1));
+    });
+    world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.team.add(&e, Team(2));
+    });
+    world.update();
+    assert_eq!(1, world.count(lone));
+    assert_eq!(3, world.count(either));
+}
+
+#[test]
+fn test_aspect_info()
+{
+    let moving = aspect!(<TestComponents> all: [position, team] none: [feature]);
+    assert_eq!(vec!["feature", "position", "team"], moving.info().fields);
+    assert!(!moving.info().layers);
+    assert!(!moving.info().opaque);
+    assert!(moving.info().reads("team"));
+    assert!(!moving.info().reads("blank_data"));
+
+    let filtered = aspect!(<TestComponents> all: [position] layers: [1] where: [|_, _| true]);
+    assert!(filtered.info().layers);
+    assert!(filtered.info().reads("blank_data"));
+
+    let combined = aspect!(<TestComponents> all: [team]).and(!aspect!(<TestComponents> all: [position]));
+    assert_eq!(vec!["position", "team"], combined.info().fields);
+    assert!(Aspect::from_fn(|_, _: &TestComponents| true).info().opaque);
+}
+
+#[test]
+fn test_once_builders()
+{
+    components! {
+        LabelComponents {
+            #[cold] label: String
+        }
+    }
+
+    systems! {
+        LabelSystems<LabelComponents, ()>;
+    }
+
+    let mut world = World::<LabelSystems>::new();
+    let name = String::from("player");
+    let entity = world.create_entity(move |e: BuildData<LabelComponents>, c: &mut LabelComponents| {
+        c.label.add(&e, name);
+    });
+    let renamed = String::from("hero");
+    world.modify_entity(entity, move |e: ModifyData<LabelComponents>, c: &mut LabelComponents| {
+        c.label.insert(&e, renamed);
+    });
+    world.spawner().spawn(|e: BuildData<LabelComponents>, c: &mut LabelComponents| {
+        c.label.add(&e, String::from("spawned"));
+    });
+    world.update();
+    world.with_entity_data(&entity, |e, c| assert_eq!("hero", c.label[e]));
+    assert_eq!(2, world.entities().count());
+}
+
+#[test]
+fn test_tuple_builder()
+{
+    let mut world = World::<TestSystems>::new();
+    let entity = world.create_entity((
+        TestComponents::position(Position { x: 1.0, y: 2.0 }),
+        TestComponents::team(Team(3)),
+        TestComponents::feature(SomeFeature),
+    ));
+    let lone = world.create_entity(TestComponents::position(Position { x: 4.0, y: 0.0 }));
+    world.update();
+    world.with_entity_data(&entity, |e, c| {
+        assert_eq!(Position { x: 1.0, y: 2.0 }, c.position[e]);
+        assert_eq!(Team(3), c.team[e]);
+        assert!(c.feature.has(&e));
+    });
+    world.with_entity_data(&lone, |e, c| assert!(!c.team.has(&e)));
+}
+
+#[test]
+fn test_edit_data()
+{
+    use ecs::EditData;
+
+    fn nudge<U: EditData<TestComponents>>(e: &U, c: &mut TestComponents)
+    {
+        let position = c.position.get(e).unwrap_or(Position { x: 0.0, y: 0.0 });
+        c.position.set(e, Position { x: position.x + 1.0, y: position.y });
+    }
+
+    let mut world = World::<TestSystems>::new();
+    let entity = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| nudge(&e, c));
+    world.modify_entity(entity, |e: ModifyData<TestComponents>, c: &mut TestComponents| nudge(&e, c));
+    world.with_entity_data(&entity, |e, c| {
+        nudge(&e, c);
+        assert_eq!(3.0, c.position[e].x);
+    });
+}
+
+#[test]
+fn test_remove_entity_after()
+{
+    use ecs::Despawning;
+
+    let mut world = World::<TestSystems>::new();
+    let entity = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+    });
+    world.update();
+    assert!(world.remove_entity_after(entity, 2));
+    assert_eq!(Some(Despawning { remaining: 2 }), world.despawning(&entity));
+
+    world.update();
+    assert_eq!(Some(Despawning { remaining: 1 }), world.despawning(&entity));
+    assert!(world.with_entity_data(&entity, |_, _| ()).is_some());
+
+    let report = world.update();
+    assert_eq!(1, report.removed);
+    assert_eq!(None, world.despawning(&entity));
+    assert!(world.with_entity_data(&entity, |_, _| ()).is_none());
+    assert!(!world.remove_entity_after(entity, 1));
+}
+
+#[test]
+fn test_transfer_entity()
+{
+    use ecs::ComponentManager;
+
+    systems! {
+        StagingSystems<TestComponents, ()>;
+    }
+
+    let mut staging = World::<StagingSystems>::new();
+    let mut world = World::<TestSystems>::new();
+    let entity = staging.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 1.0, y: 2.0 });
+        c.feature.add(&e, SomeFeature);
+        c.layers_mut().add(&e, 4);
+    });
+    staging.update();
+    staging.set_name(&entity, "player");
+
+    let moved = staging.transfer_entity(entity, &mut world).unwrap();
+    // Until the flush, the entity can't be transferred a second time
+    match staging.transfer_entity(entity, &mut world)
+    {
+        Err(ecs::Error::NoSuchEntity(e)) => assert_eq!(entity, e),
+        other => panic!("expected NoSuchEntity, got {:?}", other),
+    }
+    staging.update();
+    world.update();
+    assert_eq!(0, staging.entities().count());
+    assert_eq!(None, staging.names().entity("player"));
+    assert_eq!(Some(moved), world.names().entity("player"));
+    world.with_entity_data(&moved, |e, c| {
+        assert_eq!(Position { x: 1.0, y: 2.0 }, c.position[e]);
+        assert!(c.feature.has(&e));
+        assert_eq!(4, c.layers().get(&e));
+    });
+    assert!(staging.transfer_entity(entity, &mut world).is_err());
+    assert_eq!(1, world.entities().count());
+
+    // In immediate mode, the entity is gone from this world straight away
+    staging.set_immediate(true);
+    let entity = staging.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 3.0, y: 4.0 });
+    });
+    staging.transfer_entity(entity, &mut world).unwrap();
+    assert_eq!(0, staging.entities().count());
+}
+
+#[test]
+fn test_detached_components()
+{
+    components! {
+        RenderComponents {
+            #[hot] sprite: Position
+        }
+    }
+
+    components! {
+        SplitComponents {
+            #[hot] position: Position,
+            #[flag] moved: SomeFeature,
+            #[nested] #[detached] render: RenderComponents
+        }
+    }
+
+    pub struct Reactivations(usize);
+    impl System for Reactivations
+    {
+        type Components = SplitComponents;
+        type Services = ();
+        fn reactivated(&mut self, _: &EntityData<SplitComponents>, _: &SplitComponents)
+        {
+            self.0 += 1;
+        }
+    }
+    impl Process for Reactivations
+    {
+        fn process(&mut self, _: &mut DataHelper<SplitComponents, ()>) {}
+    }
+
+    systems! {
+        SplitSystems<SplitComponents, ()> {
+            reactivations: Reactivations = Reactivations(0)
+        }
+    }
+
+    let mut world = World::<SplitSystems>::new();
+    let entity = world.create_entity(|e: BuildData<SplitComponents>, c: &mut SplitComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+    });
+    let drawn = world.add_query(aspect!(<SplitComponents> all: [render.sprite]));
+    world.update();
+
+    // Rebuilding render data doesn't reactivate the entity until the detached fields are flushed
+    world.with_entity_data(&entity, |e, c| {
+        c.render.sprite.set(&e.nested(), Position { x: 1.0, y: 1.0 });
+    });
+    world.update();
+    assert_eq!(0, world.systems.reactivations.0);
+    assert!(world.query(drawn).unwrap().is_empty());
+    assert_eq!(1, world.flush_detached());
+    assert_eq!(1, world.systems.reactivations.0);
+    assert_eq!(1, world.query(drawn).unwrap().len());
+    assert_eq!(0, world.flush_detached());
+
+    world.with_entity_data(&entity, |e, c| {
+        c.moved.set(&e, SomeFeature);
+    });
+    world.update();
+    assert_eq!(2, world.systems.reactivations.0);
+
+    // Resetting the field reactivates the entities that had its components
+    world.reset_nested(|c| &mut c.render);
+    assert_eq!(0, world.render.len());
+    assert_eq!(1, world.position.len());
+    world.update();
+    assert_eq!(3, world.systems.reactivations.0);
+    assert!(world.query(drawn).unwrap().is_empty());
+}
+
+#[test]
+fn test_dyn_system_swap()
+{
+    use ecs::system::DynSystem;
+
+    pub struct Mover(f32);
+    impl System for Mover { type Components = TestComponents; type Services = (); }
+    impl EntityProcess for Mover
+    {
+        fn process(&mut self, entities: EntityIter<TestComponents>, c: &mut DataHelper<TestComponents, ()>)
+        {
+            for e in entities
+            {
+                c.position[e].x += self.0;
+            }
+        }
+    }
+
+    systems! {
+        ReloadSystems<TestComponents, ()> {
+            mover: DynSystem<TestComponents, ()> = DynSystem::new(Box::new(
+                EntitySystem::new(Mover(1.0), aspect!(<TestComponents> all: [position]))
+            ))
+        }
+    }
+
+    let mut world = World::<ReloadSystems>::new();
+    let entity = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+    });
+    world.create_entity(());
+    world.update();
+    assert_eq!(2, world.systems.mover.entities().count());
+
+    // The reloaded system moves the entity without it being reactivated
+    let reloaded = Box::new(EntitySystem::new(Mover(10.0), aspect!(<TestComponents> all: [position])));
+    world.systems.mover.swap(reloaded, &world.data.components);
+    world.update();
+    world.with_entity_data(&entity, |e, c| assert_eq!(11.0, c.position[e].x));
+}
+
+inspect_struct!(Position { x, y });
+
+#[test]
+fn test_inspect()
+{
+    use ecs::inspect::{self, InspectValue};
+
+    components! {
+        InspectComponents {
+            #[hot] #[inspect] position: Position,
+            #[cold] #[inspect] tags: Vec<String>,
+            #[flag] #[inspect] selected: (),
+            #[hot] team: Team
+        }
+    }
+
+    systems! {
+        InspectSystems<InspectComponents, ()>;
+    }
+
+    let mut world = World::<InspectSystems>::new();
+    let entity = world.create_entity(|e: BuildData<InspectComponents>, c: &mut InspectComponents| {
+        c.position.add(&e, Position { x: 1.0, y: 2.0 });
+        c.tags.add(&e, vec!["boss".to_string()]);
+        c.team.add(&e, Team(1));
+    });
+    world.create_entity(());
+    world.update();
+    world.set_name(&entity, "ogre");
+
+    let model = inspect::entity(&world.data, &entity).unwrap();
+    assert_eq!(Some("ogre".to_string()), model.name);
+    assert_eq!(vec![
+        ("position", InspectValue::Struct(vec![("x", InspectValue::Float(1.0)), ("y", InspectValue::Float(2.0))])),
+        ("tags", InspectValue::List(vec![InspectValue::Text("boss".to_string())])),
+    ], model.components);
+
+    let all = inspect::entities(&world.data);
+    assert_eq!(2, all.len());
+    assert_eq!(entity, all[0].entity);
+    assert!(all[1].components.is_empty());
+}
+
+reflect_struct!(Position { x, y });
+
+#[test]
+fn test_reflect()
+{
+    use ecs::ComponentManager;
+
+    components! {
+        ReflectComponents {
+            #[hot] #[reflect] position: Position,
+            #[hot] team: Team
+        }
+    }
+
+    systems! {
+        ReflectSystems<ReflectComponents, ()>;
+    }
+
+    let mut world = World::<ReflectSystems>::new();
+    let entity = world.create_entity(|e: BuildData<ReflectComponents>, c: &mut ReflectComponents| {
+        c.position.add(&e, Position { x: 1.0, y: 2.0 });
+        c.team.add(&e, Team(1));
+    });
+
+    world.with_entity_data(&entity, |e, c| {
+        assert!(c.reflect_component(&e, "team").is_none());
+        let position = c.reflect_component_mut(&e, "position").unwrap();
+        assert_eq!(&["x", "y"], position.field_names());
+        assert_eq!(Some("1".to_string()), position.get_field("x"));
+        position.set_field("x", "3.5").unwrap();
+        position.set_f64("y", -1.0).unwrap();
+        assert!(position.set_field("x", "fast").is_err());
+        assert!(position.set_field("z", "1").is_err());
+        assert_eq!(None, position.get_f64("z"));
+    });
+    world.with_entity_data(&entity, |e, c| {
+        assert_eq!(Position { x: 3.5, y: -1.0 }, c.position[e]);
+    });
+}
+
+#[test]
+fn test_metrics()
+{
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use ecs::metrics::Metrics;
+
+    struct Recorder(Arc<Mutex<Vec<(String, f64)>>>);
+
+    impl Metrics for Recorder
+    {
+        fn counter(&mut self, name: &str, value: u64)
+        {
+            self.0.lock().unwrap().push((name.to_string(), value as f64));
+        }
+
+        fn gauge(&mut self, name: &str, value: f64)
+        {
+            self.0.lock().unwrap().push((name.to_string(), value));
+        }
+
+        fn timing(&mut self, name: &str, _: Duration)
+        {
+            self.0.lock().unwrap().push((name.to_string(), 0.0));
+        }
+    }
+
+    let recorded = Arc::new(Mutex::new(Vec::new()));
+    let mut world = World::<TestSystems>::new();
+    world.set_metrics(Box::new(Recorder(recorded.clone())));
+    let entity = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.team.add(&e, Team(1));
+    });
+    world.create_entity(());
+    world.update();
+
+    let total = |name: &str| -> f64 {
+        recorded.lock().unwrap().iter().filter(|&(n, _)| n == name).map(|&(_, v)| v).sum()
+    };
+    let count = |name: &str| recorded.lock().unwrap().iter().filter(|&(n, _)| n == name).count();
+    assert_eq!(2.0, total("ecs.entities.created"));
+    assert_eq!(2.0, total("ecs.entities"));
+    assert_eq!(1, count("ecs.update"));
+    assert_eq!(2, count("ecs.flush"));
+    assert_eq!(1, count("ecs.components.team.capacity"));
+
+    recorded.lock().unwrap().clear();
+    world.remove_entity(entity);
+    world.update();
+    assert_eq!(1.0, total("ecs.entities.removed"));
+    assert_eq!(1.0, total("ecs.entities"));
+    assert_eq!(0, count("ecs.components.team.capacity"));
+
+    assert!(world.take_metrics().is_some());
+    recorded.lock().unwrap().clear();
+    world.update();
+    assert!(recorded.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_batched_entity_system()
+{
+    use ecs::system::{BatchedEntityProcess, BatchedEntitySystem};
+
+    pub struct ChunkLog(Vec<String>);
+    impl System for ChunkLog { type Components = TestComponents; type Services = (); }
+    impl BatchedEntityProcess for ChunkLog
+    {
+        fn begin_chunk(&mut self, _: &mut DataHelper<TestComponents, ()>)
+        {
+            self.0.push("begin".to_string());
+        }
+
+        fn process_chunk(&mut self, entities: EntityIter<TestComponents>, data: &mut DataHelper<TestComponents, ()>)
+        {
+            let mut xs = Vec::new();
+            for e in entities
+            {
+                xs.push(data.position[e].x.to_string());
+            }
+            self.0.push(xs.join(","));
+        }
+
+        fn end_chunk(&mut self, _: &mut DataHelper<TestComponents, ()>)
+        {
+            self.0.push("end".to_string());
+        }
+    }
+
+    systems! {
+        BatchedSystems<TestComponents, ()> {
+            log: BatchedEntitySystem<ChunkLog> = BatchedEntitySystem::new(ChunkLog(Vec::new()),
+                aspect!(<TestComponents> all: [position]), 2
+            )
+        }
+    }
+
+    let mut world = World::<BatchedSystems>::new();
+    for x in 0..5
+    {
+        world.create_entity(move |e: BuildData<TestComponents>, c: &mut TestComponents| {
+            c.position.add(&e, Position { x: x as f32, y: 0.0 });
+        });
+    }
+    world.create_entity(());
+    world.update();
+    assert_eq!(vec!["begin", "0,1", "end", "begin", "2,3", "end", "begin", "4", "end"], world.systems.log.0);
+}
+
+#[test]
+fn test_par_entity_system()
+{
+    use ecs::system::{CommandBuffer, ParEntityProcess, ParEntitySystem};
+
+    #[derive(Clone)]
+    pub struct FarAway(usize);
+    impl System for FarAway { type Components = TestComponents; type Services = (); }
+    impl ParEntityProcess for FarAway
+    {
+        fn process_slice(&mut self, entities: EntityIter<TestComponents>, c: &TestComponents, commands: &mut CommandBuffer<TestComponents, ()>)
+        {
+            for e in entities
+            {
+                self.0 += 1;
+                if c.position[e].x > 50.0
+                {
+                    commands.remove_entity(**e);
+                }
+            }
+        }
+
+        fn join(&mut self, worker: FarAway)
+        {
+            self.0 += worker.0;
+        }
+    }
+
+    /// Records the thread it ran on.
+    #[derive(Clone)]
+    pub struct OnThread(Option<std::thread::ThreadId>);
+    impl System for OnThread { type Components = TestComponents; type Services = (); }
+    impl ParEntityProcess for OnThread
+    {
+        fn process_slice(&mut self, _: EntityIter<TestComponents>, _: &TestComponents, _: &mut CommandBuffer<TestComponents, ()>)
+        {
+            self.0 = Some(std::thread::current().id());
+        }
+
+        fn join(&mut self, worker: OnThread)
+        {
+            self.0 = worker.0;
+        }
+    }
+
+    systems! {
+        ParSystems<TestComponents, ()> {
+            far: ParEntitySystem<FarAway> = ParEntitySystem::new(FarAway(0),
+                aspect!(<TestComponents> all: [position]), 7
+            ),
+            whole: ParEntitySystem<OnThread> = ParEntitySystem::new(OnThread(None),
+                aspect!(<TestComponents> all: [position]), 1000
+            )
+        }
+    }
+
+    let mut world = World::<ParSystems>::new();
+    for x in 0..100
+    {
+        world.create_entity(move |e: BuildData<TestComponents>, c: &mut TestComponents| {
+            c.position.add(&e, Position { x: x as f32, y: 0.0 });
+        });
+    }
+    world.update();
+    assert_eq!(100, world.systems.far.0);
+    assert_eq!(51, world.systems.far.interested().count());
+
+    // A single chunk isn't worth handing to another thread
+    assert_eq!(Some(std::thread::current().id()), world.systems.whole.0);
+}
+
+#[test]
+fn test_shared_components()
+{
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Material(&'static str);
+
+    components! {
+        TileComponents {
+            #[shared] #[inspect] material: Material,
+            #[hot] position: Position
+        }
+    }
+
+    systems! {
+        TileSystems<TileComponents, ()>;
+    }
+
+    impl ecs::inspect::InspectComponent for Material
+    {
+        fn inspect(&self) -> ecs::inspect::InspectValue
+        {
+            ecs::inspect::InspectValue::Text(self.0.to_string())
+        }
+    }
+
+    let mut world = World::<TileSystems>::new();
+    let first = world.create_entity(TileComponents::material(Material("stone")));
+    world.update();
+    let stone = world.with_entity_data(&first, |e, c| c.material.get(&e).unwrap()).unwrap();
+    let tiles: Vec<Entity> = (0..3).map(|_| {
+        let stone = stone.clone();
+        world.create_entity(move |e: BuildData<TileComponents>, c: &mut TileComponents| {
+            c.material.share(&e, stone);
+        })
+    }).collect();
+    world.update();
+    assert_eq!(5, std::sync::Arc::strong_count(&stone));
+
+    world.with_entity_data(&tiles[0], |e, c| {
+        assert_eq!(Material("stone"), c.material[e]);
+        c.material.make_mut(&e).unwrap().0 = "moss";
+    });
+    assert_eq!(4, std::sync::Arc::strong_count(&stone));
+    world.with_entity_data(&tiles[1], |e, c| {
+        assert_eq!(Material("stone"), c.material[e]);
+    });
+    world.with_entity_data(&tiles[0], |e, c| {
+        assert_eq!(Material("moss"), c.material[e]);
+    });
+    let model = ecs::inspect::entity(&world.data, &tiles[0]).unwrap();
+    assert_eq!(vec![("material", ecs::inspect::InspectValue::Text("moss".to_string()))], model.components);
+
+    world.remove_entity(tiles[1]);
+    world.update();
+    assert_eq!(3, std::sync::Arc::strong_count(&stone));
+    assert_eq!(3, world.memory_report().get("material").unwrap().len);
+}
+
+#[test]
+fn test_component_mask()
+{
+    let mut world = World::<TestSystems>::new();
+    let entity = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+        c.feature.add(&e, SomeFeature);
+    });
+    let empty = world.create_entity(());
+    world.update();
+
+    let mask = world.component_mask(&entity).unwrap();
+    assert_eq!(vec![1, 3], mask.fields());
+    assert_eq!(vec!["position", "feature"], mask.names::<TestComponents>());
+    assert!(mask.contains(1) && !mask.contains(2));
+    assert!(world.component_mask(&empty).unwrap().is_empty());
+
+    world.with_entity_data(&entity, |e, c| {
+        c.team.set(&e, Team(0));
+        assert_eq!(3, e.mask(c).len());
+    });
+
+    world.remove_entity(entity);
+    world.update();
+    assert_eq!(None, world.component_mask(&entity));
+}
+
+#[test]
+fn test_compact()
+{
+    let mut world = World::<TestSystems>::new();
+    let entities: Vec<Entity> = (0..10).map(|x| {
+        world.create_entity(move |e: BuildData<TestComponents>, c: &mut TestComponents| {
+            c.position.add(&e, Position { x: x as f32, y: 0.0 });
+            c.feature.add(&e, SomeFeature);
+        })
+    }).collect();
+    world.update();
+    for &entity in &entities[..8]
+    {
+        world.remove_entity(entity);
+    }
+    world.update();
+    assert_eq!(10, world.high_water_mark());
+    assert!(world.memory_report().get("position").unwrap().capacity >= 10);
+
+    assert_eq!(2, world.compact());
+    assert_eq!(2, world.high_water_mark());
+    assert!(world.memory_report().get("position").unwrap().capacity < 10);
+    assert_eq!(0, world.compact());
+
+    let mut indices: Vec<usize> = world.systems.print_position.interested().map(|e| e.index()).collect();
+    indices.sort();
+    assert_eq!(vec![0, 1], indices);
+    for (x, &entity) in [8.0, 9.0].iter().zip(&entities[8..])
+    {
+        world.with_entity_data(&entity, |e, c| {
+            assert_eq!(*x, c.position[e].x);
+            assert!(c.feature.has(&e));
+        });
+    }
+
+    let entity = world.create_entity(());
+    world.update();
+    assert_eq!(2, world.with_entity_data(&entity, |e, _| e.index()).unwrap());
+}
+
+#[test]
+fn test_custom_storage()
+{
+    use ecs::component::ComponentStorage;
+
+    /// Components in pages of four, allocated as they're needed.
+    pub struct Paged<T>(Vec<Option<Vec<Option<T>>>>);
+
+    impl<T> Default for Paged<T>
+    {
+        fn default() -> Paged<T>
+        {
+            Paged(Vec::new())
+        }
+    }
+
+    impl<T: Send + Sync> ComponentStorage<T> for Paged<T>
+    {
+        fn insert(&mut self, index: usize, component: T) -> Option<T>
+        {
+            if self.0.len() <= index / 4
+            {
+                self.0.resize_with(index / 4 + 1, || None);
+            }
+            let page = self.0[index / 4].get_or_insert_with(|| (0..4).map(|_| None).collect());
+            page[index % 4].replace(component)
+        }
+
+        fn remove(&mut self, index: usize) -> Option<T>
+        {
+            self.0.get_mut(index / 4)?.as_mut()?[index % 4].take()
+        }
+
+        fn get(&self, index: usize) -> Option<&T>
+        {
+            self.0.get(index / 4)?.as_ref()?[index % 4].as_ref()
+        }
+
+        fn get_mut(&mut self, index: usize) -> Option<&mut T>
+        {
+            self.0.get_mut(index / 4)?.as_mut()?[index % 4].as_mut()
+        }
+
+        fn get_pair_mut(&mut self, a: usize, b: usize) -> Option<(&mut T, &mut T)>
+        {
+            let (a, b) = if a / 4 == b / 4
+            {
+                let page = self.0.get_mut(a / 4)?.as_mut()?;
+                let [a, b] = page.get_disjoint_mut([a % 4, b % 4]).ok()?;
+                (a, b)
+            }
+            else
+            {
+                let [page_a, page_b] = self.0.get_disjoint_mut([a / 4, b / 4]).ok()?;
+                (&mut page_a.as_mut()?[a % 4], &mut page_b.as_mut()?[b % 4])
+            };
+            Some((a.as_mut()?, b.as_mut()?))
+        }
+
+        fn len(&self) -> usize
+        {
+            self.iter().count()
+        }
+
+        fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = (usize, &'a T)> + 'a>
+        {
+            Box::new(self.0.iter().enumerate().filter_map(|(p, page)| page.as_ref().map(|page| (p, page)))
+                .flat_map(|(p, page)| page.iter().enumerate().filter_map(move |(i, c)| c.as_ref().map(|c| (p * 4 + i, c)))))
+        }
+
+        fn for_each_mut(&mut self, f: &mut dyn FnMut(&mut T))
+        {
+            self.0.iter_mut().flatten().flatten().flatten().for_each(f);
+        }
+    }
+
+    components! {
+        PagedComponents {
+            #[storage(Paged<Position>)] position: Position,
+            #[hot] team: Team,
+        }
+    }
+
+    systems! {
+        PagedSystems<PagedComponents, ()> {
+            positions: EntitySystem<CountPaged> = EntitySystem::new(CountPaged(0),
+                aspect!(<PagedComponents> all: [position])
+            )
+        }
+    }
+
+    pub struct CountPaged(usize);
+    impl System for CountPaged { type Components = PagedComponents; type Services = (); }
+    impl EntityProcess for CountPaged
+    {
+        fn process(&mut self, entities: EntityIter<PagedComponents>, _: &mut DataHelper<PagedComponents, ()>)
+        {
+            self.0 = entities.count();
+        }
+    }
+
+    let mut world = World::<PagedSystems>::new();
+    let entities: Vec<Entity> = (0..6).map(|x| {
+        world.create_entity(move |e: BuildData<PagedComponents>, c: &mut PagedComponents| {
+            if x % 2 == 0
+            {
+                c.position.add(&e, Position { x: x as f32, y: 0.0 });
+            }
+            c.team.add(&e, Team(x));
+        })
+    }).collect();
+    world.update();
+    assert_eq!(3, world.systems.positions.0);
+    assert_eq!(3, world.memory_report().get("position").unwrap().len);
+
+    for &(a, b) in &[(0, 2), (4, 0)]
+    {
+        world.with_entity_pair(&entities[a], &entities[b], |ea, eb, c| {
+            let (pa, pb) = c.position.get_pair_mut(&ea, &eb).unwrap();
+            assert_eq!((a as f32, b as f32), (pa.x, pb.x));
+        }).unwrap();
+    }
+    world.with_entity_pair(&entities[0], &entities[1], |a, b, c| assert!(c.position.get_pair_mut(&a, &b).is_none())).unwrap();
+
+    world.with_entity_data(&entities[4], |e, c| {
+        c.position[e].y = 2.0;
+        assert_eq!(Position { x: 4.0, y: 2.0 }, c.position[e]);
+    });
+    world.modify_entity(entities[4], |e: ModifyData<PagedComponents>, c: &mut PagedComponents| {
+        assert!(c.position.remove(&e).is_some());
+    });
+    world.update();
+    assert_eq!(2, world.systems.positions.0);
+}
+
+#[test]
+fn test_arena_storage()
+{
+    use ecs::component::{Arena, ComponentStorage};
+
+    let mut arena = Arena::with_page_size(4);
+    for wave in 0..3
+    {
+        for index in 0..6
+        {
+            assert_eq!(None, arena.insert(index * 10, wave * 10 + index));
+        }
+        assert_eq!(Some(&(wave * 10 + 5)), arena.get(50));
+        assert_eq!(6, arena.len());
+        assert_eq!(2, arena.pages());
+        for index in 0..6
+        {
+            assert_eq!(Some(wave * 10 + index), arena.remove(index * 10));
+        }
+    }
+    // Pairs within a page and across pages
+    for index in 0..6
+    {
+        arena.insert(index, index);
+    }
+    assert_eq!(Some((&mut 1, &mut 2)), arena.get_pair_mut(1, 2));
+    assert_eq!(Some((&mut 5, &mut 0)), arena.get_pair_mut(5, 0));
+    assert_eq!(None, arena.get_pair_mut(1, 7));
+    for index in 0..6
+    {
+        arena.remove(index);
+    }
+    arena.shrink_to_fit();
+    assert_eq!(0, arena.pages());
+
+    components! {
+        ArenaComponents {
+            #[storage(Arena<Position>)] position: Position
+        }
+    }
+
+    systems! {
+        ArenaSystems<ArenaComponents, ()>;
+    }
+
+    let mut world = World::<ArenaSystems>::new();
+    for _ in 0..2
+    {
+        let entities: Vec<Entity> = (0..100).map(|_| world.create_entity(ArenaComponents::position(Position { x: 1.0, y: 1.0 }))).collect();
+        world.update();
+        assert_eq!(100, world.memory_report().get("position").unwrap().len);
+        assert_eq!(256, world.memory_report().get("position").unwrap().capacity);
+        for entity in entities
+        {
+            world.remove_entity(entity);
+        }
+        world.update();
+    }
+}
+
+soa_struct!(Position => PositionColumns { x: f32, y: f32 });
+
+#[test]
+fn test_soa()
+{
+    components! {
+        SoaComponents {
+            #[soa] position: Position
+        }
+    }
+
+    systems! {
+        SoaSystems<SoaComponents, ()>;
+    }
+
+    let mut world = World::<SoaSystems>::new();
+    let entities: Vec<Entity> = (0..4).map(|i| world.create_entity(SoaComponents::position(Position { x: i as f32, y: 0.0 }))).collect();
+    world.update();
+    assert_eq!(vec![0.0, 1.0, 2.0, 3.0], world.position.columns().x);
+
+    let columns = world.position.columns_mut();
+    for (x, y) in columns.x.iter().zip(columns.y.iter_mut())
+    {
+        *y = *x * 2.0;
+    }
+    world.with_entity_data(&entities[2], |e, c| {
+        assert_eq!(Some(Position { x: 2.0, y: 4.0 }), c.position.get(&e));
+        assert_eq!(Some(2), c.position.position(&e));
+    });
+
+    world.modify_entity(entities[0], |e: ModifyData<SoaComponents>, c: &mut SoaComponents| {
+        assert_eq!(Some(Position { x: 0.0, y: 0.0 }), c.position.remove(&e));
+    });
+    world.update();
+    assert_eq!(3, world.position.len());
+    assert_eq!(vec![3.0, 1.0, 2.0], world.position.columns().x);
+    assert_eq!(&[entities[3], entities[1], entities[2]], world.position.entities());
+    world.with_entity_data(&entities[3], |e, c| {
+        assert_eq!(Some(Position { x: 3.0, y: 6.0 }), c.position.get(&e));
+    });
+}
+
+#[test]
+fn test_dynamic_column()
+{
+    use std::sync::Arc;
+    use ecs::column::DynamicColumn;
+    use ecs::Error;
+
+    let counter = Arc::new(());
+    let mut column = DynamicColumn::new::<(String, Arc<()>)>();
+    for name in &["a", "b", "c", "d"]
+    {
+        column.push((name.to_string(), counter.clone())).unwrap();
+    }
+    assert_eq!(5, Arc::strong_count(&counter));
+    assert!(column.is::<(String, Arc<()>)>());
+    assert_eq!(::std::mem::size_of::<(String, Arc<()>)>(), column.stride());
+    match column.push(1u32)
+    {
+        Err(Error::WrongType { found, .. }) => assert_eq!("u32", found),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!(None, column.get::<String>(0));
+
+    let (name, _) = column.swap_remove::<(String, Arc<()>)>(0).unwrap();
+    assert_eq!("a", name);
+    assert!(column.swap_remove_drop(0));
+    assert_eq!(3, Arc::strong_count(&counter));
+    let names: Vec<&str> = column.as_slice::<(String, Arc<()>)>().unwrap().iter().map(|value| &value.0[..]).collect();
+    assert_eq!(vec!["c", "b"], names);
+    assert_eq!(2, column.len());
+
+    column.shrink_to_fit();
+    assert_eq!(2, column.capacity());
+    drop(column);
+    assert_eq!(1, Arc::strong_count(&counter));
+
+    let mut units = DynamicColumn::new::<()>();
+    units.push(()).unwrap();
+    assert_eq!(Some(&()), units.get::<()>(0));
+}
+
+#[test]
+fn test_sparse_column()
+{
+    use std::sync::Arc;
+    use ecs::column::SparseColumn;
+
+    let counter = Arc::new(());
+    let mut column = SparseColumn::new::<(String, Arc<()>)>();
+    for &index in &[3, 70, 5]
+    {
+        assert_eq!(None, column.insert(index, (index.to_string(), counter.clone())).unwrap());
+    }
+    assert!(column.insert(5, 1u32).is_err());
+    assert_eq!(4, Arc::strong_count(&counter));
+    assert_eq!(vec![3, 5, 70], column.indices());
+    assert!(!column.contains(4));
+    assert_eq!(None, column.get::<(String, Arc<()>)>(4));
+    assert_eq!("70", column.get::<(String, Arc<()>)>(70).unwrap().0);
+
+    let (old, _) = column.insert(3, ("three".to_string(), counter.clone())).unwrap().unwrap();
+    assert_eq!("3", old);
+    assert_eq!(4, Arc::strong_count(&counter));
+    assert!(column.remove_drop(70));
+    assert!(!column.remove_drop(70));
+    assert_eq!(3, Arc::strong_count(&counter));
+    column.shrink_to_fit();
+    assert_eq!(6, column.capacity());
+    assert_eq!(2, column.len());
+    drop(column);
+    assert_eq!(1, Arc::strong_count(&counter));
+}
+
+#[test]
+fn test_immediate_mode()
+{
+    let mut world = World::<TestSystems>::new();
+    let query = world.add_query(aspect!(<TestComponents> all: [team]));
+    let team = |e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.team.add(&e, Team(1));
+    };
+
+    let queued = world.create_entity(team);
+    assert_eq!(0, world.query(query).unwrap().len());
+    world.flush();
+    assert_eq!(1, world.query(query).unwrap().len());
+
+    world.set_immediate(true);
+    let entity = world.create_entity(team);
+    assert_eq!(2, world.query(query).unwrap().len());
+    world.remove_entity(queued);
+    assert_eq!(1, world.query(query).unwrap().len());
+    assert!(!world.is_alive(&queued));
+
+    world.set_immediate(false);
+    world.remove_entity(entity);
+    assert!(world.is_alive(&entity));
+    world.update();
+    assert_eq!(0, world.query(query).unwrap().len());
+}
+
+#[test]
+fn test_child_entities()
+{
+    use ecs::{ChildSpawner, EntityEvent};
+
+    let mut world = World::<TestSystems>::new();
+    let mut gun = None;
+    let owner = world.create_entity_with_children(|e: BuildData<TestComponents>, c: &mut TestComponents, children: &mut ChildSpawner<TestComponents>| {
+        c.team.add(&e, Team(1));
+        let owner = children.parent();
+        gun = Some(children.spawn_with(move |e: BuildData<TestComponents>, c: &mut TestComponents, children: &mut ChildSpawner<TestComponents>| {
+            c.position.add(&e, Position { x: owner.id() as f32, y: 0.0 });
+            children.spawn(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+                c.team.add(&e, Team(3));
+            });
+        }));
+        children.spawn(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+            c.team.add(&e, Team(2));
+        });
+    });
+    let gun = gun.unwrap();
+    world.update();
+
+    let created: Vec<Entity> = world.entity_events().iter().map(|event| match *event {
+        EntityEvent::Created(entity) => entity,
+        EntityEvent::Removed(entity) => panic!("{:?} removed", entity),
+    }).collect();
+    assert_eq!(4, created.len());
+    assert_eq!(vec![owner, gun], created[..2].to_vec());
+    assert_eq!(Some(&Position { x: owner.id() as f32, y: 0.0 }), world.component(&gun, |c| &c.position));
+    let teams: Vec<Team> = created[2..].iter().map(|e| *world.component(e, |c| &c.team).unwrap()).collect();
+    assert_eq!(vec![Team(3), Team(2)], teams);
+}
+
+#[test]
+fn test_removed_components()
+{
+    use ecs::manager::RemovedComponents;
+
+    systems! {
+        RemovedSystems<TestComponents, ()> {
+            removed: RemovedComponents<Position, TestComponents> = RemovedComponents::new(|e, c: &TestComponents| c.position.get(e))
+        }
+    }
+
+    let mut world = World::<RemovedSystems>::new();
+    let entities: Vec<Entity> = (0..4).map(|i| world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        if i != 1 {
+            c.position.add(&e, Position { x: i as f32, y: 0.0 });
+        }
+    })).collect();
+    world.update();
+
+    world.remove_entity(entities[2]);
+    world.remove_entity(entities[1]);
+    world.update();
+    world.remove_entity(entities[0]);
+    world.compact();
+    let removed: Vec<(Entity, Position)> = world.systems.removed.drain().collect();
+    assert_eq!(vec![(entities[2], Position { x: 2.0, y: 0.0 }), (entities[0], Position { x: 0.0, y: 0.0 })], removed);
+    assert!(world.systems.removed.is_empty());
+}
+
+#[test]
+fn test_cleanup_system()
+{
+    use ecs::system::CleanupSystem;
+
+    systems! {
+        CleanupSystems<TestComponents, ()> {
+            dead: CleanupSystem<TestComponents> = CleanupSystem::new(aspect!(<TestComponents> all: [feature])),
+            dying: CleanupSystem<TestComponents> = CleanupSystem::with_delay(aspect!(<TestComponents> all: [team]), 2)
+        }
+    }
+
+    let mut world = World::<CleanupSystems>::new();
+    let alive = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+    });
+    let dying = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.team.add(&e, Team(1));
+    });
+    world.update();
+    assert!(world.is_alive(&alive));
+    assert!(world.systems.dying.is_scheduled(&dying));
+    assert_eq!(2, world.despawning(&dying).unwrap().remaining);
+
+    world.modify_entity(alive, |e: ModifyData<TestComponents>, c: &mut TestComponents| {
+        c.feature.insert(&e, SomeFeature);
+    });
+    world.update();
+    assert!(!world.is_alive(&alive));
+    assert!(world.is_alive(&dying));
+    assert_eq!(1, world.systems.dying.len());
+
+    world.update();
+    assert!(!world.is_alive(&dying));
+    assert!(world.systems.dying.is_empty());
+}
+
+#[test]
+fn test_transform_system()
+{
+    use ecs::ComponentList;
+    use ecs::system::{Parent, Transform, TransformComponents, TransformSystem};
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    pub struct Offset(i32, i32);
+
+    impl Transform for Offset
+    {
+        fn within(&self, parent: &Offset) -> Offset { Offset(self.0 + parent.0, self.1 + parent.1) }
+    }
+
+    components! {
+        TransformTestComponents {
+            #[hot] local: Offset,
+            #[hot] global: Offset,
+            #[cold] parent: Parent
+        }
+    }
+
+    impl TransformComponents for TransformTestComponents
+    {
+        type Transform = Offset;
+        fn local_transforms(&self) -> &ComponentList<Self, Offset> { &self.local }
+        fn global_transforms_mut(&mut self) -> &mut ComponentList<Self, Offset> { &mut self.global }
+        fn parents(&self) -> &ComponentList<Self, Parent> { &self.parent }
+    }
+
+    systems! {
+        TransformSystems<TransformTestComponents, ()> {
+            transform: TransformSystem<TransformTestComponents> = TransformSystem::new()
+        }
+    }
+
+    let mut world = World::<TransformSystems>::new();
+    let offsets = [Offset(1, 1), Offset(0, 1), Offset(1, 0), Offset(5, 5)];
+    let entities: Vec<Entity> = offsets.iter().map(|&offset| world.create_entity(TransformTestComponents::local(offset))).collect();
+    // 0 is held by 1, which is held by 2. 3 is its own parent.
+    for &(child, parent) in &[(0, 1), (1, 2), (3, 3)]
+    {
+        let parent = entities[parent];
+        world.modify_entity(entities[child], |e: ModifyData<TransformTestComponents>, c: &mut TransformTestComponents| {
+            c.parent.insert(&e, Parent(parent));
+        });
+    }
+    world.update();
+    assert_eq!(4, world.systems.transform.len());
+    let globals: Vec<Offset> = entities.iter().map(|e| *world.component(e, |c| &c.global).unwrap()).collect();
+    assert_eq!(vec![Offset(2, 2), Offset(1, 1), Offset(1, 0), Offset(5, 5)], globals);
+
+    world.remove_entity(entities[2]);
+    world.update();
+    assert_eq!(Some(&Offset(1, 2)), world.component(&entities[0], |c| &c.global));
+}
+
+#[test]
+fn test_fsm_system()
+{
+    use ecs::ComponentList;
+    use ecs::system::{FsmComponents, FsmProcess, FsmSystem, StateMachine};
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    pub enum Ai { Idle, Chase }
+
+    components! {
+        FsmTestComponents {
+            #[hot] ai: StateMachine<Ai>
+        }
+    }
+
+    impl FsmComponents<Ai> for FsmTestComponents
+    {
+        fn state_machines(&self) -> &ComponentList<Self, StateMachine<Ai>> { &self.ai }
+        fn state_machines_mut(&mut self) -> &mut ComponentList<Self, StateMachine<Ai>> { &mut self.ai }
+    }
+
+    pub struct AiProcess(Vec<String>);
+    impl System for AiProcess { type Components = FsmTestComponents; type Services = (); }
+    impl FsmProcess for AiProcess
+    {
+        type State = Ai;
+        fn enter(&mut self, state: &Ai, _: &EntityData<FsmTestComponents>, _: &mut DataHelper<FsmTestComponents, ()>)
+        {
+            self.0.push(format!("enter {:?}", state));
+        }
+
+        fn exit(&mut self, state: &Ai, _: &EntityData<FsmTestComponents>, _: &mut DataHelper<FsmTestComponents, ()>)
+        {
+            self.0.push(format!("exit {:?}", state));
+        }
+
+        fn update(&mut self, state: &Ai, e: &EntityData<FsmTestComponents>, data: &mut DataHelper<FsmTestComponents, ()>)
+        {
+            self.0.push(format!("update {:?}", state));
+            if *state == Ai::Chase
+            {
+                data.ai.borrow(e).unwrap().transition(Ai::Idle);
+            }
+        }
+    }
+
+    systems! {
+        FsmSystems<FsmTestComponents, ()> {
+            ai: FsmSystem<AiProcess> = FsmSystem::new(AiProcess(Vec::new()))
+        }
+    }
+
+    let mut world = World::<FsmSystems>::new();
+    let entity = world.create_entity(FsmTestComponents::ai(StateMachine::new(Ai::Idle)));
+    world.update();
+    world.with_entity_data(&entity, |e, c| c.ai[e].transition(Ai::Chase));
+    world.update();
+    world.update();
+    world.with_entity_data(&entity, |e, c| c.ai[e].transition(Ai::Idle));
+    world.update();
+    assert_eq!(vec!["enter Idle", "update Idle", "exit Idle", "enter Chase", "update Chase", "exit Chase", "enter Idle", "update Idle", "update Idle"],
+        world.systems.ai.0);
+}
+
+#[test]
+fn test_event_system()
+{
+    use ecs::manager::QueueManager;
+    use ecs::system::{EventProcess, EventServices, EventSystem};
+
+    services! {
+        SoundServices {
+            sounds: QueueManager<&'static str> = QueueManager::new()
+        }
+    }
+
+    impl EventServices<&'static str> for SoundServices
+    {
+        fn events(&mut self) -> &mut QueueManager<&'static str> { &mut self.sounds }
+    }
+
+    pub struct Mixer(Vec<Vec<&'static str>>);
+    impl System for Mixer { type Components = TestComponents; type Services = SoundServices; }
+    impl EventProcess for Mixer
+    {
+        type Event = &'static str;
+        fn process(&mut self, sounds: &[&'static str], data: &mut DataHelper<TestComponents, SoundServices>)
+        {
+            self.0.push(sounds.to_vec());
+            if sounds.contains(&"boom")
+            {
+                data.services.sounds.push("echo").unwrap();
+            }
+        }
+    }
+
+    systems! {
+        SoundSystems<TestComponents, SoundServices> {
+            mixer: EventSystem<Mixer> = EventSystem::new(Mixer(Vec::new()))
+        }
+    }
+
+    let mut world = World::<SoundSystems>::new();
+    world.update();
+    assert!(world.systems.mixer.0.is_empty());
+
+    world.services.sounds.push("step").unwrap();
+    world.services.sounds.push_with_priority("boom", 1).unwrap();
+    world.update();
+    world.update();
+    world.update();
+    assert_eq!(vec![vec!["boom", "step"], vec!["echo"]], world.systems.mixer.0);
+}
+
+#[test]
+fn test_entity_event_system()
+{
+    use ecs::manager::QueueManager;
+    use ecs::system::{EntityEventProcess, EntityEventSystem, EventServices, TargetedEvent};
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    pub struct DamageEvent { target: Entity, amount: i32 }
+
+    impl TargetedEvent for DamageEvent
+    {
+        fn target(&self) -> Entity { self.target }
+    }
+
+    components! {
+        HealthComponents {
+            #[hot] health: i32
+        }
+    }
+
+    services! {
+        DamageServices {
+            damage: QueueManager<DamageEvent> = QueueManager::new()
+        }
+    }
+
+    impl EventServices<DamageEvent> for DamageServices
+    {
+        fn events(&mut self) -> &mut QueueManager<DamageEvent> { &mut self.damage }
+    }
+
+    pub struct Health(Vec<(Entity, Vec<i32>)>);
+    impl System for Health { type Components = HealthComponents; type Services = DamageServices; }
+    impl EntityEventProcess for Health
+    {
+        type Event = DamageEvent;
+        fn process(&mut self, entity: &EntityData<HealthComponents>, events: &[DamageEvent], data: &mut DataHelper<HealthComponents, DamageServices>)
+        {
+            self.0.push((***entity, events.iter().map(|event| event.amount).collect()));
+            *data.health.borrow(entity).unwrap() -= events.iter().map(|event| event.amount).sum::<i32>();
+        }
+    }
+
+    systems! {
+        DamageSystems<HealthComponents, DamageServices> {
+            health: EntityEventSystem<Health> = EntityEventSystem::new(Health(Vec::new()),
+                aspect!(<HealthComponents> all: [health]))
+        }
+    }
+
+    let mut world = World::<DamageSystems>::new();
+    let a = world.create_entity(|e: BuildData<HealthComponents>, c: &mut HealthComponents| { c.health.add(&e, 10); });
+    let b = world.create_entity(|e: BuildData<HealthComponents>, c: &mut HealthComponents| { c.health.add(&e, 10); });
+    let wall = world.create_entity(|_: BuildData<HealthComponents>, _: &mut HealthComponents| {});
+    let gone = world.create_entity(|e: BuildData<HealthComponents>, c: &mut HealthComponents| { c.health.add(&e, 10); });
+    world.remove_entity(gone);
+    world.update();
+    assert!(world.systems.health.0.is_empty());
+
+    for &(target, amount) in &[(b, 1), (wall, 5), (a, 2), (gone, 5), (b, 3)]
+    {
+        world.services.damage.push(DamageEvent { target, amount }).unwrap();
+    }
+    world.update();
+    assert_eq!(vec![(b, vec![1, 3]), (a, vec![2])], world.systems.health.0);
+    assert!(world.services.damage.is_empty());
+    assert_eq!(Some(8), world.with_entity_data(&a, |e, c| c.health[e]));
+    assert_eq!(Some(6), world.with_entity_data(&b, |e, c| c.health[e]));
+}
+
+#[cfg(feature="serde")]
+#[test]
+fn test_message_bus()
+{
+    use ecs::Error;
+    use ecs::manager::{LocalNetwork, MessageBus};
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub enum Message { Spawn { x: f32, y: f32 }, Input(String) }
+
+    services! {
+        NetServices {
+            bus: MessageBus<Message> = MessageBus::new()
+        }
+    }
+
+    systems! {
+        NetSystems<TestComponents, NetServices>;
+    }
+
+    let mut server = World::<NetSystems>::new();
+    let mut client = World::<NetSystems>::new();
+    assert!(client.services.bus.send_to(0, &Message::Input("jump".to_string())).is_err());
+
+    let network = LocalNetwork::new();
+    server.services.bus.set_transport(network.connect(0));
+    client.services.bus.set_transport(network.connect(1));
+    assert!(matches!(client.services.bus.send_to(2, &Message::Input("jump".to_string())), Err(Error::Transport(_))));
+
+    client.services.bus.send_to(0, &Message::Input("jump".to_string())).unwrap();
+    client.services.bus.send_to(0, &Message::Input("fire".to_string())).unwrap();
+    let inputs: Vec<Message> = server.services.bus.drain_from(1).unwrap().collect();
+    assert_eq!(vec![Message::Input("jump".to_string()), Message::Input("fire".to_string())], inputs);
+    assert_eq!(0, server.services.bus.drain_from(1).unwrap().count());
+
+    let spawned = server.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 1.0, y: 2.0 });
+    });
+    let position = server.with_entity_data(&spawned, |e, c| c.position[e]).unwrap();
+    server.services.bus.send_to(1, &Message::Spawn { x: position.x, y: position.y }).unwrap();
+    let messages: Vec<Message> = client.services.bus.drain_from(0).unwrap().collect();
+    for message in messages
+    {
+        if let Message::Spawn { x, y } = message
+        {
+            client.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+                c.position.add(&e, Position { x, y });
+            });
+        }
+    }
+    client.update();
+    assert_eq!(1, client.entities().count());
+}
+
+#[test]
+fn test_prediction()
+{
+    use ecs::predict::Predictor;
+
+    let mut world = World::<TestSystems>::new();
+    let player = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+    });
+    let mut predictor = Predictor::<TestComponents, f32>::new();
+    predictor.predict("position", |c| &mut c.position);
+    let step = |world: &mut World<TestSystems>, dx: &f32| {
+        world.with_entity_data(&player, |e, c| c.position[e].x += *dx);
+    };
+
+    for _ in 0..3
+    {
+        predictor.input(1.0);
+        predictor.update(&mut world, step);
+    }
+    assert_eq!(3, predictor.frame());
+    assert_eq!(3, predictor.pending());
+
+    // The server applied the first input, but the player was also knocked back.
+    let mispredictions = predictor.reconcile(&mut world, 0, |world| {
+        world.with_entity_data(&player, |e, c| c.position[e].x = -4.0);
+    }, step);
+    assert_eq!(2, predictor.pending());
+    assert_eq!(1, mispredictions.len());
+    assert_eq!(player, mispredictions[0].entity);
+    assert_eq!("position", mispredictions[0].component);
+    assert_eq!(Some(3.0), mispredictions[0].predicted::<Position>().map(|p| p.x));
+    assert_eq!(Some(-2.0), mispredictions[0].corrected::<Position>().map(|p| p.x));
+    assert!(mispredictions[0].corrected::<Team>().is_none());
+
+    let mispredictions = predictor.reconcile(&mut world, 2, |world| {
+        world.with_entity_data(&player, |e, c| c.position[e].x = -2.0);
+    }, step);
+    assert!(mispredictions.is_empty());
+    assert_eq!(0, predictor.pending());
+}
+
+delta_struct!(Position { x, y });
+
+#[test]
+fn test_delta_encoding()
+{
+    use ecs::replication::{BitReader, BitWriter, DeltaCodec, DeltaDecoder, DeltaEncoder};
+
+    let mut world = World::<TestSystems>::new();
+    let entity = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 1.0, y: -2.5 });
+    });
+    let mut encoder = DeltaEncoder::<Position>::new();
+    let mut decoder = DeltaDecoder::<Position>::new();
+
+    let position = world.with_entity_data(&entity, |e, c| c.position[e]).unwrap();
+    let full = encoder.encode(entity, &position);
+    assert_eq!(8, full.len());
+    assert_eq!(position, decoder.decode(entity, &full).unwrap());
+
+    world.with_entity_data(&entity, |e, c| c.position[e].x = 3.0);
+    let position = world.with_entity_data(&entity, |e, c| c.position[e]).unwrap();
+    let moved = encoder.encode(entity, &position);
+    assert_eq!(5, moved.len());
+    assert_eq!(position, decoder.decode(entity, &moved).unwrap());
+
+    let unchanged = encoder.encode(entity, &position);
+    assert_eq!(vec![0], unchanged);
+    assert_eq!(position, decoder.decode(entity, &unchanged).unwrap());
+    assert!(decoder.decode(entity, &[]).is_err());
+
+    // A codec that sends coordinates as whole numbers.
+    pub struct Grid;
+    impl DeltaCodec<Position> for Grid
+    {
+        fn encode(&self, _: Option<&Position>, new: &Position, out: &mut BitWriter)
+        {
+            out.write_bits(new.x as i8 as u64, 8);
+            out.write_bits(new.y as i8 as u64, 8);
+        }
+
+        fn decode(&self, _: Option<&Position>, input: &mut BitReader) -> ecs::Result<Position>
+        {
+            Ok(Position { x: input.read_bits(8)? as i8 as f32, y: input.read_bits(8)? as i8 as f32 })
+        }
+    }
+
+    let mut encoder = DeltaEncoder::with_codec(Grid);
+    let mut decoder = DeltaDecoder::with_codec(Grid);
+    let bytes = encoder.encode(entity, &Position { x: 3.2, y: -2.5 });
+    assert_eq!(2, bytes.len());
+    assert_eq!(Position { x: 3.0, y: -2.0 }, decoder.decode(entity, &bytes).unwrap());
+}
+
+#[cfg(all(feature = "serde", feature = "compress"))]
+#[test]
+fn test_snapshot_compression()
+{
+    use std::io::{Read, Write};
+    use ecs::compress::{self, Decoder, Encoder};
+    use ecs::scene::Scene;
+
+    components! {
+        CompressComponents {
+            #[hot] #[persist] position: Position,
+            #[cold] #[persist] health: u32
+        }
+    }
+
+    systems! {
+        CompressSystems<CompressComponents, ()>;
+    }
+
+    let mut world = World::<CompressSystems>::new();
+    for i in 0..5000
+    {
+        world.create_entity(move |e: BuildData<CompressComponents>, c: &mut CompressComponents| {
+            c.position.add(&e, Position { x: (i % 100) as f32, y: (i / 100) as f32 });
+            c.health.add(&e, 100);
+        });
+    }
+    world.update();
+
+    let snapshot = world.snapshot(1).unwrap();
+    let json = snapshot.to_json();
+    let compressed = snapshot.write_compressed(Vec::new()).unwrap();
+    assert!(compressed.len() * 4 < json.len());
+    assert_eq!(json.as_bytes(), &compress::decompress(&compressed).unwrap()[..]);
+    assert_eq!(snapshot.to_value(), Scene::read_compressed(&compressed[..]).unwrap().to_value());
+
+    // Streaming in small pieces gives the same bytes back
+    let data: Vec<u8> = (0..200000u64).map(|i| (i * i / 7 % 251) as u8).collect();
+    let mut encoder = Encoder::new(Vec::new());
+    for chunk in data.chunks(1000)
+    {
+        encoder.write_all(chunk).unwrap();
+    }
+    let stream = encoder.finish().unwrap();
+    assert_eq!(stream, compress::compress(&data));
+    let mut decoder = Decoder::new(&stream[..]);
+    let mut piece = [0; 777];
+    let mut decompressed = Vec::new();
+    loop
+    {
+        match decoder.read(&mut piece).unwrap()
+        {
+            0 => break,
+            n => decompressed.extend_from_slice(&piece[..n]),
+        }
+    }
+    assert_eq!(data, decompressed);
+
+    assert_eq!(Vec::<u8>::new(), compress::decompress(&compress::compress(&[])).unwrap());
+    assert_eq!(b"abc".to_vec(), compress::decompress(&compress::compress(b"abc")).unwrap());
+    assert!(compress::decompress(&stream[..stream.len() - 1]).is_err());
+    assert!(Scene::read_compressed(&b"not compressed"[..]).is_err());
+}
+
+#[test]
+fn test_determinism()
+{
+    use ecs::DeterminismConfig;
+    use ecs::system::{InteractProcess, InteractSystem};
+
+    pub struct Pairs(std::cell::RefCell<Vec<(usize, usize)>>);
+    impl InteractProcess for Pairs
+    {
+        fn process(&self, a: EntityIter<TestComponents>, b: EntityIter<TestComponents>, _: &mut DataHelper<TestComponents, ()>)
+        {
+            let b: Vec<_> = b.map(|e| e.index()).collect();
+            for a in a
+            {
+                for &b in &b
+                {
+                    self.0.borrow_mut().push((a.index(), b));
+                }
+            }
+        }
+    }
+    impl System for Pairs { type Components = TestComponents; type Services = (); }
+
+    systems! {
+        PairSystems<TestComponents, ()> {
+            pairs: InteractSystem<Pairs> = InteractSystem::new(Pairs(Default::default()),
+                aspect!(<TestComponents> all: [team]),
+                aspect!(<TestComponents> all: [position]))
+        }
+    }
+
+    let run = |seed: u64| {
+        let mut world = World::<PairSystems>::new();
+        world.set_determinism(Some(DeterminismConfig { seed }));
+        assert!(world.is_deterministic());
+        assert!(world.wall_clock().is_none());
+        for i in 0..20
+        {
+            world.create_entity(move |e: BuildData<TestComponents>, c: &mut TestComponents| {
+                if i % 2 == 0 { c.team.add(&e, Team(i)); }
+                if i % 3 == 0 { c.position.add(&e, Position { x: 0.0, y: 0.0 }); }
+            });
+        }
+        world.update();
+        let entities: Vec<usize> = world.entities().map(|e| e.index()).collect();
+        let rolls: Vec<i32> = (0..5).map(|_| world.rng().range(0..100)).collect();
+        let pairs = world.systems.pairs.inner.0.borrow().clone();
+        (entities, rolls, pairs)
+    };
+
+    let (entities, rolls, pairs) = run(7);
+    assert_eq!((0..20).collect::<Vec<_>>(), entities);
+    assert!(rolls.iter().all(|roll| (0..100).contains(roll)));
+    let mut sorted = pairs.clone();
+    sorted.sort();
+    assert_eq!(sorted, pairs);
+    assert_eq!(70, pairs.len());
+    assert_eq!((entities, rolls.clone(), pairs), run(7));
+    assert!(rolls != run(8).1);
+
+    let mut world = World::<TestSystems>::new();
+    assert!(!world.is_deterministic());
+    assert!(world.wall_clock().is_some());
+    world.set_determinism(Some(DeterminismConfig { seed: 7 }));
+    world.set_determinism(None);
+    assert!(world.wall_clock().is_some());
+}
+
+#[test]
+fn test_rng_streams()
+{
+    use ecs::manager::Rng;
+
+    services! {
+        RngServices {
+            rng: Rng = Rng::seeded(42)
+        }
+    }
+
+    pub struct Spawner(Vec<i32>);
+    impl Process for Spawner
+    {
+        fn process(&mut self, data: &mut DataHelper<TestComponents, RngServices>)
+        {
+            self.0.push(data.services.rng.stream("spawner").range(0..1000));
+        }
+    }
+    impl System for Spawner { type Components = TestComponents; type Services = RngServices; }
+
+    pub struct Noise;
+    impl Process for Noise
+    {
+        fn process(&mut self, data: &mut DataHelper<TestComponents, RngServices>)
+        {
+            data.services.rng.next_u64();
+            data.services.rng.stream("noise").next_u64();
+        }
+    }
+    impl System for Noise { type Components = TestComponents; type Services = RngServices; }
+
+    systems! {
+        SpawnerSystems<TestComponents, RngServices> {
+            spawner: Spawner = Spawner(Vec::new())
+        }
+    }
+
+    systems! {
+        NoisySystems<TestComponents, RngServices> {
+            noise: Noise = Noise,
+            spawner: Spawner = Spawner(Vec::new())
+        }
+    }
+
+    let mut quiet = World::<SpawnerSystems>::new();
+    let mut noisy = World::<NoisySystems>::new();
+    let saved = noisy.services.rng.clone();
+    for _ in 0..10
+    {
+        quiet.update();
+        noisy.update();
+    }
+    assert_eq!(quiet.systems.spawner.0, noisy.systems.spawner.0);
+    assert!(quiet.systems.spawner.0.iter().any(|&n| n != quiet.systems.spawner.0[0]));
+    assert!(quiet.services.rng != noisy.services.rng);
+
+    // A clone carries on from the same place
+    noisy.services.rng = saved;
+    noisy.update();
+    assert_eq!(quiet.systems.spawner.0[0], noisy.systems.spawner.0[10]);
+
+    let mut rng = Rng::seeded(42);
+    let first = rng.stream("spawner").next_u64();
+    rng.reseed(42);
+    assert_eq!(first, rng.stream("spawner").next_u64());
+    assert_eq!(42, rng.seed());
+    assert!(Rng::seeded(43).stream("spawner").next_u64() != first);
+}
+
+#[test]
+fn test_interest_tracker()
+{
+    use ecs::InterestTracker;
+
+    pub struct Changes(Vec<(&'static str, Entity)>);
+    impl System for Changes
+    {
+        type Components = TestComponents;
+        type Services = ();
+        fn activated(&mut self, e: &EntityData<TestComponents>, _: &TestComponents)
+        {
+            self.0.push(("activated", ***e));
+        }
+        fn reactivated(&mut self, e: &EntityData<TestComponents>, _: &TestComponents)
+        {
+            self.0.push(("reactivated", ***e));
+        }
+        fn deactivated(&mut self, e: &EntityData<TestComponents>, _: &TestComponents)
+        {
+            self.0.push(("deactivated", ***e));
+        }
+    }
+
+    // A wrapper that only looks at the first of its entities
+    pub struct First
+    {
+        interest: InterestTracker<TestComponents>,
+        inner: Changes,
+        first: Option<Entity>,
+    }
+    impl System for First
+    {
+        type Components = TestComponents;
+        type Services = ();
+        fn activated(&mut self, e: &EntityData<TestComponents>, c: &TestComponents)
+        {
+            self.interest.on_activated(e, c, &mut self.inner);
+        }
+        fn activated_batch(&mut self, e: &[EntityData<TestComponents>], c: &TestComponents)
+        {
+            self.interest.on_activated_batch(e, c, &mut self.inner);
+        }
+        fn reactivated(&mut self, e: &EntityData<TestComponents>, c: &TestComponents)
+        {
+            self.interest.on_reactivated(e, c, &mut self.inner);
+        }
+        fn deactivated(&mut self, e: &EntityData<TestComponents>, c: &TestComponents)
+        {
+            self.interest.on_deactivated(e, c, &mut self.inner);
+        }
+    }
+    impl Process for First
+    {
+        fn process(&mut self, _: &mut DataHelper<TestComponents, ()>)
+        {
+            self.first = self.interest.iter().next().map(|e| **e);
+        }
+    }
+
+    systems! {
+        FirstSystems<TestComponents, ()> {
+            first: First = First {
+                interest: InterestTracker::new(aspect!(<TestComponents> all: [position])),
+                inner: Changes(Vec::new()),
+                first: None
+            }
+        }
+    }
+
+    let mut world = World::<FirstSystems>::new();
+    let a = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+    });
+    let b = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 1.0, y: 0.0 });
+    });
+    let unmatched = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.team.add(&e, Team(0));
+    });
+    world.update();
+    assert_eq!(vec![("activated", a), ("activated", b)], world.systems.first.inner.0);
+    assert_eq!(Some(a), world.systems.first.first);
+    assert_eq!(2, world.systems.first.interest.len());
+
+    world.modify_entity(b, |e: ModifyData<TestComponents>, c: &mut TestComponents| {
+        c.feature.insert(&e, SomeFeature);
+    });
+    world.modify_entity(a, |e: ModifyData<TestComponents>, c: &mut TestComponents| {
+        c.position.remove(&e);
+    });
+    world.remove_entity(unmatched);
+    world.update();
+    assert_eq!(vec![("reactivated", b), ("deactivated", a)], world.systems.first.inner.0[2..].to_vec());
+    assert_eq!(Some(b), world.systems.first.first);
+
+    world.remove_entity(b);
+    world.update();
+    assert_eq!(Some(&("deactivated", b)), world.systems.first.inner.0.last());
+    assert!(world.systems.first.interest.is_empty());
+    assert_eq!(None, world.systems.first.first);
+}
+
+#[test]
+fn test_merged_system_manager()
+{
+    use ecs::system::MergedSystemManager;
+
+    pub struct Fall;
+    impl EntityProcess for Fall
+    {
+        fn process(&mut self, en: EntityIter<TestComponents>, co: &mut DataHelper<TestComponents, ()>)
+        {
+            for e in en
+            {
+                co.position[e].y -= 1.0;
+            }
+        }
+    }
+    impl System for Fall { type Components = TestComponents; type Services = (); }
+
+    systems! {
+        PhysicsSystems<TestComponents, ()> {
+            gravity: EntitySystem<Fall> = EntitySystem::new(Fall, aspect!(<TestComponents> all: [position]))
+        }
+    }
+
+    systems! {
+        GameSystems<TestComponents, ()> {
+            #[group(physics)]
+            physics: MergedSystemManager<PhysicsSystems> = MergedSystemManager::new(),
+            log: ReactivationLog = ReactivationLog(Vec::new())
+        }
+    }
+
+    let mut world = World::<GameSystems>::new();
+    let entity = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+    });
+    world.update();
+    assert_eq!(1, world.systems.physics.gravity.interested().count());
+    world.with_entity_data(&entity, |e, c| assert_eq!(-1.0, c.position[e].y));
+
+    process!(world, physics.gravity);
+    world.with_entity_data(&entity, |e, c| assert_eq!(-2.0, c.position[e].y));
+
+    // Pausing the field's group pauses the whole bundle
+    world.pause_group("physics");
+    world.update();
+    world.with_entity_data(&entity, |e, c| assert_eq!(-2.0, c.position[e].y));
+    world.resume_group("physics");
+
+    world.modify_entity(entity, |e: ModifyData<TestComponents>, c: &mut TestComponents| {
+        c.feature.insert(&e, SomeFeature);
+    });
+    world.remove_entity(entity);
+    world.update();
+    assert_eq!(vec![entity], world.systems.log.0);
+    assert_eq!(0, world.systems.physics.gravity.interested().count());
+}
+
+#[test]
+fn test_set_aspect()
+{
+    pub struct Active(Vec<Entity>);
+    impl EntityProcess for Active
+    {
+        fn process(&mut self, _: EntityIter<TestComponents>, _: &mut DataHelper<TestComponents, ()>) {}
+    }
+    impl System for Active
+    {
+        type Components = TestComponents;
+        type Services = ();
+        fn activated(&mut self, e: &EntityData<TestComponents>, _: &TestComponents)
+        {
+            self.0.push(***e);
+        }
+        fn deactivated(&mut self, e: &EntityData<TestComponents>, _: &TestComponents)
+        {
+            self.0.retain(|entity| *entity != ***e);
+        }
+    }
+
+    fn within(radius: f32) -> Aspect<TestComponents>
+    {
+        aspect!(<TestComponents> all: [position] where: [move |en, co| co.position[*en].x <= radius])
+    }
+
+    systems! {
+        RadiusSystems<TestComponents, ()> {
+            ai: EntitySystem<Active> = EntitySystem::new(Active(Vec::new()), within(1.0))
+        }
+    }
+
+    let mut world = World::<RadiusSystems>::new();
+    let near = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 1.0, y: 0.0 });
+    });
+    let far = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 5.0, y: 0.0 });
+    });
+    world.update();
+    assert_eq!(vec![near], world.systems.ai.0);
+
+    world.systems.ai.set_aspect(within(10.0), &world.data);
+    assert_eq!(vec![near, far], world.systems.ai.0);
+    assert_eq!(2, world.systems.ai.interested().count());
+
+    // Entities that haven't been activated yet are left for the next flush
+    let pending = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+    });
+    world.systems.ai.set_aspect(within(2.0), &world.data);
+    assert_eq!(vec![near], world.systems.ai.0);
+    world.update();
+    assert_eq!(vec![near, pending], world.systems.ai.0);
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn test_corrupt_compressed_headers()
+{
+    use ecs::compress::{compress, decompress};
+
+    let bytes = compress(&[7; 1000]);
+    assert_eq!(vec![7; 1000], decompress(&bytes).unwrap());
+
+    // A header cut short
+    assert!(decompress(&bytes[..5]).is_err());
+
+    // A compressed length no block could have, which mustn't be allocated
+    let mut oversized = bytes.clone();
+    oversized[4..8].copy_from_slice(&0x7FFF_FFFFu32.to_le_bytes());
+    assert!(decompress(&oversized).is_err());
+
+    // A block longer than a full block
+    let mut overlong = bytes;
+    overlong[0..4].copy_from_slice(&(1u32 << 20).to_le_bytes());
+    assert!(decompress(&overlong).is_err());
+}
+
+#[test]
+fn test_despawning_marker()
+{
+    pub struct Living;
+    impl EntityProcess for Living
+    {
+        fn process(&mut self, _: EntityIter<TestComponents>, _: &mut DataHelper<TestComponents, ()>) {}
+    }
+    impl System for Living { type Components = TestComponents; type Services = (); }
+
+    systems! {
+        LivingSystems<TestComponents, ()> {
+            living: EntitySystem<Living> = EntitySystem::new(Living,
+                aspect!(<TestComponents> all: [position]).and(!Aspect::despawning()))
+        }
+    }
+
+    let mut world = World::<LivingSystems>::new();
+    let dying = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 0.0, y: 0.0 });
+    });
+    let removed = world.create_entity(|e: BuildData<TestComponents>, c: &mut TestComponents| {
+        c.position.add(&e, Position { x: 1.0, y: 0.0 });
+    });
+    world.update();
+    assert_eq!(2, world.systems.living.interested().count());
+
+    world.remove_entity_after(dying, 3);
+    world.with_entity_data(&dying, |e, c| assert!(Aspect::despawning().check(&e, c)));
+    world.update();
+    assert_eq!(vec![removed], world.systems.living.interested().map(|e| **e).collect::<Vec<_>>());
+
+    // Removing an entity twice in one update removes it once
+    world.remove_entity_after(removed, 0);
+    world.remove_entity(removed);
+    let report = world.update();
+    assert_eq!(1, report.removed);
+    assert!(world.with_entity_data(&removed, |_, _| ()).is_none());
+    assert!(world.with_entity_data(&dying, |_, _| ()).is_some());
+}
+
+#[cfg(feature = "ron")]
+#[test]
+fn test_ron_scenes()
+{
+    use ecs::scene::Scene;
+
+    components! {
+        RonComponents {
+            #[hot] #[persist] position: Position,
+            #[hot] #[persist] health: u32
+        }
+    }
+
+    systems! {
+        RonSystems<RonComponents, ()>;
+    }
+
+    let ron = Scene::from_ron(r#"(
+        version: 1,
+        entities: [
+            (name: "player", components: { "position": (x: 1.0, y: 2.0), "health": 10 }),
+            (components: { "position": (x: -3.5, y: 0.0) }),
+        ],
+    )"#).unwrap();
+    let json = Scene::from_json(r#"{
+        "version": 1,
+        "entities": [
+            { "name": "player", "components": { "position": { "x": 1.0, "y": 2.0 }, "health": 10 } },
+            { "components": { "position": { "x": -3.5, "y": 0.0 } } }
+        ]
+    }"#).unwrap();
+    assert_eq!(json.to_value(), ron.to_value());
+
+    let mut world = World::<RonSystems>::new();
+    let entities = ron.spawn(&mut world.data).unwrap();
+    world.update();
+    assert_eq!(Some(entities[0]), world.names().entity("player"));
+    world.with_entity_data(&entities[0], |e, c| {
+        assert_eq!(Position { x: 1.0, y: 2.0 }, c.position[e]);
+        assert_eq!(10, c.health[e]);
+    });
+
+    // Both formats read back what they write
+    let snapshot = world.snapshot(1).unwrap();
+    assert_eq!(snapshot.to_value(), Scene::from_ron(&snapshot.to_ron()).unwrap().to_value());
+    assert_eq!(snapshot.to_value(), Scene::from_json(&snapshot.to_json()).unwrap().to_value());
+    assert!(Scene::from_ron("(entities: [").is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_transient_components()
+{
+    use ecs::Error;
+
+    // Every field is persistent unless marked `#[transient]`
+    components! {
+        #[persist]
+        CloneComponents {
+            #[hot] position: Position,
+            #[flag] selected: (),
+            #[hot] #[transient] velocity: Position
+        }
+    }
+
+    systems! {
+        CloneSystems<CloneComponents, ()>;
+    }
+
+    let mut world = World::<CloneSystems>::new();
+    let original = world.create_entity(|e: BuildData<CloneComponents>, c: &mut CloneComponents| {
+        c.position.add(&e, Position { x: 3.0, y: 4.0 });
+        c.selected.add(&e, ());
+        c.velocity.add(&e, Position { x: 1.0, y: 0.0 });
+    });
+    world.update();
+    world.set_name(&original, "original");
+
+    let bytes = world.serialize_entity(original).unwrap();
+    let copy = world.deserialize_entity(&bytes).unwrap();
+    let clone = world.clone_entity(original).unwrap();
+    world.update();
+    assert_eq!(3, world.entities().count());
+    assert_eq!(Some(original), world.names().entity("original"));
+    for entity in &[copy, clone]
+    {
+        world.with_entity_data(entity, |e, c| {
+            assert_eq!(Position { x: 3.0, y: 4.0 }, c.position[e]);
+            assert!(c.selected.has(&e));
+            assert!(!c.velocity.has(&e));
+        });
+    }
+
+    world.remove_entity(original);
+    world.update();
+    assert_eq!(Err(Error::NoSuchEntity(original)), world.clone_entity(original));
+}
+
+#[test]
+fn test_dense_bytes()
+{
+    use ecs::Pod;
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    pub struct Vertex
+    {
+        pub x: f32,
+        pub y: f32,
+    }
+    unsafe impl Pod for Vertex {}
+
+    components! {
+        VertexComponents {
+            #[dense] vertex: Vertex,
+            #[hot] weight: f32
+        }
+    }
+
+    systems! {
+        VertexSystems<VertexComponents, ()>;
+    }
+
+    let mut world = World::<VertexSystems>::new();
+    let entities: Vec<_> = (0..3).map(|i| world.create_entity(move |e: BuildData<VertexComponents>, c: &mut VertexComponents| {
+        c.vertex.add(&e, Vertex { x: i as f32, y: 0.0 });
+    })).collect();
+    world.update();
+
+    let bytes = world.vertex.as_bytes().unwrap();
+    assert_eq!(24, bytes.len());
+    assert_eq!(2.0f32.to_ne_bytes(), bytes[16..20]);
+    assert!(world.weight.as_bytes().is_none());
+
+    assert_eq!(Some(0..3), world.vertex.take_changed_range());
+    assert_eq!(None, world.vertex.take_changed_range());
+
+    // Only the positions that were written are reported
+    world.with_entity_data(&entities[1], |e, c| c.vertex[e].y = 5.0);
+    assert_eq!(Some(1..2), world.vertex.take_changed_range());
+
+    // Removing a component moves the last one into its place
+    world.remove_entity(entities[0]);
+    world.update();
+    assert_eq!(Some(0..1), world.vertex.take_changed_range());
+    assert_eq!(2, world.vertex.as_slice().unwrap().len());
+
+    // Removing the last one only shortens the slice
+    world.remove_entity(entities[1]);
+    world.update();
+    assert_eq!(1, world.vertex.as_slice().unwrap().len());
+    assert_eq!(None, world.vertex.take_changed_range());
+}